@@ -0,0 +1,52 @@
+use async_trait::async_trait;
+use ergoreq::middleware::middleware::{Middleware, Next};
+use ergoreq::utils::response_from_parts;
+use ergoreq::wrappers::client_wrapper::ErgoClient;
+use http::{Extensions, HeaderMap, StatusCode};
+use reqwest::redirect::Policy;
+use reqwest::{Request, Response};
+
+/// Answer requests to hosts under maintenance locally, without sending them.
+struct MaintenanceMiddleware {
+    host: &'static str,
+}
+
+#[async_trait]
+impl Middleware for MaintenanceMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> ergoreq::Result<Response> {
+        if req.url().host_str() == Some(self.host) {
+            return Ok(response_from_parts(
+                StatusCode::SERVICE_UNAVAILABLE,
+                HeaderMap::new(),
+                "Under maintenance",
+                req.url().to_owned(),
+            ));
+        }
+        next.run(req, ext).await
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let client = reqwest::ClientBuilder::new()
+        .redirect(Policy::none()) // Remember this!
+        .build()
+        .unwrap();
+
+    let client = ErgoClient::new(client).with_middleware(MaintenanceMiddleware {
+        host: "example.com",
+    });
+
+    let response = client.get("https://example.com").send().await.unwrap();
+
+    println!(
+        "Response status: {}, body: {}",
+        response.status(),
+        response.text().await.unwrap()
+    );
+}
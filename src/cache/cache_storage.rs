@@ -0,0 +1,81 @@
+use bytes::Bytes;
+use dashmap::DashMap;
+use http::{HeaderMap, HeaderValue, StatusCode};
+
+/// A response stored by a [`CacheStorage`].
+#[derive(Clone, Debug)]
+pub struct CachedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl CachedResponse {
+    /// Get the `ETag` validator of this response.
+    pub fn etag(&self) -> Option<&HeaderValue> {
+        self.headers.get(http::header::ETAG)
+    }
+
+    /// Get the `Last-Modified` validator of this response.
+    pub fn last_modified(&self) -> Option<&HeaderValue> {
+        self.headers.get(http::header::LAST_MODIFIED)
+    }
+}
+
+/// Storage backend of the cache middleware
+/// ([`crate::middleware::revalidation_middleware::RevalidationMiddleware`]).
+///
+/// Storage errors should not fail requests, implementations are expected to log them and
+/// behave as a cache miss.
+///
+/// # Notice
+/// Methods are async so backends can do IO without blocking the runtime. Like
+/// [`crate::middleware::middleware::Middleware`], implement it with `#[async_trait(?Send)]` on
+/// `wasm32`.
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+pub trait CacheStorage: Send + Sync {
+    /// Get the stored response of `key`.
+    async fn get(&self, key: &str) -> Option<CachedResponse>;
+
+    /// Store `response` under `key`, replacing the previous one.
+    async fn put(&self, key: &str, response: CachedResponse);
+
+    /// Remove the stored response of `key`.
+    async fn remove(&self, key: &str);
+
+    /// Remove all stored responses.
+    async fn clear(&self);
+}
+
+/// Unbounded in-memory `CacheStorage`.
+#[derive(Default)]
+pub struct MemoryCacheStorage {
+    store: DashMap<String, CachedResponse>,
+}
+
+impl MemoryCacheStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl CacheStorage for MemoryCacheStorage {
+    async fn get(&self, key: &str) -> Option<CachedResponse> {
+        self.store.get(key).map(|v| v.value().to_owned())
+    }
+
+    async fn put(&self, key: &str, response: CachedResponse) {
+        self.store.insert(key.to_owned(), response);
+    }
+
+    async fn remove(&self, key: &str) {
+        self.store.remove(key);
+    }
+
+    async fn clear(&self) {
+        self.store.clear();
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::Bytes;
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+use super::cache_storage::{CacheStorage, CachedResponse};
+
+const INDEX_FILE: &str = "index.json";
+const OBJECTS_DIR: &str = "objects";
+
+#[derive(Clone, Serialize, Deserialize)]
+struct IndexEntry {
+    hash: String,
+    size: u64,
+    status: u16,
+    headers: Vec<(String, Vec<u8>)>,
+    last_access: u128,
+}
+
+/// The stored responses, with the number of entries referring to each body.
+#[derive(Default)]
+struct Index {
+    entries: HashMap<String, IndexEntry>,
+    references: HashMap<String, usize>,
+    /// Size of unique bodies, bodies shared by several entries are counted once.
+    size: u64,
+}
+
+impl Index {
+    fn new(entries: HashMap<String, IndexEntry>) -> Self {
+        let mut index = Self::default();
+        for (key, entry) in entries {
+            index.insert(key, entry);
+        }
+        index
+    }
+
+    /// Insert `entry`, returns the hash of a body no entry refers to anymore.
+    fn insert(&mut self, key: String, entry: IndexEntry) -> Option<String> {
+        let references = self.references.entry(entry.hash.to_owned()).or_default();
+        if *references == 0 {
+            self.size += entry.size;
+        }
+        *references += 1;
+        let previous = self.entries.insert(key, entry)?;
+        self.release(previous)
+    }
+
+    /// Remove the entry of `key`, returns the hash of a body no entry refers to anymore.
+    fn remove(&mut self, key: &str) -> Option<String> {
+        let entry = self.entries.remove(key)?;
+        self.release(entry)
+    }
+
+    fn release(&mut self, entry: IndexEntry) -> Option<String> {
+        let references = self.references.get_mut(&entry.hash)?;
+        *references -= 1;
+        if *references > 0 {
+            return None;
+        }
+        self.references.remove(&entry.hash);
+        self.size -= entry.size;
+        Some(entry.hash)
+    }
+
+    /// Remove least recently used entries until bodies fit in `max_bytes`, returns the hashes
+    /// of bodies no entry refers to anymore.
+    fn evict(&mut self, max_bytes: u64) -> Vec<String> {
+        if self.size <= max_bytes {
+            return vec![];
+        }
+        let mut keys = self
+            .entries
+            .iter()
+            .map(|(k, v)| (v.last_access, k.to_owned()))
+            .collect::<Vec<_>>();
+        keys.sort_unstable();
+        let mut orphans = vec![];
+        for (_, key) in keys {
+            if self.size <= max_bytes {
+                break;
+            }
+            tracing::debug!("Evict cached response: {}", key);
+            orphans.extend(self.remove(&key));
+        }
+        orphans
+    }
+}
+
+/// Persistent `CacheStorage` on the file system.
+///
+/// Bodies are stored content-addressed (by their SHA-256) under `objects/`, and responses are
+/// listed in `index.json`. Every file is written to a temporary file first and renamed, so a
+/// crash never leaves a partially written file behind. File system calls run on the blocking
+/// thread pool of `tokio`.
+///
+/// When the total size of bodies exceeds `max_bytes`, least recently used responses are evicted.
+/// The index is saved once per write, access times are persisted with the next write, not on
+/// every read.
+pub struct DiskCacheStorage {
+    directory: PathBuf,
+    max_bytes: u64,
+    index: Mutex<Index>,
+    size: AtomicU64,
+    temp_counter: AtomicU64,
+}
+
+impl DiskCacheStorage {
+    /// Open (or create) a cache in `directory`, keeping at most `max_bytes` of bodies.
+    pub fn open<P: AsRef<Path>>(directory: P, max_bytes: u64) -> std::io::Result<Self> {
+        let directory = directory.as_ref().to_owned();
+        fs::create_dir_all(directory.join(OBJECTS_DIR))?;
+        let entries = match fs::read(directory.join(INDEX_FILE)) {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|e| {
+                tracing::warn!("Disk cache index is corrupted and will be reset: {}", e);
+                HashMap::new()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        let index = Index::new(entries);
+        Ok(Self {
+            directory,
+            max_bytes,
+            size: AtomicU64::new(index.size),
+            index: Mutex::new(index),
+            temp_counter: AtomicU64::new(0),
+        })
+    }
+
+    /// Get the total size of stored bodies.
+    pub fn size(&self) -> u64 {
+        self.size.load(Ordering::SeqCst)
+    }
+
+    fn now() -> u128 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|v| v.as_millis())
+            .unwrap_or_default()
+    }
+
+    fn object_path(&self, hash: &str) -> PathBuf {
+        self.directory.join(OBJECTS_DIR).join(hash)
+    }
+
+    /// Write `content` to `path` through a synced temporary file, on the blocking thread pool.
+    async fn write_atomic(&self, path: PathBuf, content: Vec<u8>) -> std::io::Result<()> {
+        let temp_path = path.with_extension(format!(
+            "{}-{}.tmp",
+            std::process::id(),
+            self.temp_counter.fetch_add(1, Ordering::SeqCst)
+        ));
+        let write = move || {
+            let result = fs::File::create(&temp_path)
+                .and_then(|mut file| {
+                    file.write_all(&content)?;
+                    file.sync_all()
+                })
+                .and_then(|_| fs::rename(&temp_path, path));
+            if result.is_err() {
+                let _ = fs::remove_file(&temp_path);
+            }
+            result
+        };
+        tokio::task::spawn_blocking(write)
+            .await
+            .map_err(std::io::Error::other)?
+    }
+
+    /// Save the index, remove the bodies of `orphans` and update the size.
+    async fn commit(&self, index: &Index, orphans: Vec<String>) {
+        self.size.store(index.size, Ordering::SeqCst);
+        for hash in orphans {
+            if let Err(e) = tokio::fs::remove_file(self.object_path(&hash)).await {
+                tracing::warn!("Failed to remove cached body {}: {}", hash, e);
+            }
+        }
+        let result = serde_json::to_vec(&index.entries).map_err(std::io::Error::other);
+        let result = match result {
+            Ok(content) => {
+                self.write_atomic(self.directory.join(INDEX_FILE), content)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to save disk cache index: {}", e);
+        }
+    }
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|v| format!("{:02x}", v)).collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl CacheStorage for DiskCacheStorage {
+    async fn get(&self, key: &str) -> Option<CachedResponse> {
+        let entry = self.index.lock().await.entries.get(key)?.to_owned();
+        let body = match tokio::fs::read(self.object_path(&entry.hash)).await {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::warn!("Failed to read cached body of {}: {}", key, e);
+                let mut index = self.index.lock().await;
+                if index.entries.get(key).is_some_and(|v| v.hash == entry.hash) {
+                    index.remove(key);
+                    self.size.store(index.size, Ordering::SeqCst);
+                }
+                return None;
+            }
+        };
+        if let Some(entry) = self.index.lock().await.entries.get_mut(key) {
+            entry.last_access = Self::now();
+        }
+        let mut headers = HeaderMap::new();
+        for (name, value) in &entry.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::try_from(name.as_str()),
+                HeaderValue::from_bytes(value),
+            ) {
+                headers.append(name, value);
+            }
+        }
+        let response = CachedResponse {
+            status: StatusCode::from_u16(entry.status).ok()?,
+            headers,
+            body: Bytes::from(body),
+        };
+        Some(response)
+    }
+
+    async fn put(&self, key: &str, response: CachedResponse) {
+        let size = response.body.len() as u64;
+        if size > self.max_bytes {
+            return;
+        }
+        let hash = Self::hex(&Sha256::digest(&response.body));
+        let object_path = self.object_path(&hash);
+
+        let mut index = self.index.lock().await;
+        if !index.references.contains_key(&hash) {
+            if let Err(e) = self.write_atomic(object_path, response.body.to_vec()).await {
+                tracing::warn!("Failed to write cached body of {}: {}", key, e);
+                return;
+            }
+        }
+        let mut orphans = index
+            .insert(
+                key.to_owned(),
+                IndexEntry {
+                    hash,
+                    size,
+                    status: response.status.as_u16(),
+                    headers: response
+                        .headers
+                        .iter()
+                        .map(|(k, v)| (k.as_str().to_owned(), v.as_bytes().to_vec()))
+                        .collect(),
+                    last_access: Self::now(),
+                },
+            )
+            .into_iter()
+            .collect::<Vec<_>>();
+        orphans.extend(index.evict(self.max_bytes));
+        self.commit(&index, orphans).await;
+    }
+
+    async fn remove(&self, key: &str) {
+        let mut index = self.index.lock().await;
+        if index.entries.contains_key(key) {
+            let orphans = index.remove(key).into_iter().collect();
+            self.commit(&index, orphans).await;
+        }
+    }
+
+    async fn clear(&self) {
+        let mut index = self.index.lock().await;
+        let orphans = std::mem::take(&mut *index).references.into_keys().collect();
+        self.commit(&index, orphans).await;
+    }
+}
+
+#[cfg(test)]
+mod test_disk_cache_storage {
+    use bytes::Bytes;
+    use http::{HeaderMap, HeaderValue, StatusCode};
+
+    use super::DiskCacheStorage;
+    use crate::cache::cache_storage::{CacheStorage, CachedResponse};
+
+    fn response(body: &'static str) -> CachedResponse {
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::ETAG, HeaderValue::from_static("\"v1\""));
+        CachedResponse {
+            status: StatusCode::OK,
+            headers,
+            body: Bytes::from_static(body.as_bytes()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_persist_and_evict() {
+        let directory =
+            std::env::temp_dir().join(format!("ergoreq-disk-cache-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        {
+            let storage = DiskCacheStorage::open(&directory, 10).unwrap();
+            storage.put("a", response("12345")).await;
+            // Same body is stored once.
+            storage.put("b", response("12345")).await;
+            assert_eq!(storage.size(), 5);
+        }
+
+        let storage = DiskCacheStorage::open(&directory, 10).unwrap();
+        let cached = storage.get("a").await.unwrap();
+        assert_eq!(cached.body, "12345");
+        assert_eq!(cached.etag().unwrap(), "\"v1\"");
+
+        tokio::time::sleep(std::time::Duration::from_millis(2)).await;
+        storage.put("c", response("abcdefgh")).await;
+        // `a` and `b` share the least recently used body, both of them are evicted.
+        assert!(storage.get("a").await.is_none());
+        assert!(storage.get("b").await.is_none());
+        assert_eq!(storage.get("c").await.unwrap().body, "abcdefgh");
+        assert_eq!(storage.size(), 8);
+
+        storage.clear().await;
+        assert_eq!(storage.size(), 0);
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache_shared_body() {
+        let directory = std::env::temp_dir().join(format!(
+            "ergoreq-disk-cache-shared-test-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&directory);
+
+        let storage = DiskCacheStorage::open(&directory, 100).unwrap();
+        storage.put("a", response("12345")).await;
+        storage.put("b", response("12345")).await;
+        storage.put("c", response("678")).await;
+        // The body of `b` is kept while `a` refers to it.
+        storage.remove("b").await;
+        assert_eq!(storage.size(), 8);
+        assert_eq!(storage.get("a").await.unwrap().body, "12345");
+        // Replacing the body of `a` releases the old one.
+        storage.put("a", response("abc")).await;
+        assert_eq!(storage.size(), 6);
+        assert_eq!(
+            std::fs::read_dir(directory.join("objects"))
+                .unwrap()
+                .count(),
+            2
+        );
+
+        let storage = DiskCacheStorage::open(&directory, 100).unwrap();
+        assert_eq!(storage.size(), 6);
+        assert!(storage.get("b").await.is_none());
+        let _ = std::fs::remove_dir_all(&directory);
+    }
+}
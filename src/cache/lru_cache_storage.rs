@@ -0,0 +1,184 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use dashmap::DashMap;
+
+use super::cache_storage::{CacheStorage, CachedResponse};
+
+struct LruEntry {
+    response: CachedResponse,
+    last_access: AtomicU64,
+}
+
+/// Bounded in-memory `CacheStorage`, evicting least recently used responses.
+///
+/// Both the number of responses and the total size of bodies are bounded. It is safe for
+/// concurrent use, and it is the default backend of
+/// [`crate::middleware::revalidation_middleware::RevalidationMiddleware`].
+pub struct LruCacheStorage {
+    max_entries: usize,
+    max_body_bytes: u64,
+    entries: DashMap<String, LruEntry>,
+    body_bytes: AtomicU64,
+    clock: AtomicU64,
+    evict_lock: Mutex<()>,
+}
+
+impl LruCacheStorage {
+    /// Create a storage keeping at most `max_entries` responses and `max_body_bytes` of bodies.
+    pub fn new(max_entries: usize, max_body_bytes: u64) -> Self {
+        Self {
+            max_entries,
+            max_body_bytes,
+            entries: DashMap::new(),
+            body_bytes: AtomicU64::new(0),
+            clock: AtomicU64::new(0),
+            evict_lock: Mutex::new(()),
+        }
+    }
+
+    /// Get the number of stored responses.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no response is stored.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get the total size of stored bodies.
+    pub fn body_bytes(&self) -> u64 {
+        self.body_bytes.load(Ordering::SeqCst)
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::SeqCst)
+    }
+
+    fn remove_entry(&self, key: &str) {
+        if let Some((_, entry)) = self.entries.remove(key) {
+            self.body_bytes
+                .fetch_sub(entry.response.body.len() as u64, Ordering::SeqCst);
+        }
+    }
+
+    fn evict(&self) {
+        let _guard = self.evict_lock.lock().unwrap_or_else(|e| e.into_inner());
+        while self.entries.len() > self.max_entries || self.body_bytes() > self.max_body_bytes {
+            let oldest = self
+                .entries
+                .iter()
+                .min_by_key(|v| v.last_access.load(Ordering::SeqCst))
+                .map(|v| v.key().to_owned());
+            match oldest {
+                Some(oldest) => {
+                    tracing::debug!("Evict cached response: {}", oldest);
+                    self.remove_entry(&oldest);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+impl Default for LruCacheStorage {
+    /// Keep at most 1024 responses and 64 MiB of bodies.
+    fn default() -> Self {
+        Self::new(1024, 64 * 1024 * 1024)
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl CacheStorage for LruCacheStorage {
+    async fn get(&self, key: &str) -> Option<CachedResponse> {
+        let entry = self.entries.get(key)?;
+        entry.last_access.store(self.tick(), Ordering::SeqCst);
+        Some(entry.response.to_owned())
+    }
+
+    async fn put(&self, key: &str, response: CachedResponse) {
+        let size = response.body.len() as u64;
+        if size > self.max_body_bytes || self.max_entries == 0 {
+            return;
+        }
+        self.body_bytes.fetch_add(size, Ordering::SeqCst);
+        let previous = self.entries.insert(
+            key.to_owned(),
+            LruEntry {
+                response,
+                last_access: AtomicU64::new(self.tick()),
+            },
+        );
+        if let Some(previous) = previous {
+            self.body_bytes
+                .fetch_sub(previous.response.body.len() as u64, Ordering::SeqCst);
+        }
+        self.evict();
+    }
+
+    async fn remove(&self, key: &str) {
+        self.remove_entry(key);
+    }
+
+    async fn clear(&self) {
+        let keys = self
+            .entries
+            .iter()
+            .map(|v| v.key().to_owned())
+            .collect::<Vec<_>>();
+        for key in keys {
+            self.remove_entry(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_lru_cache_storage {
+    use bytes::Bytes;
+    use http::{HeaderMap, StatusCode};
+
+    use super::LruCacheStorage;
+    use crate::cache::cache_storage::{CacheStorage, CachedResponse};
+
+    fn response(body: &'static str) -> CachedResponse {
+        CachedResponse {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from_static(body.as_bytes()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_evict_by_entries() {
+        let storage = LruCacheStorage::new(2, 1024);
+        storage.put("a", response("a")).await;
+        storage.put("b", response("b")).await;
+        storage.get("a").await;
+        storage.put("c", response("c")).await;
+        assert!(storage.get("a").await.is_some());
+        assert!(storage.get("b").await.is_none());
+        assert!(storage.get("c").await.is_some());
+        assert_eq!(storage.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_evict_by_body_bytes() {
+        let storage = LruCacheStorage::new(10, 8);
+        storage.put("a", response("1234")).await;
+        storage.put("b", response("5678")).await;
+        assert_eq!(storage.body_bytes(), 8);
+        storage.put("b", response("56")).await;
+        assert_eq!(storage.body_bytes(), 6);
+        storage.put("c", response("abcd")).await;
+        assert!(storage.get("a").await.is_none());
+        assert_eq!(storage.body_bytes(), 6);
+        // Bodies larger than the limit are never stored.
+        storage.put("d", response("123456789")).await;
+        assert!(storage.get("d").await.is_none());
+        storage.clear().await;
+        assert!(storage.is_empty());
+        assert_eq!(storage.body_bytes(), 0);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod cache_storage;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod disk_cache_storage;
+pub mod lru_cache_storage;
@@ -66,24 +66,12 @@ impl ErgoCookieContainer {
         let cookie_domain = cookie_domain.to_ascii_lowercase();
         let request_domain = request_domain.to_ascii_lowercase();
 
-        // Check for an exact match between the cookie domain and request domain
-        if cookie_domain == request_domain {
-            return true;
-        }
-
-        if cookie_domain.starts_with('.') {
-            // Remove the leading '.' from the cookie domain and compare
-            // the end of the request domain with the modified cookie domain
-            if request_domain.ends_with(&cookie_domain[1..]) {
-                return true;
-            }
-        }
-        // Check if the request domain ends with the modified cookie domain and is separated by a dot
-        else if request_domain.ends_with(&format!(".{}", cookie_domain)) {
-            return true;
-        }
+        // A leading '.' of the cookie domain is ignored
+        let cookie_domain = cookie_domain.strip_prefix('.').unwrap_or(&cookie_domain);
 
-        false
+        // Check for an exact match, or if the request domain ends with the cookie domain and is
+        // separated by a dot
+        request_domain == cookie_domain || request_domain.ends_with(&format!(".{}", cookie_domain))
     }
 
     /// Get a mutable reference to inner storage.
@@ -94,7 +82,7 @@ impl ErgoCookieContainer {
     }
 
     fn remove_target_cookie(&self, cookie: Cookie, url: &reqwest::Url) {
-        let domain = match cookie.domain().and_then(|v| Some(v.to_string())) {
+        let domain = match cookie.domain().map(|v| v.to_string()) {
             Some(domain) => domain,
             None => match url.host_str() {
                 Some(domain) => domain.to_owned(),
@@ -127,11 +115,7 @@ impl ErgoCookieContainer {
             for cookie_map in path_map.iter() {
                 cookie_map.retain(|_, v| {
                     if let Some(exp) = v.expires_datetime() {
-                        if Utc::now().timestamp() >= exp.unix_timestamp() {
-                            false
-                        } else {
-                            true
-                        }
+                        Utc::now().timestamp() < exp.unix_timestamp()
                     } else {
                         true
                     }
@@ -213,7 +197,7 @@ impl CookieContainer for ErgoCookieContainer {
                 }
             }
 
-            let domain = match cookie.domain().and_then(|v| Some(v.to_string())) {
+            let domain = match cookie.domain().map(|v| v.to_string()) {
                 Some(domain) => domain,
                 None => match url.host_str() {
                     Some(domain) => domain.to_owned(),
@@ -272,10 +256,11 @@ impl CookieContainer for ErgoCookieContainer {
             if self.match_domain_only {
                 if let Some(cookie_map) = path_map.get("") {
                     for cookie in cookie_map.value() {
-                        if !self.ignore_secure {
-                            if cookie.secure().unwrap_or(false) && url.scheme() != "https" {
-                                continue;
-                            }
+                        if !self.ignore_secure
+                            && cookie.secure().unwrap_or(false)
+                            && url.scheme() != "https"
+                        {
+                            continue;
                         }
                         result.push(cookie.value().encoded().stripped().to_string());
                     }
@@ -283,10 +268,11 @@ impl CookieContainer for ErgoCookieContainer {
             } else {
                 if let Some(cookie_map) = path_map.get(url.path()) {
                     for cookie in cookie_map.value() {
-                        if !self.ignore_secure {
-                            if cookie.secure().unwrap_or(false) && url.scheme() != "https" {
-                                continue;
-                            }
+                        if !self.ignore_secure
+                            && cookie.secure().unwrap_or(false)
+                            && url.scheme() != "https"
+                        {
+                            continue;
                         }
                         result.push(cookie.value().encoded().stripped().to_string());
                     }
@@ -358,6 +344,10 @@ mod test_default_cookie_container {
             "c.google.com",
             "abc.google.com"
         ));
+        assert!(!ErgoCookieContainer::is_domain_match(
+            ".google.com",
+            "evilgoogle.com"
+        ));
     }
 
     #[test]
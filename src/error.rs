@@ -10,6 +10,47 @@ pub enum Error {
     Custom(Box<dyn std::error::Error + Send + Sync + 'static>),
     InvalidRedirectUrl(String),
     Internal(Box<dyn std::error::Error + Send + Sync + 'static>),
+    RequestNotCloneable,
+    MockNotMatched(reqwest::Method, url::Url),
+    CircuitOpen(String),
+    OAuth2TokenRequest(http::StatusCode, String),
+    Middleware {
+        name: &'static str,
+        position: usize,
+        source: Box<Error>,
+    },
+    DeadlineExceeded(std::time::Duration),
+    RedirectNotAllowed(
+        crate::middleware::auto_redirect_middleware::RedirectMode,
+        url::Url,
+    ),
+    UnexpectedStatus(http::StatusCode, url::Url, String),
+    Io(std::io::Error),
+    ChecksumMismatch(String, String),
+    WebSocket(String),
+    RobotsDisallowed(url::Url),
+    Deserialize {
+        status: http::StatusCode,
+        path: String,
+        body: String,
+        source: serde_json::Error,
+    },
+    UnresolvedPathParam(String),
+    Codec(String),
+    Cancelled,
+    BodyTooLarge(u64),
+    LineTooLong(usize),
+    InvalidSelector(String),
+    UnexpectedContentType {
+        expected: String,
+        actual: Option<String>,
+        status: http::StatusCode,
+    },
+    TruncatedBody {
+        expected: Option<u64>,
+        received: u64,
+    },
+    InvalidUrl(String),
 }
 
 pub type Result<T> = core::result::Result<T, Error>;
@@ -30,11 +71,130 @@ impl std::fmt::Display for Error {
                 write!(f, "The redirect location is invalid")
             }
             Error::RedirectLocationEmpty => write!(f, "The redirect location is empty"),
+            Error::RequestNotCloneable => {
+                write!(
+                    f,
+                    "The request cannot be cloned, because its body is a stream"
+                )
+            }
+            Error::MockNotMatched(method, url) => {
+                write!(f, "No mock rule matched request: {method} {url}")
+            }
+            Error::CircuitOpen(host) => write!(f, "The circuit of host is open: {host}"),
+            Error::OAuth2TokenRequest(status, body) => {
+                write!(f, "Token endpoint responded with {status}: {body}")
+            }
+            Error::Middleware {
+                name,
+                position,
+                source,
+            } => write!(
+                f,
+                "Middleware '{name}' at position {position} failed: {source}"
+            ),
+            Error::DeadlineExceeded(deadline) => {
+                write!(f, "The deadline of {deadline:?} is exceeded")
+            }
+            Error::RedirectNotAllowed(mode, url) => {
+                write!(f, "Redirect to '{url}' is not allowed in {mode:?} mode")
+            }
+            Error::UnexpectedStatus(status, url, body) => {
+                write!(f, "Request to '{url}' responded with {status}: {body}")
+            }
+            Error::Io(inner) => write!(f, "IO error: {inner}"),
+            Error::ChecksumMismatch(expected, actual) => {
+                write!(f, "Checksum mismatch, expected {expected} but got {actual}")
+            }
+            Error::WebSocket(reason) => write!(f, "WebSocket error: {reason}"),
+            Error::RobotsDisallowed(url) => {
+                write!(f, "Request to '{url}' is disallowed by robots.txt")
+            }
+            Error::Deserialize {
+                status,
+                path,
+                body,
+                source,
+            } => write!(
+                f,
+                "Deserializing the {status} response failed at '{path}': {source}, body: {body}"
+            ),
+            Error::UnresolvedPathParam(name) => {
+                write!(f, "The path parameter {{{name}}} is not resolved")
+            }
+            Error::Codec(reason) => write!(f, "Codec error: {reason}"),
+            Error::Cancelled => write!(f, "The request is cancelled"),
+            Error::BodyTooLarge(max_bytes) => {
+                write!(
+                    f,
+                    "The response body exceeds the limit of {max_bytes} bytes"
+                )
+            }
+            Error::LineTooLong(max_line) => {
+                write!(
+                    f,
+                    "A line of the response body exceeds the limit of {max_line} bytes"
+                )
+            }
+            Error::InvalidSelector(reason) => write!(f, "Invalid CSS selector {reason}"),
+            Error::UnexpectedContentType {
+                expected,
+                actual,
+                status,
+            } => write!(
+                f,
+                "Expected a {expected} response but got {} ({status})",
+                actual.as_deref().unwrap_or("no content type")
+            ),
+            Error::TruncatedBody { expected, received } => match expected {
+                Some(expected) => write!(
+                    f,
+                    "The response body is truncated, received {received} of {expected} bytes"
+                ),
+                None => write!(f, "The response body is truncated after {received} bytes"),
+            },
+            Error::InvalidUrl(reason) => write!(f, "Invalid url: {reason}"),
         }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Reqwest(inner) => Some(inner),
+            Error::Http(inner) => Some(inner),
+            Error::Io(inner) => Some(inner),
+            Error::Custom(inner) | Error::Internal(inner) => Some(inner.as_ref()),
+            Error::Middleware { source, .. } => Some(source.as_ref()),
+            Error::Deserialize { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl Error {
+    /// Get the innermost error, unwrapping [`Error::Middleware`].
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Middleware { source, .. } => source.root(),
+            error => error,
+        }
+    }
+
+    /// Wrap an error raised by a middleware with its provenance.
+    ///
+    /// Only errors created by middlewares are wrapped, typed errors like
+    /// [`Error::TooManyRedirect`] or errors of the transport pass through unchanged.
+    pub(crate) fn in_middleware(self, name: &'static str, position: usize) -> Self {
+        match self {
+            Error::Custom(_) | Error::Internal(_) | Error::Http(_) => Error::Middleware {
+                name,
+                position,
+                source: Box::new(self),
+            },
+            error => error,
+        }
+    }
+}
 
 impl From<anyhow::Error> for Error {
     fn from(value: anyhow::Error) -> Self {
@@ -44,6 +204,22 @@ impl From<anyhow::Error> for Error {
 
 impl From<reqwest::Error> for Error {
     fn from(value: reqwest::Error) -> Self {
+        // a body over the limit of `with_max_response_bytes`, or a truncated body when
+        // `with_integrity_check` is set, fails inside `reqwest`
+        let mut source = std::error::Error::source(&value);
+        while let Some(error) = source {
+            match error.downcast_ref::<Error>() {
+                Some(Error::BodyTooLarge(max_bytes)) => return Self::BodyTooLarge(*max_bytes),
+                Some(Error::TruncatedBody { expected, received }) => {
+                    return Self::TruncatedBody {
+                        expected: *expected,
+                        received: *received,
+                    }
+                }
+                _ => {}
+            }
+            source = error.source();
+        }
         Self::Reqwest(value)
     }
 }
@@ -54,6 +230,12 @@ impl From<http::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
 impl From<chrono::OutOfRangeError> for Error {
     fn from(value: OutOfRangeError) -> Self {
         Self::Internal(Box::new(value))
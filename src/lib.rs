@@ -1,5 +1,13 @@
 #![allow(rustdoc::broken_intra_doc_links)]
 #![doc = include_str!("../README.md")]
+/// Invoke `$mac` with every HTTP method having a shortcut, so `ErgoClient` and
+/// `ErgoStringToRequestExt` provide the same set.
+macro_rules! for_each_http_method {
+    ($mac:ident) => {
+        $mac!(get, post, put, patch, delete, head, options, trace);
+    };
+}
+
 pub mod wrappers;
 
 pub mod cookie;
@@ -10,11 +18,21 @@ pub mod error;
 
 pub mod utils;
 
+pub mod scheduler;
+
+pub mod cache;
+
 pub use crate::cookie::cookie_container::ErgoCookieContainer;
 pub use crate::error::Error;
 pub use crate::error::Result;
+pub use crate::middleware::cancellation_middleware::CancellationToken;
+pub use crate::scheduler::priority_scheduler::RequestPriority;
+pub use crate::wrappers::client_builder::{ErgoClientBuilder, ErgoScopedClientBuilder};
 pub use crate::wrappers::client_wrapper::ErgoClient;
+pub use crate::wrappers::endpoint::ErgoEndpoint;
 pub use crate::wrappers::request_builder_wrapper::ErgoRequestBuilder;
+pub use crate::wrappers::resource::ErgoResource;
+pub use crate::wrappers::response_wrapper::ErgoResponse;
 pub use async_trait::async_trait;
 pub use cookie as cookie_process;
 pub use dashmap;
@@ -23,3 +41,4 @@ pub use retry_policies;
 pub use url;
 pub use utils::string_ext::ErgoStringToRequestExt;
 pub use utils::string_url_builder::StringUrlBuilderTrait;
+pub use utils::url_builder::ErgoUrlBuilder;
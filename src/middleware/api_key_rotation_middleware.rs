@@ -0,0 +1,217 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use http::{Extensions, HeaderName, HeaderValue, StatusCode};
+use reqwest::{Request, Response};
+
+use super::middleware::{Middleware, MiddlewarePhase, Next};
+use crate::utils::timer::{sleep, Instant};
+
+/// Where [`ApiKeyRotationMiddleware`] puts the API key.
+#[derive(Clone, Debug)]
+pub enum ApiKeyPlacement {
+    /// Set the key as the value of a header, like `x-api-key`.
+    Header(HeaderName),
+    /// Append the key to the query with the given parameter name.
+    Query(String),
+    /// Set `Authorization: Bearer <key>`.
+    Bearer,
+}
+
+/// How [`ApiKeyRotationMiddleware`] picks the next key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RotationStrategy {
+    /// Use available keys in turn.
+    #[default]
+    RoundRobin,
+    /// Use the available key which was used least recently.
+    LeastRecentlyUsed,
+}
+
+struct KeyState {
+    key: String,
+    last_used: Option<Instant>,
+    benched_until: Option<Instant>,
+}
+
+struct Rotation {
+    keys: Vec<KeyState>,
+    cursor: usize,
+}
+
+/// Rotate requests among a pool of API keys.
+///
+/// A key receiving `401 Unauthorized`, `403 Forbidden` or `429 Too Many Requests` is benched for
+/// a cool-down (60 seconds by default), and the other keys are used meanwhile. When every key is
+/// benched, requests wait until the first one resumes.
+///
+/// It runs in [`MiddlewarePhase::PostRetry`] by default, so a retry of a rejected attempt is sent
+/// with another key.
+///
+/// # Example
+/// ```
+/// # use ergoreq::middleware::api_key_rotation_middleware::{ApiKeyPlacement, ApiKeyRotationMiddleware};
+/// # use ergoreq::ErgoClient;
+/// let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+///     ApiKeyRotationMiddleware::new(
+///         vec!["key-1".to_owned(), "key-2".to_owned()],
+///         ApiKeyPlacement::Header(http::HeaderName::from_static("x-api-key")),
+///     ),
+/// );
+/// ```
+pub struct ApiKeyRotationMiddleware {
+    placement: ApiKeyPlacement,
+    strategy: RotationStrategy,
+    cool_down: Duration,
+    rotation: Mutex<Rotation>,
+}
+
+impl ApiKeyRotationMiddleware {
+    /// Create an `ApiKeyRotationMiddleware` with the pool of `keys`.
+    ///
+    /// # Panics
+    /// Panics if `keys` is empty.
+    pub fn new(keys: Vec<String>, placement: ApiKeyPlacement) -> Self {
+        assert!(!keys.is_empty(), "at least one API key is required");
+        Self {
+            placement,
+            strategy: RotationStrategy::default(),
+            cool_down: Duration::from_secs(60),
+            rotation: Mutex::new(Rotation {
+                keys: keys
+                    .into_iter()
+                    .map(|key| KeyState {
+                        key,
+                        last_used: None,
+                        benched_until: None,
+                    })
+                    .collect(),
+                cursor: 0,
+            }),
+        }
+    }
+
+    /// Set the [`RotationStrategy`].
+    pub fn with_strategy(mut self, strategy: RotationStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Set how long a rejected key is benched.
+    pub fn with_cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+        self
+    }
+
+    /// Get the number of keys which are not benched.
+    pub fn available_keys(&self) -> usize {
+        let now = Instant::now();
+        self.lock()
+            .keys
+            .iter()
+            .filter(|v| v.benched_until.is_none_or(|until| until <= now))
+            .count()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Rotation> {
+        self.rotation.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Pick a key, or return how long to wait until one is available.
+    fn pick(&self) -> Result<String, Duration> {
+        let mut rotation = self.lock();
+        let now = Instant::now();
+        let available = |v: &KeyState| v.benched_until.is_none_or(|until| until <= now);
+        let len = rotation.keys.len();
+
+        let index = match self.strategy {
+            RotationStrategy::RoundRobin => (0..len)
+                .map(|offset| (rotation.cursor + offset) % len)
+                .find(|&i| available(&rotation.keys[i])),
+            RotationStrategy::LeastRecentlyUsed => rotation
+                .keys
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| available(v))
+                .min_by_key(|(_, v)| v.last_used)
+                .map(|(i, _)| i),
+        };
+        let Some(index) = index else {
+            let resume = rotation
+                .keys
+                .iter()
+                .filter_map(|v| v.benched_until)
+                .min()
+                .expect("every key is benched");
+            return Err(resume.duration_since(now));
+        };
+
+        rotation.cursor = (index + 1) % len;
+        let state = &mut rotation.keys[index];
+        state.benched_until = None;
+        state.last_used = Some(now);
+        Ok(state.key.to_owned())
+    }
+
+    fn bench(&self, key: &str) {
+        let until = Instant::now() + self.cool_down;
+        if let Some(state) = self.lock().keys.iter_mut().find(|v| v.key == key) {
+            state.benched_until = Some(until);
+        }
+    }
+
+    fn apply(&self, req: &mut Request, key: &str) -> crate::error::Result<()> {
+        match &self.placement {
+            ApiKeyPlacement::Header(name) => {
+                let value =
+                    HeaderValue::try_from(key).map_err(|e| crate::Error::Internal(Box::new(e)))?;
+                req.headers_mut().insert(name, value);
+            }
+            ApiKeyPlacement::Query(name) => {
+                req.url_mut().query_pairs_mut().append_pair(name, key);
+            }
+            ApiKeyPlacement::Bearer => {
+                let value = HeaderValue::try_from(format!("Bearer {}", key))
+                    .map_err(|e| crate::Error::Internal(Box::new(e)))?;
+                req.headers_mut().insert(http::header::AUTHORIZATION, value);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for ApiKeyRotationMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        let key = loop {
+            match self.pick() {
+                Ok(key) => break key,
+                Err(wait) => {
+                    tracing::debug!("Every API key is benched, wait for {:?}", wait);
+                    sleep(wait).await;
+                }
+            }
+        };
+        self.apply(&mut req, &key)?;
+
+        let response = next.run(req, ext).await?;
+        if matches!(
+            response.status(),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN | StatusCode::TOO_MANY_REQUESTS
+        ) {
+            tracing::debug!("API key is rejected with {}, bench it", response.status());
+            self.bench(&key);
+        }
+        Ok(response)
+    }
+
+    fn default_phase(&self) -> MiddlewarePhase {
+        MiddlewarePhase::PostRetry
+    }
+}
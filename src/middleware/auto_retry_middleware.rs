@@ -1,71 +1,644 @@
+use super::deadline_middleware::Deadline;
 use super::middleware::Middleware;
 use crate::middleware::middleware::Next;
-use async_trait::async_trait;
-use http::Extensions;
+use crate::utils::body_factory::BodyFactory;
+use crate::utils::random::random_bytes;
+use crate::utils::response::content_length;
+use crate::utils::timer::{sleep, system_now};
+use crate::wrappers::endpoint_pool::replace_origin;
+use http::{Extensions, HeaderMap, HeaderName, Method, StatusCode};
 use reqwest::{Request, Response};
 use retry_policies::{RetryDecision, RetryPolicy};
-use std::{sync::Arc, time::SystemTime};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tracing::instrument;
 
-pub(crate) struct AutoRetryMiddleware(Arc<dyn RetryPolicy + Send + Sync + 'static>);
+/// The max size of a retried response body read before the next attempt, larger or chunked
+/// bodies are dropped with their connection.
+const MAX_RETRY_DRAIN: u64 = 64 * 1024;
 
-impl AutoRetryMiddleware {
-    pub fn new(policy: Arc<dyn RetryPolicy + Send + Sync + 'static>) -> Self {
-        Self(policy)
+/// The name of the `Idempotency-Key` header.
+pub(crate) const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// The `Idempotency-Key` header of a request, see
+/// [`crate::ErgoRequestBuilder::with_idempotency_key`].
+///
+/// A request with this header is retried even if its method is not idempotent, like `POST`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IdempotencyKey {
+    /// A random UUID generated each time the request is sent.
+    Auto,
+    /// The given key.
+    Fixed(String),
+}
+
+impl IdempotencyKey {
+    /// Get the key, generating a random UUID (version 4) for [`IdempotencyKey::Auto`].
+    pub(crate) fn resolve(&self) -> String {
+        match self {
+            IdempotencyKey::Fixed(key) => key.to_owned(),
+            IdempotencyKey::Auto => {
+                let mut bytes = random_bytes::<16>();
+                bytes[6] = (bytes[6] & 0x0f) | 0x40;
+                bytes[8] = (bytes[8] & 0x3f) | 0x80;
+                let hex = bytes.iter().map(|v| format!("{v:02x}")).collect::<String>();
+                format!(
+                    "{}-{}-{}-{}-{}",
+                    &hex[..8],
+                    &hex[8..12],
+                    &hex[12..16],
+                    &hex[16..20],
+                    &hex[20..]
+                )
+            }
+        }
     }
 }
 
-#[async_trait]
-impl Middleware for AutoRetryMiddleware {
-    #[instrument(skip(self, ext, next))]
-    async fn handle(
+/// The `Idempotency-Key` sent with a request, inserted into the `Extensions` of the request by
+/// [`crate::ErgoRequestBuilder::with_idempotency_key`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SentIdempotencyKey(pub String);
+
+/// How an attempt is handled by auto retry, returned by [`RetryClassifier::classify`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RetryDecisionKind {
+    /// Return the result to the caller.
+    Done,
+    /// Retry after the delay of the [`RetryPolicy`].
+    Retry,
+    /// Retry after the given delay, instead of the delay of the [`RetryPolicy`].
+    ///
+    /// The delay is capped by [`RetryOptions::with_max_retry_after`].
+    RetryAfter(Duration),
+}
+
+/// Decide whether the result of an attempt should be retried.
+///
+/// Whether a retry actually happens is still decided by the [`RetryPolicy`].
+///
+/// # Example
+/// ```
+/// # use ergoreq::middleware::auto_retry_middleware::{
+/// #     DefaultRetryClassifier, RetryClassifier, RetryDecisionKind, RetryOptions,
+/// # };
+/// # use ergoreq::ErgoClient;
+/// /// Retry like the default, and also when the server asks to.
+/// struct RetryOnHint;
+///
+/// impl RetryClassifier for RetryOnHint {
+///     fn classify(&self, result: &ergoreq::Result<reqwest::Response>) -> RetryDecisionKind {
+///         match result {
+///             Ok(response) if response.headers().contains_key("x-should-retry") => {
+///                 RetryDecisionKind::Retry
+///             }
+///             result => DefaultRetryClassifier::new().classify(result),
+///         }
+///     }
+/// }
+///
+/// let client = ErgoClient::new(reqwest::Client::new())
+///     .with_retry_count(3)
+///     .with_retry_options(RetryOptions::new().with_classifier(RetryOnHint));
+/// ```
+pub trait RetryClassifier: Send + Sync + 'static {
+    /// Classify the result of an attempt.
+    fn classify(&self, result: &crate::Result<Response>) -> RetryDecisionKind;
+}
+
+/// The default [`RetryClassifier`].
+///
+/// Errors are retried, except [`crate::Error::TooManyRedirect`],
+/// [`crate::Error::RedirectNotAllowed`] and [`crate::Error::CircuitOpen`]. Responses are retried
+/// if their status is retryable, `429` and `503` responses honor `Retry-After`.
+#[derive(Clone, Debug)]
+pub struct DefaultRetryClassifier {
+    retry_statuses: Vec<StatusCode>,
+}
+
+impl DefaultRetryClassifier {
+    /// Create a `DefaultRetryClassifier` retrying `429`, `500`, `502`, `503` and `504` responses.
+    pub fn new() -> Self {
+        Self::with_retry_statuses([
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::BAD_GATEWAY,
+            StatusCode::SERVICE_UNAVAILABLE,
+            StatusCode::GATEWAY_TIMEOUT,
+        ])
+    }
+
+    /// Create a `DefaultRetryClassifier` retrying responses with the given statuses.
+    pub fn with_retry_statuses<I>(statuses: I) -> Self
+    where
+        I: IntoIterator<Item = StatusCode>,
+    {
+        Self {
+            retry_statuses: statuses.into_iter().collect(),
+        }
+    }
+}
+
+impl Default for DefaultRetryClassifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RetryClassifier for DefaultRetryClassifier {
+    fn classify(&self, result: &crate::Result<Response>) -> RetryDecisionKind {
+        match result {
+            Ok(response) if self.retry_statuses.contains(&response.status()) => {
+                match response.status() {
+                    StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => {
+                        parse_retry_after(response.headers())
+                            .map(RetryDecisionKind::RetryAfter)
+                            .unwrap_or(RetryDecisionKind::Retry)
+                    }
+                    _ => RetryDecisionKind::Retry,
+                }
+            }
+            Ok(_) => RetryDecisionKind::Done,
+            Err(
+                crate::Error::TooManyRedirect(_, _)
+                | crate::Error::RedirectNotAllowed(_, _)
+                | crate::Error::CircuitOpen(_),
+            ) => RetryDecisionKind::Done,
+            Err(_) => RetryDecisionKind::Retry,
+        }
+    }
+}
+
+/// A retry budget shared by requests, preventing retry storms against a struggling upstream.
+///
+/// Every retry withdraws one token, and every successful attempt deposits `ratio` tokens, so
+/// retries are limited to about `ratio` of successful requests. The budget starts full.
+///
+/// # Example
+/// ```
+/// # use ergoreq::middleware::auto_retry_middleware::RetryBudget;
+/// # use ergoreq::ErgoClient;
+/// // at most about 20% of requests are retries
+/// let client = ErgoClient::new(reqwest::Client::new())
+///     .with_retry_count(3)
+///     .with_retry_budget(RetryBudget::new(0.2));
+/// ```
+pub struct RetryBudget {
+    ratio: f64,
+    max_tokens: f64,
+    tokens: Mutex<f64>,
+}
+
+impl RetryBudget {
+    /// Create a `RetryBudget` depositing `ratio` tokens per success, holding at most 10 tokens.
+    ///
+    /// # Panics
+    /// Panics if `ratio` is not a positive number.
+    pub fn new(ratio: f64) -> Self {
+        assert!(
+            ratio.is_finite() && ratio > 0.0,
+            "ratio of a retry budget must be positive"
+        );
+        Self {
+            ratio,
+            max_tokens: 10.0,
+            tokens: Mutex::new(10.0),
+        }
+    }
+
+    /// Set the most tokens the budget can hold, which is the largest burst of retries.
+    pub fn with_max_tokens(mut self, max_tokens: u32) -> Self {
+        self.max_tokens = max_tokens as f64;
+        *self.tokens.get_mut().unwrap_or_else(|e| e.into_inner()) = self.max_tokens;
+        self
+    }
+
+    /// Get the number of retries currently allowed.
+    pub fn available(&self) -> u32 {
+        *self.lock() as u32
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, f64> {
+        self.tokens.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn deposit(&self) {
+        let mut tokens = self.lock();
+        *tokens = (*tokens + self.ratio).min(self.max_tokens);
+    }
+
+    fn try_withdraw(&self) -> bool {
+        let mut tokens = self.lock();
+        if *tokens >= 1.0 {
+            *tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+type RetryObserver = dyn Fn(u32, &crate::Result<Response>, Duration) + Send + Sync + 'static;
+
+/// Options of auto retry, besides its [`RetryPolicy`].
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// # use ergoreq::middleware::auto_retry_middleware::RetryOptions;
+/// # use ergoreq::ErgoClient;
+/// let client = ErgoClient::new(reqwest::Client::new())
+///     .with_retry_count(3)
+///     .with_retry_options(RetryOptions::new().with_max_retry_after(Duration::from_secs(10)));
+/// ```
+#[derive(Clone)]
+pub struct RetryOptions {
+    max_retry_after: Duration,
+    classifier: Arc<dyn RetryClassifier>,
+    observer: Option<Arc<RetryObserver>>,
+    budget: Option<Arc<RetryBudget>>,
+    retry_non_idempotent: bool,
+    fallback_hosts: Vec<url::Url>,
+}
+
+impl RetryOptions {
+    /// Create `RetryOptions` with a [`DefaultRetryClassifier`], waiting at most 60 seconds for
+    /// `Retry-After`.
+    ///
+    /// Only idempotent methods (`GET`, `HEAD`, `PUT`, `DELETE`, `OPTIONS` and `TRACE`) and
+    /// requests with an `Idempotency-Key` header are retried.
+    pub fn new() -> Self {
+        Self {
+            max_retry_after: Duration::from_secs(60),
+            classifier: Arc::new(DefaultRetryClassifier::new()),
+            observer: None,
+            budget: None,
+            retry_non_idempotent: false,
+            fallback_hosts: vec![],
+        }
+    }
+
+    /// Set the response statuses to retry, replacing the default ones.
+    ///
+    /// When no retry is left, the last response is returned.
+    ///
+    /// # Notice
+    /// This replaces the classifier by a [`DefaultRetryClassifier`].
+    pub fn with_retry_statuses<I>(self, statuses: I) -> Self
+    where
+        I: IntoIterator<Item = StatusCode>,
+    {
+        self.with_classifier(DefaultRetryClassifier::with_retry_statuses(statuses))
+    }
+
+    /// Set the [`RetryClassifier`] deciding which attempts are retried.
+    pub fn with_classifier<C>(mut self, classifier: C) -> Self
+    where
+        C: RetryClassifier,
+    {
+        self.classifier = Arc::new(classifier);
+        self
+    }
+
+    /// Call `observer` before each retry, with the number of the retry (starting from `1`), the
+    /// result of the failed attempt and the delay before the retry.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::middleware::auto_retry_middleware::RetryOptions;
+    /// let options = RetryOptions::new().with_observer(|attempt, result, delay| {
+    ///     let cause = match result {
+    ///         Ok(response) => response.status().to_string(),
+    ///         Err(e) => e.to_string(),
+    ///     };
+    ///     tracing::warn!("Retry #{} in {:?}, caused by {}", attempt, delay, cause);
+    /// });
+    /// ```
+    pub fn with_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(u32, &crate::Result<Response>, Duration) + Send + Sync + 'static,
+    {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Whether to retry requests with non-idempotent methods like `POST` and `PATCH`, even
+    /// without an `Idempotency-Key` header.
+    pub fn with_retry_non_idempotent(mut self, retry_non_idempotent: bool) -> Self {
+        self.retry_non_idempotent = retry_non_idempotent;
+        self
+    }
+
+    /// Whether `req` can be retried without repeating its side effects.
+    fn is_retryable(&self, req: &Request) -> bool {
+        self.retry_non_idempotent
+            || req.headers().contains_key(IDEMPOTENCY_KEY)
+            || matches!(
+                *req.method(),
+                Method::GET
+                    | Method::HEAD
+                    | Method::PUT
+                    | Method::DELETE
+                    | Method::OPTIONS
+                    | Method::TRACE
+            )
+    }
+
+    /// Fail over to the given hosts in order, after the retries on the previous host are
+    /// exhausted.
+    ///
+    /// Only the scheme, host and port of the url are replaced, the path and query are kept.
+    /// The `Cookie` header of the previous host is not sent to fallback hosts. Retries start
+    /// over on each fallback host.
+    ///
+    /// # Panics
+    /// Panics if a host is not a valid url.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::middleware::auto_retry_middleware::RetryOptions;
+    /// let options = RetryOptions::new()
+    ///     .with_fallback_hosts(["https://backup1.example.com", "https://backup2.example.com"]);
+    /// ```
+    pub fn with_fallback_hosts<I, S>(mut self, hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.fallback_hosts = hosts
+            .into_iter()
+            .map(|v| url::Url::parse(v.as_ref()).expect("fallback host must be a valid url"))
+            .collect();
+        self
+    }
+
+    /// Limit retries with a [`RetryBudget`], which can be shared by several clients.
+    pub fn with_budget(mut self, budget: Arc<RetryBudget>) -> Self {
+        self.budget = Some(budget);
+        self
+    }
+
+    /// Set the longest wait honored from a `Retry-After` header, longer waits are capped.
+    pub fn with_max_retry_after(mut self, max_retry_after: Duration) -> Self {
+        self.max_retry_after = max_retry_after;
+        self
+    }
+}
+
+impl Default for RetryOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parse `Retry-After` in seconds or HTTP-date.
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers
+        .get(http::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        date.signed_duration_since(chrono::Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// The outcome of one attempt, recorded in [`RetryAttempts`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AttemptOutcome {
+    /// The attempt got a response with this status.
+    Status(StatusCode),
+    /// The attempt failed with this error message.
+    Error(String),
+}
+
+/// Attempts made by auto retry for a request.
+///
+/// It is inserted into the `Extensions` of the request, and can be read from the response with
+/// [`crate::ErgoResponse::extension`].
+#[derive(Clone, Debug, Default)]
+pub struct RetryAttempts {
+    outcomes: Vec<AttemptOutcome>,
+    total_backoff: Duration,
+}
+
+impl RetryAttempts {
+    /// Get the number of attempts, including the first one.
+    pub fn count(&self) -> usize {
+        self.outcomes.len()
+    }
+
+    /// Whether the request was retried.
+    pub fn is_retried(&self) -> bool {
+        self.count() > 1
+    }
+
+    /// Get the outcome of each attempt, in order.
+    pub fn outcomes(&self) -> &[AttemptOutcome] {
+        &self.outcomes
+    }
+
+    /// Get the total time waited between attempts.
+    pub fn total_backoff(&self) -> Duration {
+        self.total_backoff
+    }
+
+    fn record(&mut self, result: &crate::Result<Response>) {
+        self.outcomes.push(match result {
+            Ok(response) => AttemptOutcome::Status(response.status()),
+            Err(e) => AttemptOutcome::Error(e.to_string()),
+        });
+    }
+}
+
+pub(crate) struct AutoRetryMiddleware {
+    policy: Arc<dyn RetryPolicy + Send + Sync + 'static>,
+    options: RetryOptions,
+}
+
+impl AutoRetryMiddleware {
+    pub fn new(
+        policy: Arc<dyn RetryPolicy + Send + Sync + 'static>,
+        options: RetryOptions,
+    ) -> Self {
+        Self { policy, options }
+    }
+
+    /// Send `req` to the origin of `host` instead.
+    fn fail_over(req: &mut Request, host: &url::Url) {
+        replace_origin(req.url_mut(), host);
+        req.headers_mut().remove(http::header::COOKIE);
+        req.headers_mut().remove(http::header::HOST);
+    }
+
+    async fn run_attempts(
         &self,
         req: Request,
         ext: &mut Extensions,
         next: Next<'_>,
+        attempts: &mut RetryAttempts,
     ) -> crate::Result<Response> {
         let mut current_retry_times = 0;
-        let client = next.get_inner_client_owned();
-        let origin_req = match req.try_clone() {
-            Some(req) => req,
-            None => return next.run(req, ext).await,
+        let factory = ext.get::<BodyFactory>().cloned();
+        // `None` if the request cannot be retried
+        let mut origin_req = if !self.options.is_retryable(&req) {
+            tracing::debug!(
+                "Request method {} is not idempotent, will not retry",
+                req.method()
+            );
+            None
+        } else if factory.is_some() {
+            Some(BodyFactory::without_body(&req))
+        } else {
+            req.try_clone()
         };
-        let request_start_time = SystemTime::now();
-        let mut response = next.run(req, ext).await;
+        let mut fallback_hosts = self.options.fallback_hosts.iter();
+        let mut request_start_time = system_now();
+        // every attempt runs through the middlewares after this one
+        let mut response = next.clone().run(req, ext).await;
         loop {
-            if let Ok(response) = response {
-                return Ok(response);
-            } else {
-                let error = response.unwrap_err();
-                match error {
-                    crate::Error::TooManyRedirect(_, _) => return Err(error),
-                    _ => (),
-                };
-                current_retry_times += 1;
-                match self.0.should_retry(request_start_time, current_retry_times) {
-                    RetryDecision::Retry { execute_after } => {
-                        let should_wait_for = match execute_after.duration_since(SystemTime::now())
-                        {
-                            Ok(duration) => duration,
-                            Err(_) => std::time::Duration::from_secs(0),
-                        };
-                        if !should_wait_for.is_zero() {
-                            #[cfg(not(target_arch = "wasm32"))]
-                            tokio::time::sleep(should_wait_for).await;
-                            #[cfg(target_arch = "wasm32")]
-                            wasm_timer::Delay::new(should_wait_for)
-                                .await
-                                .expect("failed sleeping");
-                        }
-                        if let Some(req) = origin_req.try_clone() {
-                            response = client.execute(req).await.map_err(crate::Error::from);
-                        } else {
-                            return Err(error);
-                        }
+            attempts.record(&response);
+            let Some(origin_req) = &mut origin_req else {
+                return response;
+            };
+            let retry_after = match self.options.classifier.classify(&response) {
+                RetryDecisionKind::Done => {
+                    if let (Some(budget), Ok(_)) = (&self.options.budget, &response) {
+                        budget.deposit();
                     }
-                    RetryDecision::DoNotRetry => return Err(error),
+                    return response;
+                }
+                RetryDecisionKind::Retry => None,
+                RetryDecisionKind::RetryAfter(retry_after) => Some(retry_after),
+            };
+            current_retry_times += 1;
+            let should_wait_for = match self
+                .policy
+                .should_retry(request_start_time, current_retry_times)
+            {
+                // `Retry-After` of the server takes precedence over the backoff
+                RetryDecision::Retry { execute_after } => match retry_after {
+                    Some(retry_after) => retry_after.min(self.options.max_retry_after),
+                    None => execute_after
+                        .duration_since(system_now())
+                        .unwrap_or_default(),
+                },
+                RetryDecision::DoNotRetry => {
+                    let Some(host) = fallback_hosts.next() else {
+                        return response;
+                    };
+                    tracing::debug!("Retries exhausted, fail over to {}", host);
+                    Self::fail_over(origin_req, host);
+                    current_retry_times = 0;
+                    request_start_time = system_now();
+                    Duration::ZERO
+                }
+            };
+            // do not wait for an attempt which cannot finish before the deadline
+            if let Some(deadline) = ext.get::<Deadline>() {
+                if should_wait_for >= deadline.remaining() {
+                    return Err(crate::Error::DeadlineExceeded(deadline.budget()));
+                }
+            }
+            let req = match &factory {
+                Some(factory) => factory.rebuild(origin_req).await?,
+                None => match origin_req.try_clone() {
+                    Some(req) => req,
+                    None => return response,
+                },
+            };
+            if let Some(budget) = &self.options.budget {
+                if !budget.try_withdraw() {
+                    tracing::debug!("Retry budget exhausted, will not retry");
+                    return response;
+                }
+            }
+            if let Some(observer) = &self.options.observer {
+                observer(attempts.count() as u32, &response, should_wait_for);
+            }
+            // drain a small body, so the connection can be reused, or else drop it
+            if let Ok(previous) = response {
+                if content_length(&previous).is_some_and(|v| v <= MAX_RETRY_DRAIN) {
+                    let _ = previous.bytes().await;
                 }
             }
+            sleep(should_wait_for).await;
+            attempts.total_backoff += should_wait_for;
+            response = next.clone().run(req, ext).await;
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for AutoRetryMiddleware {
+    #[instrument(skip(self, ext, next))]
+    async fn handle(
+        &self,
+        req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::Result<Response> {
+        let mut attempts = RetryAttempts::default();
+        let response = self.run_attempts(req, ext, next, &mut attempts).await;
+        ext.insert(attempts);
+        response
+    }
+}
+
+#[cfg(test)]
+mod test_auto_retry_middleware {
+    use std::time::Duration;
+
+    use http::{HeaderMap, HeaderValue};
+
+    use super::{parse_retry_after, RetryBudget};
+
+    #[test]
+    fn test_parse_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(parse_retry_after(&headers), None);
+
+        headers.insert(http::header::RETRY_AFTER, HeaderValue::from_static(" 120 "));
+        assert_eq!(parse_retry_after(&headers), Some(Duration::from_secs(120)));
+
+        headers.insert(
+            http::header::RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(parse_retry_after(&headers), Some(Duration::ZERO));
+
+        let date = (chrono::Utc::now() + chrono::Duration::seconds(30)).to_rfc2822();
+        headers.insert(
+            http::header::RETRY_AFTER,
+            HeaderValue::from_str(&date).unwrap(),
+        );
+        let wait = parse_retry_after(&headers).unwrap();
+        assert!(wait > Duration::from_secs(28) && wait <= Duration::from_secs(30));
+
+        headers.insert(http::header::RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_retry_budget() {
+        let budget = RetryBudget::new(0.5).with_max_tokens(2);
+        assert!(budget.try_withdraw());
+        assert!(budget.try_withdraw());
+        assert!(!budget.try_withdraw());
+        budget.deposit();
+        assert!(!budget.try_withdraw());
+        budget.deposit();
+        assert_eq!(budget.available(), 1);
+        for _ in 0..10 {
+            budget.deposit();
         }
+        assert_eq!(budget.available(), 2);
     }
 }
@@ -0,0 +1,94 @@
+use std::sync::{Arc, Mutex};
+
+use futures::channel::oneshot;
+use futures::future::{select, Either, Shared};
+use futures::FutureExt;
+use http::Extensions;
+use reqwest::{Request, Response};
+
+use super::middleware::{Middleware, Next};
+
+/// Cancel requests from outside, see [`crate::ErgoRequestBuilder::with_cancellation_token`].
+///
+/// Clones share the same state, so cancelling one of them cancels every request using any
+/// clone. A token cannot be reset once cancelled.
+///
+/// # Example
+/// ```
+/// # use ergoreq::middleware::cancellation_middleware::CancellationToken;
+/// let token = CancellationToken::new();
+/// let child = token.clone();
+/// token.cancel();
+/// assert!(child.is_cancelled());
+/// ```
+#[derive(Clone, Debug)]
+pub struct CancellationToken {
+    sender: Arc<Mutex<Option<oneshot::Sender<()>>>>,
+    receiver: Shared<oneshot::Receiver<()>>,
+}
+
+impl CancellationToken {
+    /// Create a `CancellationToken` which is not cancelled.
+    pub fn new() -> Self {
+        let (sender, receiver) = oneshot::channel();
+        Self {
+            sender: Arc::new(Mutex::new(Some(sender))),
+            receiver: receiver.shared(),
+        }
+    }
+
+    /// Cancel every request using this token.
+    pub fn cancel(&self) {
+        // dropping the sender completes the receiver
+        self.sender.lock().unwrap().take();
+    }
+
+    /// Whether this token is cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.sender.lock().unwrap().is_none()
+    }
+
+    /// Wait until this token is cancelled.
+    pub async fn cancelled(&self) {
+        let _ = self.receiver.to_owned().await;
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fail the request with [`crate::Error::Cancelled`] as soon as the token is cancelled, even
+/// while waiting for a retry or following a redirect.
+pub(crate) struct CancellationMiddleware(CancellationToken);
+
+impl CancellationMiddleware {
+    pub fn new(token: CancellationToken) -> Self {
+        Self(token)
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for CancellationMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        if self.0.is_cancelled() {
+            return Err(crate::Error::Cancelled);
+        }
+        let cancelled = Box::pin(self.0.cancelled());
+        match select(Box::pin(next.run(req, ext)), cancelled).await {
+            Either::Left((result, _)) => result,
+            Either::Right(_) => {
+                tracing::debug!("Request cancelled");
+                Err(crate::Error::Cancelled)
+            }
+        }
+    }
+}
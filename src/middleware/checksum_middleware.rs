@@ -0,0 +1,197 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use http::{Extensions, HeaderName, HeaderValue};
+use md5::Md5;
+use reqwest::{Request, Response};
+use sha2::{Digest, Sha256, Sha512};
+
+use super::middleware::{Middleware, Next};
+
+/// A digest of the request body attached by [`ChecksumMiddleware`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChecksumAlgorithm {
+    /// `Content-MD5`, base64 encoded MD5 of the body.
+    ContentMd5,
+    /// `x-amz-content-sha256`, hex encoded SHA-256 of the body, as used by AWS Signature V4.
+    ///
+    /// Requests without a body get the SHA-256 of an empty payload.
+    AmzContentSha256,
+    /// `Content-Digest: sha-256=:...:`, as defined by RFC 9530.
+    ContentDigestSha256,
+    /// `Content-Digest: sha-512=:...:`, as defined by RFC 9530.
+    ContentDigestSha512,
+}
+
+impl ChecksumAlgorithm {
+    pub(crate) fn header_name(&self) -> HeaderName {
+        match self {
+            ChecksumAlgorithm::ContentMd5 => HeaderName::from_static("content-md5"),
+            ChecksumAlgorithm::AmzContentSha256 => HeaderName::from_static("x-amz-content-sha256"),
+            ChecksumAlgorithm::ContentDigestSha256 | ChecksumAlgorithm::ContentDigestSha512 => {
+                HeaderName::from_static("content-digest")
+            }
+        }
+    }
+
+    fn compute(&self, body: &[u8]) -> String {
+        let mut digest = BodyDigest::new(*self);
+        digest.update(body);
+        digest.finish()
+    }
+
+    /// Whether `header` (the value of [`Self::header_name`]) carries `digest`.
+    ///
+    /// `Content-Digest` may list several algorithms, any of them matches.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn header_matches(&self, header: &HeaderValue, digest: &str) -> bool {
+        let Ok(header) = header.to_str() else {
+            return false;
+        };
+        match self {
+            ChecksumAlgorithm::ContentMd5 => header.trim() == digest,
+            ChecksumAlgorithm::AmzContentSha256 => header.trim().eq_ignore_ascii_case(digest),
+            ChecksumAlgorithm::ContentDigestSha256 | ChecksumAlgorithm::ContentDigestSha512 => {
+                header.split(',').any(|v| v.trim() == digest)
+            }
+        }
+    }
+}
+
+/// An incremental digest of a body, formatted like the header of its [`ChecksumAlgorithm`].
+pub(crate) struct BodyDigest {
+    algorithm: ChecksumAlgorithm,
+    md5: Md5,
+    sha256: Sha256,
+    sha512: Sha512,
+}
+
+impl BodyDigest {
+    pub fn new(algorithm: ChecksumAlgorithm) -> Self {
+        Self {
+            algorithm,
+            md5: Md5::new(),
+            sha256: Sha256::new(),
+            sha512: Sha512::new(),
+        }
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) {
+        match self.algorithm {
+            ChecksumAlgorithm::ContentMd5 => self.md5.update(chunk),
+            ChecksumAlgorithm::AmzContentSha256 | ChecksumAlgorithm::ContentDigestSha256 => {
+                self.sha256.update(chunk)
+            }
+            ChecksumAlgorithm::ContentDigestSha512 => self.sha512.update(chunk),
+        }
+    }
+
+    pub fn finish(self) -> String {
+        match self.algorithm {
+            ChecksumAlgorithm::ContentMd5 => STANDARD.encode(self.md5.finalize()),
+            ChecksumAlgorithm::AmzContentSha256 => self
+                .sha256
+                .finalize()
+                .iter()
+                .map(|v| format!("{:02x}", v))
+                .collect(),
+            ChecksumAlgorithm::ContentDigestSha256 => {
+                format!("sha-256=:{}:", STANDARD.encode(self.sha256.finalize()))
+            }
+            ChecksumAlgorithm::ContentDigestSha512 => {
+                format!("sha-512=:{}:", STANDARD.encode(self.sha512.finalize()))
+            }
+        }
+    }
+}
+
+/// Attach digests of the request body as headers.
+///
+/// Digests are computed from buffered bodies only, requests with a stream body are sent
+/// without them. Several `Content-Digest` algorithms are joined into one header.
+///
+/// # Notice
+/// Headers which are already present are never recomputed. Retries and redirects replay the
+/// request after this middleware, so the body is hashed once however many attempts are made.
+///
+/// # Example
+/// ```
+/// # use ergoreq::middleware::checksum_middleware::{ChecksumAlgorithm, ChecksumMiddleware};
+/// # use ergoreq::ErgoClient;
+/// let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+///     ChecksumMiddleware::new(ChecksumAlgorithm::AmzContentSha256)
+///         .with_algorithm(ChecksumAlgorithm::ContentMd5),
+/// );
+/// ```
+pub struct ChecksumMiddleware {
+    algorithms: Vec<ChecksumAlgorithm>,
+}
+
+impl ChecksumMiddleware {
+    /// Create a `ChecksumMiddleware` attaching the digest of `algorithm`.
+    pub fn new(algorithm: ChecksumAlgorithm) -> Self {
+        Self {
+            algorithms: vec![algorithm],
+        }
+    }
+
+    /// Attach the digest of `algorithm` too.
+    pub fn with_algorithm(mut self, algorithm: ChecksumAlgorithm) -> Self {
+        if !self.algorithms.contains(&algorithm) {
+            self.algorithms.push(algorithm);
+        }
+        self
+    }
+
+    fn attach(&self, req: &mut Request) {
+        let body = match req.body() {
+            Some(body) => match body.as_bytes() {
+                Some(body) => Some(body),
+                None => {
+                    tracing::debug!("Body of {} is a stream, skip checksum", req.url());
+                    return;
+                }
+            },
+            None => None,
+        };
+
+        let mut values: Vec<(HeaderName, String)> = vec![];
+        for algorithm in &self.algorithms {
+            let name = algorithm.header_name();
+            if req.headers().contains_key(&name) {
+                continue;
+            }
+            let value = match (algorithm, body) {
+                (_, Some(body)) => algorithm.compute(body),
+                (ChecksumAlgorithm::AmzContentSha256, None) => algorithm.compute(&[]),
+                (_, None) => continue,
+            };
+            match values.iter_mut().find(|(k, _)| k == name) {
+                Some((_, existing)) => {
+                    existing.push_str(", ");
+                    existing.push_str(&value);
+                }
+                None => values.push((name, value)),
+            }
+        }
+
+        for (name, value) in values {
+            let value =
+                HeaderValue::try_from(value).expect("digest is always a valid header value");
+            req.headers_mut().insert(name, value);
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for ChecksumMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        self.attach(&mut req);
+        next.run(req, ext).await
+    }
+}
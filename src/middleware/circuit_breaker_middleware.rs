@@ -0,0 +1,236 @@
+use std::time::Duration;
+
+use dashmap::DashMap;
+use http::Extensions;
+use reqwest::{Request, Response};
+
+use super::middleware::{Middleware, MiddlewarePhase, Next};
+use crate::utils::timer::Instant;
+
+type FailurePredicate = Box<dyn Fn(&Response) -> bool + Send + Sync + 'static>;
+
+/// State of the circuit of a host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent normally.
+    Closed,
+    /// Requests are rejected locally with [`crate::Error::CircuitOpen`].
+    Open,
+    /// One probe request is sent to decide whether the circuit should be closed again.
+    HalfOpen,
+}
+
+enum HostCircuit {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { probe_started_at: Option<Instant> },
+}
+
+/// Stop sending requests to a failing host for a while.
+///
+/// Each host has its own circuit. After `failure_threshold` consecutive failures the circuit
+/// opens, and requests are rejected with [`crate::Error::CircuitOpen`] without being sent.
+/// After `cool_down`, the circuit becomes half-open and lets one probe request through: the
+/// circuit is closed if it succeeds, and opened again if it fails.
+///
+/// A request is failed if it returns an error, or its response has a `5xx` status. Use
+/// [`Self::with_failure_predicate`] to change how responses are judged.
+///
+/// It runs in [`MiddlewarePhase::PostRetry`] by default, so every failed attempt counts, and
+/// requests rejected by an open circuit are not retried.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// # use ergoreq::middleware::circuit_breaker_middleware::CircuitBreakerMiddleware;
+/// # use ergoreq::ErgoClient;
+/// let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+///     CircuitBreakerMiddleware::new()
+///         .with_failure_threshold(3)
+///         .with_cool_down(Duration::from_secs(10)),
+/// );
+/// ```
+pub struct CircuitBreakerMiddleware {
+    failure_threshold: u32,
+    cool_down: Duration,
+    is_failure: FailurePredicate,
+    circuits: DashMap<String, HostCircuit>,
+}
+
+impl CircuitBreakerMiddleware {
+    /// Create a `CircuitBreakerMiddleware` opening after 5 consecutive failures for 30 seconds.
+    pub fn new() -> Self {
+        Self {
+            failure_threshold: 5,
+            cool_down: Duration::from_secs(30),
+            is_failure: Box::new(|response| response.status().is_server_error()),
+            circuits: DashMap::new(),
+        }
+    }
+
+    /// Set how many consecutive failures open the circuit.
+    ///
+    /// # Panics
+    /// Panics if `threshold` is `0`.
+    pub fn with_failure_threshold(mut self, threshold: u32) -> Self {
+        assert!(threshold > 0, "failure threshold must be positive");
+        self.failure_threshold = threshold;
+        self
+    }
+
+    /// Set how long the circuit stays open before a probe request is allowed.
+    pub fn with_cool_down(mut self, cool_down: Duration) -> Self {
+        self.cool_down = cool_down;
+        self
+    }
+
+    /// Set how to judge whether a response is a failure.
+    ///
+    /// Errors returned by next middlewares are always failures.
+    pub fn with_failure_predicate<F>(mut self, predicate: F) -> Self
+    where
+        F: Fn(&Response) -> bool + Send + Sync + 'static,
+    {
+        self.is_failure = Box::new(predicate);
+        self
+    }
+
+    /// Get the circuit state of the given `host`.
+    pub fn state(&self, host: &str) -> CircuitState {
+        match self.circuits.get(&host.to_ascii_lowercase()).as_deref() {
+            None | Some(HostCircuit::Closed { .. }) => CircuitState::Closed,
+            Some(HostCircuit::Open { until }) if *until > Instant::now() => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Close the circuit of the given `host` manually.
+    pub fn reset(&self, host: &str) {
+        self.circuits.remove(&host.to_ascii_lowercase());
+    }
+
+    /// Decide whether a request can be sent, returns `true` if it is a probe.
+    fn acquire(&self, host: &str) -> crate::error::Result<bool> {
+        let mut circuit = self
+            .circuits
+            .entry(host.to_owned())
+            .or_insert(HostCircuit::Closed { failures: 0 });
+        let now = Instant::now();
+        match *circuit {
+            HostCircuit::Closed { .. } => Ok(false),
+            HostCircuit::Open { until } if until > now => {
+                Err(crate::Error::CircuitOpen(host.to_owned()))
+            }
+            // A probe which takes longer than the cool down is treated as lost.
+            HostCircuit::HalfOpen {
+                probe_started_at: Some(started_at),
+            } if now.duration_since(started_at) < self.cool_down => {
+                Err(crate::Error::CircuitOpen(host.to_owned()))
+            }
+            _ => {
+                tracing::debug!("Circuit of {} is half-open, send a probe request", host);
+                *circuit = HostCircuit::HalfOpen {
+                    probe_started_at: Some(now),
+                };
+                Ok(true)
+            }
+        }
+    }
+
+    fn record(&self, host: &str, is_probe: bool, failed: bool) {
+        let mut circuit = self
+            .circuits
+            .entry(host.to_owned())
+            .or_insert(HostCircuit::Closed { failures: 0 });
+        if !failed {
+            *circuit = HostCircuit::Closed { failures: 0 };
+            return;
+        }
+        let open = match *circuit {
+            HostCircuit::Closed { failures } if !is_probe => {
+                let failures = failures + 1;
+                *circuit = HostCircuit::Closed { failures };
+                failures >= self.failure_threshold
+            }
+            HostCircuit::Open { .. } => false,
+            _ => true,
+        };
+        if open {
+            tracing::debug!("Circuit of {} is open for {:?}", host, self.cool_down);
+            *circuit = HostCircuit::Open {
+                until: Instant::now() + self.cool_down,
+            };
+        }
+    }
+}
+
+impl Default for CircuitBreakerMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for CircuitBreakerMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        let host = match req.url().host_str() {
+            Some(host) => host.to_ascii_lowercase(),
+            None => return next.run(req, ext).await,
+        };
+        let is_probe = self.acquire(&host)?;
+        let result = next.run(req, ext).await;
+        let failed = match &result {
+            Ok(response) => (self.is_failure)(response),
+            Err(_) => true,
+        };
+        self.record(&host, is_probe, failed);
+        result
+    }
+
+    fn default_phase(&self) -> MiddlewarePhase {
+        MiddlewarePhase::PostRetry
+    }
+}
+
+#[cfg(test)]
+mod test_circuit_breaker_middleware {
+    use std::time::Duration;
+
+    use super::{CircuitBreakerMiddleware, CircuitState};
+
+    #[test]
+    fn test_circuit_transitions() {
+        let breaker = CircuitBreakerMiddleware::new()
+            .with_failure_threshold(2)
+            .with_cool_down(Duration::from_millis(50));
+
+        assert!(!breaker.acquire("example.com").unwrap());
+        breaker.record("example.com", false, true);
+        assert_eq!(breaker.state("example.com"), CircuitState::Closed);
+        breaker.record("example.com", false, true);
+        assert_eq!(breaker.state("example.com"), CircuitState::Open);
+        assert!(matches!(
+            breaker.acquire("example.com"),
+            Err(crate::Error::CircuitOpen(_))
+        ));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(breaker.state("example.com"), CircuitState::HalfOpen);
+        assert!(breaker.acquire("example.com").unwrap());
+        // Only one probe at a time.
+        assert!(breaker.acquire("example.com").is_err());
+        breaker.record("example.com", true, true);
+        assert_eq!(breaker.state("example.com"), CircuitState::Open);
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.acquire("example.com").unwrap());
+        breaker.record("example.com", true, false);
+        assert_eq!(breaker.state("example.com"), CircuitState::Closed);
+    }
+}
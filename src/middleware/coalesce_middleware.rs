@@ -0,0 +1,191 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use futures::channel::oneshot;
+use futures::future::{FutureExt, Shared};
+use http::{Extensions, HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use reqwest::{Request, Response};
+
+use super::middleware::{Middleware, Next};
+use crate::utils::response::response_from_parts;
+
+/// The error received by requests which waited for a failed coalesced request.
+#[derive(Debug)]
+pub struct CoalescedRequestError(Arc<str>);
+
+impl std::fmt::Display for CoalescedRequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Coalesced request failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for CoalescedRequestError {}
+
+struct BufferedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    url: url::Url,
+    body: Bytes,
+}
+
+impl BufferedResponse {
+    fn to_response(&self) -> Response {
+        response_from_parts(
+            self.status,
+            self.headers.to_owned(),
+            self.body.to_owned(),
+            self.url.to_owned(),
+        )
+    }
+}
+
+type SharedResult = Result<Arc<BufferedResponse>, Arc<str>>;
+
+type InFlight = Shared<oneshot::Receiver<SharedResult>>;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct RequestKey {
+    method: Method,
+    url: String,
+    vary: Vec<Option<HeaderValue>>,
+}
+
+/// Remove the in-flight entry even if the leading request is cancelled.
+struct InFlightGuard<'a> {
+    in_flight: &'a DashMap<RequestKey, InFlight>,
+    key: &'a RequestKey,
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight.remove(self.key);
+    }
+}
+
+/// Share one upstream response among identical concurrent requests.
+///
+/// Requests are identical if they have the same method, url and values of the vary headers
+/// (`Accept`, `Authorization` and `Cookie` by default). Only `GET` and `HEAD` requests are
+/// coalesced. The first request is sent, its body is buffered once, and every request that
+/// arrived while it was in flight receives a copy of the response.
+///
+/// If the first request fails, waiting requests fail with [`CoalescedRequestError`]
+/// wrapped in [`crate::Error::Custom`], see [`crate::Error::root`].
+pub struct CoalesceMiddleware {
+    vary_headers: Vec<HeaderName>,
+    in_flight: DashMap<RequestKey, InFlight>,
+}
+
+impl CoalesceMiddleware {
+    /// Create a `CoalesceMiddleware` with default vary headers.
+    pub fn new() -> Self {
+        Self {
+            vary_headers: vec![
+                http::header::ACCEPT,
+                http::header::AUTHORIZATION,
+                http::header::COOKIE,
+            ],
+            in_flight: DashMap::new(),
+        }
+    }
+
+    /// Add a header whose value must be equal for requests to be coalesced.
+    pub fn with_vary_header(mut self, header: HeaderName) -> Self {
+        if !self.vary_headers.contains(&header) {
+            self.vary_headers.push(header);
+        }
+        self
+    }
+
+    fn key(&self, req: &Request) -> RequestKey {
+        RequestKey {
+            method: req.method().to_owned(),
+            url: req.url().to_string(),
+            vary: self
+                .vary_headers
+                .iter()
+                .map(|name| req.headers().get(name).cloned())
+                .collect(),
+        }
+    }
+
+    async fn buffer(response: Response) -> crate::error::Result<BufferedResponse> {
+        let status = response.status();
+        let headers = response.headers().to_owned();
+        let url = response.url().to_owned();
+        let body = response.bytes().await?;
+        Ok(BufferedResponse {
+            status,
+            headers,
+            url,
+            body,
+        })
+    }
+}
+
+impl Default for CoalesceMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for CoalesceMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            return next.run(req, ext).await;
+        }
+        let key = self.key(&req);
+
+        let sender = match self.in_flight.entry(key.to_owned()) {
+            Entry::Occupied(entry) => {
+                let in_flight = entry.get().to_owned();
+                drop(entry);
+                tracing::debug!("Wait for in-flight request: {}", next.redact_url(req.url()));
+                return match in_flight.await {
+                    Ok(Ok(buffered)) => Ok(buffered.to_response()),
+                    Ok(Err(message)) => Err(crate::Error::Custom(Box::new(CoalescedRequestError(
+                        message,
+                    )))),
+                    // The leading request is cancelled, send this request by itself.
+                    Err(_) => next.run(req, ext).await,
+                };
+            }
+            Entry::Vacant(entry) => {
+                let (sender, receiver) = oneshot::channel();
+                entry.insert(receiver.shared());
+                sender
+            }
+        };
+
+        let guard = InFlightGuard {
+            in_flight: &self.in_flight,
+            key: &key,
+        };
+        let result = match next.run(req, ext).await {
+            Ok(response) => Self::buffer(response).await,
+            Err(e) => Err(e),
+        };
+        drop(guard);
+
+        match result {
+            Ok(buffered) => {
+                let buffered = Arc::new(buffered);
+                let _ = sender.send(Ok(buffered.to_owned()));
+                Ok(buffered.to_response())
+            }
+            Err(e) => {
+                let _ = sender.send(Err(e.to_string().into()));
+                Err(e)
+            }
+        }
+    }
+}
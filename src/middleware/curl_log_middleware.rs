@@ -0,0 +1,76 @@
+use http::Extensions;
+use reqwest::{Request, Response};
+use tracing::Level;
+
+use super::middleware::{Middleware, Next};
+use crate::utils::curl::request_to_curl_redacted;
+use crate::utils::redactor::Redactor;
+
+/// Log every request passing through this middleware as a `curl` command.
+///
+/// The command is emitted with `tracing` under the `ergoreq::curl` target. Cookies injected
+/// from the `CookieContainer` are already present when this middleware runs.
+///
+/// The logged command is redacted, so scrubbed secrets must be filled in before replaying it.
+/// Secrets are scrubbed by the [`Redactor`] of this middleware, or else the one of the
+/// `ErgoClient`, or else [`Redactor::new`]. Use `Redactor::empty()` to log everything.
+pub struct CurlLogMiddleware {
+    level: Level,
+    redactor: Option<Redactor>,
+}
+
+impl CurlLogMiddleware {
+    /// Create a `CurlLogMiddleware` logging with `DEBUG` level.
+    pub fn new() -> Self {
+        Self {
+            level: Level::DEBUG,
+            redactor: None,
+        }
+    }
+
+    /// Set the `tracing` level used to emit the command.
+    pub fn with_level(mut self, level: Level) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Scrub secrets with `redactor` instead of the one of the `ErgoClient`.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(redactor);
+        self
+    }
+}
+
+impl Default for CurlLogMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for CurlLogMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        let client_redactor = next.get_redactor();
+        let redactor = self
+            .redactor
+            .as_ref()
+            .or(client_redactor.as_deref())
+            .cloned()
+            .unwrap_or_default();
+        let command = request_to_curl_redacted(&req, &redactor);
+        match self.level {
+            Level::TRACE => tracing::trace!(target: "ergoreq::curl", "{}", command),
+            Level::DEBUG => tracing::debug!(target: "ergoreq::curl", "{}", command),
+            Level::INFO => tracing::info!(target: "ergoreq::curl", "{}", command),
+            Level::WARN => tracing::warn!(target: "ergoreq::curl", "{}", command),
+            Level::ERROR => tracing::error!(target: "ergoreq::curl", "{}", command),
+        }
+        next.run(req, ext).await
+    }
+}
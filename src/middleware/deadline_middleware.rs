@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use http::Extensions;
+use reqwest::{Request, Response};
+
+use super::middleware::{Middleware, Next};
+use crate::utils::timer::{timeout, Instant};
+
+/// The overall deadline of a request, set by `with_deadline`.
+///
+/// It is inserted into the `Extensions` of the request, so middlewares can budget their waits.
+#[derive(Clone, Copy, Debug)]
+pub struct Deadline {
+    budget: Duration,
+    expires_at: Instant,
+}
+
+impl Deadline {
+    fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            expires_at: Instant::now() + budget,
+        }
+    }
+
+    /// Get the total time allowed for the request.
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// Get the time left before the deadline.
+    pub fn remaining(&self) -> Duration {
+        let now = Instant::now();
+        if now >= self.expires_at {
+            Duration::ZERO
+        } else {
+            self.expires_at - now
+        }
+    }
+
+    /// Whether the deadline has passed.
+    pub fn is_expired(&self) -> bool {
+        self.remaining().is_zero()
+    }
+}
+
+/// Fail the request with [`crate::Error::DeadlineExceeded`] if all attempts and redirect hops
+/// take longer than the budget.
+pub(crate) struct DeadlineMiddleware(Duration);
+
+impl DeadlineMiddleware {
+    pub fn new(budget: Duration) -> Self {
+        Self(budget)
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for DeadlineMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        let deadline = Deadline::new(self.0);
+        ext.insert(deadline);
+        match timeout(self.0, next.run(req, ext)).await {
+            Some(result) => result,
+            None => {
+                tracing::debug!("Deadline of {:?} exceeded", self.0);
+                Err(crate::Error::DeadlineExceeded(self.0))
+            }
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::{Arc, Mutex};
+
+use http::{Extensions, HeaderMap, StatusCode};
+use reqwest::{Request, Response};
+
+use super::middleware::{Middleware, Next};
+use crate::utils::response::response_from_parts;
+
+/// Keep the request instead of sending it, answering an empty `200 OK`, see
+/// [`crate::ErgoRequestBuilder::dry_run`].
+///
+/// It must be the last middleware, so the request has every change of the others.
+pub(crate) struct DryRunMiddleware(Arc<Mutex<Option<Request>>>);
+
+impl DryRunMiddleware {
+    pub fn new(captured: Arc<Mutex<Option<Request>>>) -> Self {
+        Self(captured)
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for DryRunMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        _ext: &mut Extensions,
+        _next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        let url = req.url().to_owned();
+        *self.0.lock().unwrap() = Some(req);
+        Ok(response_from_parts(
+            StatusCode::OK,
+            HeaderMap::new(),
+            "",
+            url,
+        ))
+    }
+}
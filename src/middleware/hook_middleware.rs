@@ -0,0 +1,96 @@
+use futures::future::BoxFuture;
+use http::Extensions;
+use reqwest::{Request, Response};
+
+use super::middleware::{Middleware, Next};
+
+/// Run an async closure on each request before it is sent.
+///
+/// Created by [`crate::ErgoClient::on_request`] and
+/// [`crate::ErgoRequestBuilder::on_request`]. Returning an error aborts the request.
+pub struct OnRequestMiddleware<F> {
+    hook: F,
+}
+
+impl<F> OnRequestMiddleware<F>
+where
+    F: for<'a> Fn(&'a mut Request, &'a mut Extensions) -> BoxFuture<'a, crate::Result<()>>
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Create an `OnRequestMiddleware` running `hook`.
+    pub fn new(hook: F) -> Self {
+        Self { hook }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl<F> Middleware for OnRequestMiddleware<F>
+where
+    F: for<'a> Fn(&'a mut Request, &'a mut Extensions) -> BoxFuture<'a, crate::Result<()>>
+        + Send
+        + Sync
+        + 'static,
+{
+    async fn handle(
+        &self,
+        mut req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        (self.hook)(&mut req, ext).await?;
+        next.run(req, ext).await
+    }
+
+    fn name(&self) -> &'static str {
+        concat!(module_path!(), "::OnRequestMiddleware")
+    }
+}
+
+/// Run an async closure on each response before it is returned.
+///
+/// Created by [`crate::ErgoClient::on_response`] and
+/// [`crate::ErgoRequestBuilder::on_response`]. Returning an error fails the request.
+pub struct OnResponseMiddleware<F> {
+    hook: F,
+}
+
+impl<F> OnResponseMiddleware<F>
+where
+    F: for<'a> Fn(&'a mut Response, &'a mut Extensions) -> BoxFuture<'a, crate::Result<()>>
+        + Send
+        + Sync
+        + 'static,
+{
+    /// Create an `OnResponseMiddleware` running `hook`.
+    pub fn new(hook: F) -> Self {
+        Self { hook }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl<F> Middleware for OnResponseMiddleware<F>
+where
+    F: for<'a> Fn(&'a mut Response, &'a mut Extensions) -> BoxFuture<'a, crate::Result<()>>
+        + Send
+        + Sync
+        + 'static,
+{
+    async fn handle(
+        &self,
+        req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        let mut response = next.run(req, ext).await?;
+        (self.hook)(&mut response, ext).await?;
+        Ok(response)
+    }
+
+    fn name(&self) -> &'static str {
+        concat!(module_path!(), "::OnResponseMiddleware")
+    }
+}
@@ -1,12 +1,37 @@
 use crate::cookie::cookie_container::CookieContainer;
 use crate::cookie::cookie_parser::ErgoCookieParser;
-use async_trait::async_trait;
+use crate::utils::redactor::Redactor;
+use crate::utils::response::limit_body;
+use crate::utils::timer::Instant;
+use crate::wrappers::client_pool::ClientPool;
+use crate::wrappers::response_wrapper::RequestTimings;
 use http::{Extensions, HeaderValue};
 use reqwest::{Request, Response};
 use std::sync::Arc;
 use tracing::instrument;
 
-#[async_trait]
+/// Where a middleware runs relative to the built-in auto redirect and auto retry middlewares.
+///
+/// Phases run in declaration order. Within a phase, global middlewares run before per-request
+/// ones, each in insertion order.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MiddlewarePhase {
+    /// Before auto redirect, the middleware sees the overall request once.
+    #[default]
+    PreRedirect,
+    /// After auto redirect and before auto retry, the middleware sees every redirect hop.
+    PreRetry,
+    /// After auto retry, closest to the transport, the middleware sees every attempt.
+    PostRetry,
+}
+
+/// A middleware wrapping the execution of requests.
+///
+/// # Notice
+/// On `wasm32` the futures of `reqwest` are not `Send`, so implement this trait with
+/// `#[async_trait(?Send)]` there.
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
 pub trait Middleware: 'static + Send + Sync {
     /// Handle each request and can make changes for `Request` and `Response`
     async fn handle(
@@ -15,6 +40,21 @@ pub trait Middleware: 'static + Send + Sync {
         ext: &mut Extensions,
         next: Next<'_>,
     ) -> crate::error::Result<Response>;
+
+    /// The name of this middleware, used to inspect middleware stacks.
+    ///
+    /// Defaults to the type name.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// The phase this middleware runs in when added without one, like with
+    /// [`crate::ErgoClient::with_middleware`].
+    ///
+    /// Defaults to [`MiddlewarePhase::PreRedirect`].
+    fn default_phase(&self) -> MiddlewarePhase {
+        MiddlewarePhase::default()
+    }
 }
 
 /// This struct is used to execute `Request` with [`Middleware`]s
@@ -28,6 +68,10 @@ pub struct Next<'a> {
     client: &'a reqwest::Client,
     middlewares: &'a [Arc<dyn Middleware>],
     cookie_store: Option<Arc<dyn CookieContainer>>,
+    client_pool: Option<Arc<ClientPool>>,
+    redactor: Option<Arc<Redactor>>,
+    max_response_bytes: Option<u64>,
+    position: usize,
 }
 
 impl<'a> Next<'a> {
@@ -35,11 +79,18 @@ impl<'a> Next<'a> {
         client: &'a reqwest::Client,
         middlewares: &'a [Arc<dyn Middleware>],
         cookie_store: Option<Arc<dyn CookieContainer>>,
+        client_pool: Option<Arc<ClientPool>>,
+        redactor: Option<Arc<Redactor>>,
+        max_response_bytes: Option<u64>,
     ) -> Self {
         Self {
             client,
             middlewares,
             cookie_store,
+            client_pool,
+            redactor,
+            max_response_bytes,
+            position: 0,
         }
     }
 
@@ -74,6 +125,9 @@ impl<'a> Next<'a> {
     /// Acquired the actual response.
     ///
     /// Run this method will stop running middlewares left for this request permanently.
+    ///
+    /// The body is limited by [`crate::ErgoRequestBuilder::with_max_response_bytes`] here, so
+    /// middlewares buffering bodies (like caches) never read more than the limit.
     #[instrument(skip(self))]
     pub async fn run_without_middleware(self, mut req: Request) -> crate::error::Result<Response> {
         Self::set_cookie_header(self.cookie_store.to_owned(), &mut req);
@@ -83,13 +137,51 @@ impl<'a> Next<'a> {
             .await
             .map_err(crate::error::Error::from)?;
         Self::store_cookies(self.cookie_store, &response);
-        Ok(response)
+        match self.max_response_bytes {
+            Some(max_bytes) => limit_body(response, max_bytes),
+            None => Ok(response),
+        }
     }
 
     pub fn get_inner_client_owned(&self) -> reqwest::Client {
         self.client.to_owned()
     }
 
+    /// Get the [`ClientPool`] of the `ErgoClient` sending this request.
+    pub fn get_client_pool(&self) -> Option<Arc<ClientPool>> {
+        self.client_pool.to_owned()
+    }
+
+    /// Get the [`Redactor`] of the `ErgoClient` sending this request.
+    pub fn get_redactor(&self) -> Option<Arc<Redactor>> {
+        self.redactor.to_owned()
+    }
+
+    /// Redact `url` for logs with the [`Redactor`] of the `ErgoClient`, or else
+    /// [`Redactor::new`].
+    pub fn redact_url(&self, url: &url::Url) -> url::Url {
+        match &self.redactor {
+            Some(redactor) => redactor.redact_url(url),
+            None => Redactor::new().redact_url(url),
+        }
+    }
+
+    /// Send this request with `client` instead, for the left middlewares.
+    pub fn with_client<'b>(self, client: &'b reqwest::Client) -> Next<'b>
+    where
+        'a: 'b,
+    {
+        Next {
+            client,
+            middlewares: self.middlewares,
+            cookie_store: self.cookie_store,
+            client_pool: self.client_pool,
+            redactor: self.redactor,
+            max_response_bytes: self.max_response_bytes,
+            position: self.position,
+        }
+    }
+
     /// Pass this `Request` to next middleware, wait for `Response`
     ///
     /// You can pass some useful information by adding [`http::Extensions`] in `extensions` parameter
@@ -97,22 +189,29 @@ impl<'a> Next<'a> {
     pub async fn run(
         mut self,
         mut req: Request,
-        extensions: &'a mut Extensions,
+        extensions: &mut Extensions,
     ) -> crate::error::Result<Response> {
         if let Some((current, left)) = self.middlewares.split_first() {
             tracing::debug!("Run request with middleware");
             self.middlewares = left;
+            let position = self.position;
+            self.position += 1;
             let cookie_container = self.cookie_store.to_owned();
             Self::set_cookie_header(cookie_container.to_owned(), &mut req);
             let response = current
                 .handle(req, extensions, self)
                 .await
-                .map_err(crate::error::Error::from)?;
+                .map_err(|e| e.in_middleware(current.name(), position))?;
             Self::store_cookies(cookie_container, &response);
             Ok(response)
         } else {
             tracing::debug!("No middleware found, will run without middleware");
-            self.run_without_middleware(req).await
+            let started_at = Instant::now();
+            let response = self.run_without_middleware(req).await;
+            if let Some(timings) = extensions.get_mut::<RequestTimings>() {
+                timings.attempt(started_at);
+            }
+            response
         }
     }
 }
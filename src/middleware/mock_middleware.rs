@@ -0,0 +1,289 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use http::{Extensions, HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use regex::Regex;
+use reqwest::{Request, Response};
+use serde::Serialize;
+
+use super::middleware::{Middleware, Next};
+use crate::utils::response::response_from_parts;
+
+type HeaderPredicate = Box<dyn Fn(&HeaderValue) -> bool + Send + Sync + 'static>;
+
+/// A canned response returned by a [`MockRule`].
+#[derive(Clone, Debug)]
+pub struct MockResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Vec<u8>,
+}
+
+impl MockResponse {
+    /// Create an empty response with given `status`.
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            status,
+            headers: HeaderMap::new(),
+            body: vec![],
+        }
+    }
+
+    /// Add a header to this response.
+    ///
+    /// # Panics
+    /// Panics if `key` or `value` is not a valid header.
+    pub fn header(mut self, key: &str, value: &str) -> Self {
+        self.headers.append(
+            HeaderName::try_from(key).expect("invalid header name"),
+            HeaderValue::try_from(value).expect("invalid header value"),
+        );
+        self
+    }
+
+    /// Set the body of this response.
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    /// Set a json body and the `Content-Type` header of this response.
+    ///
+    /// # Panics
+    /// Panics if `json` cannot be serialized.
+    pub fn json<T: Serialize + ?Sized>(mut self, json: &T) -> Self {
+        self.body = serde_json::to_vec(json).expect("failed to serialize mock body");
+        self.headers.insert(
+            http::header::CONTENT_TYPE,
+            HeaderValue::from_static("application/json"),
+        );
+        self
+    }
+}
+
+/// A matcher with a canned response, registered in [`MockMiddleware`].
+///
+/// A rule without any matcher matches every request.
+pub struct MockRule {
+    method: Option<Method>,
+    path: Option<Regex>,
+    headers: Vec<(HeaderName, HeaderPredicate)>,
+    response: MockResponse,
+    expected_calls: Option<usize>,
+    calls: AtomicUsize,
+}
+
+impl MockRule {
+    /// Create a rule matching every request and responding `200 OK` with empty body.
+    pub fn new() -> Self {
+        Self {
+            method: None,
+            path: None,
+            headers: vec![],
+            response: MockResponse::new(StatusCode::OK),
+            expected_calls: None,
+            calls: AtomicUsize::new(0),
+        }
+    }
+
+    /// Only match requests with given `method`.
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = Some(method);
+        self
+    }
+
+    /// Only match requests whose url path matches given regex.
+    ///
+    /// # Panics
+    /// Panics if `pattern` is not a valid regex.
+    pub fn path_regex(mut self, pattern: &str) -> Self {
+        self.path = Some(Regex::new(pattern).expect("invalid path regex"));
+        self
+    }
+
+    /// Only match requests having header `key` with exactly `value`.
+    ///
+    /// # Panics
+    /// Panics if `key` is not a valid header name.
+    pub fn header(self, key: &str, value: &str) -> Self {
+        let value = value.to_owned();
+        self.header_matches(key, move |v| v.as_bytes() == value.as_bytes())
+    }
+
+    /// Only match requests having header `key` for which `predicate` returns `true`.
+    ///
+    /// # Panics
+    /// Panics if `key` is not a valid header name.
+    pub fn header_matches<F>(mut self, key: &str, predicate: F) -> Self
+    where
+        F: Fn(&HeaderValue) -> bool + Send + Sync + 'static,
+    {
+        self.headers.push((
+            HeaderName::try_from(key).expect("invalid header name"),
+            Box::new(predicate),
+        ));
+        self
+    }
+
+    /// Set the response returned when this rule matches.
+    pub fn respond_with(mut self, response: MockResponse) -> Self {
+        self.response = response;
+        self
+    }
+
+    /// Expect this rule to be matched exactly `times` times.
+    ///
+    /// Check it with [`MockMiddleware::assert_expectations`].
+    pub fn expect(mut self, times: usize) -> Self {
+        self.expected_calls = Some(times);
+        self
+    }
+
+    fn is_match(&self, req: &Request) -> bool {
+        if let Some(method) = &self.method {
+            if method != req.method() {
+                return false;
+            }
+        }
+        if let Some(path) = &self.path {
+            if !path.is_match(req.url().path()) {
+                return false;
+            }
+        }
+        self.headers
+            .iter()
+            .all(|(name, predicate)| req.headers().get_all(name).iter().any(predicate))
+    }
+}
+
+impl Default for MockRule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Respond to requests with canned responses instead of sending them.
+///
+/// Rules are tried in registration order, the first matched rule answers the request.
+/// Requests that match no rule fail with [`crate::Error::MockNotMatched`], unless
+/// [`MockMiddleware::passthrough_unmatched`] is enabled.
+///
+/// # Notice
+/// Register it as the last middleware, because middlewares after it will not run for matched
+/// requests. Requires the `mock` feature, usually enabled for `dev-dependencies` only.
+///
+/// # Example
+/// ```
+/// # use std::sync::Arc;
+/// # use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+/// # use ergoreq::ErgoClient;
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mock = Arc::new(
+///     MockMiddleware::new().with_rule(
+///         MockRule::new()
+///             .method(http::Method::GET)
+///             .path_regex(r"^/users/\d+$")
+///             .respond_with(MockResponse::new(http::StatusCode::OK).body("ergo"))
+///             .expect(1),
+///     ),
+/// );
+/// let client = ErgoClient::new(reqwest::Client::new()).with_middleware_arc(mock.clone());
+///
+/// let response = client.get("https://example.com/users/1").send().await.unwrap();
+/// assert_eq!(response.text().await.unwrap(), "ergo");
+/// mock.assert_expectations();
+/// # }
+/// ```
+pub struct MockMiddleware {
+    rules: Vec<MockRule>,
+    passthrough_unmatched: bool,
+}
+
+impl MockMiddleware {
+    /// Create a `MockMiddleware` without any rule.
+    pub fn new() -> Self {
+        Self {
+            rules: vec![],
+            passthrough_unmatched: false,
+        }
+    }
+
+    /// Register a rule.
+    pub fn with_rule(mut self, rule: MockRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Pass unmatched requests to next middleware instead of returning an error.
+    pub fn passthrough_unmatched(mut self, passthrough: bool) -> Self {
+        self.passthrough_unmatched = passthrough;
+        self
+    }
+
+    /// How many times the rule at `index` (in registration order) was matched.
+    pub fn call_count(&self, index: usize) -> usize {
+        self.rules
+            .get(index)
+            .map(|v| v.calls.load(Ordering::SeqCst))
+            .unwrap_or_default()
+    }
+
+    /// Check all call-count expectations set with [`MockRule::expect`].
+    ///
+    /// # Panics
+    /// Panics with all unmet expectations.
+    pub fn assert_expectations(&self) {
+        let unmet = self
+            .rules
+            .iter()
+            .enumerate()
+            .filter_map(|(index, rule)| {
+                let calls = rule.calls.load(Ordering::SeqCst);
+                match rule.expected_calls {
+                    Some(expected) if expected != calls => Some(format!(
+                        "rule #{index} expected {expected} call(s), got {calls}"
+                    )),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        if !unmet.is_empty() {
+            panic!("Mock expectations not met: {}", unmet.join("; "));
+        }
+    }
+}
+
+impl Default for MockMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for MockMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        match self.rules.iter().find(|rule| rule.is_match(&req)) {
+            Some(rule) => {
+                rule.calls.fetch_add(1, Ordering::SeqCst);
+                tracing::debug!("Mocked response for {} {}", req.method(), req.url());
+                Ok(response_from_parts(
+                    rule.response.status,
+                    rule.response.headers.to_owned(),
+                    rule.response.body.to_owned(),
+                    req.url().to_owned(),
+                ))
+            }
+            None if self.passthrough_unmatched => next.run(req, ext).await,
+            None => Err(crate::Error::MockNotMatched(
+                req.method().to_owned(),
+                req.url().to_owned(),
+            )),
+        }
+    }
+}
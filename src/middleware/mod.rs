@@ -1,5 +1,50 @@
+#[allow(clippy::module_inception)]
 pub mod middleware;
 
 pub mod auto_redirect_middleware;
 
 pub mod auto_retry_middleware;
+
+pub mod deadline_middleware;
+
+pub mod cancellation_middleware;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod dry_run_middleware;
+
+pub mod curl_log_middleware;
+
+#[cfg(all(feature = "mock", not(target_arch = "wasm32")))]
+pub mod mock_middleware;
+
+pub mod rate_limit_middleware;
+
+pub mod circuit_breaker_middleware;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod coalesce_middleware;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod revalidation_middleware;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod throttle_middleware;
+
+pub mod checksum_middleware;
+
+pub mod oauth2_client_credentials_middleware;
+
+pub mod oauth2_refresh_token_middleware;
+
+pub mod oauth1_middleware;
+
+pub mod jwt_middleware;
+
+pub mod api_key_rotation_middleware;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod proxy_rotation_middleware;
+
+pub mod hook_middleware;
+
+pub mod robots_middleware;
@@ -0,0 +1,195 @@
+use std::time::Duration;
+
+use futures::lock::Mutex;
+use http::{Extensions, HeaderValue, StatusCode};
+use reqwest::{Request, Response};
+use serde::Deserialize;
+
+use super::middleware::{Middleware, Next};
+use crate::utils::timer::Instant;
+use crate::wrappers::client_wrapper::ErgoClient;
+
+/// A successful response of an OAuth2 token endpoint.
+#[derive(Clone, Deserialize)]
+pub(crate) struct TokenResponse {
+    pub(crate) access_token: String,
+    pub(crate) expires_in: Option<u64>,
+    pub(crate) refresh_token: Option<String>,
+}
+
+impl TokenResponse {
+    /// Send a token request, failing with [`crate::Error::OAuth2TokenRequest`] on error status.
+    pub(crate) async fn fetch(
+        request: crate::wrappers::request_builder_wrapper::ErgoRequestBuilder,
+    ) -> crate::error::Result<Self> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(crate::Error::OAuth2TokenRequest(status, body));
+        }
+        Ok(response.json().await?)
+    }
+
+    pub(crate) fn expires_at(&self) -> Option<Instant> {
+        self.expires_in
+            .map(|v| Instant::now() + Duration::from_secs(v))
+    }
+}
+
+struct CachedToken {
+    header: HeaderValue,
+    expires_at: Option<Instant>,
+}
+
+/// Authorize requests with tokens of the OAuth2 client credentials grant.
+///
+/// Tokens are fetched from `token_url` and cached until they are about to expire
+/// (30 seconds before by default). Concurrent requests share one token request instead of
+/// stampeding the token endpoint. The client credentials are sent with HTTP Basic
+/// authentication unless [`Self::with_credentials_in_body`] is set.
+///
+/// Token requests are sent with the `token_client` given to [`Self::new`], so its proxies,
+/// default headers and middlewares (for example retries) apply to them.
+///
+/// # Notice
+/// Don't add this middleware to `token_client`, token requests would wait for themselves.
+///
+/// A `401 Unauthorized` response drops the cached token, so the next request fetches a new one.
+///
+/// # Example
+/// ```
+/// # use ergoreq::middleware::oauth2_client_credentials_middleware::OAuth2ClientCredentialsMiddleware;
+/// # use ergoreq::ErgoClient;
+/// let token_client = ErgoClient::new(reqwest::Client::new());
+/// let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+///     OAuth2ClientCredentialsMiddleware::new(
+///         token_client,
+///         "https://auth.example.com/token",
+///         "id",
+///         "secret",
+///     )
+///     .with_scope("read write"),
+/// );
+/// ```
+pub struct OAuth2ClientCredentialsMiddleware {
+    token_url: String,
+    client_id: String,
+    client_secret: String,
+    scope: Option<String>,
+    credentials_in_body: bool,
+    refresh_margin: Duration,
+    token_client: ErgoClient,
+    token: Mutex<Option<CachedToken>>,
+}
+
+impl OAuth2ClientCredentialsMiddleware {
+    /// Create an `OAuth2ClientCredentialsMiddleware` sending token requests to `token_url` with
+    /// `token_client` and the client credentials.
+    pub fn new(
+        token_client: ErgoClient,
+        token_url: &str,
+        client_id: &str,
+        client_secret: &str,
+    ) -> Self {
+        Self {
+            token_url: token_url.to_owned(),
+            client_id: client_id.to_owned(),
+            client_secret: client_secret.to_owned(),
+            scope: None,
+            credentials_in_body: false,
+            refresh_margin: Duration::from_secs(30),
+            token_client,
+            token: Mutex::new(None),
+        }
+    }
+
+    /// Request tokens with the given `scope`.
+    pub fn with_scope(mut self, scope: &str) -> Self {
+        self.scope = Some(scope.to_owned());
+        self
+    }
+
+    /// Send `client_id` and `client_secret` as form fields instead of HTTP Basic authentication.
+    pub fn with_credentials_in_body(mut self, in_body: bool) -> Self {
+        self.credentials_in_body = in_body;
+        self
+    }
+
+    /// Fetch a new token when the cached one expires within `margin`.
+    pub fn with_refresh_margin(mut self, margin: Duration) -> Self {
+        self.refresh_margin = margin;
+        self
+    }
+
+    /// Drop the cached token.
+    pub async fn invalidate(&self) {
+        *self.token.lock().await = None;
+    }
+
+    async fn fetch_token(&self) -> crate::error::Result<CachedToken> {
+        let mut form = vec![("grant_type", "client_credentials")];
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope));
+        }
+        let mut request = self.token_client.post(&self.token_url);
+        if self.credentials_in_body {
+            form.push(("client_id", &self.client_id));
+            form.push(("client_secret", &self.client_secret));
+        } else {
+            request = request.basic_auth(&self.client_id, Some(&self.client_secret));
+        }
+
+        tracing::debug!("Fetch OAuth2 token from {}", self.token_url);
+        let token = TokenResponse::fetch(request.form(&form)).await?;
+        let header = HeaderValue::try_from(format!("Bearer {}", token.access_token))
+            .map_err(|e| crate::Error::Internal(Box::new(e)))?;
+        Ok(CachedToken {
+            header,
+            expires_at: token.expires_at(),
+        })
+    }
+
+    async fn authorization(&self) -> crate::error::Result<HeaderValue> {
+        // Holding the lock while fetching makes concurrent requests wait for the same token.
+        let mut token = self.token.lock().await;
+        let fresh = token.as_ref().is_some_and(|v| {
+            v.expires_at
+                .map(|expires_at| Instant::now() + self.refresh_margin < expires_at)
+                .unwrap_or(true)
+        });
+        if !fresh {
+            *token = Some(self.fetch_token().await?);
+        }
+        Ok(token
+            .as_ref()
+            .map(|v| v.header.to_owned())
+            .expect("token is fetched"))
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for OAuth2ClientCredentialsMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        let authorization = self.authorization().await?;
+        req.headers_mut()
+            .insert(http::header::AUTHORIZATION, authorization.to_owned());
+
+        let response = next.run(req, ext).await?;
+        if response.status() == StatusCode::UNAUTHORIZED {
+            let mut token = self.token.lock().await;
+            // Another request may have replaced the rejected token already.
+            if token.as_ref().is_some_and(|v| v.header == authorization) {
+                tracing::debug!("Token is rejected, drop it");
+                *token = None;
+            }
+        }
+        Ok(response)
+    }
+}
@@ -0,0 +1,197 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::lock::Mutex;
+use http::{Extensions, HeaderValue, StatusCode};
+use reqwest::{Request, Response};
+
+use super::middleware::{Middleware, Next};
+use super::oauth2_client_credentials_middleware::TokenResponse;
+use crate::utils::timer::Instant;
+use crate::wrappers::client_wrapper::ErgoClient;
+
+type RotationHook = Arc<dyn Fn(&str) + Send + Sync + 'static>;
+
+struct TokenPair {
+    header: HeaderValue,
+    refresh_token: String,
+    expires_at: Option<Instant>,
+}
+
+/// Authorize requests with an OAuth2 access token, refreshing it with a refresh token.
+///
+/// The access token is refreshed when it is about to expire (30 seconds before by default), or
+/// when a response is `401 Unauthorized`. In the latter case the original request is replayed
+/// once with the new token, if its body can be cloned. Concurrent requests share one refresh.
+///
+/// When the token endpoint rotates the refresh token, the hook set by
+/// [`Self::on_refresh_token_rotated`] receives the new one, so it can be persisted.
+///
+/// Refresh requests are sent with the `token_client` given to [`Self::new`], so its proxies,
+/// default headers and middlewares (for example retries) apply to them.
+///
+/// # Notice
+/// Don't add this middleware to `token_client`, refresh requests would wait for themselves.
+///
+/// # Example
+/// ```
+/// # use ergoreq::middleware::oauth2_refresh_token_middleware::OAuth2RefreshTokenMiddleware;
+/// # use ergoreq::ErgoClient;
+/// let token_client = ErgoClient::new(reqwest::Client::new());
+/// let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+///     OAuth2RefreshTokenMiddleware::new(
+///         token_client,
+///         "https://auth.example.com/token",
+///         "id",
+///         "access-token",
+///         "refresh-token",
+///     )
+///     .on_refresh_token_rotated(|token| println!("save {token}")),
+/// );
+/// ```
+pub struct OAuth2RefreshTokenMiddleware {
+    token_url: String,
+    client_id: String,
+    client_secret: Option<String>,
+    refresh_margin: Duration,
+    token_client: ErgoClient,
+    rotation_hook: Option<RotationHook>,
+    tokens: Mutex<TokenPair>,
+}
+
+impl OAuth2RefreshTokenMiddleware {
+    /// Create an `OAuth2RefreshTokenMiddleware` sending refresh requests to `token_url` with
+    /// `token_client`, a public client id and the current token pair.
+    ///
+    /// The access token is assumed not to expire until it is rejected, use
+    /// [`Self::with_expires_in`] if its lifetime is known.
+    ///
+    /// # Panics
+    /// Panics if `access_token` is not a valid header value.
+    pub fn new(
+        token_client: ErgoClient,
+        token_url: &str,
+        client_id: &str,
+        access_token: &str,
+        refresh_token: &str,
+    ) -> Self {
+        Self {
+            token_url: token_url.to_owned(),
+            client_id: client_id.to_owned(),
+            client_secret: None,
+            refresh_margin: Duration::from_secs(30),
+            token_client,
+            rotation_hook: None,
+            tokens: Mutex::new(TokenPair {
+                header: Self::bearer(access_token).expect("invalid access token"),
+                refresh_token: refresh_token.to_owned(),
+                expires_at: None,
+            }),
+        }
+    }
+
+    /// Authenticate refresh requests with `client_secret`, using HTTP Basic authentication.
+    pub fn with_client_secret(mut self, client_secret: &str) -> Self {
+        self.client_secret = Some(client_secret.to_owned());
+        self
+    }
+
+    /// Set the remaining lifetime of the initial access token.
+    pub fn with_expires_in(mut self, expires_in: Duration) -> Self {
+        self.tokens.get_mut().expires_at = Some(Instant::now() + expires_in);
+        self
+    }
+
+    /// Refresh the access token when it expires within `margin`.
+    pub fn with_refresh_margin(mut self, margin: Duration) -> Self {
+        self.refresh_margin = margin;
+        self
+    }
+
+    /// Call `hook` with the new refresh token whenever the token endpoint rotates it.
+    pub fn on_refresh_token_rotated<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&str) + Send + Sync + 'static,
+    {
+        self.rotation_hook = Some(Arc::new(hook));
+        self
+    }
+
+    fn bearer(access_token: &str) -> crate::error::Result<HeaderValue> {
+        HeaderValue::try_from(format!("Bearer {}", access_token))
+            .map_err(|e| crate::Error::Internal(Box::new(e)))
+    }
+
+    async fn refresh(&self, tokens: &mut TokenPair) -> crate::error::Result<()> {
+        let mut form = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", tokens.refresh_token.as_str()),
+        ];
+        let mut request = self.token_client.post(&self.token_url);
+        match &self.client_secret {
+            Some(secret) => request = request.basic_auth(&self.client_id, Some(secret)),
+            None => form.push(("client_id", &self.client_id)),
+        }
+
+        tracing::debug!("Refresh OAuth2 token with {}", self.token_url);
+        let token = TokenResponse::fetch(request.form(&form)).await?;
+        tokens.header = Self::bearer(&token.access_token)?;
+        tokens.expires_at = token.expires_at();
+        if let Some(refresh_token) = token.refresh_token {
+            if refresh_token != tokens.refresh_token {
+                if let Some(hook) = &self.rotation_hook {
+                    hook(&refresh_token);
+                }
+                tokens.refresh_token = refresh_token;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get a valid authorization header, refreshing the token if it expires soon or equals
+    /// the `rejected` one.
+    async fn authorization(
+        &self,
+        rejected: Option<&HeaderValue>,
+    ) -> crate::error::Result<HeaderValue> {
+        let mut tokens = self.tokens.lock().await;
+        let expiring = tokens
+            .expires_at
+            .is_some_and(|v| Instant::now() + self.refresh_margin >= v);
+        if expiring || rejected.is_some_and(|v| v == tokens.header) {
+            self.refresh(&mut tokens).await?;
+        }
+        Ok(tokens.header.to_owned())
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for OAuth2RefreshTokenMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        let authorization = self.authorization(None).await?;
+        req.headers_mut()
+            .insert(http::header::AUTHORIZATION, authorization.to_owned());
+        let replay = req.try_clone();
+
+        let response = next.clone().run(req, ext).await?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        tracing::debug!("Token is rejected, refresh it");
+        let authorization = self.authorization(Some(&authorization)).await?;
+        let Some(mut replay) = replay else {
+            return Ok(response);
+        };
+        replay
+            .headers_mut()
+            .insert(http::header::AUTHORIZATION, authorization);
+        next.run(replay, ext).await
+    }
+}
@@ -0,0 +1,136 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use dashmap::DashMap;
+use http::Extensions;
+use reqwest::{Request, Response};
+
+use super::middleware::{Middleware, Next};
+use crate::wrappers::client_pool::ClientPool;
+
+type ProxyProvider = Arc<dyn Fn(&Request) -> Option<String> + Send + Sync + 'static>;
+
+/// The proxy picked by [`ProxyRotationMiddleware`], inserted into the `Extensions` of the request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelectedProxy(pub String);
+
+enum Selection {
+    RoundRobin(Vec<String>),
+    StickyPerDomain(Vec<String>, DashMap<String, String>),
+    Provider(ProxyProvider),
+}
+
+/// Send each request through a proxy picked from a list or a provider.
+///
+/// Requests are executed on the client of the picked proxy in the [`ClientPool`] of the
+/// `ErgoClient`, so proxies can change per request although `reqwest` fixes them per client.
+/// Middlewares after this one (including auto redirect and auto retry) use the same client,
+/// add it in [`crate::middleware::middleware::MiddlewarePhase::PostRetry`] to pick a proxy for
+/// every retried attempt.
+///
+/// # Example
+/// ```
+/// # use ergoreq::middleware::proxy_rotation_middleware::ProxyRotationMiddleware;
+/// # use ergoreq::ErgoClient;
+/// let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+///     ProxyRotationMiddleware::round_robin(vec![
+///         "http://proxy-1.example.com:8080".to_owned(),
+///         "http://proxy-2.example.com:8080".to_owned(),
+///     ]),
+/// );
+/// ```
+pub struct ProxyRotationMiddleware {
+    selection: Selection,
+    cursor: AtomicUsize,
+    fallback_pool: ClientPool,
+}
+
+impl ProxyRotationMiddleware {
+    fn new(selection: Selection) -> Self {
+        Self {
+            selection,
+            cursor: AtomicUsize::new(0),
+            fallback_pool: ClientPool::new(),
+        }
+    }
+
+    /// Use `proxies` in turn.
+    ///
+    /// # Panics
+    /// Panics if `proxies` is empty.
+    pub fn round_robin(proxies: Vec<String>) -> Self {
+        assert!(!proxies.is_empty(), "at least one proxy is required");
+        Self::new(Selection::RoundRobin(proxies))
+    }
+
+    /// Assign `proxies` to hosts in turn, and keep sending requests to a host through the same proxy.
+    ///
+    /// # Panics
+    /// Panics if `proxies` is empty.
+    pub fn sticky_per_domain(proxies: Vec<String>) -> Self {
+        assert!(!proxies.is_empty(), "at least one proxy is required");
+        Self::new(Selection::StickyPerDomain(proxies, DashMap::new()))
+    }
+
+    /// Ask `provider` for the proxy of each request, `None` sends it without proxy.
+    pub fn from_provider<F>(provider: F) -> Self
+    where
+        F: Fn(&Request) -> Option<String> + Send + Sync + 'static,
+    {
+        Self::new(Selection::Provider(Arc::new(provider)))
+    }
+
+    fn next_of<'a>(&self, proxies: &'a [String]) -> &'a String {
+        &proxies[self.cursor.fetch_add(1, Ordering::Relaxed) % proxies.len()]
+    }
+
+    fn select(&self, req: &Request) -> Option<String> {
+        match &self.selection {
+            Selection::RoundRobin(proxies) => Some(self.next_of(proxies).to_owned()),
+            Selection::StickyPerDomain(proxies, assigned) => {
+                let host = req
+                    .url()
+                    .host_str()
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+                Some(
+                    assigned
+                        .entry(host)
+                        .or_insert_with(|| self.next_of(proxies).to_owned())
+                        .to_owned(),
+                )
+            }
+            Selection::Provider(provider) => provider(req),
+        }
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for ProxyRotationMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        let Some(proxy) = self.select(&req) else {
+            ext.remove::<SelectedProxy>();
+            return next.run(req, ext).await;
+        };
+
+        let client = match next.get_client_pool() {
+            Some(pool) => pool.client_for_proxy(&proxy)?,
+            None => self.fallback_pool.client_for_proxy(&proxy)?,
+        };
+        tracing::debug!(
+            "Send {} through proxy {}",
+            next.redact_url(req.url()),
+            url::Url::parse(&proxy)
+                .map(|v| next.redact_url(&v).to_string())
+                .unwrap_or_else(|_| proxy.to_owned())
+        );
+        ext.insert(SelectedProxy(proxy));
+        next.with_client(&client).run(req, ext).await
+    }
+}
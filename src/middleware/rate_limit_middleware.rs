@@ -0,0 +1,199 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use http::Extensions;
+use reqwest::{Request, Response};
+
+use super::middleware::{Middleware, MiddlewarePhase, Next};
+use crate::utils::timer::sleep;
+use crate::utils::token_bucket::TokenBucket;
+
+/// Burst size and sustained rate of a token bucket.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    burst: u32,
+    per_second: f64,
+}
+
+impl RateLimit {
+    /// Allow `burst` requests at once, refilled with `per_second` requests per second.
+    ///
+    /// # Panics
+    /// Panics if `burst` is `0` or `per_second` is not a positive number.
+    pub fn new(burst: u32, per_second: f64) -> Self {
+        assert!(burst > 0, "burst of a rate limit must be positive");
+        assert!(
+            per_second.is_finite() && per_second > 0.0,
+            "rate of a rate limit must be positive"
+        );
+        Self { burst, per_second }
+    }
+
+    /// Allow one request per `interval`, without burst.
+    ///
+    /// # Panics
+    /// Panics if `interval` is zero.
+    pub fn every(interval: Duration) -> Self {
+        Self::new(1, 1.0 / interval.as_secs_f64())
+    }
+
+    /// Get the burst size.
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    /// Get the sustained rate, in requests per second.
+    pub fn per_second(&self) -> f64 {
+        self.per_second
+    }
+
+    fn bucket(&self) -> TokenBucket {
+        TokenBucket::new(self.burst as f64, self.per_second)
+    }
+}
+
+/// Limit request rate with token buckets, globally and per host.
+///
+/// When the budget is exhausted, requests wait until a token is available instead of failing.
+///
+/// It runs in [`MiddlewarePhase::PostRetry`] by default, so every retry and redirect hop takes a
+/// token.
+///
+/// # Example
+/// ```
+/// # use ergoreq::middleware::rate_limit_middleware::{RateLimit, RateLimitMiddleware};
+/// # use ergoreq::ErgoClient;
+/// let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+///     RateLimitMiddleware::new()
+///         .with_global_limit(RateLimit::new(20, 10.0))
+///         .with_per_host_limit(RateLimit::new(5, 2.0)),
+/// );
+/// ```
+pub struct RateLimitMiddleware {
+    global: Option<TokenBucket>,
+    per_host: Option<RateLimit>,
+    host_limits: DashMap<String, RateLimit>,
+    host_buckets: DashMap<String, Arc<TokenBucket>>,
+}
+
+impl RateLimitMiddleware {
+    /// Create a `RateLimitMiddleware` without any limit.
+    pub fn new() -> Self {
+        Self {
+            global: None,
+            per_host: None,
+            host_limits: DashMap::new(),
+            host_buckets: DashMap::new(),
+        }
+    }
+
+    /// Limit the rate of all requests passing through this middleware.
+    pub fn with_global_limit(mut self, limit: RateLimit) -> Self {
+        self.global = Some(limit.bucket());
+        self
+    }
+
+    /// Limit the rate of requests to each host.
+    ///
+    /// Every host gets its own bucket, unless it is configured by [`Self::with_host_limit`].
+    pub fn with_per_host_limit(mut self, limit: RateLimit) -> Self {
+        self.per_host = Some(limit);
+        self
+    }
+
+    /// Limit the rate of requests to the given `host`.
+    pub fn with_host_limit(self, host: &str, limit: RateLimit) -> Self {
+        self.set_host_limit(host, limit);
+        self
+    }
+
+    /// Change the limit of the given `host` at runtime.
+    ///
+    /// The bucket of this host is reset.
+    pub fn set_host_limit(&self, host: &str, limit: RateLimit) {
+        let host = host.to_ascii_lowercase();
+        self.host_buckets.remove(&host);
+        self.host_limits.insert(host, limit);
+    }
+
+    /// Remove the limit of the given `host` set with [`Self::set_host_limit`] at runtime.
+    ///
+    /// Requests to this host fall back to the per-host limit, if any.
+    pub fn remove_host_limit(&self, host: &str) {
+        let host = host.to_ascii_lowercase();
+        self.host_buckets.remove(&host);
+        self.host_limits.remove(&host);
+    }
+
+    /// Wait for a token of the global bucket and the bucket of the host of `url`.
+    pub(crate) async fn wait(&self, url: &url::Url) {
+        let global_wait = self
+            .global
+            .as_ref()
+            .map(|v| v.reserve(1.0))
+            .unwrap_or_default();
+        let host_wait = url
+            .host_str()
+            .and_then(|host| self.host_bucket(&host.to_ascii_lowercase()))
+            .map(|v| v.reserve(1.0))
+            .unwrap_or_default();
+        let wait = global_wait.max(host_wait);
+        if !wait.is_zero() {
+            tracing::debug!("Rate limited, wait for {:?}", wait);
+            sleep(wait).await;
+        }
+    }
+
+    fn host_bucket(&self, host: &str) -> Option<Arc<TokenBucket>> {
+        if let Some(bucket) = self.host_buckets.get(host) {
+            return Some(bucket.to_owned());
+        }
+        let limit = self
+            .host_limits
+            .get(host)
+            .map(|v| *v.value())
+            .or(self.per_host)?;
+        Some(
+            self.host_buckets
+                .entry(host.to_owned())
+                .or_insert_with(|| Arc::new(limit.bucket()))
+                .to_owned(),
+        )
+    }
+}
+
+impl Default for RateLimitMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for RateLimitMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        self.wait(req.url()).await;
+        next.run(req, ext).await
+    }
+
+    fn default_phase(&self) -> MiddlewarePhase {
+        MiddlewarePhase::PostRetry
+    }
+}
+
+#[cfg(test)]
+mod test_rate_limit_middleware {
+    use super::RateLimit;
+
+    #[test]
+    #[should_panic]
+    fn test_invalid_rate_limit() {
+        RateLimit::new(1, 0.0);
+    }
+}
@@ -0,0 +1,202 @@
+use std::sync::Arc;
+
+use http::header::HeaderName;
+use http::{Extensions, HeaderMap, Method, StatusCode};
+use reqwest::{Request, Response};
+
+use super::middleware::{Middleware, Next};
+use crate::cache::cache_storage::{CacheStorage, CachedResponse};
+use crate::cache::lru_cache_storage::LruCacheStorage;
+use crate::utils::response::response_from_parts;
+
+/// How a response was produced by [`RevalidationMiddleware`].
+///
+/// It is inserted into the `Extensions` of the request, and can be read from the response with
+/// [`crate::ErgoResponse::extension`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CacheStatus {
+    /// Nothing was stored for this url, the response comes from the server.
+    Miss,
+    /// Validators were sent, and the server returned a new response.
+    Updated,
+    /// The server returned `304 Not Modified`, the stored body is served.
+    Revalidated,
+}
+
+/// Revalidate `GET` responses with `ETag`/`Last-Modified` validators.
+///
+/// The validators and body of `200 OK` responses are remembered per url. Later requests to the
+/// same url send `If-None-Match`/`If-Modified-Since`, and a `304 Not Modified` response is
+/// transparently replaced by the stored body with `200 OK`, so callers always see fresh or
+/// revalidated content.
+///
+/// Requests already carrying conditional headers, and responses with `Cache-Control: no-store`
+/// are left untouched. Responses with `Vary` are not stored, because they are only stored per
+/// url.
+///
+/// Responses are kept in a default [`LruCacheStorage`], use [`Self::with_storage`] to change
+/// the [`CacheStorage`] backend.
+pub struct RevalidationMiddleware {
+    store: Arc<dyn CacheStorage>,
+}
+
+impl RevalidationMiddleware {
+    /// Create a `RevalidationMiddleware` with an empty [`LruCacheStorage`].
+    pub fn new() -> Self {
+        Self {
+            store: Arc::new(LruCacheStorage::default()),
+        }
+    }
+
+    /// Store responses in the given [`CacheStorage`].
+    pub fn with_storage<S>(mut self, storage: Arc<S>) -> Self
+    where
+        S: CacheStorage + 'static,
+    {
+        self.store = storage;
+        self
+    }
+
+    /// Forget the stored response of `url`.
+    pub async fn invalidate(&self, url: &str) {
+        self.store.remove(url).await;
+    }
+
+    /// Forget all stored responses.
+    pub async fn clear(&self) {
+        self.store.clear().await;
+    }
+
+    fn is_no_store(headers: &HeaderMap) -> bool {
+        headers
+            .get_all(http::header::CACHE_CONTROL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .any(|v| v.trim().eq_ignore_ascii_case("no-store"))
+    }
+
+    /// Update the headers of `stored` with the headers of a `304 Not Modified` response.
+    ///
+    /// The framing of the stored body and hop-by-hop headers are kept, see RFC 9111 3.2.
+    fn update_headers(stored: &mut HeaderMap, not_modified: &HeaderMap) {
+        let listed = not_modified
+            .get_all(http::header::CONNECTION)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .filter_map(|v| HeaderName::try_from(v.trim()).ok())
+            .collect::<Vec<_>>();
+        for name in not_modified.keys() {
+            let skipped = [
+                http::header::CONTENT_LENGTH,
+                http::header::CONTENT_ENCODING,
+                http::header::TRANSFER_ENCODING,
+                http::header::CONNECTION,
+                http::header::TE,
+                http::header::TRAILER,
+                http::header::UPGRADE,
+                HeaderName::from_static("keep-alive"),
+                HeaderName::from_static("proxy-connection"),
+            ];
+            if skipped.contains(name) || listed.contains(name) {
+                continue;
+            }
+            stored.remove(name);
+            for value in not_modified.get_all(name) {
+                stored.append(name, value.to_owned());
+            }
+        }
+    }
+
+    /// Store a `200 OK` response with validators, and return a response with the same content.
+    async fn store_response(&self, key: String, response: Response) -> crate::Result<Response> {
+        if response.headers().contains_key(http::header::VARY) {
+            // The stored response of another variant must not be served either.
+            self.store.remove(&key).await;
+            return Ok(response);
+        }
+        if response.status() != StatusCode::OK
+            || (!response.headers().contains_key(http::header::ETAG)
+                && !response.headers().contains_key(http::header::LAST_MODIFIED))
+            || Self::is_no_store(response.headers())
+        {
+            return Ok(response);
+        }
+
+        let headers = response.headers().to_owned();
+        let url = response.url().to_owned();
+        let body = response.bytes().await?;
+        self.store
+            .put(
+                &key,
+                CachedResponse {
+                    status: StatusCode::OK,
+                    headers: headers.to_owned(),
+                    body: body.to_owned(),
+                },
+            )
+            .await;
+        Ok(response_from_parts(StatusCode::OK, headers, body, url))
+    }
+}
+
+impl Default for RevalidationMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for RevalidationMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        if req.method() != Method::GET
+            || req.headers().contains_key(http::header::IF_NONE_MATCH)
+            || req.headers().contains_key(http::header::IF_MODIFIED_SINCE)
+        {
+            return next.run(req, ext).await;
+        }
+        let key = req.url().to_string();
+
+        let cached = self.store.get(&key).await;
+        if let Some(cached) = &cached {
+            if let Some(etag) = cached.etag() {
+                req.headers_mut()
+                    .insert(http::header::IF_NONE_MATCH, etag.to_owned());
+            }
+            if let Some(last_modified) = cached.last_modified() {
+                req.headers_mut()
+                    .insert(http::header::IF_MODIFIED_SINCE, last_modified.to_owned());
+            }
+        }
+
+        let response = next.run(req, ext).await?;
+
+        let conditional = cached.is_some();
+        if let (Some(mut cached), StatusCode::NOT_MODIFIED) = (cached, response.status()) {
+            tracing::debug!("Not modified, serve stored response for {}", key);
+            Self::update_headers(&mut cached.headers, response.headers());
+            self.store.put(&key, cached.to_owned()).await;
+            ext.insert(CacheStatus::Revalidated);
+            return Ok(response_from_parts(
+                cached.status,
+                cached.headers,
+                cached.body,
+                response.url().to_owned(),
+            ));
+        }
+
+        ext.insert(if conditional {
+            CacheStatus::Updated
+        } else {
+            CacheStatus::Miss
+        });
+        self.store_response(key, response).await
+    }
+}
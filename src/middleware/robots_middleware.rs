@@ -0,0 +1,369 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use dashmap::DashMap;
+use futures::lock::Mutex;
+use http::Extensions;
+use reqwest::{Request, Response};
+
+use super::middleware::{Middleware, Next};
+use super::rate_limit_middleware::{RateLimit, RateLimitMiddleware};
+use crate::utils::timer::Instant;
+use crate::wrappers::client_wrapper::ErgoClient;
+
+/// Only the first 500 KiB of a `robots.txt` are parsed, see RFC 9309.
+const MAX_ROBOTS_SIZE: usize = 500 * 1024;
+
+/// At least five redirects are followed when fetching `robots.txt`, see RFC 9309.
+const MIN_ROBOTS_REDIRECTS: u16 = 5;
+
+/// What [`RobotsMiddleware`] does with a request to a disallowed path.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RobotsMode {
+    /// Fail with [`crate::Error::RobotsDisallowed`] without sending the request.
+    #[default]
+    Refuse,
+    /// Send the request, with a [`RobotsDisallowed`] in its `Extensions`.
+    Flag,
+}
+
+/// Inserted into the `Extensions` of a request to a path disallowed by `robots.txt`, in
+/// [`RobotsMode::Flag`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RobotsDisallowed(pub url::Url);
+
+/// The rules of a `robots.txt` for a user-agent.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct RobotsRules {
+    /// `(allow, pattern)` of each rule.
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl RobotsRules {
+    fn disallow_all() -> Self {
+        Self {
+            rules: vec![(false, "/".to_owned())],
+            crawl_delay: None,
+        }
+    }
+
+    /// Parse the rules of the groups matching `agent`, or of the `*` groups if none matches.
+    fn parse(text: &str, agent: &str) -> Self {
+        // (user-agents, rules) of each group
+        let mut groups: Vec<(Vec<String>, Self)> = vec![];
+        let mut in_agents = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        groups.push((vec![], Self::default()));
+                        in_agents = true;
+                    }
+                    if let Some((agents, _)) = groups.last_mut() {
+                        agents.push(value.to_ascii_lowercase());
+                    }
+                }
+                key @ ("allow" | "disallow") => {
+                    in_agents = false;
+                    if let Some((_, rules)) = groups.last_mut() {
+                        if !value.is_empty() {
+                            rules.rules.push((key == "allow", value.to_owned()));
+                        }
+                    }
+                }
+                "crawl-delay" => {
+                    in_agents = false;
+                    let delay = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|v| v.is_finite() && *v > 0.0);
+                    if let (Some((_, rules)), Some(delay)) = (groups.last_mut(), delay) {
+                        rules.crawl_delay = Some(Duration::from_secs_f64(delay));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let agent = agent.to_ascii_lowercase();
+        let merge = |name: &str| {
+            groups
+                .iter()
+                .filter(|(agents, _)| agents.iter().any(|v| v == name))
+                .fold(None, |merged: Option<Self>, (_, rules)| {
+                    let mut merged = merged.unwrap_or_default();
+                    merged.rules.extend(rules.rules.iter().cloned());
+                    merged.crawl_delay = merged.crawl_delay.or(rules.crawl_delay);
+                    Some(merged)
+                })
+        };
+        merge(&agent).or_else(|| merge("*")).unwrap_or_default()
+    }
+
+    /// Check `path` (with its query) against the rules, the longest matching pattern wins and
+    /// `Allow` wins a tie.
+    fn is_allowed(&self, path: &str) -> bool {
+        if path == "/robots.txt" {
+            return true;
+        }
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| pattern_matches(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+/// Match `path` against a `robots.txt` path pattern, supporting `*` and a trailing `$`.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = path.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts = parts.collect::<Vec<_>>();
+    for (i, part) in parts.iter().enumerate() {
+        if anchored && i + 1 == parts.len() {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+struct CachedRobots {
+    rules: Arc<RobotsRules>,
+    fetched_at: Instant,
+}
+
+/// Respect the `robots.txt` of each site, for crawlers.
+///
+/// The `robots.txt` of each origin is fetched before its first request, and cached for
+/// [`Self::with_cache_ttl`] (one day by default). Requests to disallowed paths are refused or
+/// flagged depending on [`RobotsMode`]. The `Crawl-delay` of a site limits the rate of requests
+/// to its host.
+///
+/// Following RFC 9309, a missing `robots.txt` (a `4xx` status) allows everything, and an
+/// unreachable one (a `5xx` status or a network error) disallows everything until it is fetched
+/// again.
+///
+/// `robots.txt` is fetched with the `reqwest::Client` of the request, without middlewares. Use
+/// [`Self::with_robots_client`] to fetch it with another [`ErgoClient`]. At least five
+/// redirects are followed when fetching it.
+///
+/// # Example
+/// ```
+/// # use ergoreq::middleware::robots_middleware::RobotsMiddleware;
+/// # use ergoreq::ErgoClient;
+/// let client = ErgoClient::builder()
+///     .user_agent("ExampleBot/1.0")
+///     .with_middleware(RobotsMiddleware::new("ExampleBot"))
+///     .build()
+///     .unwrap();
+/// ```
+pub struct RobotsMiddleware {
+    agent: String,
+    mode: RobotsMode,
+    cache_ttl: Duration,
+    robots_client: Option<ErgoClient>,
+    cache: DashMap<String, Arc<Mutex<Option<CachedRobots>>>>,
+    rate_limiter: RateLimitMiddleware,
+}
+
+impl RobotsMiddleware {
+    /// Create a `RobotsMiddleware` following the rules for the user-agent `agent`, like
+    /// `ExampleBot`.
+    pub fn new(agent: &str) -> Self {
+        Self {
+            agent: agent
+                .split('/')
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_owned(),
+            mode: RobotsMode::default(),
+            cache_ttl: Duration::from_secs(24 * 60 * 60),
+            robots_client: None,
+            cache: DashMap::new(),
+            rate_limiter: RateLimitMiddleware::new(),
+        }
+    }
+
+    /// Set what to do with requests to disallowed paths.
+    pub fn with_mode(mut self, mode: RobotsMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set how long a fetched `robots.txt` is used.
+    pub fn with_cache_ttl(mut self, cache_ttl: Duration) -> Self {
+        self.cache_ttl = cache_ttl;
+        self
+    }
+
+    /// Fetch `robots.txt` with `client`.
+    pub fn with_robots_client(mut self, client: ErgoClient) -> Self {
+        self.robots_client = Some(client);
+        self
+    }
+
+    async fn fetch(&self, url: &url::Url, next: &Next<'_>) -> RobotsRules {
+        let client = self
+            .robots_client
+            .to_owned()
+            .unwrap_or_else(|| ErgoClient::new(next.get_inner_client_owned()));
+        let Ok(robots_url) = url.join("/robots.txt") else {
+            return RobotsRules::default();
+        };
+
+        tracing::debug!("Fetch {}", robots_url);
+        let max_redirection = client.get_auto_redirect_count().max(MIN_ROBOTS_REDIRECTS);
+        let response = match client
+            .get(robots_url)
+            .with_max_redirection(max_redirection)
+            .send()
+            .await
+        {
+            Ok(response) => response,
+            Err(e) => {
+                tracing::debug!("Fetching robots.txt failed: {}", e);
+                return RobotsRules::disallow_all();
+            }
+        };
+        let status = response.status();
+        if status.is_client_error() {
+            return RobotsRules::default();
+        }
+        if !status.is_success() {
+            return RobotsRules::disallow_all();
+        }
+        match response.bytes().await {
+            Ok(body) => {
+                let body = &body[..body.len().min(MAX_ROBOTS_SIZE)];
+                RobotsRules::parse(&String::from_utf8_lossy(body), &self.agent)
+            }
+            Err(_) => RobotsRules::disallow_all(),
+        }
+    }
+
+    /// Get the rules of the origin of `url`, fetching them if not cached.
+    async fn rules(&self, url: &url::Url, next: &Next<'_>) -> Arc<RobotsRules> {
+        let origin = url.origin().ascii_serialization();
+        let entry = self.cache.entry(origin).or_default().to_owned();
+        // holding the lock while fetching makes concurrent requests wait for the same fetch
+        let mut cached = entry.lock().await;
+        if let Some(cached) = cached
+            .as_ref()
+            .filter(|v| v.fetched_at.elapsed() < self.cache_ttl)
+        {
+            return cached.rules.to_owned();
+        }
+
+        let rules = Arc::new(self.fetch(url, next).await);
+        if let Some(host) = url.host_str() {
+            match rules.crawl_delay {
+                Some(delay) => self
+                    .rate_limiter
+                    .set_host_limit(host, RateLimit::every(delay)),
+                // the `Crawl-delay` may have been removed since the last fetch
+                None => self.rate_limiter.remove_host_limit(host),
+            }
+        }
+        *cached = Some(CachedRobots {
+            rules: rules.to_owned(),
+            fetched_at: Instant::now(),
+        });
+        rules
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for RobotsMiddleware {
+    async fn handle(
+        &self,
+        req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        let rules = self.rules(req.url(), &next).await;
+        let path = match req.url().query() {
+            Some(query) => format!("{}?{}", req.url().path(), query),
+            None => req.url().path().to_owned(),
+        };
+        if !rules.is_allowed(&path) {
+            match self.mode {
+                RobotsMode::Refuse => {
+                    return Err(crate::Error::RobotsDisallowed(req.url().to_owned()))
+                }
+                RobotsMode::Flag => {
+                    ext.insert(RobotsDisallowed(req.url().to_owned()));
+                }
+            }
+        }
+        self.rate_limiter.wait(req.url()).await;
+        next.run(req, ext).await
+    }
+}
+
+#[cfg(test)]
+mod test_robots_middleware {
+    use std::time::Duration;
+
+    use super::{pattern_matches, RobotsRules};
+
+    #[test]
+    fn test_pattern_matches() {
+        assert!(pattern_matches("/private", "/private/a"));
+        assert!(!pattern_matches("/private", "/public"));
+        assert!(pattern_matches("/*.php$", "/a/index.php"));
+        assert!(!pattern_matches("/*.php$", "/a/index.php?b=c"));
+        assert!(pattern_matches("/a*b*c", "/a-b-c-d"));
+        assert!(!pattern_matches("/a*c*b", "/a-b-c"));
+        assert!(pattern_matches("/exact$", "/exact"));
+        assert!(!pattern_matches("/exact$", "/exact/"));
+    }
+
+    #[test]
+    fn test_parse_rules() {
+        let text = "\
+            User-agent: *\n\
+            Disallow: /\n\
+            \n\
+            # the rules for us\n\
+            User-agent: OtherBot\n\
+            User-agent: examplebot\n\
+            Disallow: /private # comment\n\
+            Allow: /private/public\n\
+            Disallow:\n\
+            Crawl-delay: 0.5\n\
+            Sitemap: https://example.com/sitemap.xml\n";
+
+        let rules = RobotsRules::parse(text, "ExampleBot");
+        assert_eq!(rules.crawl_delay, Some(Duration::from_millis(500)));
+        assert!(rules.is_allowed("/"));
+        assert!(!rules.is_allowed("/private/a"));
+        assert!(rules.is_allowed("/private/public/a"));
+        assert!(rules.is_allowed("/robots.txt"));
+
+        let rules = RobotsRules::parse(text, "AnotherBot");
+        assert!(!rules.is_allowed("/a"));
+        assert!(rules.is_allowed("/robots.txt"));
+
+        // `Allow` wins a tie
+        let rules = RobotsRules::parse("User-agent: *\nDisallow: /a\nAllow: /a\n", "bot");
+        assert!(rules.is_allowed("/a"));
+    }
+}
@@ -0,0 +1,196 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use dashmap::DashMap;
+use futures::stream::{Stream, StreamExt};
+use http::{Extensions, HeaderValue};
+use reqwest::{Body, Request, Response};
+
+use super::middleware::{Middleware, Next};
+use crate::utils::body_factory::BodyFactory;
+use crate::utils::response::map_body_stream;
+use crate::utils::timer::sleep;
+use crate::utils::token_bucket::TokenBucket;
+
+/// Size of chunks a buffered request body is split into when the upload is throttled.
+const UPLOAD_CHUNK_SIZE: usize = 16 * 1024;
+
+/// Bandwidth limits of one direction, globally and per host.
+#[derive(Default)]
+struct Bandwidth {
+    global: Option<Arc<TokenBucket>>,
+    per_host: Option<u64>,
+    host_buckets: DashMap<String, Arc<TokenBucket>>,
+}
+
+impl Bandwidth {
+    fn bucket(bytes_per_second: u64) -> TokenBucket {
+        assert!(bytes_per_second > 0, "bandwidth limit must be positive");
+        // Allow a burst of one second.
+        TokenBucket::new(bytes_per_second as f64, bytes_per_second as f64)
+    }
+
+    fn buckets(&self, host: Option<&str>) -> Vec<Arc<TokenBucket>> {
+        let mut buckets = self.global.iter().cloned().collect::<Vec<_>>();
+        if let (Some(limit), Some(host)) = (self.per_host, host) {
+            buckets.push(
+                self.host_buckets
+                    .entry(host.to_ascii_lowercase())
+                    .or_insert_with(|| Arc::new(Self::bucket(limit)))
+                    .to_owned(),
+            );
+        }
+        buckets
+    }
+}
+
+/// Delay every chunk of `stream` until all `buckets` have enough budget for it.
+fn throttle<S, E>(stream: S, buckets: Vec<Arc<TokenBucket>>) -> impl Stream<Item = Result<Bytes, E>>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+{
+    stream.then(move |chunk| {
+        let wait = match &chunk {
+            Ok(bytes) => buckets
+                .iter()
+                .map(|v| v.reserve(bytes.len() as f64))
+                .max()
+                .unwrap_or_default(),
+            Err(_) => Default::default(),
+        };
+        async move {
+            if !wait.is_zero() {
+                sleep(wait).await;
+            }
+            chunk
+        }
+    })
+}
+
+/// Limit the bandwidth of response bodies, and optionally request bodies, in bytes per second.
+///
+/// Bodies are throttled while they are streamed, so a polite crawler does not need to sleep
+/// between whole requests. Limits can be set globally and per host, a chunk waits until both
+/// budgets allow it.
+///
+/// # Notice
+/// Only buffered request bodies are throttled. A throttled request body becomes a stream, a
+/// [`BodyFactory`] producing it again is registered so the request can still be retried and
+/// redirected.
+///
+/// # Example
+/// ```
+/// # use ergoreq::middleware::throttle_middleware::ThrottleMiddleware;
+/// # use ergoreq::ErgoClient;
+/// let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+///     ThrottleMiddleware::new()
+///         .with_global_download_limit(1024 * 1024)
+///         .with_per_host_download_limit(256 * 1024),
+/// );
+/// ```
+#[derive(Default)]
+pub struct ThrottleMiddleware {
+    download: Bandwidth,
+    upload: Bandwidth,
+}
+
+impl ThrottleMiddleware {
+    /// Create a `ThrottleMiddleware` without any limit.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the total download bandwidth of all responses.
+    ///
+    /// # Panics
+    /// Panics if `bytes_per_second` is `0`.
+    pub fn with_global_download_limit(mut self, bytes_per_second: u64) -> Self {
+        self.download.global = Some(Arc::new(Bandwidth::bucket(bytes_per_second)));
+        self
+    }
+
+    /// Limit the download bandwidth of responses from each host.
+    ///
+    /// # Panics
+    /// Panics if `bytes_per_second` is `0`.
+    pub fn with_per_host_download_limit(mut self, bytes_per_second: u64) -> Self {
+        assert!(bytes_per_second > 0, "bandwidth limit must be positive");
+        self.download.per_host = Some(bytes_per_second);
+        self
+    }
+
+    /// Limit the total upload bandwidth of all request bodies.
+    ///
+    /// # Panics
+    /// Panics if `bytes_per_second` is `0`.
+    pub fn with_global_upload_limit(mut self, bytes_per_second: u64) -> Self {
+        self.upload.global = Some(Arc::new(Bandwidth::bucket(bytes_per_second)));
+        self
+    }
+
+    /// Limit the upload bandwidth of request bodies to each host.
+    ///
+    /// # Panics
+    /// Panics if `bytes_per_second` is `0`.
+    pub fn with_per_host_upload_limit(mut self, bytes_per_second: u64) -> Self {
+        assert!(bytes_per_second > 0, "bandwidth limit must be positive");
+        self.upload.per_host = Some(bytes_per_second);
+        self
+    }
+
+    /// Throttle the buffered body of `req`, and register a [`BodyFactory`] producing the same
+    /// throttled body, so the request can still be retried or redirected.
+    fn throttle_upload(&self, req: &mut Request, ext: &mut Extensions) {
+        let buckets = self.upload.buckets(req.url().host_str());
+        if buckets.is_empty() {
+            return;
+        }
+        let Some(body) = req
+            .body()
+            .and_then(|v| v.as_bytes())
+            .map(Bytes::copy_from_slice)
+        else {
+            return;
+        };
+        let length = body.len();
+        let chunks = (0..length)
+            .step_by(UPLOAD_CHUNK_SIZE)
+            .map(move |start| body.slice(start..(start + UPLOAD_CHUNK_SIZE).min(length)))
+            .collect::<Vec<_>>();
+        let make_body = move || {
+            let chunks = chunks.clone().into_iter().map(Ok::<_, Infallible>);
+            Body::wrap_stream(throttle(futures::stream::iter(chunks), buckets.to_owned()))
+        };
+        req.headers_mut()
+            .entry(http::header::CONTENT_LENGTH)
+            .or_insert_with(|| HeaderValue::from(length));
+        *req.body_mut() = Some(make_body());
+        ext.insert(BodyFactory::new(move || {
+            let body = make_body();
+            async move { Ok(body) }
+        }));
+    }
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait::async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait::async_trait)]
+impl Middleware for ThrottleMiddleware {
+    async fn handle(
+        &self,
+        mut req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> crate::error::Result<Response> {
+        self.throttle_upload(&mut req, ext);
+        let response = next.run(req, ext).await?;
+
+        let buckets = self.download.buckets(response.url().host_str());
+        if buckets.is_empty() {
+            return Ok(response);
+        }
+        Ok(map_body_stream(response, |stream| {
+            throttle(stream, buckets)
+        }))
+    }
+}
@@ -0,0 +1 @@
+pub mod priority_scheduler;
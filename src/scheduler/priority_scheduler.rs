@@ -0,0 +1,237 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Mutex};
+
+use futures::channel::oneshot;
+
+/// Priority of a request, higher priority requests are dispatched first.
+///
+/// Set it with [`crate::ErgoRequestBuilder::with_priority`], or as an extension with
+/// [`crate::ErgoRequestBuilder::with_extension`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RequestPriority(pub i32);
+
+impl RequestPriority {
+    pub const LOW: RequestPriority = RequestPriority(-100);
+    pub const NORMAL: RequestPriority = RequestPriority(0);
+    pub const HIGH: RequestPriority = RequestPriority(100);
+}
+
+struct Waiter {
+    priority: RequestPriority,
+    sequence: u64,
+    sender: oneshot::Sender<()>,
+}
+
+impl PartialEq for Waiter {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Waiter {}
+
+impl PartialOrd for Waiter {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Waiter {
+    /// Higher priority first, then first come first served.
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+struct SchedulerState {
+    running: usize,
+    sequence: u64,
+    waiting: BinaryHeap<Waiter>,
+}
+
+/// A bounded worker pool dispatching requests by [`RequestPriority`].
+///
+/// At most `max_concurrency` requests are running at the same time, the others wait in a
+/// queue ordered by priority, so interactive requests are not starved by bulk requests.
+pub struct PriorityScheduler {
+    max_concurrency: usize,
+    state: Mutex<SchedulerState>,
+}
+
+/// A running slot of [`PriorityScheduler`], released on drop.
+pub struct SchedulerPermit {
+    scheduler: Arc<PriorityScheduler>,
+}
+
+impl Drop for SchedulerPermit {
+    fn drop(&mut self) {
+        self.scheduler.release();
+    }
+}
+
+/// Give the slot back if the waiting request is cancelled after being dispatched.
+struct PendingPermit {
+    scheduler: Arc<PriorityScheduler>,
+    receiver: Option<oneshot::Receiver<()>>,
+}
+
+impl Drop for PendingPermit {
+    fn drop(&mut self) {
+        if let Some(mut receiver) = self.receiver.take() {
+            receiver.close();
+            if let Ok(Some(())) = receiver.try_recv() {
+                self.scheduler.release();
+            }
+        }
+    }
+}
+
+impl PriorityScheduler {
+    /// Create a scheduler running at most `max_concurrency` requests at the same time.
+    ///
+    /// # Panics
+    /// Panics if `max_concurrency` is `0`.
+    pub fn new(max_concurrency: usize) -> Self {
+        assert!(max_concurrency > 0, "max concurrency must be positive");
+        Self {
+            max_concurrency,
+            state: Mutex::new(SchedulerState {
+                running: 0,
+                sequence: 0,
+                waiting: BinaryHeap::new(),
+            }),
+        }
+    }
+
+    /// Get the max number of running requests.
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency
+    }
+
+    /// Get the number of running requests.
+    pub fn running(&self) -> usize {
+        self.lock().running
+    }
+
+    /// Get the number of waiting requests.
+    pub fn waiting(&self) -> usize {
+        self.lock().waiting.len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, SchedulerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wait for a running slot.
+    pub async fn acquire(self: &Arc<Self>, priority: RequestPriority) -> SchedulerPermit {
+        let receiver = {
+            let mut state = self.lock();
+            if state.running < self.max_concurrency && state.waiting.is_empty() {
+                state.running += 1;
+                return SchedulerPermit {
+                    scheduler: self.to_owned(),
+                };
+            }
+            let (sender, receiver) = oneshot::channel();
+            state.sequence += 1;
+            let sequence = state.sequence;
+            state.waiting.push(Waiter {
+                priority,
+                sequence,
+                sender,
+            });
+            receiver
+        };
+
+        let mut pending = PendingPermit {
+            scheduler: self.to_owned(),
+            receiver: Some(receiver),
+        };
+        // The sender is only dropped after a successful send, or with the scheduler.
+        let _ = pending
+            .receiver
+            .as_mut()
+            .expect("receiver is taken only on drop")
+            .await;
+        pending.receiver = None;
+        SchedulerPermit {
+            scheduler: self.to_owned(),
+        }
+    }
+
+    /// Hand the slot to the next waiting request, or free it.
+    fn release(&self) {
+        let mut state = self.lock();
+        while let Some(waiter) = state.waiting.pop() {
+            if waiter.sender.send(()).is_ok() {
+                return;
+            }
+        }
+        state.running -= 1;
+    }
+}
+
+#[cfg(test)]
+mod test_priority_scheduler {
+    use std::sync::{Arc, Mutex};
+
+    use super::{PriorityScheduler, RequestPriority};
+
+    #[tokio::test]
+    async fn test_dispatch_by_priority() {
+        let scheduler = Arc::new(PriorityScheduler::new(1));
+        let order = Arc::new(Mutex::new(vec![]));
+
+        let first = scheduler.acquire(RequestPriority::NORMAL).await;
+
+        let mut handles = vec![];
+        for priority in [
+            RequestPriority::LOW,
+            RequestPriority::NORMAL,
+            RequestPriority::HIGH,
+        ] {
+            let scheduler = scheduler.to_owned();
+            let order = order.to_owned();
+            handles.push(tokio::spawn(async move {
+                let _permit = scheduler.acquire(priority).await;
+                order.lock().unwrap().push(priority);
+            }));
+            tokio::task::yield_now().await;
+        }
+        while scheduler.waiting() < 3 {
+            tokio::task::yield_now().await;
+        }
+
+        drop(first);
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        assert_eq!(
+            *order.lock().unwrap(),
+            vec![
+                RequestPriority::HIGH,
+                RequestPriority::NORMAL,
+                RequestPriority::LOW
+            ]
+        );
+        assert_eq!(scheduler.running(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_releases_slot() {
+        let scheduler = Arc::new(PriorityScheduler::new(1));
+        let first = scheduler.acquire(RequestPriority::NORMAL).await;
+        {
+            let waiting = scheduler.acquire(RequestPriority::HIGH);
+            futures::pin_mut!(waiting);
+            assert!(futures::poll!(waiting.as_mut()).is_pending());
+        }
+        drop(first);
+        assert_eq!(scheduler.running(), 0);
+        let _permit = scheduler.acquire(RequestPriority::NORMAL).await;
+        assert_eq!(scheduler.running(), 1);
+    }
+}
@@ -0,0 +1,51 @@
+use std::future::Future;
+use std::sync::Arc;
+
+use futures::future::BoxFuture;
+use futures::FutureExt;
+use reqwest::{Body, Request};
+
+type MakeBody = dyn Fn() -> BoxFuture<'static, crate::Result<Body>> + Send + Sync + 'static;
+
+/// Produce a fresh body for each attempt of a request, set by
+/// [`crate::ErgoRequestBuilder::body_factory`].
+///
+/// It is inserted into the `Extensions` of the request. Auto retry and auto redirect use it
+/// instead of cloning the request, so requests with streamed bodies can be retried.
+#[derive(Clone)]
+pub struct BodyFactory(Arc<MakeBody>);
+
+impl BodyFactory {
+    /// Create a `BodyFactory` from an async closure.
+    pub fn new<F, Fut>(factory: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = crate::Result<Body>> + Send + 'static,
+    {
+        Self(Arc::new(move || factory().boxed()))
+    }
+
+    /// Produce a fresh body.
+    pub async fn make(&self) -> crate::Result<Body> {
+        (self.0)().await
+    }
+
+    /// Copy `req` with a fresh body.
+    pub async fn rebuild(&self, req: &Request) -> crate::Result<Request> {
+        let mut request = Self::without_body(req);
+        *request.body_mut() = Some(self.make().await?);
+        Ok(request)
+    }
+
+    /// Copy `req` without its body.
+    pub(crate) fn without_body(req: &Request) -> Request {
+        let mut request = Request::new(req.method().to_owned(), req.url().to_owned());
+        *request.headers_mut() = req.headers().to_owned();
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            *request.version_mut() = req.version();
+        }
+        *request.timeout_mut() = req.timeout().copied();
+        request
+    }
+}
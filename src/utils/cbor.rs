@@ -0,0 +1,66 @@
+//! A CBOR (RFC 8949) codec for request and response bodies, see
+//! [`crate::ErgoRequestBuilder::cbor`], backed by [`ciborium`].
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// The `Content-Type` of CBOR bodies.
+pub const CONTENT_TYPE: &str = "application/cbor";
+
+/// Encode `value` as CBOR.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> crate::Result<Vec<u8>> {
+    let mut buffer = vec![];
+    ciborium::into_writer(value, &mut buffer).map_err(|e| crate::Error::Codec(e.to_string()))?;
+    Ok(buffer)
+}
+
+/// Decode a `T` from CBOR `bytes`.
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> crate::Result<T> {
+    let mut rest = bytes;
+    let value = ciborium::from_reader(&mut rest).map_err(|e| crate::Error::Codec(e.to_string()))?;
+    match rest.is_empty() {
+        true => Ok(value),
+        false => Err(crate::Error::Codec(format!(
+            "trailing bytes at {}",
+            bytes.len() - rest.len()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test_cbor {
+    use serde_json::{json, Value};
+
+    use super::{from_slice, to_vec};
+
+    #[test]
+    fn test_cbor_round_trip() {
+        let value = json!({
+            "name": "ergo",
+            "numbers": [0, 23, 24, 65536, -1, -25, -40000, 5000000000u64, 1.5],
+            "nested": {"ok": true, "none": null},
+            "long": "a".repeat(300),
+        });
+        let bytes = to_vec(&value).unwrap();
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), value);
+
+        // examples of RFC 8949 appendix A
+        assert_eq!(
+            to_vec(&json!([1, -1000])).unwrap(),
+            [0x82, 0x01, 0x39, 0x03, 0xe7]
+        );
+        assert_eq!(from_slice::<f64>(&[0xf9, 0x3c, 0x00]).unwrap(), 1.0);
+        assert_eq!(from_slice::<f64>(&[0xf9, 0xc4, 0x00]).unwrap(), -4.0);
+        assert_eq!(
+            from_slice::<Value>(&[0x9f, 0x01, 0x82, 0x02, 0x03, 0xff]).unwrap(),
+            json!([1, [2, 3]])
+        );
+        assert_eq!(
+            from_slice::<String>(&[0x7f, 0x62, b'e', b'r', 0x62, b'g', b'o', 0xff]).unwrap(),
+            "ergo"
+        );
+        // a tagged date time string
+        assert_eq!(from_slice::<String>(&[0xc0, 0x61, b'a']).unwrap(), "a");
+        assert!(from_slice::<Value>(&[0x82, 0x01]).is_err());
+    }
+}
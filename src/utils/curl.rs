@@ -0,0 +1,168 @@
+use reqwest::{Method, Request};
+
+use super::redactor::Redactor;
+
+/// Quote a value for a POSIX shell.
+///
+/// Printable values are wrapped in single quotes, values containing control characters or
+/// non UTF-8 bytes are rendered with the ANSI-C `$'...'` quoting supported by bash and zsh.
+fn shell_quote(value: &[u8]) -> String {
+    match std::str::from_utf8(value) {
+        Ok(value) if !value.chars().any(|c| c.is_control() && c != '\n') => {
+            format!("'{}'", value.replace('\'', "'\\''"))
+        }
+        _ => {
+            let mut quoted = String::from("$'");
+            for byte in value {
+                match byte {
+                    b'\'' => quoted.push_str("\\'"),
+                    b'\\' => quoted.push_str("\\\\"),
+                    b'\n' => quoted.push_str("\\n"),
+                    b'\r' => quoted.push_str("\\r"),
+                    b'\t' => quoted.push_str("\\t"),
+                    0x20..=0x7e => quoted.push(*byte as char),
+                    _ => quoted.push_str(&format!("\\x{:02x}", byte)),
+                }
+            }
+            quoted.push('\'');
+            quoted
+        }
+    }
+}
+
+/// Render a [`Request`] as a copy-pasteable `curl` command.
+///
+/// Method, url, headers, timeout and buffered body are rendered. A streaming body cannot be read
+/// without consuming it, so it is replaced by `--data-binary @-`.
+///
+/// # Notice
+/// Headers configured with `reqwest::ClientBuilder::default_headers` are added by `reqwest`
+/// while sending, so they are not visible here.
+///
+/// # Example
+/// ```
+/// # use ergoreq::utils::curl::request_to_curl;
+/// let request = reqwest::Request::new(
+///     reqwest::Method::GET,
+///     "https://example.com/".parse().unwrap(),
+/// );
+/// assert_eq!(request_to_curl(&request), "curl 'https://example.com/'");
+/// ```
+pub fn request_to_curl(request: &Request) -> String {
+    render(request, None)
+}
+
+/// Render a [`Request`] as a `curl` command, with secrets scrubbed by `redactor`.
+///
+/// See [`request_to_curl`].
+pub fn request_to_curl_redacted(request: &Request, redactor: &Redactor) -> String {
+    render(request, Some(redactor))
+}
+
+fn render(request: &Request, redactor: Option<&Redactor>) -> String {
+    let mut parts = vec!["curl".to_owned()];
+
+    match *request.method() {
+        Method::GET => (),
+        Method::HEAD => parts.push("--head".to_owned()),
+        ref method => {
+            parts.push("-X".to_owned());
+            parts.push(method.as_str().to_owned());
+        }
+    }
+
+    let url = match redactor {
+        Some(redactor) => redactor.redact_url(request.url()),
+        None => request.url().to_owned(),
+    };
+    parts.push(shell_quote(url.as_str().as_bytes()));
+
+    let headers = match redactor {
+        Some(redactor) => redactor.redact_headers(request.headers()),
+        None => request.headers().to_owned(),
+    };
+    for (name, value) in &headers {
+        let mut header = format!("{}: ", name).into_bytes();
+        header.extend_from_slice(value.as_bytes());
+        parts.push("-H".to_owned());
+        parts.push(shell_quote(&header));
+    }
+
+    if let Some(timeout) = request.timeout() {
+        parts.push("--max-time".to_owned());
+        parts.push(format!("{}", timeout.as_secs_f64()));
+    }
+
+    if let Some(body) = request.body() {
+        match body.as_bytes() {
+            Some([]) => (),
+            Some(bytes) => {
+                parts.push("--data-binary".to_owned());
+                match redactor {
+                    Some(redactor) => parts.push(shell_quote(&redactor.redact_body(bytes))),
+                    None => parts.push(shell_quote(bytes)),
+                }
+            }
+            None => {
+                parts.push("--data-binary".to_owned());
+                parts.push("@-".to_owned());
+            }
+        }
+    }
+
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod test_curl {
+    use super::{request_to_curl, request_to_curl_redacted, shell_quote};
+    use crate::utils::redactor::Redactor;
+    use reqwest::{Method, Request};
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(shell_quote(b"plain"), "'plain'");
+        assert_eq!(shell_quote(b"it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(b"a\x00b"), "$'a\\x00b'");
+        assert_eq!(shell_quote(&[0xff, b'\'']), "$'\\xff\\''");
+    }
+
+    #[test]
+    fn test_request_to_curl() {
+        let mut request = Request::new(Method::POST, "https://example.com/a?b=c".parse().unwrap());
+        request.headers_mut().insert(
+            http::header::CONTENT_TYPE,
+            http::HeaderValue::from_static("application/json"),
+        );
+        *request.body_mut() = Some(r#"{"name":"ergo"}"#.into());
+
+        assert_eq!(
+            request_to_curl(&request),
+            r#"curl -X POST 'https://example.com/a?b=c' -H 'content-type: application/json' --data-binary '{"name":"ergo"}'"#
+        );
+
+        let request = Request::new(Method::HEAD, "https://example.com/".parse().unwrap());
+        assert_eq!(
+            request_to_curl(&request),
+            "curl --head 'https://example.com/'"
+        );
+    }
+
+    #[test]
+    fn test_request_to_curl_redacted() {
+        let mut request = Request::new(
+            Method::POST,
+            "https://example.com/?token=abc".parse().unwrap(),
+        );
+        request.headers_mut().insert(
+            http::header::AUTHORIZATION,
+            http::HeaderValue::from_static("Bearer abc"),
+        );
+        *request.body_mut() = Some(r#"{"password":"abc"}"#.into());
+
+        assert_eq!(
+            request_to_curl_redacted(&request, &Redactor::new()),
+            r#"curl -X POST 'https://example.com/?token=%5BREDACTED%5D' -H 'authorization: [REDACTED]' --data-binary '{"password":"[REDACTED]"}'"#
+        );
+    }
+}
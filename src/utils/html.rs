@@ -0,0 +1,129 @@
+//! Parsed html documents of responses, see [`crate::ErgoResponse::html`].
+//!
+//! Documents are parsed with [`scraper`], which is re-exported to name its types.
+
+pub use scraper;
+use scraper::{ElementRef, Html, Selector};
+
+/// A parsed html document, with the url it was fetched from to resolve relative links.
+///
+/// # Example
+/// ```
+/// # use ergoreq::utils::html::HtmlDocument;
+/// let document = HtmlDocument::parse(
+///     r#"<ul><li><a href="/a">A</a></li><li><a href="b">B</a></li></ul>"#,
+///     "https://example.com/list/".parse().unwrap(),
+/// );
+/// assert_eq!(document.select_text("li").unwrap(), ["A", "B"]);
+/// assert_eq!(
+///     document.select_links("a").unwrap(),
+///     [
+///         "https://example.com/a".parse().unwrap(),
+///         "https://example.com/list/b".parse().unwrap(),
+///     ]
+/// );
+/// ```
+#[derive(Debug)]
+pub struct HtmlDocument {
+    html: Html,
+    url: url::Url,
+}
+
+impl HtmlDocument {
+    /// Parse `text` as a document fetched from `url`.
+    pub fn parse(text: &str, url: url::Url) -> Self {
+        Self {
+            html: Html::parse_document(text),
+            url,
+        }
+    }
+
+    /// Get the parsed [`Html`].
+    pub fn inner(&self) -> &Html {
+        &self.html
+    }
+
+    /// Get the url the document was fetched from.
+    pub fn url(&self) -> &url::Url {
+        &self.url
+    }
+
+    /// Get the elements matching the CSS selector `css`, in document order.
+    ///
+    /// An invalid selector fails with [`crate::Error::InvalidSelector`].
+    pub fn select(&self, css: &str) -> crate::Result<Vec<ElementRef<'_>>> {
+        let selector = parse_selector(css)?;
+        Ok(self.html.select(&selector).collect())
+    }
+
+    /// Get the first element matching `css`.
+    pub fn select_first(&self, css: &str) -> crate::Result<Option<ElementRef<'_>>> {
+        let selector = parse_selector(css)?;
+        Ok(self.html.select(&selector).next())
+    }
+
+    /// Get the trimmed text of each element matching `css`.
+    pub fn select_text(&self, css: &str) -> crate::Result<Vec<String>> {
+        Ok(self
+            .select(css)?
+            .into_iter()
+            .map(|v| v.text().collect::<String>().trim().to_owned())
+            .collect())
+    }
+
+    /// Get the value of attribute `attr` of each element matching `css` which has it.
+    pub fn select_attr(&self, css: &str, attr: &str) -> crate::Result<Vec<String>> {
+        Ok(self
+            .select(css)?
+            .into_iter()
+            .filter_map(|v| v.value().attr(attr).map(str::to_owned))
+            .collect())
+    }
+
+    /// Get the `href` of each element matching `css`, resolved against the url of the
+    /// document, or its `<base href>`. Invalid links are skipped.
+    pub fn select_links(&self, css: &str) -> crate::Result<Vec<url::Url>> {
+        let base = self
+            .select_attr("base[href]", "href")?
+            .first()
+            .and_then(|v| self.url.join(v).ok())
+            .unwrap_or_else(|| self.url.to_owned());
+        Ok(self
+            .select_attr(css, "href")?
+            .iter()
+            .filter_map(|v| base.join(v.trim()).ok())
+            .collect())
+    }
+}
+
+fn parse_selector(css: &str) -> crate::Result<Selector> {
+    Selector::parse(css).map_err(|e| crate::Error::InvalidSelector(format!("{css}: {e}")))
+}
+
+#[cfg(test)]
+mod test_html {
+    use super::HtmlDocument;
+
+    #[test]
+    fn test_html_document() {
+        let document = HtmlDocument::parse(
+            r#"<html><head><base href="https://cdn.example.com/"></head>
+            <body><img src="x.png" alt="X"><img src="y.png"><a href="z">Z</a></body></html>"#,
+            "https://example.com/page".parse().unwrap(),
+        );
+        assert_eq!(document.select_attr("img", "alt").unwrap(), ["X"]);
+        assert_eq!(
+            document.select_first("img").unwrap().unwrap().attr("src"),
+            Some("x.png")
+        );
+        assert_eq!(
+            document.select_links("a").unwrap(),
+            ["https://cdn.example.com/z".parse().unwrap()]
+        );
+        assert!(document.select_first("p").unwrap().is_none());
+        assert!(matches!(
+            document.select("a[").unwrap_err(),
+            crate::Error::InvalidSelector(_)
+        ));
+    }
+}
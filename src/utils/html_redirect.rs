@@ -0,0 +1,100 @@
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+/// Only bodies up to this size are searched for an html redirect.
+pub(crate) const MAX_HTML_REDIRECT_BODY: u64 = 16 * 1024;
+
+fn meta_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"(?is)<meta\s[^>]*>").expect("invalid meta regex"))
+}
+
+fn refresh_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(r#"(?i)http-equiv\s*=\s*["']?refresh\b"#).expect("invalid refresh regex")
+    })
+}
+
+fn content_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(r#"(?is)\scontent\s*=\s*(?:"([^"]*)"|'([^']*)')"#)
+            .expect("invalid content regex")
+    })
+}
+
+fn refresh_url_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(r#"(?is)^\s*[\d.]*\s*[;,]\s*(?:url\s*=\s*)?["']?([^"']+)"#)
+            .expect("invalid refresh url regex")
+    })
+}
+
+fn script_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(
+            r#"(?i)\blocation\.replace\(\s*["']([^"']+)["']\s*\)|\blocation(?:\.href)?\s*=\s*["']([^"']+)["']"#,
+        )
+        .expect("invalid script regex")
+    })
+}
+
+/// Find the location of a `<meta http-equiv="refresh">` or `location.replace` redirect in `html`.
+pub(crate) fn find_html_redirect(html: &str) -> Option<String> {
+    let meta_location = meta_regex()
+        .find_iter(html)
+        .map(|v| v.as_str())
+        .filter(|v| refresh_regex().is_match(v))
+        .find_map(|v| {
+            let content = content_regex().captures(v)?;
+            let content = content.get(1).or(content.get(2))?.as_str();
+            let location = refresh_url_regex().captures(content)?.get(1)?;
+            Some(location.as_str().trim().to_owned())
+        });
+    let location = meta_location.or_else(|| {
+        let captures = script_regex().captures(html)?;
+        let location = captures.get(1).or(captures.get(2))?;
+        Some(location.as_str().trim().to_owned())
+    })?;
+    Some(location.replace("&amp;", "&"))
+}
+
+#[cfg(test)]
+mod test_html_redirect {
+    use super::find_html_redirect;
+
+    #[test]
+    fn test_find_html_redirect() {
+        assert_eq!(
+            find_html_redirect(r#"<meta http-equiv="refresh" content="0; url=/next?a=1&amp;b=2">"#),
+            Some("/next?a=1&b=2".to_owned())
+        );
+        assert_eq!(
+            find_html_redirect(
+                r#"<META CONTENT='5;URL="https://example.com/"' HTTP-EQUIV=Refresh>"#
+            ),
+            Some("https://example.com/".to_owned())
+        );
+        assert_eq!(
+            find_html_redirect(r#"<script>window.location.replace("/moved");</script>"#),
+            Some("/moved".to_owned())
+        );
+        assert_eq!(
+            find_html_redirect(r#"<script>location.href = '/moved'</script>"#),
+            Some("/moved".to_owned())
+        );
+        // a refresh without url only reloads the page
+        assert_eq!(
+            find_html_redirect(r#"<meta http-equiv="refresh" content="30">"#),
+            None
+        );
+        assert_eq!(
+            find_html_redirect(r#"<meta charset="utf-8"><p>Hello</p>"#),
+            None
+        );
+    }
+}
@@ -0,0 +1,114 @@
+/// A container of the JSON text being scanned.
+enum Frame {
+    /// An object with the last key read, expecting a key when `expect_key`.
+    Object {
+        key: Option<String>,
+        expect_key: bool,
+    },
+    /// An array with the index of the current element.
+    Array(usize),
+}
+
+/// Get the path of the value at `line` and `column` (1-based, as reported by `serde_json`) of
+/// `text`, like `items[2].id`, or `.` for the root value.
+pub(crate) fn json_path_at(text: &str, line: usize, column: usize) -> String {
+    let offset = text
+        .split_inclusive('\n')
+        .take(line.saturating_sub(1))
+        .map(|v| v.len())
+        .sum::<usize>()
+        + column;
+    let text = &text.as_bytes()[..offset.min(text.len())];
+
+    let mut frames = vec![];
+    let mut i = 0;
+    while i < text.len() {
+        match text[i] {
+            b'{' => frames.push(Frame::Object {
+                key: None,
+                expect_key: true,
+            }),
+            b'[' => frames.push(Frame::Array(0)),
+            b'}' | b']' => {
+                frames.pop();
+            }
+            b',' => match frames.last_mut() {
+                Some(Frame::Object { expect_key, .. }) => *expect_key = true,
+                Some(Frame::Array(index)) => *index += 1,
+                None => {}
+            },
+            b'"' => {
+                let start = i + 1;
+                i = start;
+                while i < text.len() && text[i] != b'"' {
+                    // skip the escaped character
+                    i += if text[i] == b'\\' { 2 } else { 1 };
+                }
+                if let Some(Frame::Object { key, expect_key }) = frames.last_mut() {
+                    if *expect_key {
+                        let end = i.min(text.len());
+                        *key = Some(String::from_utf8_lossy(&text[start..end]).into_owned());
+                        *expect_key = false;
+                    }
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let mut path = String::new();
+    for frame in &frames {
+        match frame {
+            Frame::Object { key: Some(key), .. } => {
+                if !path.is_empty() {
+                    path.push('.');
+                }
+                path.push_str(key);
+            }
+            Frame::Object { key: None, .. } => {}
+            Frame::Array(index) => path.push_str(&format!("[{index}]")),
+        }
+    }
+    if path.is_empty() {
+        path.push('.');
+    }
+    path
+}
+
+#[cfg(test)]
+mod test_json_path {
+    use serde::Deserialize;
+
+    use super::json_path_at;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Item {
+        id: u64,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Items {
+        items: Vec<Item>,
+    }
+
+    fn path_of_error(text: &str) -> String {
+        let error = serde_json::from_str::<Items>(text).unwrap_err();
+        json_path_at(text, error.line(), error.column())
+    }
+
+    #[test]
+    fn test_json_path_at() {
+        assert_eq!(
+            path_of_error(r#"{"items": [{"id": 1}, {"id": 2}, {"id": "3"}]}"#),
+            "items[2].id"
+        );
+        assert_eq!(
+            path_of_error("{\n  \"other\": \"a,{\\\"\",\n  \"items\": [\n    {\"id\": -1}\n  ]\n}"),
+            "items[0].id"
+        );
+        assert_eq!(path_of_error("[]"), ".");
+    }
+}
@@ -1,2 +1,28 @@
+pub mod body_factory;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+pub mod curl;
+#[cfg(feature = "html")]
+pub mod html;
+#[cfg(all(feature = "html-redirect", not(target_arch = "wasm32")))]
+pub(crate) mod html_redirect;
+pub(crate) mod json_path;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+pub mod multipart;
+pub(crate) mod path_template;
+pub mod random;
+pub mod redactor;
+pub mod response;
 pub mod string_ext;
 pub mod string_url_builder;
+pub(crate) mod timer;
+pub(crate) mod token_bucket;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod upload;
+pub mod url_builder;
+
+pub use random::random_bytes;
+pub use redactor::redact_url;
+#[cfg(not(target_arch = "wasm32"))]
+pub use response::response_from_parts;
@@ -0,0 +1,59 @@
+//! A MessagePack codec for request and response bodies, see
+//! [`crate::ErgoRequestBuilder::msgpack`], backed by [`rmp_serde`].
+//!
+//! Structs are encoded as maps with their field names.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// The `Content-Type` of MessagePack bodies.
+pub const CONTENT_TYPE: &str = "application/msgpack";
+
+/// Encode `value` as MessagePack.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> crate::Result<Vec<u8>> {
+    rmp_serde::to_vec_named(value).map_err(|e| crate::Error::Codec(e.to_string()))
+}
+
+/// Decode a `T` from MessagePack `bytes`.
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> crate::Result<T> {
+    let mut rest = bytes;
+    let value = rmp_serde::from_read(&mut rest).map_err(|e| crate::Error::Codec(e.to_string()))?;
+    match rest.is_empty() {
+        true => Ok(value),
+        false => Err(crate::Error::Codec(format!(
+            "trailing bytes at {}",
+            bytes.len() - rest.len()
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test_msgpack {
+    use serde_json::{json, Value};
+
+    use super::{from_slice, to_vec};
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        let value = json!({
+            "name": "ergo",
+            "numbers": [0, 127, 128, 65536, -1, -33, -129, -40000, 5000000000u64, 1.5],
+            "nested": {"ok": true, "none": null},
+            "long": "a".repeat(300),
+        });
+        let bytes = to_vec(&value).unwrap();
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), value);
+
+        assert_eq!(
+            to_vec(&json!({"a": [1, -1]})).unwrap(),
+            [0x81, 0xa1, b'a', 0x92, 0x01, 0xff]
+        );
+        // bin 8 is decoded as an array of bytes
+        assert_eq!(
+            from_slice::<Vec<u8>>(&[0xc4, 0x02, 0x01, 0x02]).unwrap(),
+            [1, 2]
+        );
+        assert!(from_slice::<Value>(&[0x92, 0x01]).is_err());
+        assert!(from_slice::<Value>(&[0x01, 0x02]).is_err());
+    }
+}
@@ -0,0 +1,300 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use bytes::Bytes;
+#[cfg(not(target_arch = "wasm32"))]
+use futures::stream::BoxStream;
+#[cfg(not(target_arch = "wasm32"))]
+use futures::StreamExt;
+use reqwest::Body;
+use serde::Serialize;
+
+use super::body_factory::BodyFactory;
+use super::random::random_bytes;
+
+#[derive(Clone, Debug)]
+enum PartSource {
+    Bytes(Bytes),
+    #[cfg(not(target_arch = "wasm32"))]
+    File(PathBuf),
+}
+
+#[derive(Clone, Debug)]
+struct Part {
+    name: String,
+    file_name: Option<String>,
+    mime: Option<String>,
+    source: PartSource,
+}
+
+impl Part {
+    /// Get the headers of this part, after the boundary line.
+    fn headers(&self) -> String {
+        let mut headers = format!(
+            "Content-Disposition: form-data; name=\"{}\"",
+            escape(&self.name)
+        );
+        if let Some(file_name) = &self.file_name {
+            headers.push_str(&format!("; filename=\"{}\"", escape(file_name)));
+        }
+        if let Some(mime) = &self.mime {
+            headers.push_str(&format!("\r\nContent-Type: {mime}"));
+        }
+        headers
+    }
+}
+
+/// Escape a name in `Content-Disposition` like browsers do.
+fn escape(name: &str) -> String {
+    name.replace('"', "%22")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// A `multipart/form-data` body described by its parts, set by
+/// [`crate::ErgoRequestBuilder::multipart_form`].
+///
+/// Unlike `reqwest::multipart::Form`, the body is encoded again from the parts (and files are
+/// read again) for each retry or redirect, with the same boundary.
+///
+/// # Example
+/// ```no_run
+/// # use ergoreq::utils::multipart::ErgoMultipart;
+/// # use ergoreq::ErgoClient;
+/// let client = ErgoClient::new(reqwest::Client::new()).with_retry_count(3);
+/// let form = ErgoMultipart::new()
+///     .text("title", "holiday")
+///     .json("meta", &serde_json::json!({"public": true}))
+///     .file("photo", "photo.jpg");
+/// let request = client.put("https://example.com/upload").multipart_form(form);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ErgoMultipart {
+    boundary: String,
+    parts: Vec<Part>,
+    error: Option<Arc<serde_json::Error>>,
+}
+
+impl ErgoMultipart {
+    /// Create an empty `ErgoMultipart` with a random boundary.
+    pub fn new() -> Self {
+        Self {
+            boundary: random_bytes::<16>()
+                .iter()
+                .map(|v| format!("{:02x}", v))
+                .collect(),
+            parts: vec![],
+            error: None,
+        }
+    }
+
+    /// Get the boundary between parts.
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    /// Get the `Content-Type` header of this body.
+    pub fn content_type(&self) -> String {
+        format!("multipart/form-data; boundary={}", self.boundary)
+    }
+
+    /// Add a text field.
+    pub fn text<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.parts.push(Part {
+            name: name.into(),
+            file_name: None,
+            mime: None,
+            source: PartSource::Bytes(Bytes::from(value.into())),
+        });
+        self
+    }
+
+    /// Add a field serialized as JSON, with `Content-Type: application/json`.
+    ///
+    /// A serialization error is returned when the request is sent.
+    pub fn json<N: Into<String>, T: Serialize + ?Sized>(mut self, name: N, value: &T) -> Self {
+        match serde_json::to_vec(value) {
+            Ok(json) => self.parts.push(Part {
+                name: name.into(),
+                file_name: None,
+                mime: Some("application/json".to_owned()),
+                source: PartSource::Bytes(Bytes::from(json)),
+            }),
+            Err(e) => self.error = Some(Arc::new(e)),
+        }
+        self
+    }
+
+    /// Add a file from memory, the mime type is guessed from `file_name`.
+    pub fn bytes<N, B, F>(mut self, name: N, bytes: B, file_name: F) -> Self
+    where
+        N: Into<String>,
+        B: Into<Bytes>,
+        F: Into<String>,
+    {
+        let file_name = file_name.into();
+        self.parts.push(Part {
+            name: name.into(),
+            mime: Some(guess_mime(&file_name)),
+            file_name: Some(file_name),
+            source: PartSource::Bytes(bytes.into()),
+        });
+        self
+    }
+
+    /// Add a file read from `path` when the request is sent, the file name is the last
+    /// component of `path` and the mime type is guessed from it.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn file<N: Into<String>, P: Into<PathBuf>>(mut self, name: N, path: P) -> Self {
+        let path = path.into();
+        let file_name = path
+            .file_name()
+            .map(|v| v.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        self.parts.push(Part {
+            name: name.into(),
+            mime: Some(guess_mime(&file_name)),
+            file_name: Some(file_name),
+            source: PartSource::File(path),
+        });
+        self
+    }
+
+    /// Get the encoded chunks of the body, a file is read where its path is.
+    fn chunks(&self) -> Vec<PartSource> {
+        let mut chunks = vec![];
+        for part in &self.parts {
+            let head = format!("--{}\r\n{}\r\n\r\n", self.boundary, part.headers());
+            chunks.push(PartSource::Bytes(Bytes::from(head)));
+            chunks.push(part.source.to_owned());
+            chunks.push(PartSource::Bytes(Bytes::from_static(b"\r\n")));
+        }
+        let tail = format!("--{}--\r\n", self.boundary);
+        chunks.push(PartSource::Bytes(Bytes::from(tail)));
+        chunks
+    }
+
+    /// Get the length of the encoded body.
+    pub(crate) async fn content_length(&self) -> crate::Result<u64> {
+        let mut length = 0;
+        for chunk in self.chunks() {
+            length += match chunk {
+                PartSource::Bytes(bytes) => bytes.len() as u64,
+                #[cfg(not(target_arch = "wasm32"))]
+                PartSource::File(path) => tokio::fs::metadata(path).await?.len(),
+            };
+        }
+        Ok(length)
+    }
+
+    /// Create a [`BodyFactory`] encoding this body for each attempt.
+    pub(crate) fn into_factory(self) -> crate::Result<BodyFactory> {
+        if let Some(error) = self.error {
+            return Err(crate::Error::Custom(Box::new(error)));
+        }
+        let chunks = self.chunks();
+        #[cfg(not(target_arch = "wasm32"))]
+        let factory = BodyFactory::new(move || {
+            let stream = futures::stream::iter(chunks.to_owned()).flat_map(chunk_stream);
+            async move { Ok(Body::wrap_stream(stream)) }
+        });
+        // only parts in memory on wasm, so encode the whole body at once
+        #[cfg(target_arch = "wasm32")]
+        let factory = {
+            let body = chunks
+                .into_iter()
+                .map(|PartSource::Bytes(bytes)| bytes)
+                .collect::<Vec<_>>()
+                .concat();
+            BodyFactory::new(move || {
+                let body = body.to_owned();
+                async move { Ok(Body::from(body)) }
+            })
+        };
+        Ok(factory)
+    }
+}
+
+impl Default for ErgoMultipart {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn guess_mime(file_name: &str) -> String {
+    mime_guess::from_path(file_name)
+        .first_or_octet_stream()
+        .to_string()
+}
+
+/// Stream the bytes of `chunk`.
+#[cfg(not(target_arch = "wasm32"))]
+fn chunk_stream(chunk: PartSource) -> BoxStream<'static, std::io::Result<Bytes>> {
+    match chunk {
+        PartSource::Bytes(bytes) => futures::stream::once(async { Ok(bytes) }).boxed(),
+        PartSource::File(path) => {
+            use tokio::io::AsyncReadExt;
+
+            futures::stream::try_unfold(None, move |file| {
+                let path = path.to_owned();
+                async move {
+                    let mut file = match file {
+                        Some(file) => file,
+                        None => tokio::fs::File::open(path).await?,
+                    };
+                    let mut buffer = vec![0u8; 64 * 1024];
+                    let read = file.read(&mut buffer).await?;
+                    if read == 0 {
+                        return Ok(None);
+                    }
+                    buffer.truncate(read);
+                    Ok(Some((Bytes::from(buffer), Some(file))))
+                }
+            })
+            .boxed()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_multipart {
+    use futures::TryStreamExt;
+
+    use super::ErgoMultipart;
+
+    #[tokio::test]
+    async fn test_encode() {
+        let form = ErgoMultipart::new()
+            .text("a\"b", "1")
+            .json("meta", &serde_json::json!({"x": 1}))
+            .bytes("file", "content", "note.txt");
+        let boundary = form.boundary().to_owned();
+        let length = form.content_length().await.unwrap();
+        let factory = form.into_factory().unwrap();
+
+        for _ in 0..2 {
+            let body = factory.make().await.unwrap();
+            let body = http_body_util_collect(body).await;
+            assert_eq!(body.len() as u64, length);
+            assert_eq!(
+                String::from_utf8(body).unwrap(),
+                format!(
+                    "--{boundary}\r\nContent-Disposition: form-data; name=\"a%22b\"\r\n\r\n1\r\n\
+                     --{boundary}\r\nContent-Disposition: form-data; name=\"meta\"\r\nContent-Type: application/json\r\n\r\n{{\"x\":1}}\r\n\
+                     --{boundary}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"note.txt\"\r\nContent-Type: text/plain\r\n\r\ncontent\r\n\
+                     --{boundary}--\r\n"
+                )
+            );
+        }
+    }
+
+    async fn http_body_util_collect(body: reqwest::Body) -> Vec<u8> {
+        let chunks = reqwest::Response::from(http::Response::new(body))
+            .bytes_stream()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        chunks.concat()
+    }
+}
@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+/// Percent-encode `value` so it stays a single path segment, only unreserved characters are
+/// kept.
+pub(crate) fn encode_path_param(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Replace every `{name}` placeholder of the path of `url` with the percent-encoded parameter
+/// `name` of `params`.
+///
+/// Fails with [`crate::Error::UnresolvedPathParam`] if a placeholder has no parameter.
+pub(crate) fn expand_path_params(
+    url: &mut url::Url,
+    params: &HashMap<String, String>,
+) -> crate::Result<()> {
+    // braces of the path are percent-encoded when the url is parsed
+    let path = url.path().replace("%7b", "%7B").replace("%7d", "%7D");
+    let mut expanded = String::with_capacity(path.len());
+    let mut rest = path.as_str();
+    while let Some(start) = rest.find("%7B") {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 3..];
+        let Some(end) = after.find("%7D") else {
+            expanded.push_str(&rest[start..]);
+            rest = "";
+            break;
+        };
+        let name = &after[..end];
+        let value = params
+            .get(name)
+            .ok_or_else(|| crate::Error::UnresolvedPathParam(name.to_owned()))?;
+        expanded.push_str(&encode_path_param(value));
+        rest = &after[end + 3..];
+    }
+    expanded.push_str(rest);
+    url.set_path(&expanded);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_path_template {
+    use std::collections::HashMap;
+
+    use super::expand_path_params;
+
+    #[test]
+    fn test_expand_path_params() {
+        let params = HashMap::from([
+            ("id".to_owned(), "a/b c".to_owned()),
+            ("repo".to_owned(), "ergo?req#1".to_owned()),
+        ]);
+        let mut url =
+            url::Url::parse("https://example.com/users/{id}/repos/{repo}.json?q={id}").unwrap();
+        expand_path_params(&mut url, &params).unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://example.com/users/a%2Fb%20c/repos/ergo%3Freq%231.json?q={id}"
+        );
+
+        let mut url = url::Url::parse("https://example.com/users/{id}/{missing}").unwrap();
+        let err = expand_path_params(&mut url, &params).unwrap_err();
+        assert!(matches!(err, crate::Error::UnresolvedPathParam(name) if name == "missing"));
+    }
+}
@@ -0,0 +1,18 @@
+/// Get `N` random bytes from the random number generator of the system.
+///
+/// Used for nonces, boundaries and keys which must not be predictable.
+///
+/// # Panics
+/// If the random number generator of the system is unavailable.
+///
+/// # Example
+/// ```
+/// # use ergoreq::utils::random_bytes;
+/// let nonce = random_bytes::<16>();
+/// assert_ne!(nonce, random_bytes::<16>());
+/// ```
+pub fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).expect("the random number generator is unavailable");
+    bytes
+}
@@ -0,0 +1,241 @@
+#[cfg(not(target_arch = "wasm32"))]
+use bytes::Bytes;
+#[cfg(not(target_arch = "wasm32"))]
+use futures::stream::{BoxStream, Stream, StreamExt};
+#[cfg(not(target_arch = "wasm32"))]
+use http::{HeaderMap, StatusCode};
+use reqwest::Response;
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::{Body, ResponseBuilderExt};
+
+/// Build a [`Response`] from its parts.
+///
+/// This is the way for a [`crate::middleware::middleware::Middleware`] to return a synthetic
+/// response (from a cache, a mock or a circuit breaker) without calling `Next`.
+///
+/// # Example
+/// ```
+/// # use ergoreq::utils::response_from_parts;
+/// let response = response_from_parts(
+///     http::StatusCode::OK,
+///     http::HeaderMap::new(),
+///     "Hello",
+///     "https://example.com".parse().unwrap(),
+/// );
+/// assert_eq!(response.status(), http::StatusCode::OK);
+/// assert_eq!(response.url().as_str(), "https://example.com/");
+/// ```
+#[cfg(not(target_arch = "wasm32"))]
+pub fn response_from_parts<B>(
+    status: StatusCode,
+    headers: HeaderMap,
+    body: B,
+    url: url::Url,
+) -> Response
+where
+    B: Into<Body>,
+{
+    let mut response = http::Response::builder()
+        .status(status)
+        .url(url)
+        .body(body.into())
+        .expect("status code is already validated");
+    *response.headers_mut() = headers;
+    Response::from(response)
+}
+
+/// Get the size of the body of `response`, from its `Content-Length` header if the body lost
+/// its size, like bodies replaced by [`map_body_stream`] do.
+pub(crate) fn content_length(response: &Response) -> Option<u64> {
+    response.content_length().or_else(|| {
+        response
+            .headers()
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok()?.trim().parse().ok())
+    })
+}
+
+/// Replace the body of `response` with a stream derived from its current body stream.
+///
+/// Status, version, headers, url and extensions (like the connection upgrade) are preserved.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn map_body_stream<F, S, E>(response: Response, f: F) -> Response
+where
+    F: FnOnce(BoxStream<'static, reqwest::Result<Bytes>>) -> S,
+    S: Stream<Item = Result<Bytes, E>> + Send + 'static,
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let url = response.url().to_owned();
+    let (parts, body) = http::Response::<Body>::from(response).into_parts();
+    let stream = Response::from(http::Response::new(body)).bytes_stream();
+    let body = Body::wrap_stream(f(stream.boxed()));
+    // the url is not kept by the conversion to `http::Response`
+    with_url(Response::from(http::Response::from_parts(parts, body)), url)
+}
+
+/// Fail with [`crate::Error::BodyTooLarge`] if the body of `response` is larger than
+/// `max_bytes`, right away if its `Content-Length` is already larger, or else while streaming.
+///
+/// The body of a wasm response can't be replaced, only its `Content-Length` is checked.
+pub(crate) fn limit_body(response: Response, max_bytes: u64) -> crate::Result<Response> {
+    if response.content_length().is_some_and(|v| v > max_bytes) {
+        return Err(crate::Error::BodyTooLarge(max_bytes));
+    }
+    #[cfg(target_arch = "wasm32")]
+    return Ok(response);
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut received = 0u64;
+        Ok(map_body_stream(response, move |stream| {
+            stream.map(move |chunk| {
+                let chunk = chunk?;
+                received += chunk.len() as u64;
+                match received > max_bytes {
+                    true => Err::<_, Box<dyn std::error::Error + Send + Sync>>(
+                        crate::Error::BodyTooLarge(max_bytes).into(),
+                    ),
+                    false => Ok(chunk),
+                }
+            })
+        }))
+    }
+}
+
+/// The body of a wasm response can't be replaced, it is returned as is.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn verify_body(response: Response, _method: &http::Method) -> Response {
+    response
+}
+
+/// Fail reading the body of `response` with [`crate::Error::TruncatedBody`] if it ends before
+/// its `Content-Length`, or if the transport or the decompression fails midway.
+///
+/// Chunked bodies have no expected size, they only fail on errors. Responses to `HEAD` and
+/// `1xx`, `204` and `304` responses have no body whatever their `Content-Length`, they are
+/// returned as is.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn verify_body(response: Response, method: &http::Method) -> Response {
+    let status = response.status();
+    if method == http::Method::HEAD
+        || status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::NOT_MODIFIED
+    {
+        return response;
+    }
+    let headers = response.headers();
+    let chunked = headers
+        .get_all(http::header::TRANSFER_ENCODING)
+        .iter()
+        .any(|v| {
+            v.to_str()
+                .is_ok_and(|v| v.to_ascii_lowercase().contains("chunked"))
+        });
+    let expected = headers
+        .get(http::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.trim().parse::<u64>().ok())
+        .filter(|_| !chunked);
+    map_body_stream(response, move |stream| {
+        futures::stream::unfold(Some((stream, 0u64)), move |state| async move {
+            let (mut stream, received) = state?;
+            let truncated = || {
+                Box::<dyn std::error::Error + Send + Sync>::from(crate::Error::TruncatedBody {
+                    expected,
+                    received,
+                })
+            };
+            match stream.next().await {
+                Some(Ok(chunk)) => {
+                    let received = received + chunk.len() as u64;
+                    Some((Ok(chunk), Some((stream, received))))
+                }
+                Some(Err(e)) => match crate::Error::from(e) {
+                    crate::Error::Reqwest(e) if e.is_body() || e.is_decode() => {
+                        Some((Err(truncated()), None))
+                    }
+                    // like a body over the limit of `limit_body`
+                    e => Some((Err(e.into()), None)),
+                },
+                None if expected.is_some_and(|v| received < v) => Some((Err(truncated()), None)),
+                None => None,
+            }
+        })
+    })
+}
+
+/// Replace the url of `response`, without reading its body.
+///
+/// The url of a wasm response can't be replaced, it is returned as is.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn with_url(response: Response, _url: url::Url) -> Response {
+    response
+}
+
+/// Replace the url of `response`, without reading its body.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn with_url(response: Response, url: url::Url) -> Response {
+    let (mut parts, body) = http::Response::<Body>::from(response).into_parts();
+    let (url_parts, _) = http::Response::builder()
+        .url(url)
+        .body(())
+        .expect("no status is set")
+        .into_parts();
+    parts.extensions.extend(url_parts.extensions);
+    Response::from(http::Response::from_parts(parts, body))
+}
+
+#[cfg(test)]
+mod test_response {
+    use super::{map_body_stream, response_from_parts};
+    use http::{HeaderMap, HeaderValue, StatusCode};
+
+    #[tokio::test]
+    async fn test_response_from_parts() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::header::CONTENT_TYPE,
+            HeaderValue::from_static("text/plain"),
+        );
+        headers.append(http::header::SET_COOKIE, HeaderValue::from_static("a=1"));
+        headers.append(http::header::SET_COOKIE, HeaderValue::from_static("b=2"));
+
+        let response = response_from_parts(
+            StatusCode::IM_A_TEAPOT,
+            headers,
+            "synthetic",
+            "https://example.com/tea".parse().unwrap(),
+        );
+
+        assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+        assert_eq!(response.url().as_str(), "https://example.com/tea");
+        assert_eq!(
+            response
+                .headers()
+                .get_all(http::header::SET_COOKIE)
+                .iter()
+                .count(),
+            2
+        );
+        assert_eq!(response.text().await.unwrap(), "synthetic");
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Marker(u8);
+
+    #[tokio::test]
+    async fn test_map_body_stream_keeps_extensions() {
+        let mut response = response_from_parts(
+            StatusCode::CREATED,
+            HeaderMap::new(),
+            "hello",
+            "https://example.com/a".parse().unwrap(),
+        );
+        response.extensions_mut().insert(Marker(7));
+        let response = map_body_stream(response, |stream| stream);
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.url().as_str(), "https://example.com/a");
+        assert_eq!(response.extensions().get::<Marker>(), Some(&Marker(7)));
+        assert_eq!(response.text().await.unwrap(), "hello");
+    }
+}
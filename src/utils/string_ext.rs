@@ -11,9 +11,30 @@ macro_rules! impl_string_req_method_in_trait {
     };
 }
 
-/// Extension trait for `String` to create a `ErgoRequestBuilder` with given method.
+/// Extension trait for `String` and `Url` to create a `ErgoRequestBuilder` with given method.
 pub trait ErgoStringToRequestExt {
-    impl_string_req_method_in_trait!(get, post, put, delete, head, options, patch);
+    for_each_http_method!(impl_string_req_method_in_trait);
+
+    /// Create a `ErgoRequestBuilder` for `connect` method with given String as url.
+    ///
+    /// # Notice
+    /// A `CONNECT` request opens a tunnel, so it is never redirected or retried.
+    fn http_connect(
+        &self,
+        client: &crate::wrappers::client_wrapper::ErgoClient,
+    ) -> crate::wrappers::request_builder_wrapper::ErgoRequestBuilder {
+        self.http_request(client, reqwest::Method::CONNECT)
+    }
+
+    /// Create a `ErgoRequestBuilder` for `method` with given String as url, an alias of
+    /// [`Self::http_request`].
+    fn http_method(
+        &self,
+        client: &crate::wrappers::client_wrapper::ErgoClient,
+        method: reqwest::Method,
+    ) -> crate::wrappers::request_builder_wrapper::ErgoRequestBuilder {
+        self.http_request(client, method)
+    }
 
     fn http_request(
         &self,
@@ -52,6 +73,16 @@ impl ErgoStringToRequestExt for &String {
     }
 }
 
+impl ErgoStringToRequestExt for url::Url {
+    fn http_request(
+        &self,
+        client: &crate::wrappers::client_wrapper::ErgoClient,
+        method: reqwest::Method,
+    ) -> crate::wrappers::request_builder_wrapper::ErgoRequestBuilder {
+        client.request(method, self.to_owned())
+    }
+}
+
 #[cfg(test)]
 mod test_string_ext {
     use crate::{utils::string_ext::ErgoStringToRequestExt, wrappers::client_wrapper::ErgoClient};
@@ -73,4 +104,63 @@ mod test_string_ext {
             &Method::GET
         )
     }
+
+    #[test]
+    fn test_url_http_request() {
+        let client = ErgoClient::new(reqwest::Client::new());
+        let url = url::Url::parse("https://crates.io/a%20b").unwrap();
+        let request = url.http_post(&client).into_inner().build().unwrap();
+        assert_eq!(request.method(), &Method::POST);
+        assert_eq!(request.url(), &url);
+    }
+
+    /// Respond `503` to every request, counting them.
+    struct Unavailable(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    #[async_trait::async_trait]
+    impl crate::middleware::middleware::Middleware for Unavailable {
+        async fn handle(
+            &self,
+            req: reqwest::Request,
+            _ext: &mut http::Extensions,
+            _next: crate::middleware::middleware::Next<'_>,
+        ) -> crate::Result<reqwest::Response> {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(crate::utils::response_from_parts(
+                http::StatusCode::SERVICE_UNAVAILABLE,
+                http::HeaderMap::new(),
+                "",
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_http_connect_and_method() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        use crate::middleware::middleware::MiddlewarePhase;
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(2)
+            .with_middleware_phase(Unavailable(count.clone()), MiddlewarePhase::PostRetry);
+
+        // a CONNECT request is not retried
+        let response = "https://crates.io"
+            .http_connect(&client)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+
+        let request = "https://crates.io"
+            .http_method(&client, Method::from_bytes(b"PURGE").unwrap())
+            .into_inner()
+            .build()
+            .unwrap();
+        assert_eq!(request.method().as_str(), "PURGE");
+    }
 }
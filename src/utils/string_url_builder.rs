@@ -1,5 +1,17 @@
+use std::fmt::Display;
+
+use url::Url;
+
+use crate::utils::path_template::encode_path_param;
+
 pub trait StringUrlBuilderTrait {
+    /// The type of the URL with the segments added, `String` for strings and `Url` for `Url`.
+    type Output;
+
     /// Add a segment to the end of the URL. If the URL ends with `/`, the segment will be added directly. Otherwise, a `/` will be added before the segment.
+    ///
+    /// The segment is percent-encoded, so `/`, `?` and spaces stay inside the segment. Use
+    /// [`Self::add_raw_url_segment`] for already encoded segments.
     /// # Example
     /// ```Rust
     /// let url = "https://example.com";
@@ -23,8 +35,8 @@ pub trait StringUrlBuilderTrait {
     /// assert_eq!(url.add_url_segment(segment), "https://example.com/test?query=1");
     ///
     /// ```
-    fn add_url_segment(self, segment: &str) -> String;
-    /// Add multiple segments to the end of the URL. The segments will be added in order.
+    fn add_url_segment(self, segment: &str) -> Self::Output;
+    /// Add multiple segments to the end of the URL. The segments will be added in order and percent-encoded.
     /// # Example
     /// ```Rust
     /// let url = "https://example.com";
@@ -48,76 +60,132 @@ pub trait StringUrlBuilderTrait {
     /// assert_eq!(url.add_url_segments(segments), "https://example.com/test/test1?query=1");
     ///
     /// ```
-    fn add_url_segments(self, segments: &[&str]) -> String;
+    fn add_url_segments(self, segments: &[&str]) -> Self::Output;
+    /// Add a segment like [`Self::add_url_segment`] without percent-encoding it.
+    /// # Example
+    /// ```Rust
+    /// let url = "https://example.com";
+    /// assert_eq!(url.add_raw_url_segment("a%20b/c"), "https://example.com/a%20b/c");
+    /// ```
+    fn add_raw_url_segment(self, segment: &str) -> Self::Output;
+    /// Add a segment like [`Self::add_url_segment`] from any `Display` value, such as a
+    /// numeric ID or an enum.
+    /// # Example
+    /// ```Rust
+    /// let url = "https://example.com/users";
+    /// assert_eq!(url.add_url_segment_t(42), "https://example.com/users/42");
+    /// ```
+    fn add_url_segment_t(self, segment: impl Display) -> Self::Output
+    where
+        Self: Sized,
+    {
+        self.add_url_segment(&segment.to_string())
+    }
+    /// Add multiple segments like [`Self::add_url_segments`] from a slice, an array, a `Vec`
+    /// or a tuple of `Display` values.
+    /// # Example
+    /// ```Rust
+    /// let url = "https://example.com";
+    /// assert_eq!(url.add_url_segments_t(("users", 42, "posts")), "https://example.com/users/42/posts");
+    /// ```
+    fn add_url_segments_t(self, segments: impl UrlSegments) -> Self::Output
+    where
+        Self: Sized,
+    {
+        let segments = segments.to_segments();
+        let segments = segments.iter().map(String::as_str).collect::<Vec<_>>();
+        self.add_url_segments(&segments)
+    }
 }
 
-impl StringUrlBuilderTrait for String {
-    fn add_url_segment(self, segment: &str) -> String {
-        let query_split = self.split_once("?");
-
-        let segment = segment.trim_start_matches('/');
-
-        if let Some((url, query)) = query_split {
-            if url.ends_with("/") {
-                if query.is_empty() {
-                    format!("{}{}", url, segment)
-                } else {
-                    format!("{}{}?{}", url, segment, query)
-                }
-            } else {
-                if query.is_empty() {
-                    format!("{}/{}", url, segment)
-                } else {
-                    format!("{}/{}?{}", url, segment, query)
-                }
-            }
-        } else {
-            if self.ends_with("/") {
-                format!("{}{}", self, segment)
-            } else {
-                format!("{}/{}", self, segment)
+/// Path segments made of `Display` values, see [`StringUrlBuilderTrait::add_url_segments_t`].
+///
+/// Implemented for slices, arrays, `Vec`s and tuples of up to 6 values.
+pub trait UrlSegments {
+    /// Format every segment.
+    fn to_segments(&self) -> Vec<String>;
+}
+
+impl<T: Display> UrlSegments for [T] {
+    fn to_segments(&self) -> Vec<String> {
+        self.iter().map(ToString::to_string).collect()
+    }
+}
+
+impl<T: Display, const N: usize> UrlSegments for [T; N] {
+    fn to_segments(&self) -> Vec<String> {
+        self.as_slice().to_segments()
+    }
+}
+
+impl<T: Display> UrlSegments for Vec<T> {
+    fn to_segments(&self) -> Vec<String> {
+        self.as_slice().to_segments()
+    }
+}
+
+impl<S: UrlSegments + ?Sized> UrlSegments for &S {
+    fn to_segments(&self) -> Vec<String> {
+        (**self).to_segments()
+    }
+}
+
+macro_rules! impl_url_segments_for_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: Display),+> UrlSegments for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn to_segments(&self) -> Vec<String> {
+                let ($($name,)+) = self;
+                vec![$($name.to_string()),+]
             }
         }
+    };
+}
+
+impl_url_segments_for_tuple!(A);
+impl_url_segments_for_tuple!(A, B);
+impl_url_segments_for_tuple!(A, B, C);
+impl_url_segments_for_tuple!(A, B, C, D);
+impl_url_segments_for_tuple!(A, B, C, D, E);
+impl_url_segments_for_tuple!(A, B, C, D, E, F);
+
+/// Add the raw `segment` to the end of `url`, before its query.
+fn join_segment(url: &str, segment: &str) -> String {
+    let segment = segment.trim_start_matches('/');
+    let (path, query) = match url.split_once('?') {
+        Some((path, query)) if !query.is_empty() => (path, Some(query)),
+        Some((path, _)) => (path, None),
+        None => (url, None),
+    };
+    let separator = if path.ends_with('/') { "" } else { "/" };
+    match query {
+        Some(query) => format!("{path}{separator}{segment}?{query}"),
+        None => format!("{path}{separator}{segment}"),
     }
+}
 
-    fn add_url_segments(self, segments: &[&str]) -> String {
-        let mut url = self;
+impl StringUrlBuilderTrait for String {
+    type Output = String;
 
-        for segment in segments {
-            url = url.add_url_segment(segment);
-        }
+    fn add_url_segment(self, segment: &str) -> String {
+        self.as_str().add_url_segment(segment)
+    }
 
-        url
+    fn add_url_segments(self, segments: &[&str]) -> String {
+        self.as_str().add_url_segments(segments)
+    }
+
+    fn add_raw_url_segment(self, segment: &str) -> String {
+        join_segment(&self, segment)
     }
 }
 
 impl StringUrlBuilderTrait for &str {
+    type Output = String;
+
     fn add_url_segment(self, segment: &str) -> String {
-        let query_split = self.split_once("?");
-
-        let segment = segment.trim_start_matches('/');
-
-        if let Some((url, query)) = query_split {
-            if url.ends_with("/") {
-                if query.is_empty() {
-                    format!("{}{}", url, segment)
-                } else {
-                    format!("{}{}?{}", url, segment, query)
-                }
-            } else {
-                if query.is_empty() {
-                    format!("{}/{}", url, segment)
-                } else {
-                    format!("{}/{}?{}", url, segment, query)
-                }
-            }
-        } else {
-            if self.ends_with("/") {
-                format!("{}{}", self, segment)
-            } else {
-                format!("{}/{}", self, segment)
-            }
-        }
+        let segment = encode_path_param(segment.trim_start_matches('/'));
+        join_segment(self, &segment)
     }
 
     fn add_url_segments(self, segments: &[&str]) -> String {
@@ -129,11 +197,45 @@ impl StringUrlBuilderTrait for &str {
 
         url
     }
+
+    fn add_raw_url_segment(self, segment: &str) -> String {
+        join_segment(self, segment)
+    }
+}
+
+/// Segments are added with `path_segments_mut`, so the URL stays parsed. A URL which cannot
+/// be a base, like `mailto:`, is returned unchanged.
+impl StringUrlBuilderTrait for Url {
+    type Output = Url;
+
+    fn add_url_segment(self, segment: &str) -> Url {
+        self.add_url_segments(&[segment])
+    }
+
+    fn add_url_segments(mut self, segments: &[&str]) -> Url {
+        if let Ok(mut path) = self.path_segments_mut() {
+            path.pop_if_empty();
+            for segment in segments {
+                path.push(segment.trim_start_matches('/'));
+            }
+        }
+        self
+    }
+
+    fn add_raw_url_segment(mut self, segment: &str) -> Url {
+        if !self.cannot_be_a_base() {
+            let path = join_segment(self.path(), segment);
+            self.set_path(&path);
+        }
+        self
+    }
 }
 
 #[cfg(test)]
 mod test_string_url_builder {
-    use super::StringUrlBuilderTrait;
+    use url::Url;
+
+    use super::{StringUrlBuilderTrait, UrlSegments};
 
     #[test]
     fn test_add_url_segment() {
@@ -256,4 +358,58 @@ mod test_string_url_builder {
             "https://example.com/test/test1?query=1"
         );
     }
+
+    #[test]
+    fn test_add_url_segment_encoding() {
+        let url = "https://example.com?query=1";
+
+        assert_eq!(
+            url.add_url_segment("a b/c?d"),
+            "https://example.com/a%20b%2Fc%3Fd?query=1"
+        );
+        assert_eq!(
+            url.add_raw_url_segment("a%20b/c"),
+            "https://example.com/a%20b/c?query=1"
+        );
+        assert_eq!(
+            "https://example.com/".add_url_segments(&["é", "#1"]),
+            "https://example.com/%C3%A9/%231"
+        );
+    }
+
+    #[test]
+    fn test_add_url_segments_t() {
+        let url = "https://example.com/users";
+
+        assert_eq!(url.add_url_segment_t(42), "https://example.com/users/42");
+        assert_eq!(
+            url.add_url_segments_t((42, "posts", 7u8)),
+            "https://example.com/users/42/posts/7"
+        );
+        assert_eq!(
+            url.to_owned().add_url_segments_t(&[1, 2][..]),
+            "https://example.com/users/1/2"
+        );
+        assert_eq!(vec!['a', 'b'].to_segments(), ["a", "b"]);
+    }
+
+    #[test]
+    fn test_add_url_segment_to_url() {
+        let url = Url::parse("https://example.com/v2/?query=1").unwrap();
+
+        assert_eq!(
+            url.to_owned().add_url_segments(&["users", "/a b"]).as_str(),
+            "https://example.com/v2/users/a%20b?query=1"
+        );
+        assert_eq!(
+            url.to_owned().add_raw_url_segment("a%20b/c").as_str(),
+            "https://example.com/v2/a%20b/c?query=1"
+        );
+        assert_eq!(
+            url.add_url_segments_t(("users", 42)).as_str(),
+            "https://example.com/v2/users/42?query=1"
+        );
+        let url = Url::parse("mailto:someone@example.com").unwrap();
+        assert_eq!(url.to_owned().add_url_segment("a"), url);
+    }
 }
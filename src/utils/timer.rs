@@ -0,0 +1,48 @@
+use std::future::Future;
+use std::time::{Duration, SystemTime};
+
+use futures::future::Either;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub(crate) use wasm_timer::Instant;
+
+/// Get the current `SystemTime`, `SystemTime::now` panics on `wasm32-unknown-unknown`.
+pub(crate) fn system_now() -> SystemTime {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        SystemTime::now()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        let elapsed = wasm_timer::SystemTime::now()
+            .duration_since(wasm_timer::UNIX_EPOCH)
+            .unwrap_or_default();
+        std::time::UNIX_EPOCH + elapsed
+    }
+}
+
+/// Wait for `duration` on both native and `wasm32` targets.
+pub(crate) async fn sleep(duration: Duration) {
+    if duration.is_zero() {
+        return;
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::time::sleep(duration).await;
+    #[cfg(target_arch = "wasm32")]
+    wasm_timer::Delay::new(duration)
+        .await
+        .expect("failed sleeping");
+}
+
+/// Wait for `future` at most `duration`, returns `None` if it does not complete in time.
+pub(crate) async fn timeout<F: Future>(duration: Duration, future: F) -> Option<F::Output> {
+    futures::pin_mut!(future);
+    let delay = sleep(duration);
+    futures::pin_mut!(delay);
+    match futures::future::select(future, delay).await {
+        Either::Left((output, _)) => Some(output),
+        Either::Right(_) => None,
+    }
+}
@@ -0,0 +1,63 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::utils::timer::Instant;
+
+struct BucketState {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// A token bucket which can be overdrawn, so waiting callers are served in order.
+pub(crate) struct TokenBucket {
+    capacity: f64,
+    per_second: f64,
+    state: Mutex<BucketState>,
+}
+
+impl TokenBucket {
+    /// Create a full bucket holding `capacity` tokens, refilled with `per_second` tokens per second.
+    pub(crate) fn new(capacity: f64, per_second: f64) -> Self {
+        Self {
+            capacity,
+            per_second,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                updated_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take `tokens`, returns how long the caller should wait before they are available.
+    pub(crate) fn reserve(&self, tokens: f64) -> Duration {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.updated_at).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.per_second).min(self.capacity);
+        state.updated_at = now;
+        state.tokens -= tokens;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / self.per_second)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test_token_bucket {
+    use std::time::Duration;
+
+    use super::TokenBucket;
+
+    #[test]
+    fn test_token_bucket_reserve() {
+        let bucket = TokenBucket::new(2.0, 10.0);
+        assert!(bucket.reserve(1.0).is_zero());
+        assert!(bucket.reserve(1.0).is_zero());
+        let wait = bucket.reserve(1.0);
+        assert!(wait > Duration::from_millis(50) && wait <= Duration::from_millis(100));
+        let wait = bucket.reserve(1.0);
+        assert!(wait > Duration::from_millis(150) && wait <= Duration::from_millis(200));
+    }
+}
@@ -0,0 +1,84 @@
+use std::io::SeekFrom;
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::lock::Mutex;
+use reqwest::Body;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt};
+
+use super::body_factory::BodyFactory;
+
+/// Size of the chunks read from an upload reader.
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// The progress of an upload, passed to the callback of
+/// [`crate::ErgoRequestBuilder::with_upload_progress`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UploadProgress {
+    /// Bytes read from the reader by the current attempt.
+    pub uploaded: u64,
+    /// The length of the body.
+    pub total: u64,
+}
+
+pub(crate) type UploadProgressCallback = Arc<dyn Fn(UploadProgress) + Send + Sync + 'static>;
+
+trait UploadReader: AsyncRead + AsyncSeek + Unpin + Send {}
+
+impl<R: AsyncRead + AsyncSeek + Unpin + Send> UploadReader for R {}
+
+/// A body streamed from a seekable reader, set by
+/// [`crate::ErgoRequestBuilder::body_async_read`].
+///
+/// The reader is rewound for each attempt, so the request can be retried.
+#[derive(Clone)]
+pub(crate) struct AsyncReadBody {
+    reader: Arc<Mutex<Box<dyn UploadReader>>>,
+    len: u64,
+}
+
+impl AsyncReadBody {
+    pub(crate) fn new<R>(reader: R, len: u64) -> Self
+    where
+        R: AsyncRead + AsyncSeek + Unpin + Send + 'static,
+    {
+        Self {
+            reader: Arc::new(Mutex::new(Box::new(reader))),
+            len,
+        }
+    }
+
+    /// Create a [`BodyFactory`] streaming the reader, reporting to `progress`.
+    pub(crate) fn into_factory(self, progress: Option<UploadProgressCallback>) -> BodyFactory {
+        let len = self.len;
+        BodyFactory::new(move || {
+            let reader = self.reader.to_owned();
+            let progress = progress.to_owned();
+            async move {
+                let mut reader = reader.lock_owned().await;
+                reader.seek(SeekFrom::Start(0)).await?;
+                let chunks =
+                    futures::stream::try_unfold((reader, 0u64), move |(mut reader, uploaded)| {
+                        let progress = progress.to_owned();
+                        async move {
+                            let mut buffer = vec![0u8; UPLOAD_CHUNK_SIZE];
+                            let read = reader.read(&mut buffer).await?;
+                            if read == 0 {
+                                return Ok::<_, std::io::Error>(None);
+                            }
+                            buffer.truncate(read);
+                            let uploaded = uploaded + read as u64;
+                            if let Some(progress) = &progress {
+                                progress(UploadProgress {
+                                    uploaded,
+                                    total: len,
+                                });
+                            }
+                            Ok(Some((Bytes::from(buffer), (reader, uploaded))))
+                        }
+                    });
+                Ok(Body::wrap_stream(chunks))
+            }
+        })
+    }
+}
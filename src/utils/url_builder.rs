@@ -0,0 +1,203 @@
+use std::fmt::Display;
+
+use url::Url;
+
+/// Build a `Url` from a base, percent-encoding path segments, query pairs and the fragment.
+///
+/// Unlike [`crate::StringUrlBuilderTrait`], the URL stays parsed, so a segment like `a/b`
+/// is added as the single segment `a%2Fb`. Errors are kept until [`Self::build`].
+///
+/// # Example
+/// ```
+/// # use ergoreq::ErgoUrlBuilder;
+/// let url = ErgoUrlBuilder::new("https://example.com/v2/")
+///     .segments(["users", "john doe"])
+///     .query("fields", "name&email")
+///     .fragment("top")
+///     .build()
+///     .unwrap();
+/// assert_eq!(
+///     url.as_str(),
+///     "https://example.com/v2/users/john%20doe?fields=name%26email#top"
+/// );
+/// ```
+#[derive(Clone, Debug)]
+pub struct ErgoUrlBuilder {
+    url: Result<Url, String>,
+}
+
+impl ErgoUrlBuilder {
+    /// Create an `ErgoUrlBuilder` from the absolute URL `base`.
+    pub fn new(base: impl AsRef<str>) -> Self {
+        let base = base.as_ref();
+        Self {
+            url: Url::parse(base).map_err(|e| format!("'{base}': {e}")),
+        }
+    }
+
+    /// Add `segment` to the end of the path. A trailing `/` of the path is replaced by the
+    /// segment.
+    pub fn segment(self, segment: impl Display) -> Self {
+        self.segments([segment.to_string()])
+    }
+
+    /// Add `segments` to the end of the path in order, see [`Self::segment`].
+    pub fn segments<I>(mut self, segments: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        if let Ok(url) = &mut self.url {
+            if url.cannot_be_a_base() {
+                self.url = Err(format!("'{url}' cannot have path segments"));
+                return self;
+            }
+            if let Ok(mut path) = url.path_segments_mut() {
+                path.pop_if_empty();
+                for segment in segments {
+                    path.push(segment.as_ref());
+                }
+            }
+        }
+        self
+    }
+
+    /// Append the query pair `key=value`, keeping the pairs already in the URL.
+    pub fn query(mut self, key: impl AsRef<str>, value: impl Display) -> Self {
+        if let Ok(url) = &mut self.url {
+            url.query_pairs_mut()
+                .append_pair(key.as_ref(), &value.to_string());
+        }
+        self
+    }
+
+    /// Set the fragment of the URL.
+    pub fn fragment(mut self, fragment: impl AsRef<str>) -> Self {
+        if let Ok(url) = &mut self.url {
+            url.set_fragment(Some(fragment.as_ref()));
+        }
+        self
+    }
+
+    /// Build the `Url`, fails with [`crate::Error::InvalidUrl`] if the base is not a valid
+    /// URL or cannot have path segments.
+    pub fn build(self) -> crate::Result<Url> {
+        self.url.map_err(crate::Error::InvalidUrl)
+    }
+}
+
+impl From<Url> for ErgoUrlBuilder {
+    fn from(url: Url) -> Self {
+        Self { url: Ok(url) }
+    }
+}
+
+/// Resolve `path` against `base` like a browser would, except that `base` is always treated as
+/// a directory.
+///
+/// `https://example.com/v2` + `users` is `https://example.com/v2/users`, where RFC 3986 gives
+/// `https://example.com/users`. A `path` starting with `/` still replaces the path of
+/// `base`, see [`discards_base_path`], and absolute urls are returned as is.
+///
+/// # Example
+/// ```
+/// # use ergoreq::utils::url_builder::join_base;
+/// let base = "https://example.com/v2".parse().unwrap();
+/// assert_eq!(join_base(&base, "users").unwrap().as_str(), "https://example.com/v2/users");
+/// assert_eq!(join_base(&base, "/users").unwrap().as_str(), "https://example.com/users");
+/// ```
+pub fn join_base(base: &Url, path: &str) -> crate::Result<Url> {
+    let joined = match path.chars().next() {
+        // the path of the base is kept by queries, fragments and empty references
+        None | Some('?') | Some('#') => base.join(path),
+        _ if base.cannot_be_a_base() || base.path().ends_with('/') => base.join(path),
+        _ => {
+            let mut directory = base.to_owned();
+            directory.set_path(&format!("{}/", base.path()));
+            directory.join(path)
+        }
+    };
+    joined.map_err(|e| crate::Error::InvalidUrl(format!("'{path}' on '{base}': {e}")))
+}
+
+/// Whether joining `path` to `base` with [`join_base`] drops the path of `base`, like
+/// `https://example.com/v2/` + `/users`.
+pub fn discards_base_path(base: &Url, path: &str) -> bool {
+    path.starts_with('/') && !path.starts_with("//") && base.path() != "/"
+}
+
+#[cfg(test)]
+mod test_url_builder {
+    use url::Url;
+
+    use super::{discards_base_path, join_base, ErgoUrlBuilder};
+
+    #[test]
+    fn test_url_builder() {
+        let url = ErgoUrlBuilder::new("https://example.com?page=1")
+            .segment("a b/c?d")
+            .segment('é')
+            .query("q", "x y&z")
+            .build()
+            .unwrap();
+        assert_eq!(
+            url.as_str(),
+            "https://example.com/a%20b%2Fc%3Fd/%C3%A9?page=1&q=x+y%26z"
+        );
+
+        let url = ErgoUrlBuilder::new("https://example.com/v2")
+            .segments(["users", "1"])
+            .query("limit", 10)
+            .build()
+            .unwrap();
+        assert_eq!(url.as_str(), "https://example.com/v2/users/1?limit=10");
+
+        assert!(matches!(
+            ErgoUrlBuilder::new("example.com").segment("a").build(),
+            Err(crate::Error::InvalidUrl(_))
+        ));
+        assert!(matches!(
+            ErgoUrlBuilder::new("mailto:someone@example.com")
+                .segment("a")
+                .build(),
+            Err(crate::Error::InvalidUrl(_))
+        ));
+    }
+
+    #[test]
+    fn test_join_base() {
+        let join = |base: &str, path: &str| {
+            join_base(&Url::parse(base).unwrap(), path)
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(join("https://a.com/v2/", "users"), "https://a.com/v2/users");
+        assert_eq!(join("https://a.com/v2", "users"), "https://a.com/v2/users");
+        assert_eq!(join("https://a.com/v2", "/users"), "https://a.com/users");
+        assert_eq!(
+            join("https://a.com/v2/", "../v3/users"),
+            "https://a.com/v3/users"
+        );
+        assert_eq!(
+            join("https://a.com/v2?k=1", "users"),
+            "https://a.com/v2/users"
+        );
+        assert_eq!(
+            join("https://a.com/v2", "?page=2"),
+            "https://a.com/v2?page=2"
+        );
+        assert_eq!(join("https://a.com", "users"), "https://a.com/users");
+        assert_eq!(join("https://a.com/v2", "https://b.com/"), "https://b.com/");
+        assert_eq!(join("https://a.com/v2", "//b.com/x"), "https://b.com/x");
+
+        let base = Url::parse("https://a.com/v2/").unwrap();
+        assert!(discards_base_path(&base, "/users"));
+        assert!(!discards_base_path(&base, "users"));
+        assert!(!discards_base_path(&base, "//b.com/x"));
+        assert!(!discards_base_path(
+            &Url::parse("https://a.com").unwrap(),
+            "/users"
+        ));
+    }
+}
@@ -0,0 +1,244 @@
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Duration;
+
+use http::header::{HeaderName, HeaderValue};
+use http::HeaderMap;
+use retry_policies::RetryPolicy;
+
+use crate::cookie::cookie_container::CookieContainer;
+use crate::middleware::middleware::{Middleware, MiddlewarePhase};
+
+use super::client_pool::{no_redirect_builder, ClientPool};
+use super::client_wrapper::ErgoClient;
+
+type ClientSetting = Box<dyn FnOnce(ErgoClient) -> ErgoClient + Send>;
+type BaseConfig = Arc<dyn Fn(reqwest::ClientBuilder) -> reqwest::ClientBuilder + Send + Sync>;
+
+/// A builder of [`ErgoClient`], owning the configuration of its `reqwest::Client`.
+///
+/// The redirect policy of `reqwest` is always disabled, redirects are followed by ergoreq
+/// if [`Self::with_auto_redirect_count`] is set.
+///
+/// The [`ClientPool`] of the built client re-applies this configuration to its clients, so
+/// requests with transport options keep the user agent, timeouts and other settings.
+///
+/// # Example
+/// ```
+/// # use ergoreq::ErgoClient;
+/// # use ergoreq::middleware::curl_log_middleware::CurlLogMiddleware;
+/// let client = ErgoClient::builder()
+///     .user_agent("ergoreq")
+///     .with_auto_redirect_count(5)
+///     .with_middleware(CurlLogMiddleware::new())
+///     .build()
+///     .unwrap();
+/// ```
+pub struct ErgoClientBuilder {
+    configs: Vec<BaseConfig>,
+    settings: Vec<ClientSetting>,
+}
+
+impl ErgoClientBuilder {
+    /// Create an `ErgoClientBuilder` with the default `reqwest` configuration.
+    pub fn new() -> Self {
+        Self {
+            configs: vec![],
+            settings: vec![],
+        }
+    }
+
+    /// Configure the inner [`reqwest::ClientBuilder`] for options not wrapped by this builder.
+    ///
+    /// `f` is called again for every client built by the [`ClientPool`].
+    ///
+    /// # Notice
+    /// The redirect policy set here is ignored.
+    pub fn configure<F>(mut self, f: F) -> Self
+    where
+        F: Fn(reqwest::ClientBuilder) -> reqwest::ClientBuilder + Send + Sync + 'static,
+    {
+        self.configs.push(Arc::new(f));
+        self
+    }
+
+    /// See [`reqwest::ClientBuilder::default_headers`]
+    pub fn default_headers(self, headers: HeaderMap) -> Self {
+        self.configure(move |v| v.default_headers(headers.to_owned()))
+    }
+
+    /// See [`reqwest::ClientBuilder::user_agent`]
+    pub fn user_agent<V>(self, value: V) -> Self
+    where
+        V: TryInto<http::HeaderValue> + Clone + Send + Sync + 'static,
+        V::Error: Into<http::Error>,
+    {
+        self.configure(move |v| v.user_agent(value.to_owned()))
+    }
+
+    /// See [`reqwest::ClientBuilder::timeout`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.configure(move |v| v.timeout(timeout))
+    }
+
+    /// See [`reqwest::ClientBuilder::connect_timeout`]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn connect_timeout(self, timeout: Duration) -> Self {
+        self.configure(move |v| v.connect_timeout(timeout))
+    }
+
+    /// Configure the built [`ErgoClient`].
+    fn setting<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(ErgoClient) -> ErgoClient + Send + 'static,
+    {
+        self.settings.push(Box::new(f));
+        self
+    }
+
+    /// See [`ErgoClient::with_auto_redirect_count`]
+    pub fn with_auto_redirect_count(self, count: u16) -> Self {
+        self.setting(move |v| v.with_auto_redirect_count(count))
+    }
+
+    /// See [`ErgoClient::with_retry_count`]
+    pub fn with_retry_count(self, count: u16) -> Self {
+        self.setting(move |v| v.with_retry_count(count))
+    }
+
+    /// See [`ErgoClient::with_retry_policy`]
+    pub fn with_retry_policy<T>(self, retry_policy: T) -> Self
+    where
+        T: RetryPolicy + Send + Sync + 'static,
+    {
+        self.setting(move |v| v.with_retry_policy(retry_policy))
+    }
+
+    /// See [`ErgoClient::with_cookie_store`]
+    pub fn with_cookie_store<C>(self, cookie_store: Arc<C>) -> Self
+    where
+        C: CookieContainer + 'static,
+    {
+        self.setting(move |v| v.with_cookie_store(cookie_store))
+    }
+
+    /// See [`ErgoClient::with_middleware`]
+    pub fn with_middleware<M>(self, middleware: M) -> Self
+    where
+        M: Middleware,
+    {
+        self.setting(move |v| v.with_middleware(middleware))
+    }
+
+    /// See [`ErgoClient::with_middleware_phase`]
+    pub fn with_middleware_phase<M>(self, middleware: M, phase: MiddlewarePhase) -> Self
+    where
+        M: Middleware,
+    {
+        self.setting(move |v| v.with_middleware_phase(middleware, phase))
+    }
+
+    /// Build the `reqwest::Client` and the [`ErgoClient`] wrapping it.
+    pub fn build(self) -> crate::Result<ErgoClient> {
+        let configs = self.configs;
+        let factory = move || {
+            configs
+                .iter()
+                .fold(no_redirect_builder(), |builder, config| config(builder))
+        };
+        let inner = factory().build()?;
+        let client = ErgoClient::new(inner)
+            .with_client_pool(Arc::new(ClientPool::new().with_builder_factory(factory)));
+        Ok(self
+            .settings
+            .into_iter()
+            .fold(client, |client, setting| setting(client)))
+    }
+}
+
+impl Default for ErgoClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A builder of a child [`ErgoClient`] derived from a parent, created by [`ErgoClient::scoped`].
+///
+/// The child starts with every setting of the parent, and shares its `reqwest::Client`, so
+/// requests of both reuse the same connections.
+pub struct ErgoScopedClientBuilder {
+    client: ErgoClient,
+}
+
+impl ErgoScopedClientBuilder {
+    pub(crate) fn new(parent: ErgoClient) -> Self {
+        Self { client: parent }
+    }
+
+    /// See [`ErgoClient::with_base_url`]
+    pub fn with_base_url(mut self, base_url: url::Url) -> Self {
+        self.client = self.client.with_base_url(base_url);
+        self
+    }
+
+    /// Set a default header, replacing the values of the parent for the same header.
+    ///
+    /// # Panics
+    /// Panics if `key` or `value` is not a valid header.
+    pub fn with_default_header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: std::fmt::Debug,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: std::fmt::Debug,
+    {
+        self.client.set_default_header(
+            HeaderName::try_from(key).expect("invalid header name"),
+            HeaderValue::try_from(value).expect("invalid header value"),
+        );
+        self
+    }
+
+    /// See [`ErgoClient::with_default_query`]
+    pub fn with_default_query<K, V>(mut self, query: &[(K, V)]) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.client = self.client.with_default_query(query);
+        self
+    }
+
+    /// Use `cookie_store` instead of the cookie store of the parent.
+    pub fn with_cookie_store<C>(mut self, cookie_store: Arc<C>) -> Self
+    where
+        C: CookieContainer + 'static,
+    {
+        self.client = self.client.with_cookie_store(cookie_store);
+        self
+    }
+
+    /// Add a middleware after the middlewares of the parent, see [`ErgoClient::with_middleware`]
+    pub fn with_middleware<M>(mut self, middleware: M) -> Self
+    where
+        M: Middleware,
+    {
+        self.client = self.client.with_middleware(middleware);
+        self
+    }
+
+    /// See [`ErgoClient::with_middleware_phase`]
+    pub fn with_middleware_phase<M>(mut self, middleware: M, phase: MiddlewarePhase) -> Self
+    where
+        M: Middleware,
+    {
+        self.client = self.client.with_middleware_phase(middleware, phase);
+        self
+    }
+
+    /// Build the child [`ErgoClient`].
+    pub fn build(self) -> ErgoClient {
+        self.client
+    }
+}
@@ -0,0 +1,244 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, OnceLock};
+
+use dashmap::DashMap;
+
+/// Transport settings which `reqwest` fixes when a client is built.
+///
+/// Requests with `TransportOptions` are sent by the client of the [`ClientPool`] built for
+/// these options, see [`crate::ErgoRequestBuilder::with_transport_options`].
+///
+/// # Example
+/// ```
+/// # use ergoreq::wrappers::client_pool::TransportOptions;
+/// let options = TransportOptions::new()
+///     .with_proxy("http://127.0.0.1:8080")
+///     .with_local_address("127.0.0.1".parse().unwrap());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TransportOptions {
+    #[cfg(not(target_arch = "wasm32"))]
+    proxy: Option<String>,
+    #[cfg(not(target_arch = "wasm32"))]
+    local_address: Option<IpAddr>,
+    #[cfg(not(target_arch = "wasm32"))]
+    resolve: BTreeMap<String, SocketAddr>,
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    interface: Option<String>,
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    root_certificates: Vec<Vec<u8>>,
+    #[cfg(feature = "rustls-tls")]
+    identity: Option<Vec<u8>>,
+}
+
+impl TransportOptions {
+    /// Create `TransportOptions` without any setting.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send requests through `proxy`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_proxy<S: Into<String>>(mut self, proxy: S) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    /// Bind connections to `local_address`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_local_address(mut self, local_address: IpAddr) -> Self {
+        self.local_address = Some(local_address);
+        self
+    }
+
+    /// Connect to `address` instead of resolving `host`.
+    ///
+    /// The URL keeps its host, so the `Host` header and the TLS server name are unchanged. The
+    /// port of the URL is used, unless the URL has none and the port of `address` is not `0`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_resolve(mut self, host: &str, address: SocketAddr) -> Self {
+        self.resolve.insert(host.to_ascii_lowercase(), address);
+        self
+    }
+
+    /// Bind connections to the network `interface`, like `eth0`.
+    #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+    pub fn with_interface<S: Into<String>>(mut self, interface: S) -> Self {
+        self.interface = Some(interface.into());
+        self
+    }
+
+    /// Trust a PEM encoded root certificate in addition to the default roots.
+    #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+    pub fn with_root_certificate<B: Into<Vec<u8>>>(mut self, pem: B) -> Self {
+        self.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Authenticate with a PEM encoded client certificate and private key.
+    #[cfg(feature = "rustls-tls")]
+    pub fn with_identity<B: Into<Vec<u8>>>(mut self, pem: B) -> Self {
+        self.identity = Some(pem.into());
+        self
+    }
+
+    /// Apply these options to `builder`.
+    fn configure(
+        &self,
+        builder: reqwest::ClientBuilder,
+    ) -> crate::error::Result<reqwest::ClientBuilder> {
+        #[allow(unused_mut)]
+        let mut builder = builder;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(local_address) = self.local_address {
+            builder = builder.local_address(local_address);
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        for (host, address) in &self.resolve {
+            builder = builder.resolve(host, *address);
+        }
+        #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+        if let Some(interface) = &self.interface {
+            builder = builder.interface(interface);
+        }
+        #[cfg(any(feature = "rustls-tls", feature = "native-tls"))]
+        for pem in &self.root_certificates {
+            builder = builder.add_root_certificate(reqwest::Certificate::from_pem(pem)?);
+        }
+        #[cfg(feature = "rustls-tls")]
+        if let Some(pem) = &self.identity {
+            builder = builder.identity(reqwest::Identity::from_pem(pem)?);
+        }
+        Ok(builder)
+    }
+}
+
+/// A `reqwest::ClientBuilder` without redirect policy, redirects are followed by the browser
+/// on wasm.
+pub(crate) fn no_redirect_builder() -> reqwest::ClientBuilder {
+    let builder = reqwest::Client::builder();
+    #[cfg(not(target_arch = "wasm32"))]
+    let builder = builder.redirect(reqwest::redirect::Policy::none());
+    builder
+}
+
+type BuilderFactory = Arc<dyn Fn() -> reqwest::ClientBuilder + Send + Sync + 'static>;
+
+/// A pool of `reqwest::Client`s keyed by [`TransportOptions`].
+///
+/// `reqwest` fixes the proxy and other transport settings when a client is built, so a client
+/// is built (and kept) for every [`TransportOptions`] that is used. Pooled clients are built
+/// from [`Self::with_builder_factory`], by default a `reqwest::ClientBuilder` without redirect
+/// policy. The pool of a client built with
+/// [`crate::ErgoClient::builder`] re-applies the configuration of that builder.
+///
+/// Every [`crate::ErgoClient`] owns a pool, middlewares get it with
+/// [`crate::middleware::middleware::Next::get_client_pool`].
+pub struct ClientPool {
+    factory: BuilderFactory,
+    clients: DashMap<TransportOptions, reqwest::Client>,
+}
+
+impl ClientPool {
+    /// Create an empty `ClientPool`.
+    pub fn new() -> Self {
+        Self {
+            factory: Arc::new(no_redirect_builder),
+            clients: DashMap::new(),
+        }
+    }
+
+    /// Get the pool shared by request builders that are not created from a
+    /// [`crate::ErgoClient`].
+    pub(crate) fn shared() -> Arc<Self> {
+        static SHARED: OnceLock<Arc<ClientPool>> = OnceLock::new();
+        SHARED.get_or_init(|| Arc::new(Self::new())).to_owned()
+    }
+
+    /// Build pooled clients from the `reqwest::ClientBuilder` returned by `factory`.
+    ///
+    /// # Notice
+    /// `redirect_policy` of the builder should be set to `none`.
+    pub fn with_builder_factory<F>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> reqwest::ClientBuilder + Send + Sync + 'static,
+    {
+        self.factory = Arc::new(factory);
+        self
+    }
+
+    /// Get the client sending requests through `proxy`, building it if needed.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn client_for_proxy(&self, proxy: &str) -> crate::error::Result<reqwest::Client> {
+        self.client_for(&TransportOptions::new().with_proxy(proxy))
+    }
+
+    /// Get the client configured with `options`, building it if needed.
+    pub fn client_for(&self, options: &TransportOptions) -> crate::error::Result<reqwest::Client> {
+        if let Some(client) = self.clients.get(options) {
+            return Ok(client.to_owned());
+        }
+        let client = options.configure((self.factory)())?.build()?;
+        Ok(self
+            .clients
+            .entry(options.to_owned())
+            .or_insert(client)
+            .to_owned())
+    }
+
+    /// Get the number of pooled clients.
+    pub fn len(&self) -> usize {
+        self.clients.len()
+    }
+
+    /// Returns `true` if no client is pooled.
+    pub fn is_empty(&self) -> bool {
+        self.clients.is_empty()
+    }
+
+    /// Drop the pooled client of `proxy`, a new one is built on next use.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn remove(&self, proxy: &str) {
+        self.remove_options(&TransportOptions::new().with_proxy(proxy));
+    }
+
+    /// Drop the pooled client of `options`, a new one is built on next use.
+    pub fn remove_options(&self, options: &TransportOptions) {
+        self.clients.remove(options);
+    }
+}
+
+impl Default for ClientPool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test_client_pool {
+    use super::{ClientPool, TransportOptions};
+
+    #[test]
+    fn test_client_for_options() {
+        let pool = ClientPool::new();
+        let proxy = TransportOptions::new().with_proxy("http://127.0.0.1:18080");
+        let local = TransportOptions::new().with_local_address("127.0.0.1".parse().unwrap());
+
+        pool.client_for(&proxy).unwrap();
+        pool.client_for_proxy("http://127.0.0.1:18080").unwrap();
+        assert_eq!(pool.len(), 1);
+        pool.client_for(&local).unwrap();
+        assert_eq!(pool.len(), 2);
+
+        pool.remove("http://127.0.0.1:18080");
+        pool.remove_options(&local);
+        assert!(pool.is_empty());
+    }
+}
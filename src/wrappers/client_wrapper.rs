@@ -1,21 +1,122 @@
+use std::any::TypeId;
+use std::collections::HashMap;
+use std::future::Future;
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::SocketAddr;
+use std::time::Duration;
 use std::{ops::Deref, sync::Arc};
 
-use reqwest::{IntoUrl, Method};
+use futures::future::BoxFuture;
+use futures::{FutureExt, Stream, StreamExt};
+use http::header::{HeaderName, HeaderValue};
+use http::{Extensions, HeaderMap, StatusCode};
+use reqwest::{IntoUrl, Method, Request, Response};
 use retry_policies::policies::ExponentialBackoff;
 use retry_policies::RetryPolicy;
 
-use crate::middleware::middleware::Middleware;
+use crate::cookie::cookie_container::CookieContainer;
+use crate::middleware::auto_redirect_middleware::{
+    PermanentRedirectCache, RedirectHop, RedirectMethod, RedirectMode, RedirectObserver,
+    RedirectPolicy, RefererPolicy, DEFAULT_MAX_DRAIN,
+};
+use crate::middleware::auto_retry_middleware::{RetryBudget, RetryOptions};
+use crate::middleware::hook_middleware::{OnRequestMiddleware, OnResponseMiddleware};
+use crate::middleware::middleware::{Middleware, MiddlewarePhase};
+use crate::scheduler::priority_scheduler::PriorityScheduler;
+use crate::utils::redactor::Redactor;
+use crate::utils::url_builder::{discards_base_path, join_base, ErgoUrlBuilder};
 
+use super::client_builder::{ErgoClientBuilder, ErgoScopedClientBuilder};
+use super::client_pool::{ClientPool, TransportOptions};
+#[cfg(not(target_arch = "wasm32"))]
+use super::download::ErgoDownload;
+use super::endpoint::ErgoEndpoint;
+use super::endpoint_pool::EndpointPool;
+use super::host_config::{HostConfig, HostConfigs, HostSettings};
 use super::request_builder_wrapper::ErgoRequestBuilder;
+use super::resource::ErgoResource;
+use super::response_wrapper::ErgoResponse;
+
+/// Headers and query parameters added to every request of a client, unless set by the request.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct RequestDefaults {
+    headers: HeaderMap,
+    query: Vec<(String, String)>,
+}
+
+impl RequestDefaults {
+    /// Replace the default values of header `name` with `value`.
+    pub(crate) fn set_header(&mut self, name: HeaderName, value: HeaderValue) {
+        self.headers.insert(name, value);
+    }
+
+    /// Replace the default values of each header in `headers`.
+    pub(crate) fn set_headers(&mut self, headers: &HeaderMap) {
+        for name in headers.keys() {
+            self.headers.remove(name);
+            for value in headers.get_all(name) {
+                self.headers.append(name, value.to_owned());
+            }
+        }
+    }
+
+    /// Add default headers and query parameters missing in `request`.
+    pub(crate) fn apply(&self, request: &mut Request) {
+        for name in self.headers.keys() {
+            if !request.headers().contains_key(name) {
+                for value in self.headers.get_all(name) {
+                    request.headers_mut().append(name, value.to_owned());
+                }
+            }
+        }
+
+        let present = request
+            .url()
+            .query_pairs()
+            .map(|(k, _)| k.into_owned())
+            .collect::<Vec<_>>();
+        let missing = self
+            .query
+            .iter()
+            .filter(|(k, _)| !present.contains(k))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            request.url_mut().query_pairs_mut().extend_pairs(missing);
+        }
+    }
+}
 
 ///
 /// `ErgoClient` is a wrapper of `reqwest::Client`
 #[derive(Clone)]
 pub struct ErgoClient {
     inner: reqwest::Client,
-    middlewares: Vec<Arc<dyn Middleware>>,
+    middlewares: Vec<(TypeId, MiddlewarePhase, Arc<dyn Middleware>)>,
     global_auto_redirect: u16,
+    cookie_store: Option<Arc<dyn CookieContainer>>,
+    defaults: RequestDefaults,
+    redirect_mode: RedirectMode,
+    redirect_policy: Option<Arc<dyn RedirectPolicy>>,
+    redirect_cache: Option<Arc<PermanentRedirectCache>>,
+    referer_policy: RefererPolicy,
+    redirect_observer: Option<Arc<RedirectObserver>>,
+    redirect_methods: HashMap<StatusCode, RedirectMethod>,
+    redirect_max_drain: u64,
+    #[cfg(feature = "html-redirect")]
+    html_redirect: bool,
     global_retry_policy: Option<Arc<dyn RetryPolicy + Send + Sync + 'static>>,
+    retry_options: RetryOptions,
+    scheduler: Option<Arc<PriorityScheduler>>,
+    client_pool: Arc<ClientPool>,
+    redactor: Option<Arc<Redactor>>,
+    deadline: Option<Duration>,
+    endpoint_pool: Option<Arc<EndpointPool>>,
+    base_url: Option<url::Url>,
+    host_configs: HostConfigs,
+    transport_options: Option<TransportOptions>,
+    max_response_bytes: Option<u64>,
+    integrity_check: bool,
+    base_url_warnings: bool,
 }
 
 macro_rules! impl_method_wrap {
@@ -24,8 +125,20 @@ macro_rules! impl_method_wrap {
             paste::paste!{
             #[doc = "Return a `ErgoRequestBuilder` for `" $method "` method."]
             pub fn $method<U: reqwest::IntoUrl>(&self,url: U)->crate::wrappers::request_builder_wrapper::ErgoRequestBuilder{
-                let url_str = url.as_str().to_owned();
-                crate::wrappers::request_builder_wrapper::ErgoRequestBuilder::new(self.inner.$method(url), None,url_str, self.inner.to_owned(), self.global_auto_redirect, self.global_retry_policy.to_owned(), self.middlewares.to_owned().into_boxed_slice())
+                self.request(reqwest::Method::[<$method:upper>], url)
+        }
+    }
+    )+
+    };
+}
+
+macro_rules! impl_url_method_wrap {
+    ($($method:ident),+) => {
+        $(
+            paste::paste!{
+            #[doc = "Return a `ErgoRequestBuilder` for `" $method "` method to the url built by an `ErgoUrlBuilder`."]
+            pub fn [<$method _url>](&self,url: crate::utils::url_builder::ErgoUrlBuilder)->crate::Result<crate::wrappers::request_builder_wrapper::ErgoRequestBuilder>{
+                self.request_url(reqwest::Method::[<$method:upper>], url)
         }
     }
     )+
@@ -53,10 +166,69 @@ impl ErgoClient {
             inner: client,
             middlewares: vec![],
             global_auto_redirect: 0,
+            cookie_store: None,
+            defaults: RequestDefaults::default(),
+            redirect_mode: RedirectMode::default(),
+            redirect_policy: None,
+            redirect_cache: None,
+            referer_policy: RefererPolicy::default(),
+            redirect_observer: None,
+            redirect_methods: HashMap::new(),
+            redirect_max_drain: DEFAULT_MAX_DRAIN,
+            #[cfg(feature = "html-redirect")]
+            html_redirect: false,
             global_retry_policy: None,
+            retry_options: RetryOptions::new(),
+            scheduler: None,
+            client_pool: Arc::new(ClientPool::new()),
+            redactor: None,
+            deadline: None,
+            endpoint_pool: None,
+            base_url: None,
+            host_configs: HostConfigs::default(),
+            transport_options: None,
+            max_response_bytes: None,
+            integrity_check: false,
+            base_url_warnings: false,
         }
     }
 
+    /// Create an [`ErgoClientBuilder`], configuring the `reqwest::Client` and this client at once.
+    ///
+    /// Unlike [`ErgoClient::new`], the redirect policy of `reqwest` is always disabled.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::builder()
+    ///     .timeout(std::time::Duration::from_secs(10))
+    ///     .with_auto_redirect_count(5)
+    ///     .with_retry_count(3)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn builder() -> ErgoClientBuilder {
+        ErgoClientBuilder::new()
+    }
+
+    /// Create an [`ErgoScopedClientBuilder`] deriving a child client from this client.
+    ///
+    /// The child shares the `reqwest::Client` and so the connection pool of this client.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::new(reqwest::Client::new()).with_retry_count(3);
+    /// let tenant = client
+    ///     .scoped()
+    ///     .with_base_url("https://tenant-a.example.com/api/".parse().unwrap())
+    ///     .with_default_header("x-tenant", "a")
+    ///     .build();
+    /// ```
+    pub fn scoped(&self) -> ErgoScopedClientBuilder {
+        ErgoScopedClientBuilder::new(self.to_owned())
+    }
+
     /// Set a global auto redirect count.
     /// This count will be passed to every request initialized by this client.
     ///
@@ -66,18 +238,242 @@ impl ErgoClient {
         self
     }
 
+    /// Set a global `CookieStore`, shared by every request of this client.
+    ///
+    /// This can be overwritten by each request (use [`ErgoRequestBuilder::with_cookie_store`]),
+    /// or disabled with [`ErgoRequestBuilder::without_cookie_store`].
+    ///
+    /// # Example
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use ergoreq::{ErgoClient, ErgoCookieContainer};
+    /// let client = ErgoClient::new(reqwest::Client::new())
+    ///     .with_cookie_store(Arc::new(ErgoCookieContainer::new_secure()));
+    /// ```
+    pub fn with_cookie_store(mut self, cookie_store: Arc<dyn CookieContainer>) -> Self {
+        self.cookie_store = Some(cookie_store);
+        self
+    }
+
+    /// Set the url relative urls of requests are resolved against, like
+    /// `https://api.example.com/v2/`.
+    ///
+    /// The base url is treated as a directory with or without a trailing `/`, see
+    /// [`join_base`].
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::new(reqwest::Client::new())
+    ///     .with_base_url("https://api.example.com/v2/".parse().unwrap());
+    /// let request = client.get("users");
+    /// ```
+    pub fn with_base_url(mut self, base_url: url::Url) -> Self {
+        self.base_url = Some(base_url);
+        self
+    }
+
+    /// Log a warning when the path of a request starts with `/` and so replaces the path of
+    /// the base url, like `https://api.example.com/v2/` + `/users`.
+    pub fn with_base_url_warnings(mut self, base_url_warnings: bool) -> Self {
+        self.base_url_warnings = base_url_warnings;
+        self
+    }
+
+    /// Get the base url of this client.
+    pub fn get_base_url(&self) -> Option<&url::Url> {
+        self.base_url.as_ref()
+    }
+
+    /// Configure requests to `host` with `config`, overriding the settings of this client.
+    ///
+    /// `host` can be a `*.example.com` pattern matching every subdomain. The config is resolved
+    /// by the host of the url a request is created with, it is not resolved again when the
+    /// request is redirected to another host.
+    pub fn with_host_config(mut self, host: &str, config: HostConfig) -> Self {
+        self.host_configs.insert(host, config);
+        self
+    }
+
+    /// Get the settings registered for the host of `url`.
+    pub(crate) fn get_host_settings(&self, url: &str) -> Option<Arc<HostSettings>> {
+        let url = url::Url::parse(url).ok()?;
+        self.host_configs.find(url.host_str()?)
+    }
+
+    /// Get the global `CookieStore` of this client.
+    pub fn get_cookie_store(&self) -> Option<Arc<dyn CookieContainer>> {
+        self.cookie_store.to_owned()
+    }
+
+    /// Add a header to every request of this client, unless the request sets the same header.
+    ///
+    /// # Panics
+    /// Panics if `key` or `value` is not a valid header.
+    pub fn with_default_header<K, V>(mut self, key: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: std::fmt::Debug,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: std::fmt::Debug,
+    {
+        self.defaults.headers.append(
+            HeaderName::try_from(key).expect("invalid header name"),
+            HeaderValue::try_from(value).expect("invalid header value"),
+        );
+        self
+    }
+
+    /// Set the default `Accept` header of every request of this client, like
+    /// `application/vnd.github+json`.
+    ///
+    /// Requests can still set their own with [`ErgoRequestBuilder::accept`]. Methods expecting
+    /// a format, like [`ErgoRequestBuilder::send_json`], replace it with their own default.
+    ///
+    /// # Panics
+    /// Panics if `mime` is not a valid header value.
+    pub fn with_default_accept(mut self, mime: &str) -> Self {
+        self.defaults.set_header(
+            http::header::ACCEPT,
+            HeaderValue::from_str(mime).expect("invalid header value"),
+        );
+        self
+    }
+
+    /// Replace the default values of header `key` with `value`.
+    pub(crate) fn set_default_header(&mut self, key: HeaderName, value: HeaderValue) {
+        self.defaults.set_header(key, value);
+    }
+
+    /// Add query parameters to every request of this client, unless the request sets the same
+    /// parameter.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::new(reqwest::Client::new())
+    ///     .with_default_header("x-api-version", "2")
+    ///     .with_default_query(&[("tenant", "ergo")]);
+    /// ```
+    pub fn with_default_query<K, V>(mut self, query: &[(K, V)]) -> Self
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.defaults.query.extend(
+            query
+                .iter()
+                .map(|(k, v)| (k.as_ref().to_owned(), v.as_ref().to_owned())),
+        );
+        self
+    }
+
+    /// Set a global [`RedirectMode`], restricting which redirects are followed.
+    ///
+    /// This can be overwritten by each request (use [`ErgoRequestBuilder::with_redirect_mode`]).
+    pub fn with_redirect_mode(mut self, mode: RedirectMode) -> Self {
+        self.redirect_mode = mode;
+        self
+    }
+
+    /// Set a global [`RefererPolicy`], deciding the `Referer` header of redirect hops.
+    ///
+    /// This can be overwritten by each request (use [`ErgoRequestBuilder::with_referer_policy`]).
+    pub fn with_referer_policy(mut self, referer_policy: RefererPolicy) -> Self {
+        self.referer_policy = referer_policy;
+        self
+    }
+
+    /// Follow redirects with `status` changing the method as `method`, for every request.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::middleware::auto_redirect_middleware::RedirectMethod;
+    /// # use ergoreq::ErgoClient;
+    /// // a legacy API expecting a re-POST after `302 Found`
+    /// let client = ErgoClient::new(reqwest::Client::new())
+    ///     .with_auto_redirect_count(5)
+    ///     .with_redirect_method(http::StatusCode::FOUND, RedirectMethod::Keep);
+    /// ```
+    pub fn with_redirect_method(mut self, status: StatusCode, method: RedirectMethod) -> Self {
+        self.redirect_methods.insert(status, method);
+        self
+    }
+
+    /// Also follow `<meta http-equiv="refresh">` and `location.replace` redirects found in small
+    /// html bodies, for every request.
+    ///
+    /// This can be overwritten by each request (use [`ErgoRequestBuilder::with_html_redirect`]).
+    #[cfg(feature = "html-redirect")]
+    pub fn with_html_redirect(mut self, html_redirect: bool) -> Self {
+        self.html_redirect = html_redirect;
+        self
+    }
+
+    /// Read at most `max_drain` bytes of each redirect body before following it, 64 KiB by
+    /// default, for every request.
+    ///
+    /// Drained bodies let the connection be reused, larger bodies are abandoned. See
+    /// [`crate::middleware::auto_redirect_middleware::RedirectDrainStats`].
+    ///
+    /// This can be overwritten by each request (use [`ErgoRequestBuilder::with_redirect_max_drain`]).
+    pub fn with_redirect_max_drain(mut self, max_drain: u64) -> Self {
+        self.redirect_max_drain = max_drain;
+        self
+    }
+
+    /// Set a global [`RedirectPolicy`], deciding whether each redirect is followed.
+    ///
+    /// It only applies when the auto redirect count is set.
+    ///
+    /// This can be overwritten by each request (use [`ErgoRequestBuilder::with_redirect_policy`]).
+    pub fn with_redirect_policy<P>(mut self, policy: P) -> Self
+    where
+        P: RedirectPolicy,
+    {
+        self.redirect_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Call `observer` with each redirect hop before it is followed, for every request.
+    ///
+    /// Return `false` to stop following, the redirect response is returned.
+    ///
+    /// This can be overwritten by each request (use [`ErgoRequestBuilder::with_redirect_observer`]).
+    pub fn with_redirect_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&RedirectHop) -> bool + Send + Sync + 'static,
+    {
+        self.redirect_observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Cache permanent redirects followed by requests of this client.
+    ///
+    /// See [`PermanentRedirectCache`].
+    pub fn with_permanent_redirect_cache(mut self, cache: Arc<PermanentRedirectCache>) -> Self {
+        self.redirect_cache = Some(cache);
+        self
+    }
+
+    /// Get the [`PermanentRedirectCache`] of this client.
+    pub fn get_permanent_redirect_cache(&self) -> Option<Arc<PermanentRedirectCache>> {
+        self.redirect_cache.to_owned()
+    }
+
     /// Set a global middleware.
     ///
     /// This middleware will be passed to every request.
     ///
     /// # Notice
-    /// Global middleware will be executed before request middleware.
-    pub fn with_middleware<M>(mut self, middleware: M) -> Self
+    /// Global middleware will be executed before request middleware. It runs in the
+    /// [`Middleware::default_phase`] of `middleware`.
+    pub fn with_middleware<M>(self, middleware: M) -> Self
     where
         M: Middleware,
     {
-        self.middlewares.push(Arc::new(middleware));
-        self
+        let phase = middleware.default_phase();
+        self.with_middleware_phase(middleware, phase)
     }
 
     /// Set a global middleware. You can hold an `Arc` for this middleware.
@@ -90,10 +486,83 @@ impl ErgoClient {
     where
         M: Middleware,
     {
-        self.middlewares.push(middleware);
+        let phase = middleware.default_phase();
+        self.middlewares
+            .push((TypeId::of::<M>(), phase, middleware));
+        self
+    }
+
+    /// Set a global middleware running in the given [`MiddlewarePhase`].
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::middleware::curl_log_middleware::CurlLogMiddleware;
+    /// # use ergoreq::middleware::middleware::MiddlewarePhase;
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::new(reqwest::Client::new())
+    ///     .with_retry_count(3)
+    ///     .with_middleware_phase(CurlLogMiddleware::new(), MiddlewarePhase::PostRetry);
+    /// ```
+    pub fn with_middleware_phase<M>(mut self, middleware: M, phase: MiddlewarePhase) -> Self
+    where
+        M: Middleware,
+    {
+        self.middlewares
+            .push((TypeId::of::<M>(), phase, Arc::new(middleware)));
         self
     }
 
+    /// Run an async closure on every request before it is sent.
+    ///
+    /// A lightweight alternative to implementing [`Middleware`], registered as an
+    /// [`OnRequestMiddleware`].
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::new(reqwest::Client::new()).on_request(|req, _ext| {
+    ///     Box::pin(async move {
+    ///         req.headers_mut()
+    ///             .insert("x-client", http::HeaderValue::from_static("ergoreq"));
+    ///         Ok(())
+    ///     })
+    /// });
+    /// ```
+    pub fn on_request<F>(self, hook: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut Request, &'a mut Extensions) -> BoxFuture<'a, crate::Result<()>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.with_middleware(OnRequestMiddleware::new(hook))
+    }
+
+    /// Run an async closure on every response before it is returned.
+    ///
+    /// A lightweight alternative to implementing [`Middleware`], registered as an
+    /// [`OnResponseMiddleware`].
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::new(reqwest::Client::new()).on_response(|resp, _ext| {
+    ///     Box::pin(async move {
+    ///         tracing::info!("{} responded with {}", resp.url(), resp.status());
+    ///         Ok(())
+    ///     })
+    /// });
+    /// ```
+    pub fn on_response<F>(self, hook: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut Response, &'a mut Extensions) -> BoxFuture<'a, crate::Result<()>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.with_middleware(OnResponseMiddleware::new(hook))
+    }
+
     /// Set a global retry count. If you want to set a global `RetryPolicy`,
     /// use [`ErgoClient::with_retry_policy`]
     pub fn with_retry_count(mut self, count: u16) -> Self {
@@ -107,6 +576,65 @@ impl ErgoClient {
         self
     }
 
+    /// Set a global deadline for each request, across all retries and redirect hops.
+    ///
+    /// The request fails with [`crate::Error::DeadlineExceeded`] when it is exceeded, unlike
+    /// `timeout` of `reqwest` which applies to each attempt.
+    ///
+    /// This can be overwritten by each request (use [`ErgoRequestBuilder::with_deadline`]).
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Limit the body of each response to `max_bytes`.
+    ///
+    /// Reading a larger body fails with [`crate::Error::BodyTooLarge`], as soon as the limit is
+    /// exceeded while streaming, or when sending if the `Content-Length` is already larger.
+    ///
+    /// This can be overwritten by each request (use [`ErgoRequestBuilder::with_max_response_bytes`]).
+    pub fn with_max_response_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_response_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Fail reading a response body with [`crate::Error::TruncatedBody`] if it ends before its
+    /// `Content-Length`, or if the connection or the decompression breaks midway, for every
+    /// request.
+    ///
+    /// This can be overwritten by each request (use [`ErgoRequestBuilder::with_integrity_check`]).
+    pub fn with_integrity_check(mut self, integrity_check: bool) -> Self {
+        self.integrity_check = integrity_check;
+        self
+    }
+
+    /// Connect to `address` instead of resolving `host`, for every request.
+    ///
+    /// The `Host` header and the TLS server name are still those of the URL, see
+    /// [`TransportOptions::with_resolve`].
+    ///
+    /// # Notice
+    /// Requests are sent with a client of the [`ClientPool`], so settings of the
+    /// `reqwest::Client` of `ErgoClient` like timeouts are only inherited if it is built with
+    /// [`ErgoClient::builder`].
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::new(reqwest::Client::new())
+    ///     .with_resolve("api.example.com", "10.0.0.2:0".parse().unwrap());
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_resolve(mut self, host: &str, address: SocketAddr) -> Self {
+        self.transport_options = Some(
+            self.transport_options
+                .take()
+                .unwrap_or_default()
+                .with_resolve(host, address),
+        );
+        self
+    }
+
     /// Set a global retry policy.
     pub fn with_retry_policy<T>(mut self, retry_policy: T) -> Self
     where
@@ -116,20 +644,428 @@ impl ErgoClient {
         self
     }
 
-    impl_method_wrap!(get, post, put, patch, delete, head);
+    /// Set global [`RetryOptions`], used with the retry policy.
+    ///
+    /// This can be overwritten by each request (use [`ErgoRequestBuilder::with_retry_options`]).
+    pub fn with_retry_options(mut self, retry_options: RetryOptions) -> Self {
+        self.retry_options = retry_options;
+        self
+    }
+
+    /// Set the response statuses to retry, see [`RetryOptions::with_retry_statuses`].
+    pub fn with_retry_statuses<I>(mut self, statuses: I) -> Self
+    where
+        I: IntoIterator<Item = StatusCode>,
+    {
+        self.retry_options = self.retry_options.with_retry_statuses(statuses);
+        self
+    }
+
+    /// Call `observer` before each retry, see [`RetryOptions::with_observer`].
+    pub fn with_retry_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(u32, &crate::Result<Response>, Duration) + Send + Sync + 'static,
+    {
+        self.retry_options = self.retry_options.with_observer(observer);
+        self
+    }
+
+    /// Whether to retry non-idempotent requests, see
+    /// [`RetryOptions::with_retry_non_idempotent`].
+    pub fn with_retry_non_idempotent(mut self, retry_non_idempotent: bool) -> Self {
+        self.retry_options = self
+            .retry_options
+            .with_retry_non_idempotent(retry_non_idempotent);
+        self
+    }
+
+    /// Fail over to other hosts after retries are exhausted, see
+    /// [`RetryOptions::with_fallback_hosts`].
+    ///
+    /// # Panics
+    /// Panics if a host is not a valid url.
+    pub fn with_fallback_hosts<I, S>(mut self, hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.retry_options = self.retry_options.with_fallback_hosts(hosts);
+        self
+    }
+
+    /// Limit retries of all requests of this client with a [`RetryBudget`].
+    ///
+    /// The budget is shared by clones of this client, and kept by requests unless they set
+    /// their own [`RetryOptions`].
+    pub fn with_retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.retry_options = self.retry_options.with_budget(Arc::new(budget));
+        self
+    }
+
+    for_each_http_method!(impl_method_wrap);
+
+    /// Return a `ErgoRequestBuilder` for `connect` method.
+    ///
+    /// # Notice
+    /// A `CONNECT` request opens a tunnel, so it is never redirected or retried.
+    pub fn connect<U: IntoUrl>(&self, url: U) -> ErgoRequestBuilder {
+        self.request(Method::CONNECT, url)
+    }
 
     /// Build an `ErgoRequestBuilder` with given `Method` and `Url`
+    ///
+    /// A relative `url` is resolved against the base url of this client with [`join_base`], if
+    /// set.
+    ///
+    /// # Notice
+    /// A `CONNECT` request opens a tunnel, so it is never redirected or retried.
     pub fn request(&self, method: Method, url: impl IntoUrl) -> ErgoRequestBuilder {
+        let joined = self.base_url.as_ref().and_then(|base| {
+            if self.base_url_warnings && discards_base_path(base, url.as_str()) {
+                tracing::warn!(
+                    "'{}' replaces the path of the base url '{base}'",
+                    url.as_str()
+                );
+            }
+            join_base(base, url.as_str()).ok()
+        });
+        let request = match joined {
+            Some(url) => self.request_to(method.to_owned(), url),
+            None => self.request_to(method.to_owned(), url),
+        };
+        match method {
+            Method::CONNECT => request.with_max_redirection(0).with_retry_times(0),
+            _ => request,
+        }
+    }
+
+    /// Build an `ErgoRequestBuilder` with given `Method` and the url built by `url`.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::{ErgoClient, ErgoUrlBuilder};
+    /// # fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let url = ErgoUrlBuilder::new("https://example.com").segments(["users", "john doe"]);
+    /// let request = client.get_url(url)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn request_url(
+        &self,
+        method: Method,
+        url: ErgoUrlBuilder,
+    ) -> crate::Result<ErgoRequestBuilder> {
+        Ok(self.request(method, url.build()?))
+    }
+
+    for_each_http_method!(impl_url_method_wrap);
+
+    /// Build an `ErgoRequestBuilder` for `endpoint`, see [`ErgoEndpoint`].
+    pub fn endpoint<E: ErgoEndpoint + ?Sized>(&self, endpoint: &E) -> ErgoRequestBuilder {
+        endpoint.prepare(self.request(endpoint.method(), endpoint.url().as_ref()))
+    }
+
+    /// Send a request to `endpoint`, see [`ErgoEndpoint`].
+    pub fn send_endpoint<E: ErgoEndpoint>(
+        &self,
+        endpoint: E,
+    ) -> impl Future<Output = crate::Result<ErgoResponse>> {
+        self.endpoint(&endpoint).send()
+    }
+
+    fn request_to(&self, method: Method, url: impl IntoUrl) -> ErgoRequestBuilder {
         let url_str = url.as_str().to_owned();
-        ErgoRequestBuilder::new(
-            self.inner.request(method, url),
-            None,
-            url_str,
-            self.inner.to_owned(),
-            self.global_auto_redirect,
-            self.global_retry_policy.to_owned(),
-            self.middlewares.to_owned().into_boxed_slice(),
-        )
+        ErgoRequestBuilder::from_client(self.inner.request(method, url), url_str, self)
+    }
+
+    /// Create an [`ErgoDownload`] of `url` sending requests with this client.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn download(&self, url: impl IntoUrl) -> ErgoDownload {
+        ErgoDownload::new(self.to_owned(), url.as_str().to_owned())
+    }
+
+    /// Create an [`ErgoResource`] at `base` sending requests with this client.
+    pub fn resource<T>(&self, base: url::Url) -> ErgoResource<T>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        ErgoResource::new(self.to_owned(), base)
+    }
+
+    /// Send `requests` running at most `max_concurrency` of them at once, returns the results
+    /// in the order of `requests`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # async fn run() {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let requests = (1..=10).map(|v| client.get(format!("https://example.com/items/{v}")));
+    /// let responses = client.send_all(requests, 4).await;
+    /// # }
+    /// ```
+    pub async fn send_all<I>(
+        &self,
+        requests: I,
+        max_concurrency: usize,
+    ) -> Vec<crate::Result<ErgoResponse>>
+    where
+        I: IntoIterator<Item = ErgoRequestBuilder>,
+    {
+        futures::stream::iter(requests)
+            .map(ErgoRequestBuilder::send)
+            .buffered(max_concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Send `requests` like [`Self::send_all`], yielding each result with the index of its
+    /// request as soon as it completes.
+    pub fn send_all_stream<I>(
+        &self,
+        requests: I,
+        max_concurrency: usize,
+    ) -> impl Stream<Item = (usize, crate::Result<ErgoResponse>)>
+    where
+        I: IntoIterator<Item = ErgoRequestBuilder>,
+    {
+        futures::stream::iter(requests.into_iter().enumerate())
+            .map(|(index, request)| request.send().map(move |v| (index, v)))
+            .buffer_unordered(max_concurrency.max(1))
+    }
+
+    /// Send a prebuilt `Request` through the middlewares and settings of this client.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new()).with_auto_redirect_count(5);
+    /// let request = reqwest::Request::new(reqwest::Method::GET, "https://crates.io".parse().unwrap());
+    /// let response = client.execute(request).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn execute(&self, request: Request) -> impl Future<Output = crate::Result<ErgoResponse>> {
+        self.request_from(request).send()
+    }
+
+    /// Send a prebuilt `Request` like [`Self::execute`], with `cookie_store` instead of the
+    /// cookie store of this client.
+    pub fn execute_with<C>(
+        &self,
+        request: Request,
+        cookie_store: Arc<C>,
+    ) -> impl Future<Output = crate::Result<ErgoResponse>>
+    where
+        C: CookieContainer + 'static,
+    {
+        self.request_from(request)
+            .with_cookie_store(cookie_store)
+            .send()
+    }
+
+    /// Build an `ErgoRequestBuilder` inheriting settings of this client from `request`.
+    fn request_from(&self, request: Request) -> ErgoRequestBuilder {
+        let url_str = request.url().to_string();
+        let raw_builder = reqwest::RequestBuilder::from_parts(self.inner.to_owned(), request);
+        ErgoRequestBuilder::from_client(raw_builder, url_str, self)
+    }
+
+    /// Dispatch requests by priority with at most `max_concurrency` requests running at the same time.
+    ///
+    /// Waiting requests are dispatched high-priority-first, set the priority with
+    /// [`ErgoRequestBuilder::with_priority`].
+    ///
+    /// # Panics
+    /// Panics if `max_concurrency` is `0`.
+    pub fn with_priority_scheduler(self, max_concurrency: usize) -> Self {
+        self.with_priority_scheduler_arc(Arc::new(PriorityScheduler::new(max_concurrency)))
+    }
+
+    /// Dispatch requests by priority with a shared [`PriorityScheduler`].
+    ///
+    /// Clients sharing a scheduler share its running slots.
+    pub fn with_priority_scheduler_arc(mut self, scheduler: Arc<PriorityScheduler>) -> Self {
+        self.scheduler = Some(scheduler);
+        self
+    }
+
+    /// Get the [`PriorityScheduler`] of this client.
+    pub fn get_priority_scheduler(&self) -> Option<Arc<PriorityScheduler>> {
+        self.scheduler.to_owned()
+    }
+
+    /// Share a [`ClientPool`] with this client, pooled clients are used by middlewares
+    /// like [`crate::middleware::proxy_rotation_middleware::ProxyRotationMiddleware`].
+    pub fn with_client_pool(mut self, client_pool: Arc<ClientPool>) -> Self {
+        self.client_pool = client_pool;
+        self
+    }
+
+    /// Get the [`ClientPool`] of this client.
+    pub fn get_client_pool(&self) -> Arc<ClientPool> {
+        self.client_pool.to_owned()
+    }
+
+    /// Balance requests of this client among the endpoints of `endpoint_pool`.
+    ///
+    /// See [`EndpointPool`].
+    pub fn with_endpoint_pool(mut self, endpoint_pool: Arc<EndpointPool>) -> Self {
+        self.endpoint_pool = Some(endpoint_pool);
+        self
+    }
+
+    /// Get the [`EndpointPool`] of this client.
+    pub fn get_endpoint_pool(&self) -> Option<Arc<EndpointPool>> {
+        self.endpoint_pool.to_owned()
+    }
+
+    /// Set the [`Redactor`] used by observability middlewares of this client.
+    pub fn with_redactor(mut self, redactor: Redactor) -> Self {
+        self.redactor = Some(Arc::new(redactor));
+        self
+    }
+
+    /// Get the [`Redactor`] of this client.
+    pub fn get_redactor(&self) -> Option<Arc<Redactor>> {
+        self.redactor.to_owned()
+    }
+
+    /// Derive a client without global middlewares of type `M`.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::middleware::revalidation_middleware::RevalidationMiddleware;
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::new(reqwest::Client::new())
+    ///     .with_middleware(RevalidationMiddleware::new());
+    /// let uncached = client.without_middleware::<RevalidationMiddleware>();
+    /// assert!(uncached.middlewares().is_empty());
+    /// ```
+    pub fn without_middleware<M>(&self) -> Self
+    where
+        M: Middleware,
+    {
+        let mut client = self.to_owned();
+        client
+            .middlewares
+            .retain(|(type_id, _, _)| *type_id != TypeId::of::<M>());
+        client
+    }
+
+    /// Derive a client whose global middlewares of type `M` are replaced by `middleware`.
+    ///
+    /// `middleware` takes the position and phase of the first middleware of type `M`, or is
+    /// appended to the default phase if there is none.
+    pub fn replace_middleware<M, N>(&self, middleware: N) -> Self
+    where
+        M: Middleware,
+        N: Middleware,
+    {
+        let mut client = self.to_owned();
+        let position = client
+            .middlewares
+            .iter()
+            .position(|(type_id, _, _)| *type_id == TypeId::of::<M>());
+        let phase = position
+            .map(|v| client.middlewares[v].1)
+            .unwrap_or_default();
+        client
+            .middlewares
+            .retain(|(type_id, _, _)| *type_id != TypeId::of::<M>());
+        let entry: (TypeId, MiddlewarePhase, Arc<dyn Middleware>) =
+            (TypeId::of::<N>(), phase, Arc::new(middleware));
+        match position {
+            Some(position) => client.middlewares.insert(position, entry),
+            None => client.middlewares.push(entry),
+        }
+        client
+    }
+
+    /// Get the names of global middlewares, in execution order.
+    pub fn middlewares(&self) -> Vec<&'static str> {
+        self.get_middlewares()
+            .iter()
+            .map(|(_, v)| v.name())
+            .collect()
+    }
+
+    pub(crate) fn get_defaults(&self) -> RequestDefaults {
+        self.defaults.to_owned()
+    }
+
+    pub(crate) fn get_inner_client(&self) -> &reqwest::Client {
+        &self.inner
+    }
+
+    pub(crate) fn get_auto_redirect_count(&self) -> u16 {
+        self.global_auto_redirect
+    }
+
+    pub(crate) fn get_redirect_mode(&self) -> RedirectMode {
+        self.redirect_mode
+    }
+
+    pub(crate) fn get_referer_policy(&self) -> RefererPolicy {
+        self.referer_policy
+    }
+
+    pub(crate) fn get_redirect_observer(&self) -> Option<Arc<RedirectObserver>> {
+        self.redirect_observer.to_owned()
+    }
+
+    pub(crate) fn get_redirect_methods(&self) -> HashMap<StatusCode, RedirectMethod> {
+        self.redirect_methods.to_owned()
+    }
+
+    #[cfg(feature = "html-redirect")]
+    pub(crate) fn get_html_redirect(&self) -> bool {
+        self.html_redirect
+    }
+
+    pub(crate) fn get_redirect_max_drain(&self) -> u64 {
+        self.redirect_max_drain
+    }
+
+    pub(crate) fn get_redirect_policy(&self) -> Option<Arc<dyn RedirectPolicy>> {
+        self.redirect_policy.to_owned()
+    }
+
+    pub(crate) fn get_retry_options(&self) -> RetryOptions {
+        self.retry_options.to_owned()
+    }
+
+    pub(crate) fn get_deadline(&self) -> Option<Duration> {
+        self.deadline
+    }
+
+    pub(crate) fn get_transport_options(&self) -> Option<TransportOptions> {
+        self.transport_options.to_owned()
+    }
+
+    pub(crate) fn get_max_response_bytes(&self) -> Option<u64> {
+        self.max_response_bytes
+    }
+
+    pub(crate) fn get_integrity_check(&self) -> bool {
+        self.integrity_check
+    }
+
+    pub(crate) fn get_retry_policy(&self) -> Option<Arc<dyn RetryPolicy + Send + Sync + 'static>> {
+        self.global_retry_policy.to_owned()
+    }
+
+    /// Get global middlewares with their phases, sorted by phase.
+    pub(crate) fn get_middlewares(&self) -> Vec<(MiddlewarePhase, Arc<dyn Middleware>)> {
+        let mut middlewares = self
+            .middlewares
+            .iter()
+            .map(|(_, phase, v)| (*phase, v.to_owned()))
+            .collect::<Vec<_>>();
+        middlewares.sort_by_key(|(phase, _)| *phase);
+        middlewares
     }
 }
 
@@ -172,5 +1108,63 @@ mod test_client_wrapper {
         };
     }
 
-    impl_method_test!(get, post, put, delete, head, patch);
+    impl_method_test!(get, post, put, delete, head, patch, options, trace, connect);
+
+    #[test]
+    fn test_default_header_and_query() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_default_header("x-tenant", "ergo")
+            .with_default_header("x-api-version", "1")
+            .with_default_query(&[("tenant", "ergo"), ("version", "1")]);
+
+        let request = client.get("https://crates.io/?a=b").build().unwrap();
+        assert_eq!(request.headers()["x-tenant"], "ergo");
+        assert_eq!(request.headers()["x-api-version"], "1");
+        assert_eq!(request.url().query(), Some("a=b&tenant=ergo&version=1"));
+
+        // per-request values take precedence
+        let request = client
+            .get("https://crates.io/?version=2")
+            .header("x-api-version", "2")
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["x-tenant"], "ergo");
+        assert_eq!(request.headers().get_all("x-api-version").iter().count(), 1);
+        assert_eq!(request.headers()["x-api-version"], "2");
+        assert_eq!(request.url().query(), Some("version=2&tenant=ergo"));
+    }
+
+    #[test]
+    fn test_scoped_client() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_base_url("https://crates.io/api/".parse().unwrap())
+            .with_default_header("x-tenant", "ergo")
+            .with_default_header("x-api-version", "1");
+        let scoped = client
+            .scoped()
+            .with_base_url("https://tenant.crates.io/v2/".parse().unwrap())
+            .with_default_header("x-tenant", "scoped")
+            .build();
+
+        let request = client.get("crates?page=1").build().unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            "https://crates.io/api/crates?page=1"
+        );
+        assert_eq!(request.headers()["x-tenant"], "ergo");
+
+        let request = scoped.get("crates").build().unwrap();
+        assert_eq!(request.url().as_str(), "https://tenant.crates.io/v2/crates");
+        assert_eq!(request.headers().get_all("x-tenant").iter().count(), 1);
+        assert_eq!(request.headers()["x-tenant"], "scoped");
+        assert_eq!(request.headers()["x-api-version"], "1");
+
+        // absolute urls are not resolved
+        let request = scoped.get("https://docs.rs/").build().unwrap();
+        assert_eq!(request.url().as_str(), "https://docs.rs/");
+
+        let client = client.with_base_url("https://crates.io/api/v1".parse().unwrap());
+        let request = client.get("crates").build().unwrap();
+        assert_eq!(request.url().as_str(), "https://crates.io/api/v1/crates");
+    }
 }
@@ -0,0 +1,527 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use futures::StreamExt;
+use http::header::{
+    ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE,
+};
+use http::{HeaderMap, StatusCode};
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::Mutex;
+
+use crate::utils::response::content_length;
+
+use super::client_wrapper::ErgoClient;
+
+/// An expected digest of a downloaded file, hex encoded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DownloadChecksum {
+    Md5(String),
+    Sha256(String),
+    Sha512(String),
+}
+
+impl DownloadChecksum {
+    fn expected(&self) -> &str {
+        match self {
+            DownloadChecksum::Md5(v)
+            | DownloadChecksum::Sha256(v)
+            | DownloadChecksum::Sha512(v) => v,
+        }
+    }
+
+    /// Compute the hex encoded digest of the file at `path` with the algorithm of `self`.
+    async fn compute(&self, path: &Path) -> std::io::Result<String> {
+        let mut file = tokio::fs::File::open(path).await?;
+        let mut buffer = vec![0u8; 64 * 1024];
+        let mut md5 = Md5::new();
+        let mut sha256 = Sha256::new();
+        let mut sha512 = Sha512::new();
+        loop {
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            match self {
+                DownloadChecksum::Md5(_) => md5.update(&buffer[..read]),
+                DownloadChecksum::Sha256(_) => sha256.update(&buffer[..read]),
+                DownloadChecksum::Sha512(_) => sha512.update(&buffer[..read]),
+            }
+        }
+        let digest = match self {
+            DownloadChecksum::Md5(_) => md5.finalize().to_vec(),
+            DownloadChecksum::Sha256(_) => sha256.finalize().to_vec(),
+            DownloadChecksum::Sha512(_) => sha512.finalize().to_vec(),
+        };
+        Ok(digest.iter().map(|v| format!("{:02x}", v)).collect())
+    }
+}
+
+pub use super::response_wrapper::DownloadProgress;
+
+/// Save the progress of a segment every time it advances this many bytes.
+const SAVE_INTERVAL: u64 = 1024 * 1024;
+
+/// The resume state of a `.part` file, saved next to it as `{path}.part.meta`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+struct PartMeta {
+    /// The strong `ETag`, or else the `Last-Modified` of the remote file, sent as `If-Range`.
+    validator: Option<String>,
+    /// The size of the remote file, for segmented downloads.
+    total: Option<u64>,
+    /// The next offset and the end of each segment, for segmented downloads.
+    segments: Vec<(u64, u64)>,
+}
+
+impl PartMeta {
+    async fn load(path: &Path) -> Option<Self> {
+        let content = tokio::fs::read(path).await.ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    /// Write to a temporary file renamed to `path`, so a crash never leaves a partial state.
+    async fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let content = serde_json::to_vec(self).map_err(std::io::Error::other)?;
+        tokio::fs::write(&temp, content).await?;
+        tokio::fs::rename(&temp, path).await
+    }
+}
+
+/// Get the validator of a response usable in `If-Range`, weak `ETag`s are not allowed there.
+fn validator(headers: &HeaderMap) -> Option<String> {
+    let etag = headers
+        .get(ETAG)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.starts_with("W/"));
+    etag.or_else(|| headers.get(LAST_MODIFIED).and_then(|v| v.to_str().ok()))
+        .map(str::to_owned)
+}
+
+/// Parse `Content-Range: bytes {start}-{end}/{total}` or `bytes */{total}`, returns the start
+/// (`None` for `*`) and the total (`None` for `*`).
+fn content_range(headers: &HeaderMap) -> Option<(Option<u64>, Option<u64>)> {
+    let value = headers.get(CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let start = match range.trim() {
+        "*" => None,
+        range => Some(range.split_once('-')?.0.trim().parse().ok()?),
+    };
+    let total = match total.trim() {
+        "*" => None,
+        total => Some(total.parse().ok()?),
+    };
+    Some((start, total))
+}
+
+type ProgressCallback = Arc<dyn Fn(DownloadProgress) + Send + Sync + 'static>;
+
+/// A download of `url` to a file, created by [`ErgoClient::download`].
+///
+/// The file is written to `{path}.part` first, and renamed to `path` once complete and
+/// verified. An existing `.part` file is resumed with a `Range` request, and an interrupted body
+/// is resumed from where it stopped, at most [`Self::with_max_resumes`] times. Every request
+/// runs through the middlewares and the retry policy of the client.
+///
+/// The `ETag` or `Last-Modified` of the remote file and the progress of segments are saved in
+/// `{path}.part.meta`. Resumed requests send it as `If-Range`, so the download restarts if the
+/// remote file changed.
+///
+/// # Example
+/// ```no_run
+/// # use ergoreq::ErgoClient;
+/// # use ergoreq::wrappers::download::DownloadChecksum;
+/// # async fn run() -> ergoreq::Result<()> {
+/// let client = ErgoClient::new(reqwest::Client::new()).with_retry_count(3);
+/// client
+///     .download("https://example.com/archive.tar.gz")
+///     .with_segments(4)
+///     .with_checksum(DownloadChecksum::Sha256("9f86d0...".to_owned()))
+///     .on_progress(|v| println!("{}/{:?}", v.downloaded, v.total))
+///     .to_file("archive.tar.gz")
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ErgoDownload {
+    client: ErgoClient,
+    url: String,
+    segments: u64,
+    max_resumes: u32,
+    checksum: Option<DownloadChecksum>,
+    progress: Option<ProgressCallback>,
+}
+
+impl ErgoDownload {
+    pub(crate) fn new(client: ErgoClient, url: String) -> Self {
+        Self {
+            client,
+            url,
+            segments: 1,
+            max_resumes: 3,
+            checksum: None,
+            progress: None,
+        }
+    }
+
+    /// Download in `segments` parallel ranges if the server supports ranges.
+    ///
+    /// # Notice
+    /// Segments of a previous run are only resumed if the server sent a validator
+    /// (`ETag` or `Last-Modified`) and the size and the number of segments are the same.
+    pub fn with_segments(mut self, segments: u64) -> Self {
+        self.segments = segments.max(1);
+        self
+    }
+
+    /// Resume an interrupted body at most `max_resumes` times per segment, defaults to `3`.
+    pub fn with_max_resumes(mut self, max_resumes: u32) -> Self {
+        self.max_resumes = max_resumes;
+        self
+    }
+
+    /// Verify the downloaded file, failing with [`crate::Error::ChecksumMismatch`] and removing
+    /// it if the digest differs.
+    pub fn with_checksum(mut self, checksum: DownloadChecksum) -> Self {
+        self.checksum = Some(checksum);
+        self
+    }
+
+    /// Call `callback` whenever bytes are written.
+    pub fn on_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(DownloadProgress) + Send + Sync + 'static,
+    {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Download to `path`, returns the size of the file.
+    pub async fn to_file<P: AsRef<Path>>(self, path: P) -> crate::Result<u64> {
+        let path = path.as_ref();
+        let mut part = path.as_os_str().to_owned();
+        part.push(".part");
+        let part = PathBuf::from(part);
+        let mut meta = part.as_os_str().to_owned();
+        meta.push(".meta");
+        let meta = PathBuf::from(meta);
+
+        let size = match self.probe_ranges().await {
+            Some((total, validator)) if self.segments > 1 && total >= self.segments => {
+                self.download_segments(&part, &meta, total, validator)
+                    .await?
+            }
+            _ => self.download_single(&part, &meta).await?,
+        };
+        let _ = tokio::fs::remove_file(&meta).await;
+
+        if let Some(checksum) = &self.checksum {
+            let actual = checksum.compute(&part).await?;
+            if !actual.eq_ignore_ascii_case(checksum.expected()) {
+                tokio::fs::remove_file(&part).await?;
+                return Err(crate::Error::ChecksumMismatch(
+                    checksum.expected().to_owned(),
+                    actual,
+                ));
+            }
+        }
+        tokio::fs::rename(&part, path).await?;
+        Ok(size)
+    }
+
+    /// Get the size and the validator of the file if the server accepts byte ranges.
+    async fn probe_ranges(&self) -> Option<(u64, Option<String>)> {
+        if self.segments <= 1 {
+            return None;
+        }
+        let response = self.client.head(&self.url).send().await.ok()?;
+        let accept_ranges = response
+            .headers()
+            .get(ACCEPT_RANGES)
+            .is_some_and(|v| v.as_bytes().eq_ignore_ascii_case(b"bytes"));
+        if !response.status().is_success() || !accept_ranges {
+            return None;
+        }
+        let total = response
+            .headers()
+            .get(CONTENT_LENGTH)?
+            .to_str()
+            .ok()?
+            .parse()
+            .ok()?;
+        Some((total, validator(response.headers())))
+    }
+
+    fn report(&self, downloaded: u64, total: Option<u64>) {
+        if let Some(progress) = &self.progress {
+            progress(DownloadProgress { downloaded, total });
+        }
+    }
+
+    /// Download into `part` with one request, resuming the bytes already in it.
+    async fn download_single(&self, part: &Path, meta_path: &Path) -> crate::Result<u64> {
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(part)
+            .await?;
+        let mut offset = file.metadata().await?.len();
+        let mut meta = PartMeta::load(meta_path).await.unwrap_or_default();
+        // the preallocated file of a segmented download has holes
+        if offset > 0 && !meta.segments.is_empty() {
+            file.set_len(0).await?;
+            offset = 0;
+        }
+        let mut resumes = 0;
+        loop {
+            let mut builder = self.client.get(&self.url);
+            if offset > 0 {
+                builder = builder.header(RANGE, format!("bytes={offset}-"));
+                if let Some(validator) = &meta.validator {
+                    builder = builder.header(IF_RANGE, validator);
+                }
+            }
+            let response = builder.send().await?;
+            let range = content_range(response.headers());
+            let response = match response.status() {
+                StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {
+                    // the part file is already complete, unless the remote file has another size
+                    if range.is_some_and(|(_, total)| total == Some(offset)) {
+                        return Ok(offset);
+                    }
+                    tracing::debug!("Range of {} is not satisfiable, restart", self.url);
+                    file.set_len(0).await?;
+                    offset = 0;
+                    continue;
+                }
+                StatusCode::PARTIAL_CONTENT
+                    if range.is_some_and(|(start, _)| start == Some(offset)) =>
+                {
+                    response
+                }
+                StatusCode::PARTIAL_CONTENT if offset > 0 => {
+                    tracing::debug!("Unexpected range from {}, restart", self.url);
+                    file.set_len(0).await?;
+                    offset = 0;
+                    continue;
+                }
+                StatusCode::PARTIAL_CONTENT => {
+                    return Err(crate::Error::UnexpectedStatus(
+                        response.status(),
+                        response.url().to_owned(),
+                        "unexpected partial content".to_owned(),
+                    ));
+                }
+                _ => {
+                    let response = response.error_for_status_with_body().await?;
+                    if offset > 0 {
+                        // ranges are not supported, or the remote file changed
+                        tracing::debug!("Range is not satisfied by {}, restart", self.url);
+                        file.set_len(0).await?;
+                        offset = 0;
+                    }
+                    response
+                }
+            };
+            if let Some(validator) = validator(response.headers()) {
+                if meta.validator.as_ref() != Some(&validator) || !meta.segments.is_empty() {
+                    meta = PartMeta {
+                        validator: Some(validator),
+                        ..Default::default()
+                    };
+                    meta.save(meta_path).await?;
+                }
+            }
+            let total = content_length(&response).map(|v| v + offset);
+
+            let mut body = response.bytes_stream();
+            let mut interrupted = None;
+            while let Some(chunk) = body.next().await {
+                match chunk {
+                    Ok(chunk) => {
+                        file.write_all(&chunk).await?;
+                        offset += chunk.len() as u64;
+                        self.report(offset, total);
+                    }
+                    Err(e) => {
+                        interrupted = Some(e);
+                        break;
+                    }
+                }
+            }
+            file.flush().await?;
+            if interrupted.is_none() && total.is_none_or(|v| offset >= v) {
+                return Ok(offset);
+            }
+            if resumes >= self.max_resumes {
+                return Err(interrupted.map_or_else(|| truncated(offset), Into::into));
+            }
+            resumes += 1;
+            tracing::debug!("Download of {} interrupted at {}", self.url, offset);
+        }
+    }
+
+    /// Download `total` bytes into `part` with parallel range requests, resuming the segments
+    /// saved in `meta_path` by a previous run.
+    async fn download_segments(
+        &self,
+        part: &Path,
+        meta_path: &Path,
+        total: u64,
+        validator: Option<String>,
+    ) -> crate::Result<u64> {
+        let part_len = tokio::fs::metadata(part).await.map(|v| v.len()).ok();
+        let meta = match PartMeta::load(meta_path).await {
+            Some(meta)
+                if validator.is_some()
+                    && meta.validator == validator
+                    && meta.total == Some(total)
+                    && meta.segments.len() as u64 == self.segments
+                    && part_len == Some(total) =>
+            {
+                meta
+            }
+            _ => {
+                let file = tokio::fs::File::create(part).await?;
+                file.set_len(total).await?;
+                let size = total.div_ceil(self.segments);
+                let meta = PartMeta {
+                    validator,
+                    total: Some(total),
+                    segments: (0..self.segments)
+                        .map(|i| (i * size, ((i + 1) * size).min(total)))
+                        .collect(),
+                };
+                meta.save(meta_path).await?;
+                meta
+            }
+        };
+
+        let remaining = meta
+            .segments
+            .iter()
+            .map(|(offset, end)| end.saturating_sub(*offset))
+            .sum::<u64>();
+        let downloaded = Arc::new(AtomicU64::new(total - remaining));
+        let segments = meta.segments.to_owned();
+        let state = SegmentState {
+            meta: Mutex::new(meta),
+            path: meta_path,
+        };
+        let segments = segments
+            .into_iter()
+            .enumerate()
+            .filter(|(_, (offset, end))| offset < end)
+            .map(|(index, (offset, end))| {
+                self.download_segment(part, index, offset, end, total, &downloaded, &state)
+            });
+        futures::future::try_join_all(segments).await?;
+        Ok(total)
+    }
+
+    /// Download bytes `start..end` into `part`, saving the progress of segment `index`.
+    #[allow(clippy::too_many_arguments)]
+    async fn download_segment(
+        &self,
+        part: &Path,
+        index: usize,
+        start: u64,
+        end: u64,
+        total: u64,
+        downloaded: &AtomicU64,
+        state: &SegmentState<'_>,
+    ) -> crate::Result<()> {
+        let mut file = tokio::fs::OpenOptions::new().write(true).open(part).await?;
+        let validator = state.meta.lock().await.validator.to_owned();
+        let mut offset = start;
+        let mut resumes = 0;
+        loop {
+            let mut builder = self
+                .client
+                .get(&self.url)
+                .header(RANGE, format!("bytes={}-{}", offset, end - 1));
+            if let Some(validator) = &validator {
+                builder = builder.header(IF_RANGE, validator);
+            }
+            let response = builder.send().await?.error_for_status_with_body().await?;
+            let reason = match response.status() {
+                StatusCode::PARTIAL_CONTENT => content_range(response.headers())
+                    .filter(|(start, _)| *start != Some(offset))
+                    .map(|_| format!("content range does not start at {offset}")),
+                // the remote file changed, or ranges are not supported
+                _ => Some("range request is not satisfied".to_owned()),
+            };
+            if let Some(reason) = reason {
+                return Err(crate::Error::UnexpectedStatus(
+                    response.status(),
+                    response.url().to_owned(),
+                    reason,
+                ));
+            }
+            file.seek(std::io::SeekFrom::Start(offset)).await?;
+
+            let mut body = response.bytes_stream();
+            let mut interrupted = None;
+            let mut saved = offset;
+            while let Some(chunk) = body.next().await {
+                match chunk {
+                    Ok(chunk) => {
+                        // ignore bytes beyond the range
+                        let length = (chunk.len() as u64).min(end - offset);
+                        file.write_all(&chunk[..length as usize]).await?;
+                        offset += length;
+                        let done = downloaded.fetch_add(length, Ordering::Relaxed) + length;
+                        self.report(done, Some(total));
+                        if offset - saved >= SAVE_INTERVAL {
+                            file.flush().await?;
+                            state.save(index, offset).await?;
+                            saved = offset;
+                        }
+                    }
+                    Err(e) => {
+                        interrupted = Some(e);
+                        break;
+                    }
+                }
+            }
+            file.flush().await?;
+            state.save(index, offset).await?;
+            if interrupted.is_none() && offset >= end {
+                return Ok(());
+            }
+            if resumes >= self.max_resumes {
+                return Err(interrupted.map_or_else(|| truncated(offset), Into::into));
+            }
+            resumes += 1;
+            tracing::debug!("Segment of {} interrupted at {}", self.url, offset);
+        }
+    }
+}
+
+/// The resume state shared by the segments of a download.
+struct SegmentState<'a> {
+    meta: Mutex<PartMeta>,
+    path: &'a Path,
+}
+
+impl SegmentState<'_> {
+    /// Save that segment `index` is downloaded up to `offset`.
+    async fn save(&self, index: usize, offset: u64) -> std::io::Result<()> {
+        let mut meta = self.meta.lock().await;
+        meta.segments[index].0 = offset;
+        meta.save(self.path).await
+    }
+}
+
+/// The error of a body ending at `offset` before the expected size.
+fn truncated(offset: u64) -> crate::Error {
+    crate::Error::Io(std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        format!("body ended at {offset} before the expected size"),
+    ))
+}
@@ -0,0 +1,83 @@
+use std::borrow::Cow;
+
+use reqwest::Method;
+use url::Url;
+
+use super::request_builder_wrapper::ErgoRequestBuilder;
+
+/// An endpoint of an API described as data, see [`crate::ErgoClient::send_endpoint`].
+///
+/// Implemented for `(Method, &str)`, `(Method, String)` and `(Method, Url)`. A relative url
+/// is resolved against the base url of the client, if set.
+///
+/// # Example
+/// ```no_run
+/// # use ergoreq::{ErgoClient, ErgoEndpoint, ErgoRequestBuilder};
+/// # use reqwest::Method;
+/// struct GetUser(u64);
+///
+/// impl ErgoEndpoint for GetUser {
+///     fn method(&self) -> Method {
+///         Method::GET
+///     }
+///
+///     fn url(&self) -> std::borrow::Cow<'_, str> {
+///         format!("users/{}", self.0).into()
+///     }
+///
+///     fn prepare(&self, request: ErgoRequestBuilder) -> ErgoRequestBuilder {
+///         request.header("accept", "application/json")
+///     }
+/// }
+///
+/// # async fn run() -> ergoreq::Result<()> {
+/// let client = ErgoClient::new(reqwest::Client::new())
+///     .with_base_url("https://api.example.com/v2/".parse().unwrap());
+/// let user = client.send_endpoint(GetUser(1)).await?;
+/// let health = client.send_endpoint((Method::HEAD, "health")).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub trait ErgoEndpoint {
+    /// The method of the request.
+    fn method(&self) -> Method;
+
+    /// The url of the request, absolute or relative to the base url of the client.
+    fn url(&self) -> Cow<'_, str>;
+
+    /// Customize the request, like adding headers or a body. Returns `request` unchanged by
+    /// default.
+    fn prepare(&self, request: ErgoRequestBuilder) -> ErgoRequestBuilder {
+        request
+    }
+}
+
+impl ErgoEndpoint for (Method, &str) {
+    fn method(&self) -> Method {
+        self.0.to_owned()
+    }
+
+    fn url(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self.1)
+    }
+}
+
+impl ErgoEndpoint for (Method, String) {
+    fn method(&self) -> Method {
+        self.0.to_owned()
+    }
+
+    fn url(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.1)
+    }
+}
+
+impl ErgoEndpoint for (Method, Url) {
+    fn method(&self) -> Method {
+        self.0.to_owned()
+    }
+
+    fn url(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self.1.as_str())
+    }
+}
@@ -0,0 +1,424 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::utils::timer::{sleep, Instant};
+use crate::wrappers::client_wrapper::ErgoClient;
+
+/// How an [`EndpointPool`] selects the endpoint of each request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BalanceStrategy {
+    /// Use endpoints in turn.
+    #[default]
+    RoundRobin,
+    /// Use endpoints in turn, proportionally to their weights.
+    Weighted,
+    /// Use the endpoint with the fewest requests in flight.
+    LeastOutstanding,
+}
+
+/// Statistics of an endpoint in an [`EndpointPool`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct EndpointStats {
+    /// Number of requests sent to this endpoint.
+    pub requests: u64,
+    /// Number of requests which got a response other than `5xx`.
+    pub successes: u64,
+    /// Number of requests which failed or got a `5xx` response.
+    pub failures: u64,
+    /// Number of requests in flight.
+    pub outstanding: usize,
+    /// Whether the endpoint receives traffic, `false` while it is ejected.
+    pub healthy: bool,
+}
+
+/// The endpoint selected for a request by an [`EndpointPool`].
+///
+/// It is inserted into the `Extensions` of the request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SelectedEndpoint(pub url::Url);
+
+struct Endpoint {
+    origin: url::Url,
+    weight: u32,
+    requests: AtomicU64,
+    successes: AtomicU64,
+    failures: AtomicU64,
+    outstanding: AtomicUsize,
+    consecutive_failures: AtomicU32,
+    ejected_until: Mutex<Option<Instant>>,
+}
+
+impl Endpoint {
+    fn stats(&self) -> EndpointStats {
+        EndpointStats {
+            requests: self.requests.load(Ordering::Relaxed),
+            successes: self.successes.load(Ordering::Relaxed),
+            failures: self.failures.load(Ordering::Relaxed),
+            outstanding: self.outstanding.load(Ordering::Relaxed),
+            healthy: self.is_healthy(),
+        }
+    }
+
+    fn ejected_until(&self) -> std::sync::MutexGuard<'_, Option<Instant>> {
+        self.ejected_until.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_healthy(&self) -> bool {
+        match *self.ejected_until() {
+            Some(until) => Instant::now() >= until,
+            None => true,
+        }
+    }
+
+    fn eject(&self, cool_down: Duration) {
+        tracing::debug!("Eject endpoint {} for {:?}", self.origin, cool_down);
+        *self.ejected_until() = Some(Instant::now() + cool_down);
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+    }
+
+    fn restore(&self) {
+        *self.ejected_until() = None;
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+    }
+
+    /// Record the result of a request or a probe.
+    fn record(&self, success: bool, ejection: Option<(u32, Duration)>) {
+        if success {
+            self.consecutive_failures.store(0, Ordering::Relaxed);
+            return;
+        }
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some((max_failures, cool_down)) = ejection {
+            if failures >= max_failures {
+                self.eject(cool_down);
+            }
+        }
+    }
+}
+
+/// Balance requests of an `ErgoClient` among several origins of the same service.
+///
+/// Before the middleware chain runs, the scheme, host and port of the request url are replaced
+/// by the ones of the selected endpoint, the path and query are kept.
+///
+/// With [`Self::with_ejection`], endpoints failing repeatedly stop receiving traffic for a
+/// cool-down. If every endpoint is ejected, they are all used again rather than failing.
+///
+/// # Example
+/// ```
+/// # use std::sync::Arc;
+/// # use ergoreq::wrappers::endpoint_pool::{BalanceStrategy, EndpointPool};
+/// # use ergoreq::ErgoClient;
+/// let pool = Arc::new(
+///     EndpointPool::new(BalanceStrategy::Weighted)
+///         .with_weighted_endpoint("https://replica1.example.com", 3)
+///         .with_weighted_endpoint("https://replica2.example.com", 1),
+/// );
+/// let client = ErgoClient::new(reqwest::Client::new()).with_endpoint_pool(pool.to_owned());
+/// // urls are rewritten to the selected replica
+/// let request = client.get("https://example.com/api/items");
+/// ```
+pub struct EndpointPool {
+    strategy: BalanceStrategy,
+    endpoints: Vec<Arc<Endpoint>>,
+    next: AtomicUsize,
+    current_weights: Mutex<Vec<i64>>,
+    ejection: Option<(u32, Duration)>,
+    health_path: Option<String>,
+}
+
+impl EndpointPool {
+    /// Create an empty `EndpointPool` selecting endpoints with `strategy`.
+    pub fn new(strategy: BalanceStrategy) -> Self {
+        Self {
+            strategy,
+            endpoints: vec![],
+            next: AtomicUsize::new(0),
+            current_weights: Mutex::new(vec![]),
+            ejection: None,
+            health_path: None,
+        }
+    }
+
+    /// Add an endpoint with weight `1`.
+    ///
+    /// # Panics
+    /// Panics if `origin` is not a valid url.
+    pub fn with_endpoint(self, origin: &str) -> Self {
+        self.with_weighted_endpoint(origin, 1)
+    }
+
+    /// Add an endpoint with the given weight, used by [`BalanceStrategy::Weighted`].
+    ///
+    /// # Panics
+    /// Panics if `origin` is not a valid url or `weight` is `0`.
+    pub fn with_weighted_endpoint(mut self, origin: &str, weight: u32) -> Self {
+        assert!(weight > 0, "weight of an endpoint must be positive");
+        let origin = url::Url::parse(origin).expect("endpoint must be a valid url");
+        self.endpoints.push(Arc::new(Endpoint {
+            origin,
+            weight,
+            requests: AtomicU64::new(0),
+            successes: AtomicU64::new(0),
+            failures: AtomicU64::new(0),
+            outstanding: AtomicUsize::new(0),
+            consecutive_failures: AtomicU32::new(0),
+            ejected_until: Mutex::new(None),
+        }));
+        self.current_weights.get_mut().unwrap().push(0);
+        self
+    }
+
+    /// Eject an endpoint for `cool_down` after `max_failures` consecutive failed requests.
+    ///
+    /// Requests fail if they get an error or a `5xx` response.
+    ///
+    /// # Panics
+    /// Panics if `max_failures` is `0`.
+    pub fn with_ejection(mut self, max_failures: u32, cool_down: Duration) -> Self {
+        assert!(
+            max_failures > 0,
+            "max failures of ejection must be positive"
+        );
+        self.ejection = Some((max_failures, cool_down));
+        self
+    }
+
+    /// Probe `path` of every endpoint with `GET` in [`Self::check_health`].
+    ///
+    /// Endpoints answering other than `2xx` count as failed for [`Self::with_ejection`],
+    /// endpoints answering `2xx` are restored at once.
+    pub fn with_health_check(mut self, path: &str) -> Self {
+        self.health_path = Some(path.to_owned());
+        self
+    }
+
+    /// Probe every endpoint once with `client`, see [`Self::with_health_check`].
+    ///
+    /// Probes are not balanced, retried or counted in statistics.
+    pub async fn check_health(&self, client: &ErgoClient) {
+        let Some(path) = &self.health_path else {
+            return;
+        };
+        for endpoint in &self.endpoints {
+            let url = match endpoint.origin.join(path) {
+                Ok(url) => url,
+                Err(e) => {
+                    tracing::warn!("Invalid health check url of {}: {}", endpoint.origin, e);
+                    continue;
+                }
+            };
+            let success = match client
+                .get(url)
+                .without_endpoint_pool()
+                .with_retry_times(0)
+                .send()
+                .await
+            {
+                Ok(response) => response.status().is_success(),
+                Err(e) => {
+                    tracing::debug!("Health check of {} failed: {}", endpoint.origin, e);
+                    false
+                }
+            };
+            if success {
+                endpoint.restore();
+            } else {
+                endpoint.record(false, self.ejection);
+            }
+        }
+    }
+
+    /// Probe every endpoint with `client` each `interval`, forever.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use std::time::Duration;
+    /// # use ergoreq::wrappers::endpoint_pool::{BalanceStrategy, EndpointPool};
+    /// # use ergoreq::ErgoClient;
+    /// # async fn run() {
+    /// let pool = Arc::new(
+    ///     EndpointPool::new(BalanceStrategy::RoundRobin)
+    ///         .with_endpoint("https://replica1.example.com")
+    ///         .with_endpoint("https://replica2.example.com")
+    ///         .with_ejection(3, Duration::from_secs(30))
+    ///         .with_health_check("/healthz"),
+    /// );
+    /// let client = ErgoClient::new(reqwest::Client::new()).with_endpoint_pool(pool.to_owned());
+    /// let probing = client.to_owned();
+    /// tokio::spawn(async move {
+    ///     pool.run_health_checks(&probing, Duration::from_secs(10)).await
+    /// });
+    /// # }
+    /// ```
+    pub async fn run_health_checks(&self, client: &ErgoClient, interval: Duration) {
+        loop {
+            self.check_health(client).await;
+            sleep(interval).await;
+        }
+    }
+
+    /// Get the statistics of every endpoint.
+    pub fn stats(&self) -> Vec<(url::Url, EndpointStats)> {
+        self.endpoints
+            .iter()
+            .map(|v| (v.origin.to_owned(), v.stats()))
+            .collect()
+    }
+
+    /// Get the number of endpoints.
+    pub fn len(&self) -> usize {
+        self.endpoints.len()
+    }
+
+    /// Returns `true` if there is no endpoint.
+    pub fn is_empty(&self) -> bool {
+        self.endpoints.is_empty()
+    }
+
+    fn select_index(&self) -> Option<usize> {
+        if self.endpoints.is_empty() {
+            return None;
+        }
+        let turn = self.next.fetch_add(1, Ordering::Relaxed);
+        let healthy = self
+            .endpoints
+            .iter()
+            .map(|v| v.is_healthy())
+            .collect::<Vec<_>>();
+        // use every endpoint if all of them are ejected
+        let all_ejected = !healthy.contains(&true);
+        let usable = |index: usize| all_ejected || healthy[index];
+        let index = match self.strategy {
+            BalanceStrategy::RoundRobin => (0..self.endpoints.len())
+                .map(|v| (v + turn) % self.endpoints.len())
+                .find(|v| usable(*v))?,
+            // smooth weighted round robin
+            BalanceStrategy::Weighted => {
+                let mut current = self
+                    .current_weights
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner());
+                for (index, (weight, endpoint)) in
+                    current.iter_mut().zip(&self.endpoints).enumerate()
+                {
+                    if usable(index) {
+                        *weight += endpoint.weight as i64;
+                    }
+                }
+                let total = (0..self.endpoints.len())
+                    .filter(|v| usable(*v))
+                    .map(|v| self.endpoints[v].weight as i64)
+                    .sum::<i64>();
+                let (index, _) = current
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| usable(*index))
+                    .max_by_key(|(index, weight)| (**weight, std::cmp::Reverse(*index)))?;
+                current[index] -= total;
+                index
+            }
+            // break ties in turn, so idle endpoints share the load
+            BalanceStrategy::LeastOutstanding => (0..self.endpoints.len())
+                .map(|v| (v + turn) % self.endpoints.len())
+                .filter(|v| usable(*v))
+                .min_by_key(|v| self.endpoints[*v].outstanding.load(Ordering::Relaxed))?,
+        };
+        Some(index)
+    }
+
+    /// Select the endpoint of a request, it is tracked until the guard is dropped.
+    pub(crate) fn select(&self) -> Option<EndpointGuard> {
+        let endpoint = self.endpoints[self.select_index()?].to_owned();
+        endpoint.requests.fetch_add(1, Ordering::Relaxed);
+        endpoint.outstanding.fetch_add(1, Ordering::Relaxed);
+        Some(EndpointGuard {
+            endpoint,
+            ejection: self.ejection,
+        })
+    }
+}
+
+/// Track a request sent to an endpoint.
+pub(crate) struct EndpointGuard {
+    endpoint: Arc<Endpoint>,
+    ejection: Option<(u32, Duration)>,
+}
+
+impl EndpointGuard {
+    pub(crate) fn origin(&self) -> &url::Url {
+        &self.endpoint.origin
+    }
+
+    /// Record whether the request succeeded.
+    pub(crate) fn finish(self, success: bool) {
+        if success {
+            self.endpoint.successes.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.endpoint.failures.fetch_add(1, Ordering::Relaxed);
+        }
+        self.endpoint.record(success, self.ejection);
+    }
+}
+
+impl Drop for EndpointGuard {
+    fn drop(&mut self) {
+        self.endpoint.outstanding.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Replace the scheme, host and port of `url` by the ones of `origin`.
+pub(crate) fn replace_origin(url: &mut url::Url, origin: &url::Url) {
+    let _ = url.set_scheme(origin.scheme());
+    let _ = url.set_host(origin.host_str());
+    let _ = url.set_port(origin.port());
+}
+
+#[cfg(test)]
+mod test_endpoint_pool {
+    use super::{BalanceStrategy, EndpointPool};
+
+    fn origins(pool: &EndpointPool, count: usize) -> Vec<String> {
+        (0..count)
+            .map(|_| {
+                let guard = pool.select().unwrap();
+                guard.origin().host_str().unwrap().to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_endpoint_selection() {
+        let pool = EndpointPool::new(BalanceStrategy::RoundRobin)
+            .with_endpoint("https://a.example")
+            .with_endpoint("https://b.example");
+        assert_eq!(origins(&pool, 3), ["a.example", "b.example", "a.example"]);
+
+        let pool = EndpointPool::new(BalanceStrategy::Weighted)
+            .with_weighted_endpoint("https://a.example", 2)
+            .with_weighted_endpoint("https://b.example", 1);
+        assert_eq!(
+            origins(&pool, 6),
+            [
+                "a.example",
+                "b.example",
+                "a.example",
+                "a.example",
+                "b.example",
+                "a.example"
+            ]
+        );
+
+        let pool = EndpointPool::new(BalanceStrategy::LeastOutstanding)
+            .with_endpoint("https://a.example")
+            .with_endpoint("https://b.example");
+        let busy = pool.select().unwrap();
+        assert_eq!(busy.origin().host_str(), Some("a.example"));
+        assert_eq!(origins(&pool, 2), ["b.example", "b.example"]);
+        drop(busy);
+        assert_eq!(pool.stats()[0].1.outstanding, 0);
+        assert_eq!(pool.stats()[1].1.requests, 2);
+    }
+}
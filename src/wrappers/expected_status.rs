@@ -0,0 +1,35 @@
+use std::ops::{Bound, RangeBounds, RangeInclusive};
+
+use http::StatusCode;
+
+/// The statuses a response is expected to have, see
+/// [`crate::ErgoRequestBuilder::expect_status`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExpectedStatus(Vec<RangeInclusive<u16>>);
+
+impl ExpectedStatus {
+    /// Expect `status` in addition to the expected statuses.
+    pub(crate) fn push(&mut self, status: StatusCode) {
+        self.0.push(status.as_u16()..=status.as_u16());
+    }
+
+    /// Expect the statuses in `range` in addition to the expected statuses.
+    pub(crate) fn push_range<R: RangeBounds<u16>>(&mut self, range: R) {
+        let start = match range.start_bound() {
+            Bound::Included(v) => *v,
+            Bound::Excluded(v) => v.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(v) => *v,
+            Bound::Excluded(v) => v.saturating_sub(1),
+            Bound::Unbounded => u16::MAX,
+        };
+        self.0.push(start..=end);
+    }
+
+    /// Returns `true` if `status` is expected.
+    pub fn contains(&self, status: StatusCode) -> bool {
+        self.0.iter().any(|v| v.contains(&status.as_u16()))
+    }
+}
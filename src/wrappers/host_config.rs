@@ -0,0 +1,109 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use http::HeaderMap;
+
+use crate::middleware::rate_limit_middleware::{RateLimit, RateLimitMiddleware};
+
+/// Settings of requests to a host, registered by [`crate::ErgoClient::with_host_config`].
+///
+/// Every setting left `None` (or empty) falls back to the setting of the client.
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// # use ergoreq::middleware::rate_limit_middleware::RateLimit;
+/// # use ergoreq::wrappers::host_config::HostConfig;
+/// # use ergoreq::ErgoClient;
+/// let mut headers = http::HeaderMap::new();
+/// headers.insert("accept", "application/vnd.github+json".parse().unwrap());
+/// let client = ErgoClient::new(reqwest::Client::new()).with_host_config(
+///     "api.github.com",
+///     HostConfig {
+///         retry: Some(5),
+///         default_headers: headers,
+///         rate_limit: Some(RateLimit::new(10, 1.0)),
+///         ..Default::default()
+///     },
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HostConfig {
+    /// The retry count, `0` disables retry.
+    pub retry: Option<u16>,
+    /// The auto redirect count, `0` disables redirect.
+    pub redirect: Option<u16>,
+    /// Default headers, replacing the default headers of the client with the same name.
+    pub default_headers: HeaderMap,
+    /// Limit the rate of requests to the host, shared by every request of the client.
+    pub rate_limit: Option<RateLimit>,
+    /// The timeout of each request.
+    pub timeout: Option<Duration>,
+}
+
+/// A registered [`HostConfig`] with its state shared between requests.
+pub(crate) struct HostSettings {
+    pub(crate) config: HostConfig,
+    pub(crate) rate_limiter: Option<Arc<RateLimitMiddleware>>,
+}
+
+/// The [`HostConfig`]s of a client by host.
+///
+/// A host is matched exactly, or by a `*.example.com` pattern matching its subdomains.
+#[derive(Clone, Default)]
+pub(crate) struct HostConfigs(HashMap<String, Arc<HostSettings>>);
+
+impl HostConfigs {
+    pub(crate) fn insert(&mut self, host: &str, config: HostConfig) {
+        let rate_limiter = config
+            .rate_limit
+            .map(|v| Arc::new(RateLimitMiddleware::new().with_global_limit(v)));
+        self.0.insert(
+            host.to_ascii_lowercase(),
+            Arc::new(HostSettings {
+                config,
+                rate_limiter,
+            }),
+        );
+    }
+
+    /// Find the settings of `host`, an exact match is preferred to a wildcard one.
+    pub(crate) fn find(&self, host: &str) -> Option<Arc<HostSettings>> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let host = host.to_ascii_lowercase();
+        if let Some(settings) = self.0.get(&host) {
+            return Some(settings.to_owned());
+        }
+        // try the closest parent domain first
+        host.match_indices('.')
+            .find_map(|(i, _)| self.0.get(&format!("*{}", &host[i..])))
+            .map(|v| v.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod test_host_config {
+    use super::{HostConfig, HostConfigs};
+
+    #[test]
+    fn test_find_host() {
+        let mut configs = HostConfigs::default();
+        let config = |retry| HostConfig {
+            retry: Some(retry),
+            ..Default::default()
+        };
+        configs.insert("API.example.com", config(1));
+        configs.insert("*.example.com", config(2));
+        configs.insert("*.eu.example.com", config(3));
+
+        let retry = |host| configs.find(host).and_then(|v| v.config.retry);
+        assert_eq!(retry("api.example.com"), Some(1));
+        assert_eq!(retry("www.example.com"), Some(2));
+        assert_eq!(retry("a.eu.example.com"), Some(3));
+        assert_eq!(retry("example.com"), None);
+        assert_eq!(retry("example.org"), None);
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use http::StatusCode;
+use serde::de::DeserializeOwned;
+
+use super::response_wrapper::deserialize_detailed;
+
+/// Lines longer than this fail with [`crate::Error::LineTooLong`] by default.
+pub(crate) const DEFAULT_MAX_LINE: usize = 1 << 20;
+
+/// An incremental splitter of newline delimited JSON.
+struct JsonLinesState<S> {
+    body: Pin<Box<S>>,
+    buffer: Vec<u8>,
+    lines: VecDeque<Vec<u8>>,
+    max_line: usize,
+    error: Option<crate::Error>,
+    finished: bool,
+}
+
+impl<S> JsonLinesState<S> {
+    /// Queue the complete non blank lines of `chunk` and the buffered bytes.
+    fn feed(&mut self, chunk: &[u8]) {
+        self.buffer.extend_from_slice(chunk);
+        let mut start = 0;
+        while let Some(end) = self.buffer[start..].iter().position(|v| *v == b'\n') {
+            self.push_line(start..start + end);
+            start += end + 1;
+        }
+        self.buffer.drain(..start);
+        if self.buffer.len() > self.max_line {
+            self.error = Some(crate::Error::LineTooLong(self.max_line));
+        }
+    }
+
+    fn push_line(&mut self, range: std::ops::Range<usize>) {
+        let line = self.buffer[range].trim_ascii();
+        if line.len() > self.max_line {
+            self.error = Some(crate::Error::LineTooLong(self.max_line));
+        } else if !line.is_empty() && self.error.is_none() {
+            self.lines.push_back(line.to_vec());
+        }
+    }
+}
+
+/// Decode each line of `body` as a `T`.
+///
+/// A line which is not a valid `T` yields [`crate::Error::Deserialize`] and the next lines are
+/// still decoded, the stream ends after a transport error or [`crate::Error::LineTooLong`].
+pub(crate) fn decode_json_lines<T, S>(
+    status: StatusCode,
+    body: S,
+    max_line: usize,
+) -> impl Stream<Item = crate::Result<T>>
+where
+    T: DeserializeOwned,
+    S: Stream<Item = reqwest::Result<Bytes>>,
+{
+    let state = JsonLinesState {
+        body: Box::pin(body),
+        buffer: vec![],
+        lines: VecDeque::new(),
+        max_line,
+        error: None,
+        finished: false,
+    };
+    futures::stream::unfold(state, move |mut state| async move {
+        loop {
+            if let Some(line) = state.lines.pop_front() {
+                return Some((deserialize_detailed(status, &line), state));
+            }
+            if let Some(error) = state.error.take() {
+                state.finished = true;
+                return Some((Err(error), state));
+            }
+            if state.finished {
+                return None;
+            }
+            match state.body.next().await {
+                Some(Ok(chunk)) => state.feed(&chunk),
+                Some(Err(error)) => state.error = Some(error.into()),
+                None => {
+                    state.finished = true;
+                    let end = state.buffer.len();
+                    state.push_line(0..end);
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test_json_lines {
+    use bytes::Bytes;
+    use futures::StreamExt;
+    use http::StatusCode;
+
+    use super::decode_json_lines;
+
+    fn decode(chunks: &[&'static str], max_line: usize) -> Vec<crate::Result<u32>> {
+        let body =
+            futures::stream::iter(chunks.iter().map(|v| Ok(Bytes::from_static(v.as_bytes()))));
+        futures::executor::block_on(
+            decode_json_lines::<u32, _>(StatusCode::OK, body, max_line).collect(),
+        )
+    }
+
+    #[test]
+    fn test_decode_json_lines() {
+        let values = decode(&["1\n2", "2\r\n\n 3 \n", "x\n4"], 16);
+        assert_eq!(values.len(), 5);
+        assert!(matches!(values[..3], [Ok(1), Ok(22), Ok(3)]));
+        assert!(matches!(values[3], Err(crate::Error::Deserialize { .. })));
+        assert!(matches!(values[4], Ok(4)));
+
+        let values = decode(&["1\n", "123456", "789"], 8);
+        assert!(matches!(
+            values[..],
+            [Ok(1), Err(crate::Error::LineTooLong(8))]
+        ));
+    }
+}
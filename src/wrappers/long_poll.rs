@@ -0,0 +1,199 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use futures::Stream;
+use http::header::{ETAG, IF_NONE_MATCH};
+use http::StatusCode;
+use retry_policies::policies::ExponentialBackoff;
+use retry_policies::{RetryDecision, RetryPolicy};
+
+use super::request_builder_wrapper::ErgoRequestBuilder;
+use super::response_wrapper::ErgoResponse;
+use crate::utils::timer::{sleep, system_now};
+
+type CursorExtractor = dyn Fn(&ErgoResponse) -> Option<String> + Send + Sync + 'static;
+type CursorApplier = dyn Fn(ErgoRequestBuilder, &str) -> ErgoRequestBuilder + Send + Sync + 'static;
+
+/// Options of [`ErgoRequestBuilder::long_poll`].
+///
+/// # Example
+/// ```
+/// # use std::time::Duration;
+/// # use ergoreq::wrappers::long_poll::LongPollOptions;
+/// let options = LongPollOptions::new()
+///     .with_interval(Duration::from_millis(100))
+///     .with_cursor(
+///         |response| {
+///             response
+///                 .headers()
+///                 .get("x-cursor")
+///                 .and_then(|v| v.to_str().ok())
+///                 .map(|v| v.to_owned())
+///         },
+///         |builder, cursor| builder.query(&[("since", cursor)]),
+///     );
+/// ```
+#[derive(Clone)]
+pub struct LongPollOptions {
+    interval: Duration,
+    error_backoff: Arc<dyn RetryPolicy + Send + Sync + 'static>,
+    cursor: Option<(Arc<CursorExtractor>, Arc<CursorApplier>)>,
+}
+
+impl LongPollOptions {
+    /// Create `LongPollOptions` polling again right after each response, and backing off
+    /// exponentially at most 10 times in a row on errors.
+    pub fn new() -> Self {
+        Self {
+            interval: Duration::ZERO,
+            error_backoff: Arc::new(ExponentialBackoff::builder().build_with_max_retries(10)),
+            cursor: None,
+        }
+    }
+
+    /// Wait `interval` after each successful response before polling again.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the policy deciding how long to wait after an error, and when to give up.
+    ///
+    /// An error is a failed request, or a response with a server error or
+    /// `429 Too Many Requests` status. The count of errors is reset by a successful response.
+    pub fn with_error_backoff<P>(mut self, policy: P) -> Self
+    where
+        P: RetryPolicy + Send + Sync + 'static,
+    {
+        self.error_backoff = Arc::new(policy);
+        self
+    }
+
+    /// Extract a cursor from each successful response with `extract`, and add it to the next
+    /// request with `apply`.
+    ///
+    /// The last cursor is kept if `extract` returns `None`.
+    pub fn with_cursor<E, A>(mut self, extract: E, apply: A) -> Self
+    where
+        E: Fn(&ErgoResponse) -> Option<String> + Send + Sync + 'static,
+        A: Fn(ErgoRequestBuilder, &str) -> ErgoRequestBuilder + Send + Sync + 'static,
+    {
+        self.cursor = Some((Arc::new(extract), Arc::new(apply)));
+        self
+    }
+
+    /// Send the `ETag` of the last response as `If-None-Match`, so the server can hold the
+    /// request until the resource changes.
+    pub fn with_etag(self) -> Self {
+        self.with_cursor(
+            |response| {
+                response
+                    .headers()
+                    .get(ETAG)
+                    .and_then(|v| v.to_str().ok())
+                    .map(|v| v.to_owned())
+            },
+            |builder, etag| builder.header(IF_NONE_MATCH, etag),
+        )
+    }
+}
+
+impl Default for LongPollOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct LongPollState {
+    template: ErgoRequestBuilder,
+    options: LongPollOptions,
+    cursor: Option<String>,
+    delay: Option<Duration>,
+    errors: u32,
+    errors_start: SystemTime,
+    done: bool,
+}
+
+impl LongPollState {
+    async fn poll(&mut self) -> crate::Result<ErgoResponse> {
+        let mut builder = self
+            .template
+            .try_clone()
+            .ok_or(crate::Error::RequestNotCloneable)?;
+        if let (Some((_, apply)), Some(cursor)) = (&self.options.cursor, &self.cursor) {
+            builder = apply(builder, cursor);
+        }
+        builder.send().await
+    }
+
+    /// Decide the delay before the next request from `result`.
+    fn schedule(&mut self, result: &crate::Result<ErgoResponse>) {
+        let failed = match result {
+            Ok(response) => {
+                response.status().is_server_error()
+                    || response.status() == StatusCode::TOO_MANY_REQUESTS
+            }
+            Err(crate::Error::RequestNotCloneable) => {
+                self.done = true;
+                return;
+            }
+            Err(_) => true,
+        };
+        if !failed {
+            self.errors = 0;
+            self.delay = Some(self.options.interval);
+            if let (Some((extract, _)), Ok(response)) = (&self.options.cursor, result) {
+                if let Some(cursor) = extract(response) {
+                    self.cursor = Some(cursor);
+                }
+            }
+            return;
+        }
+
+        if self.errors == 0 {
+            self.errors_start = system_now();
+        }
+        match self
+            .options
+            .error_backoff
+            .should_retry(self.errors_start, self.errors)
+        {
+            RetryDecision::Retry { execute_after } => {
+                self.errors += 1;
+                self.delay = Some(
+                    execute_after
+                        .duration_since(system_now())
+                        .unwrap_or_default(),
+                );
+            }
+            RetryDecision::DoNotRetry => self.done = true,
+        }
+    }
+}
+
+/// Poll `template` again and again, see [`ErgoRequestBuilder::long_poll`].
+pub(crate) fn long_poll(
+    template: ErgoRequestBuilder,
+    options: LongPollOptions,
+) -> impl Stream<Item = crate::Result<ErgoResponse>> {
+    let state = LongPollState {
+        template,
+        options,
+        cursor: None,
+        delay: None,
+        errors: 0,
+        errors_start: system_now(),
+        done: false,
+    };
+    futures::stream::unfold(state, |mut state| async move {
+        if state.done {
+            return None;
+        }
+        if let Some(delay) = state.delay.take() {
+            sleep(delay).await;
+        }
+        let result = state.poll().await;
+        state.schedule(&result);
+        Some((result, state))
+    })
+}
@@ -1,2 +1,19 @@
+pub mod client_builder;
+pub mod client_pool;
 pub mod client_wrapper;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod download;
+pub mod endpoint;
+pub mod endpoint_pool;
+pub(crate) mod expected_status;
+pub mod host_config;
+pub(crate) mod json_lines;
+pub mod long_poll;
+pub mod negotiate;
+pub mod pagination;
 pub mod request_builder_wrapper;
+pub mod resource;
+pub mod response_wrapper;
+pub mod sse;
+#[cfg(all(feature = "websocket", not(target_arch = "wasm32")))]
+pub mod websocket;
@@ -0,0 +1,138 @@
+use http::StatusCode;
+use serde::de::DeserializeOwned;
+
+use super::request_builder_wrapper::content_type_matches;
+use super::response_wrapper::deserialize_detailed;
+
+/// A representation of a body which [`crate::ErgoRequestBuilder::negotiate`] can decode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BodyFormat {
+    /// `application/json` and `+json` types.
+    Json,
+    /// `application/xml`, `text/xml` and `+xml` types, requires the `xml` feature.
+    #[cfg(feature = "xml")]
+    Xml,
+    /// `application/msgpack` and its legacy names, requires the `msgpack` feature.
+    #[cfg(feature = "msgpack")]
+    Msgpack,
+    /// `application/cbor`, requires the `cbor` feature.
+    #[cfg(feature = "cbor")]
+    Cbor,
+}
+
+impl BodyFormat {
+    /// The mime sent in the `Accept` header for this format.
+    pub fn mime(&self) -> &'static str {
+        match self {
+            BodyFormat::Json => "application/json",
+            #[cfg(feature = "xml")]
+            BodyFormat::Xml => "application/xml",
+            #[cfg(feature = "msgpack")]
+            BodyFormat::Msgpack => crate::utils::msgpack::CONTENT_TYPE,
+            #[cfg(feature = "cbor")]
+            BodyFormat::Cbor => crate::utils::cbor::CONTENT_TYPE,
+        }
+    }
+
+    /// Other mimes of this format.
+    fn aliases(&self) -> &'static [&'static str] {
+        match self {
+            BodyFormat::Json => &[],
+            #[cfg(feature = "xml")]
+            BodyFormat::Xml => &["text/xml"],
+            #[cfg(feature = "msgpack")]
+            BodyFormat::Msgpack => &["application/x-msgpack", "application/vnd.msgpack"],
+            #[cfg(feature = "cbor")]
+            BodyFormat::Cbor => &[],
+        }
+    }
+
+    /// Whether the `Content-Type` `content_type` is this format.
+    fn matches(&self, content_type: &str) -> bool {
+        std::iter::once(self.mime())
+            .chain(self.aliases().iter().copied())
+            .any(|v| content_type_matches(v, content_type))
+    }
+
+    /// Decode `body` of this format as a `T`.
+    fn decode<T: DeserializeOwned>(&self, status: StatusCode, body: &[u8]) -> crate::Result<T> {
+        match self {
+            BodyFormat::Json => deserialize_detailed(status, body),
+            #[cfg(feature = "xml")]
+            BodyFormat::Xml => {
+                let text = std::str::from_utf8(body)
+                    .map_err(|e| crate::Error::Codec(format!("Invalid XML: {e}")))?;
+                quick_xml::de::from_str(text)
+                    .map_err(|e| crate::Error::Codec(format!("Invalid XML: {e}")))
+            }
+            #[cfg(feature = "msgpack")]
+            BodyFormat::Msgpack => crate::utils::msgpack::from_slice(body),
+            #[cfg(feature = "cbor")]
+            BodyFormat::Cbor => crate::utils::cbor::from_slice(body),
+        }
+    }
+}
+
+/// A body decoded by [`crate::ErgoRequestBuilder::negotiate`], with the format the server chose.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Negotiated<T> {
+    pub value: T,
+    pub format: BodyFormat,
+}
+
+/// Build an `Accept` header preferring `formats` in order, with decreasing quality values.
+pub(crate) fn accept_header(formats: &[BodyFormat]) -> String {
+    formats
+        .iter()
+        .enumerate()
+        .map(|(i, format)| match 10usize.saturating_sub(i).max(1) {
+            10 => format.mime().to_owned(),
+            quality => format!("{};q=0.{quality}", format.mime()),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Decode `body` with the first of `formats` matching `content_type`.
+pub(crate) fn decode_negotiated<T: DeserializeOwned>(
+    formats: &[BodyFormat],
+    status: StatusCode,
+    content_type: &str,
+    body: &[u8],
+) -> Option<crate::Result<Negotiated<T>>> {
+    let format = *formats.iter().find(|v| v.matches(content_type))?;
+    Some(
+        format
+            .decode(status, body)
+            .map(|value| Negotiated { value, format }),
+    )
+}
+
+#[cfg(test)]
+mod test_negotiate {
+    use http::StatusCode;
+
+    use super::{accept_header, decode_negotiated, BodyFormat, Negotiated};
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(accept_header(&[BodyFormat::Json]), "application/json");
+        let decoded = decode_negotiated::<u32>(
+            &[BodyFormat::Json],
+            StatusCode::OK,
+            "application/vnd.api+json",
+            b"1",
+        );
+        assert_eq!(
+            decoded.unwrap().unwrap(),
+            Negotiated {
+                value: 1,
+                format: BodyFormat::Json
+            }
+        );
+        assert!(
+            decode_negotiated::<u32>(&[BodyFormat::Json], StatusCode::OK, "text/html", b"1")
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,248 @@
+use std::sync::Arc;
+
+use bytes::Bytes;
+use futures::Stream;
+use http::{HeaderMap, StatusCode};
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use super::request_builder_wrapper::ErgoRequestBuilder;
+use super::response_wrapper::deserialize_detailed;
+
+/// A page fetched by [`ErgoRequestBuilder::paginate`], with its body read.
+#[derive(Clone, Debug)]
+pub struct Page {
+    /// The url the page was requested from.
+    pub url: Url,
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl Page {
+    /// Deserialize the body as JSON, failing with [`crate::Error::Deserialize`].
+    pub fn json<T: DeserializeOwned>(&self) -> crate::Result<T> {
+        deserialize_detailed(self.status, &self.body)
+    }
+
+    /// Get the body as text, invalid UTF-8 is replaced.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    /// Get the JSON value at `pointer` of the body, like `/data/items`.
+    ///
+    /// The whole body is returned for an empty pointer.
+    fn json_pointer(&self, pointer: &str) -> Option<serde_json::Value> {
+        let mut value = serde_json::from_slice::<serde_json::Value>(&self.body).ok()?;
+        value.pointer_mut(pointer).map(|v| v.take())
+    }
+}
+
+/// Decides the url of the page after a fetched page.
+pub trait PaginationStrategy: Send + Sync + 'static {
+    /// Get the url of the next page, or `None` if `page` is the last one.
+    fn next_url(&self, page: &Page) -> Option<Url>;
+}
+
+/// Follow the `rel="next"` link of the `Link` header, see RFC 8288.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LinkHeader;
+
+impl LinkHeader {
+    /// Find the target of the `rel="next"` link in a `Link` header value.
+    fn find_next(value: &str) -> Option<&str> {
+        let mut rest = value;
+        while let Some(start) = rest.find('<') {
+            let end = start + rest[start..].find('>')?;
+            let target = &rest[start + 1..end];
+            rest = &rest[end + 1..];
+            let params = &rest[..rest.find('<').unwrap_or(rest.len())];
+            let is_next = params.split(';').any(|param| {
+                let Some((name, value)) = param.split_once('=') else {
+                    return false;
+                };
+                name.trim().eq_ignore_ascii_case("rel")
+                    && value
+                        .trim()
+                        .trim_matches('"')
+                        .split_ascii_whitespace()
+                        .any(|v| v.eq_ignore_ascii_case("next"))
+            });
+            if is_next {
+                return Some(target);
+            }
+        }
+        None
+    }
+}
+
+impl PaginationStrategy for LinkHeader {
+    fn next_url(&self, page: &Page) -> Option<Url> {
+        page.headers
+            .get_all(http::header::LINK)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find_map(Self::find_next)
+            .and_then(|v| page.url.join(v).ok())
+    }
+}
+
+/// Read a cursor from the JSON body and send it as a query parameter of the next page.
+///
+/// Pagination stops when the cursor is missing, `null` or empty.
+#[derive(Clone, Debug)]
+pub struct JsonCursor {
+    pointer: String,
+    param: String,
+}
+
+impl JsonCursor {
+    /// Read the cursor at JSON `pointer` of the body (like `/meta/next_cursor`), and send it as
+    /// query parameter `param`.
+    pub fn new<P: Into<String>, Q: Into<String>>(pointer: P, param: Q) -> Self {
+        Self {
+            pointer: pointer.into(),
+            param: param.into(),
+        }
+    }
+}
+
+impl PaginationStrategy for JsonCursor {
+    fn next_url(&self, page: &Page) -> Option<Url> {
+        let cursor = match page.json_pointer(&self.pointer)? {
+            serde_json::Value::String(v) => v,
+            serde_json::Value::Number(v) => v.to_string(),
+            _ => return None,
+        };
+        if cursor.is_empty() {
+            return None;
+        }
+        Some(set_query_param(&page.url, &self.param, &cursor))
+    }
+}
+
+/// Increase a page number query parameter, until a page has no items.
+#[derive(Clone, Debug)]
+pub struct PageNumber {
+    param: String,
+    start: u64,
+    items_pointer: String,
+}
+
+impl PageNumber {
+    /// Send the page number as query parameter `param`, pages are numbered from `1`.
+    ///
+    /// The body should be a JSON array, see [`Self::with_items_pointer`] otherwise.
+    pub fn new<P: Into<String>>(param: P) -> Self {
+        Self {
+            param: param.into(),
+            start: 1,
+            items_pointer: String::new(),
+        }
+    }
+
+    /// Set the number of the first page, used if the request does not set the parameter.
+    pub fn with_start(mut self, start: u64) -> Self {
+        self.start = start;
+        self
+    }
+
+    /// Read the items of a page at JSON `pointer` of the body, like `/data`.
+    pub fn with_items_pointer<P: Into<String>>(mut self, pointer: P) -> Self {
+        self.items_pointer = pointer.into();
+        self
+    }
+}
+
+impl PaginationStrategy for PageNumber {
+    fn next_url(&self, page: &Page) -> Option<Url> {
+        match page.json_pointer(&self.items_pointer)? {
+            serde_json::Value::Array(items) if !items.is_empty() => (),
+            _ => return None,
+        }
+        let current = page
+            .url
+            .query_pairs()
+            .find(|(k, _)| *k == self.param)
+            .and_then(|(_, v)| v.parse::<u64>().ok())
+            .unwrap_or(self.start);
+        Some(set_query_param(
+            &page.url,
+            &self.param,
+            &(current + 1).to_string(),
+        ))
+    }
+}
+
+/// Replace the query parameter `name` of `url` with `value`.
+fn set_query_param(url: &Url, name: &str, value: &str) -> Url {
+    let pairs = url
+        .query_pairs()
+        .filter(|(k, _)| k != name)
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect::<Vec<_>>();
+    let mut url = url.to_owned();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(pairs)
+        .append_pair(name, value);
+    url
+}
+
+/// Fetch pages from `template`, see [`ErgoRequestBuilder::paginate`].
+pub(crate) fn paginate(
+    template: ErgoRequestBuilder,
+    strategy: Arc<dyn PaginationStrategy>,
+) -> impl Stream<Item = crate::Result<Page>> {
+    futures::stream::try_unfold(
+        (template, None::<Url>, false),
+        move |(template, next, done)| {
+            let strategy = strategy.to_owned();
+            async move {
+                if done {
+                    return Ok(None);
+                }
+                let builder = template
+                    .try_clone()
+                    .ok_or(crate::Error::RequestNotCloneable)?;
+                let builder = match next {
+                    Some(url) => builder.with_url(url)?,
+                    None => builder,
+                };
+                let url = builder.request_url()?;
+                let response = builder.send().await?.error_for_status_with_body().await?;
+                let status = response.status();
+                let headers = response.headers().to_owned();
+                let body = response.bytes().await?;
+                let page = Page {
+                    url,
+                    status,
+                    headers,
+                    body,
+                };
+                // stop on a page pointing to itself
+                let next = strategy.next_url(&page).filter(|v| *v != page.url);
+                let done = next.is_none();
+                Ok(Some((page, (template, next, done))))
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod test_pagination {
+    use super::LinkHeader;
+
+    #[test]
+    fn test_find_next_link() {
+        assert_eq!(
+            LinkHeader::find_next(
+                r#"<https://example.com/?page=1>; rel="prev", <https://example.com/?page=3>; rel="next last""#
+            ),
+            Some("https://example.com/?page=3")
+        );
+        assert_eq!(LinkHeader::find_next("</b>; rel=next"), Some("/b"));
+        assert_eq!(LinkHeader::find_next(r#"</a>; rel="prev""#), None);
+    }
+}
@@ -1,33 +1,107 @@
 use core::fmt;
-use http::{HeaderMap, Version};
+use futures::future::BoxFuture;
+use futures::{Stream, StreamExt, TryStreamExt};
+#[cfg(not(target_arch = "wasm32"))]
+use http::Version;
+use http::{Extensions, HeaderMap, StatusCode};
 use reqwest::header::{HeaderName, HeaderValue};
 use reqwest::{Body, Client, Request, RequestBuilder, Response};
 use retry_policies::policies::ExponentialBackoff;
 use retry_policies::RetryPolicy;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::collections::HashMap;
 use std::future::Future;
+#[cfg(not(target_arch = "wasm32"))]
+use std::net::SocketAddr;
+use std::ops::RangeBounds;
 use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Mutex;
 use std::time::Duration;
 use tracing::instrument;
+use url::Url;
 
 use crate::cookie::cookie_container::CookieContainer;
 
-use crate::middleware::auto_redirect_middleware::AutoRedirectMiddleware;
-use crate::middleware::auto_retry_middleware::AutoRetryMiddleware;
-use crate::middleware::middleware::{Middleware, Next};
-use crate::wrappers::client_wrapper::ErgoClient;
+use crate::middleware::auto_redirect_middleware::{
+    AutoRedirectMiddleware, PermanentRedirectCache, RedirectChain, RedirectHop, RedirectMethod,
+    RedirectMode, RedirectObserver, RedirectPolicy, RefererPolicy, DEFAULT_MAX_DRAIN,
+};
+use crate::middleware::auto_retry_middleware::{
+    AutoRetryMiddleware, IdempotencyKey, RetryOptions, SentIdempotencyKey, IDEMPOTENCY_KEY,
+};
+use crate::middleware::cancellation_middleware::{CancellationMiddleware, CancellationToken};
+use crate::middleware::deadline_middleware::DeadlineMiddleware;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::middleware::dry_run_middleware::DryRunMiddleware;
+use crate::middleware::hook_middleware::{OnRequestMiddleware, OnResponseMiddleware};
+use crate::middleware::middleware::{Middleware, MiddlewarePhase, Next};
+use crate::scheduler::priority_scheduler::{PriorityScheduler, RequestPriority};
+use crate::utils::body_factory::BodyFactory;
+use crate::utils::curl::request_to_curl;
+use crate::utils::multipart::ErgoMultipart;
+use crate::utils::path_template::expand_path_params;
+use crate::utils::redactor::Redactor;
+use crate::utils::response::{limit_body, verify_body};
+use crate::utils::timer::Instant;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::utils::upload::{AsyncReadBody, UploadProgress, UploadProgressCallback};
+use crate::wrappers::client_pool::{ClientPool, TransportOptions};
+use crate::wrappers::client_wrapper::{ErgoClient, RequestDefaults};
+use crate::wrappers::endpoint_pool::{replace_origin, EndpointPool, SelectedEndpoint};
+use crate::wrappers::expected_status::ExpectedStatus;
+use crate::wrappers::host_config::HostSettings;
+use crate::wrappers::long_poll::{long_poll, LongPollOptions};
+use crate::wrappers::negotiate::{accept_header, decode_negotiated, BodyFormat, Negotiated};
+use crate::wrappers::pagination::{paginate, Page, PaginationStrategy};
+use crate::wrappers::response_wrapper::{
+    deserialize_detailed, unexpected_status, ErgoResponse, RequestTimings,
+};
+use crate::wrappers::sse::{subscribe, SseEvent};
+#[cfg(all(feature = "websocket", not(target_arch = "wasm32")))]
+use crate::wrappers::websocket::{upgrade, ErgoWebSocket};
 
 /// A wrapper for [`reqwest::RequestBuilder`]
 pub struct ErgoRequestBuilder {
     inner: RequestBuilder,
     cookie_store: Option<Arc<dyn CookieContainer + 'static>>,
+    defaults: RequestDefaults,
     url: String,
     retry_policy: Option<Arc<dyn RetryPolicy + Send + Sync + 'static>>,
+    retry_options: RetryOptions,
     max_redirect_times: u16,
+    redirect_mode: RedirectMode,
+    redirect_policy: Option<Arc<dyn RedirectPolicy>>,
+    redirect_cache: Option<Arc<PermanentRedirectCache>>,
+    referer_policy: RefererPolicy,
+    redirect_observer: Option<Arc<RedirectObserver>>,
+    redirect_methods: HashMap<StatusCode, RedirectMethod>,
+    redirect_max_drain: u64,
+    #[cfg(feature = "html-redirect")]
+    html_redirect: bool,
     client: reqwest::Client,
-    client_middleware: Box<[Arc<dyn Middleware>]>,
-    request_middleware: Vec<Arc<dyn Middleware>>,
+    client_middleware: Vec<(MiddlewarePhase, Arc<dyn Middleware>)>,
+    request_middleware: Vec<(MiddlewarePhase, Arc<dyn Middleware>)>,
     extensions: http::Extensions,
+    scheduler: Option<Arc<PriorityScheduler>>,
+    client_pool: Arc<ClientPool>,
+    transport_options: Option<TransportOptions>,
+    redactor: Option<Arc<Redactor>>,
+    deadline: Option<Duration>,
+    endpoint_pool: Option<Arc<EndpointPool>>,
+    expected_status: Option<ExpectedStatus>,
+    required_content_type: Option<String>,
+    path_params: Option<HashMap<String, String>>,
+    idempotency_key: Option<IdempotencyKey>,
+    cancellation_token: Option<CancellationToken>,
+    max_response_bytes: Option<u64>,
+    integrity_check: bool,
+    multipart: Option<ErgoMultipart>,
+    #[cfg(not(target_arch = "wasm32"))]
+    upload_body: Option<AsyncReadBody>,
+    #[cfg(not(target_arch = "wasm32"))]
+    upload_progress: Option<UploadProgressCallback>,
 }
 
 impl ErgoRequestBuilder {
@@ -44,22 +118,129 @@ impl ErgoRequestBuilder {
         Self {
             inner: raw_builder,
             cookie_store,
+            defaults: RequestDefaults::default(),
             url,
             retry_policy: global_retry_policy,
+            retry_options: RetryOptions::new(),
             max_redirect_times: global_redirect_time,
+            redirect_mode: RedirectMode::default(),
+            redirect_policy: None,
+            redirect_cache: None,
+            referer_policy: RefererPolicy::default(),
+            redirect_observer: None,
+            redirect_methods: HashMap::new(),
+            redirect_max_drain: DEFAULT_MAX_DRAIN,
+            #[cfg(feature = "html-redirect")]
+            html_redirect: false,
             client,
-            client_middleware: middlewares,
+            client_middleware: middlewares
+                .into_vec()
+                .into_iter()
+                .map(|v| (MiddlewarePhase::default(), v))
+                .collect(),
             request_middleware: vec![],
             extensions: http::Extensions::new(),
+            scheduler: None,
+            client_pool: ClientPool::shared(),
+            transport_options: None,
+            redactor: None,
+            deadline: None,
+            endpoint_pool: None,
+            expected_status: None,
+            required_content_type: None,
+            path_params: None,
+            idempotency_key: None,
+            cancellation_token: None,
+            max_response_bytes: None,
+            integrity_check: false,
+            multipart: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            upload_body: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            upload_progress: None,
         }
     }
 
+    /// Create a new `ErgoRequestBuilder` inheriting settings of `client`
+    pub(crate) fn from_client(
+        raw_builder: RequestBuilder,
+        url: String,
+        client: &ErgoClient,
+    ) -> Self {
+        let mut builder = Self::new(
+            raw_builder,
+            client.get_cookie_store(),
+            url,
+            client.get_inner_client().to_owned(),
+            client.get_auto_redirect_count(),
+            client.get_retry_policy(),
+            Box::new([]),
+        );
+        builder.defaults = client.get_defaults();
+        builder.client_middleware = client.get_middlewares();
+        builder.scheduler = client.get_priority_scheduler();
+        builder.client_pool = client.get_client_pool();
+        builder.redactor = client.get_redactor();
+        builder.retry_options = client.get_retry_options();
+        builder.deadline = client.get_deadline();
+        builder.max_response_bytes = client.get_max_response_bytes();
+        builder.integrity_check = client.get_integrity_check();
+        builder.transport_options = client.get_transport_options();
+        builder.endpoint_pool = client.get_endpoint_pool();
+        builder.redirect_mode = client.get_redirect_mode();
+        builder.redirect_policy = client.get_redirect_policy();
+        builder.redirect_cache = client.get_permanent_redirect_cache();
+        builder.referer_policy = client.get_referer_policy();
+        builder.redirect_observer = client.get_redirect_observer();
+        builder.redirect_methods = client.get_redirect_methods();
+        builder.redirect_max_drain = client.get_redirect_max_drain();
+        #[cfg(feature = "html-redirect")]
+        {
+            builder.html_redirect = client.get_html_redirect();
+        }
+        match client.get_host_settings(&builder.url) {
+            Some(settings) => builder.with_host_settings(&settings),
+            None => builder,
+        }
+    }
+
+    /// Apply the [`HostConfig`](crate::wrappers::host_config::HostConfig) of the host of this
+    /// request over the settings of the client.
+    fn with_host_settings(mut self, settings: &HostSettings) -> Self {
+        let config = &settings.config;
+        if let Some(retry) = config.retry {
+            self = self.with_retry_times(retry);
+        }
+        if let Some(redirect) = config.redirect {
+            self.max_redirect_times = redirect;
+        }
+        if let Some(timeout) = config.timeout {
+            self = self.timeout(timeout);
+        }
+        self.defaults.set_headers(&config.default_headers);
+        // limit every attempt, before the other middlewares of the phase
+        if let Some(rate_limiter) = &settings.rate_limiter {
+            self.client_middleware
+                .insert(0, (MiddlewarePhase::PostRetry, rate_limiter.to_owned()));
+        }
+        self
+    }
+
     /// Add a per-request middleware
-    pub fn with_middleware<M>(mut self, middleware: M) -> Self
+    pub fn with_middleware<M>(self, middleware: M) -> Self
     where
         M: Middleware,
     {
-        self.request_middleware.push(Arc::new(middleware));
+        let phase = middleware.default_phase();
+        self.with_middleware_phase(middleware, phase)
+    }
+
+    /// Add a per-request middleware running in the given [`MiddlewarePhase`]
+    pub fn with_middleware_phase<M>(mut self, middleware: M, phase: MiddlewarePhase) -> Self
+    where
+        M: Middleware,
+    {
+        self.request_middleware.push((phase, Arc::new(middleware)));
         self
     }
 
@@ -70,7 +251,40 @@ impl ErgoRequestBuilder {
     where
         M: Middleware,
     {
-        self.request_middleware.push(middleware);
+        let phase = middleware.default_phase();
+        self.request_middleware.push((phase, middleware));
+        self
+    }
+
+    /// Run an async closure on this request before it is sent.
+    ///
+    /// See [`ErgoClient::on_request`].
+    pub fn on_request<F>(self, hook: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut Request, &'a mut Extensions) -> BoxFuture<'a, crate::Result<()>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.with_middleware(OnRequestMiddleware::new(hook))
+    }
+
+    /// Run an async closure on the response of this request before it is returned.
+    ///
+    /// See [`ErgoClient::on_response`].
+    pub fn on_response<F>(self, hook: F) -> Self
+    where
+        F: for<'a> Fn(&'a mut Response, &'a mut Extensions) -> BoxFuture<'a, crate::Result<()>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.with_middleware(OnResponseMiddleware::new(hook))
+    }
+
+    /// Send this request to its own url, even if the client has an `EndpointPool`.
+    pub(crate) fn without_endpoint_pool(mut self) -> Self {
+        self.endpoint_pool = None;
         self
     }
 
@@ -94,6 +308,12 @@ impl ErgoRequestBuilder {
         self
     }
 
+    /// Send this request without a `CookieStore`, even if the client has one.
+    pub fn without_cookie_store(mut self) -> Self {
+        self.cookie_store = None;
+        self
+    }
+
     /// Set `extension` for this request.
     ///
     /// `Extensions` can be get and pass some useful information for middlewares.
@@ -105,6 +325,14 @@ impl ErgoRequestBuilder {
         self
     }
 
+    /// Set the [`RequestPriority`] of this request.
+    ///
+    /// It only takes effect if the client has a priority scheduler,
+    /// see [`ErgoClient::with_priority_scheduler`].
+    pub fn with_priority(self, priority: RequestPriority) -> Self {
+        self.with_extension(priority)
+    }
+
     /// Remove a kind of `extension` for this request.
     pub fn remove_extension<T>(mut self) -> Self
     where
@@ -123,6 +351,51 @@ impl ErgoRequestBuilder {
         self.extensions.get_mut()
     }
 
+    /// Get a copy of the headers set on this request so far.
+    ///
+    /// Default headers of the client and cookies are added when sending, they are not included.
+    ///
+    /// # Notice
+    /// It fails with [`crate::Error::RequestNotCloneable`] if the body is a stream, and with the
+    /// build error if the request is invalid.
+    pub fn headers_ref(&self) -> crate::Result<HeaderMap> {
+        let request = self
+            .inner
+            .try_clone()
+            .ok_or(crate::Error::RequestNotCloneable)?
+            .build()?;
+        Ok(request.headers().to_owned())
+    }
+
+    /// Edit the headers set on this request so far with `f`.
+    ///
+    /// # Notice
+    /// It fails with the build error if the request is invalid.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let request = client
+    ///     .get("https://example.com")
+    ///     .header("x-debug", "1")
+    ///     .headers_mut(|headers| {
+    ///         headers.remove("x-debug");
+    ///     })
+    ///     .unwrap();
+    /// assert!(request.headers_ref().unwrap().is_empty());
+    /// ```
+    pub fn headers_mut<F>(mut self, f: F) -> crate::Result<Self>
+    where
+        F: FnOnce(&mut HeaderMap),
+    {
+        let (client, request) = self.inner.build_split();
+        let mut request = request?;
+        f(request.headers_mut());
+        self.inner = RequestBuilder::from_parts(client, request);
+        Ok(self)
+    }
+
     /// Get the inner [`RequestBuilder`]
     pub fn into_inner(self) -> RequestBuilder {
         self.inner
@@ -134,13 +407,42 @@ impl ErgoRequestBuilder {
         Self {
             inner: RequestBuilder::from_parts(client.to_owned(), request),
             cookie_store: None,
+            defaults: RequestDefaults::default(),
             url,
             retry_policy: None,
+            retry_options: RetryOptions::new(),
             max_redirect_times: 0,
+            redirect_mode: RedirectMode::default(),
+            redirect_policy: None,
+            redirect_cache: None,
+            referer_policy: RefererPolicy::default(),
+            redirect_observer: None,
+            redirect_methods: HashMap::new(),
+            redirect_max_drain: DEFAULT_MAX_DRAIN,
+            #[cfg(feature = "html-redirect")]
+            html_redirect: false,
             client,
-            client_middleware: Box::new([]),
+            client_middleware: vec![],
             request_middleware: vec![],
             extensions: http::Extensions::new(),
+            scheduler: None,
+            client_pool: ClientPool::shared(),
+            transport_options: None,
+            redactor: None,
+            deadline: None,
+            endpoint_pool: None,
+            expected_status: None,
+            required_content_type: None,
+            path_params: None,
+            idempotency_key: None,
+            cancellation_token: None,
+            max_response_bytes: None,
+            integrity_check: false,
+            multipart: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            upload_body: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            upload_progress: None,
         }
     }
 
@@ -156,6 +458,112 @@ impl ErgoRequestBuilder {
         self
     }
 
+    /// Send an `Idempotency-Key` header, so the server can tell retries of this request apart
+    /// from new requests.
+    ///
+    /// The key stays the same across retries and redirects, but [`IdempotencyKey::Auto`]
+    /// generates a new one each time the request is sent, like for each page of
+    /// [`Self::paginate`]. The key is inserted into the `Extensions` as a
+    /// [`SentIdempotencyKey`]. With a key, non-idempotent requests like `POST` are retried.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # use ergoreq::middleware::auto_retry_middleware::{IdempotencyKey, SentIdempotencyKey};
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new()).with_retry_count(3);
+    /// let response = client
+    ///     .post("https://api.stripe.com/v1/charges")
+    ///     .with_idempotency_key(IdempotencyKey::Auto)
+    ///     .send()
+    ///     .await?;
+    /// let key = response.extension::<SentIdempotencyKey>();
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_idempotency_key(mut self, key: IdempotencyKey) -> Self {
+        self.idempotency_key = Some(key);
+        self
+    }
+
+    /// Replace the `{name}` placeholders of the url path with the percent-encoded `params`
+    /// when this request is sent.
+    ///
+    /// A parameter is always a single path segment, `/` and other reserved characters are
+    /// encoded. Sending fails with [`crate::Error::UnresolvedPathParam`] if a placeholder has no
+    /// parameter. Calling this again adds more parameters.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let curl = client
+    ///     .get("https://api.github.com/repos/{owner}/{repo}")
+    ///     .with_path_params([("owner", "rust-lang"), ("repo", "rust")])
+    ///     .to_curl()
+    ///     .unwrap();
+    /// assert!(curl.contains("https://api.github.com/repos/rust-lang/rust"));
+    /// ```
+    pub fn with_path_params<I, K, V>(mut self, params: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: fmt::Display,
+    {
+        self.path_params
+            .get_or_insert_with(HashMap::new)
+            .extend(params.into_iter().map(|(k, v)| (k.into(), v.to_string())));
+        self
+    }
+
+    /// Configure this request with `f` if `condition` is `true`.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let token: Option<&str> = Some("secret");
+    /// let request = client
+    ///     .get("https://example.com")
+    ///     .when(token.is_some(), |b| b.bearer_auth(token.unwrap_or_default()))
+    ///     .apply(|b| b.header("x-trace", "abc"))
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(request.headers()["authorization"], "Bearer secret");
+    /// ```
+    pub fn when<F>(self, condition: bool, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        if condition {
+            f(self)
+        } else {
+            self
+        }
+    }
+
+    /// Configure this request with `f`, to share configuration between requests.
+    pub fn apply<F>(self, f: F) -> Self
+    where
+        F: FnOnce(Self) -> Self,
+    {
+        f(self)
+    }
+
+    /// Add a header like [`Self::header`] if `value` is `Some`.
+    pub fn header_opt<K, V>(self, key: K, value: Option<V>) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        match value {
+            Some(value) => self.header(key, value),
+            None => self,
+        }
+    }
+
     /// Set `retry_times` to this request
     ///
     /// If you don't want to retry, set this to `0`
@@ -186,6 +594,140 @@ impl ErgoRequestBuilder {
         self
     }
 
+    /// Set [`RetryOptions`] to this request
+    pub fn with_retry_options(mut self, retry_options: RetryOptions) -> Self {
+        self.retry_options = retry_options;
+        self
+    }
+
+    /// Set the response statuses to retry, see [`RetryOptions::with_retry_statuses`].
+    pub fn with_retry_statuses<I>(mut self, statuses: I) -> Self
+    where
+        I: IntoIterator<Item = StatusCode>,
+    {
+        self.retry_options = self.retry_options.with_retry_statuses(statuses);
+        self
+    }
+
+    /// Call `observer` before each retry, see [`RetryOptions::with_observer`].
+    pub fn with_retry_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(u32, &crate::Result<Response>, Duration) + Send + Sync + 'static,
+    {
+        self.retry_options = self.retry_options.with_observer(observer);
+        self
+    }
+
+    /// Whether to retry non-idempotent requests, see
+    /// [`RetryOptions::with_retry_non_idempotent`].
+    pub fn with_retry_non_idempotent(mut self, retry_non_idempotent: bool) -> Self {
+        self.retry_options = self
+            .retry_options
+            .with_retry_non_idempotent(retry_non_idempotent);
+        self
+    }
+
+    /// Fail over to other hosts after retries are exhausted, see
+    /// [`RetryOptions::with_fallback_hosts`].
+    ///
+    /// # Panics
+    /// Panics if a host is not a valid url.
+    pub fn with_fallback_hosts<I, S>(mut self, hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        self.retry_options = self.retry_options.with_fallback_hosts(hosts);
+        self
+    }
+
+    /// Send this request with a client of the [`ClientPool`] configured with `options`.
+    ///
+    /// # Notice
+    /// Pooled clients are built from the builder factory of the pool. Settings of the
+    /// `reqwest::Client` of `ErgoClient` like timeouts are only inherited if it is built with
+    /// [`ErgoClient::builder`].
+    pub fn with_transport_options(mut self, options: TransportOptions) -> Self {
+        self.transport_options = Some(options);
+        self
+    }
+
+    /// Fail with [`crate::Error::UnexpectedStatus`] if the status of the response, after every
+    /// middleware, is not `status`.
+    ///
+    /// Calling this again, or [`Self::expect_status_in`], expects more statuses.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # use http::StatusCode;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let response = client
+    ///     .post("https://example.com/items")
+    ///     .expect_status(StatusCode::CREATED)
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn expect_status(mut self, status: StatusCode) -> Self {
+        self.expected_status
+            .get_or_insert_with(ExpectedStatus::default)
+            .push(status);
+        self
+    }
+
+    /// Fail with [`crate::Error::UnexpectedStatus`] if the status of the response, after every
+    /// middleware, is not in `range`, like `400..500`.
+    pub fn expect_status_in<R: RangeBounds<u16>>(mut self, range: R) -> Self {
+        self.expected_status
+            .get_or_insert_with(ExpectedStatus::default)
+            .push_range(range);
+        self
+    }
+
+    /// Fail with [`crate::Error::UnexpectedContentType`] if the `Content-Type` of the response
+    /// is not `mime`, like `application/json` or `image/*`.
+    ///
+    /// Parameters like `charset` are ignored, and a structured syntax suffix matches its base
+    /// type, so `application/problem+json` matches `application/json`. Only success responses,
+    /// or responses of expected statuses (see [`Self::expect_status`]), are checked.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let response = client
+    ///     .get("https://example.com/logo")
+    ///     .require_content_type("image/*")
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn require_content_type(mut self, mime: &str) -> Self {
+        self.required_content_type = Some(mime.trim().to_ascii_lowercase());
+        self
+    }
+
+    /// Connect to `address` instead of resolving `host` for this request, in addition to the
+    /// overrides of the client.
+    ///
+    /// The `Host` header and the TLS server name are still those of the URL, see
+    /// [`TransportOptions::with_resolve`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_resolve(mut self, host: &str, address: SocketAddr) -> Self {
+        self.transport_options = Some(
+            self.transport_options
+                .take()
+                .unwrap_or_default()
+                .with_resolve(host, address),
+        );
+        self
+    }
+
     /// Set `max_redirect_times` to this request.
     ///
     /// If you don't want to redirect, set this to `0`
@@ -197,12 +739,145 @@ impl ErgoRequestBuilder {
         self
     }
 
+    /// Set a [`RedirectMode`] for this request, restricting which redirects are followed.
+    pub fn with_redirect_mode(mut self, mode: RedirectMode) -> Self {
+        self.redirect_mode = mode;
+        self
+    }
+
+    /// Set a [`RefererPolicy`] for this request, deciding the `Referer` header of redirect hops.
+    pub fn with_referer_policy(mut self, referer_policy: RefererPolicy) -> Self {
+        self.referer_policy = referer_policy;
+        self
+    }
+
+    /// Call `observer` with each redirect hop of this request before it is followed.
+    ///
+    /// Return `false` to stop following, the redirect response is returned.
+    pub fn with_redirect_observer<F>(mut self, observer: F) -> Self
+    where
+        F: Fn(&RedirectHop) -> bool + Send + Sync + 'static,
+    {
+        self.redirect_observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Follow redirects with `status` changing the method as `method`, for this request.
+    pub fn with_redirect_method(mut self, status: StatusCode, method: RedirectMethod) -> Self {
+        self.redirect_methods.insert(status, method);
+        self
+    }
+
+    /// Also follow `<meta http-equiv="refresh">` and `location.replace` redirects found in small
+    /// html bodies of this request.
+    ///
+    /// Only `text/html` responses with a `Content-Length` up to 16 KiB are searched.
+    #[cfg(feature = "html-redirect")]
+    pub fn with_html_redirect(mut self, html_redirect: bool) -> Self {
+        self.html_redirect = html_redirect;
+        self
+    }
+
+    /// Read at most `max_drain` bytes of each redirect body of this request before following it.
+    pub fn with_redirect_max_drain(mut self, max_drain: u64) -> Self {
+        self.redirect_max_drain = max_drain;
+        self
+    }
+
+    /// Set a [`RedirectPolicy`] for this request, deciding whether each redirect is followed.
+    ///
+    /// It only applies when `max_redirect_times` is not `0`.
+    pub fn with_redirect_policy<P>(mut self, policy: P) -> Self
+    where
+        P: RedirectPolicy,
+    {
+        self.redirect_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Set a deadline for this request, across all retries and redirect hops.
+    ///
+    /// The request fails with [`crate::Error::DeadlineExceeded`] when it is exceeded.
+    /// The [`crate::middleware::deadline_middleware::Deadline`] is inserted into the `Extensions` of this request.
+    pub fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Abort this request with [`crate::Error::Cancelled`] when `token` is cancelled, even while
+    /// waiting for a retry or following a redirect.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::{CancellationToken, ErgoClient};
+    /// # async fn run() {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let token = CancellationToken::new();
+    /// let request = client
+    ///     .get("https://example.com/slow")
+    ///     .with_cancellation_token(token.clone())
+    ///     .send();
+    /// token.cancel();
+    /// assert!(matches!(request.await, Err(ergoreq::Error::Cancelled)));
+    /// # }
+    /// ```
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// Limit the body of the response of this request to `max_bytes`.
+    ///
+    /// Reading a larger body fails with [`crate::Error::BodyTooLarge`], as soon as the limit is
+    /// exceeded while streaming, or when sending if the `Content-Length` is already larger.
+    pub fn with_max_response_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_response_bytes = Some(max_bytes);
+        self
+    }
+
+    /// Fail reading the response body of this request with [`crate::Error::TruncatedBody`] if
+    /// it ends before its `Content-Length`, or if the connection or the decompression breaks
+    /// midway.
+    ///
+    /// Chunked bodies have no expected size, only breaks are detected.
+    pub fn with_integrity_check(mut self, integrity_check: bool) -> Self {
+        self.integrity_check = integrity_check;
+        self
+    }
+
     /// See [`RequestBuilder::headers`]
     pub fn headers(mut self, headers: HeaderMap) -> Self {
         self.inner = self.inner.headers(headers);
         self
     }
 
+    /// Set the `Accept` header, replacing any value set before, like `text/html, */*;q=0.8`.
+    pub fn accept(self, mime: &str) -> Self {
+        self.replace_header(http::header::ACCEPT, mime)
+    }
+
+    /// Set the `Accept` header to `application/json`.
+    pub fn accept_json(self) -> Self {
+        self.accept("application/json")
+    }
+
+    /// Set the `Content-Type` header, replacing any value set before.
+    ///
+    /// Body methods like [`Self::json`] keep this content type, so
+    /// `.content_type("application/merge-patch+json").json(&patch)` sends a JSON merge patch.
+    pub fn content_type(self, mime: &str) -> Self {
+        self.replace_header(http::header::CONTENT_TYPE, mime)
+    }
+
+    /// Replace the values of header `name` with `value`.
+    fn replace_header(self, name: HeaderName, value: &str) -> Self {
+        match HeaderValue::from_str(value) {
+            Ok(value) => self.headers(HeaderMap::from_iter([(name, value)])),
+            // fail when the request is built
+            Err(_) => self.header(name, value),
+        }
+    }
+
     /// See [`RequestBuilder::basic_auth`]
     pub fn basic_auth<U, P>(mut self, username: U, password: Option<P>) -> Self
     where
@@ -228,6 +903,75 @@ impl ErgoRequestBuilder {
         self
     }
 
+    /// Produce the body with an async closure, called again for each retry or redirect.
+    ///
+    /// Unlike [`Self::body`], a request with a streamed body (like a file upload) can then be
+    /// retried. The body set by other methods is replaced.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::new(reqwest::Client::new()).with_retry_count(3);
+    /// let request = client.put("https://example.com/upload").body_factory(|| async {
+    ///     let chunks = vec![Ok::<_, std::io::Error>("large "), Ok("payload")];
+    ///     Ok(reqwest::Body::wrap_stream(futures::stream::iter(chunks)))
+    /// });
+    /// ```
+    pub fn body_factory<F, Fut>(mut self, factory: F) -> Self
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = crate::Result<Body>> + Send + 'static,
+    {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.upload_body = None;
+        }
+        self.multipart = None;
+        self.with_extension(BodyFactory::new(factory))
+    }
+
+    /// Stream the body from `reader` of `len` bytes, like an opened file.
+    ///
+    /// The reader is rewound for each retry or redirect, so the request can be retried. The
+    /// body set by other methods is replaced.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new()).with_retry_count(3);
+    /// let file = tokio::fs::File::open("video.mp4").await?;
+    /// let len = file.metadata().await?.len();
+    /// client
+    ///     .put("https://example.com/upload")
+    ///     .body_async_read(file, len)
+    ///     .with_upload_progress(|v| println!("{}/{}", v.uploaded, v.total))
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn body_async_read<R>(mut self, reader: R, len: u64) -> Self
+    where
+        R: tokio::io::AsyncRead + tokio::io::AsyncSeek + Unpin + Send + 'static,
+    {
+        self.extensions.remove::<BodyFactory>();
+        self.multipart = None;
+        self.upload_body = Some(AsyncReadBody::new(reader, len));
+        self.header(http::header::CONTENT_LENGTH, len)
+    }
+
+    /// Call `callback` whenever a chunk of the body set by [`Self::body_async_read`] is sent.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_upload_progress<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(UploadProgress) + Send + Sync + 'static,
+    {
+        self.upload_progress = Some(Arc::new(callback));
+        self
+    }
+
     /// See [`RequestBuilder::timeout`]
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.inner = self.inner.timeout(timeout);
@@ -240,7 +984,37 @@ impl ErgoRequestBuilder {
         self
     }
 
+    /// Append a single `key=value` pair to the query string.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::ErgoClient;
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let page: Option<u32> = None;
+    /// let request = client
+    ///     .get("https://example.com/search")
+    ///     .query_param("q", "rust")
+    ///     .query_param_opt("page", page)
+    ///     .header_opt("x-trace", Some("abc"))
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(request.url().as_str(), "https://example.com/search?q=rust");
+    /// assert_eq!(request.headers()["x-trace"], "abc");
+    /// ```
+    pub fn query_param<K: Serialize, V: Serialize>(self, key: K, value: V) -> Self {
+        self.query(&[(key, value)])
+    }
+
+    /// Append a single `key=value` pair to the query string if `value` is `Some`.
+    pub fn query_param_opt<K: Serialize, V: Serialize>(self, key: K, value: Option<V>) -> Self {
+        match value {
+            Some(value) => self.query_param(key, value),
+            None => self,
+        }
+    }
+
     /// See [`RequestBuilder::version`]
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn version(mut self, version: Version) -> Self {
         self.inner = self.inner.version(version);
         self
@@ -258,6 +1032,53 @@ impl ErgoRequestBuilder {
         self
     }
 
+    /// Send `value` as a MessagePack body, with the `application/msgpack` content type.
+    ///
+    /// # Notice
+    /// Requires the `msgpack` feature.
+    #[cfg(feature = "msgpack")]
+    pub fn msgpack<T: Serialize + ?Sized>(self, value: &T) -> Self {
+        self.encoded_body(
+            crate::utils::msgpack::CONTENT_TYPE,
+            crate::utils::msgpack::to_vec(value),
+        )
+    }
+
+    /// Send `value` as a CBOR body, with the `application/cbor` content type.
+    ///
+    /// # Notice
+    /// Requires the `cbor` feature.
+    #[cfg(feature = "cbor")]
+    pub fn cbor<T: Serialize + ?Sized>(self, value: &T) -> Self {
+        self.encoded_body(
+            crate::utils::cbor::CONTENT_TYPE,
+            crate::utils::cbor::to_vec(value),
+        )
+    }
+
+    /// Set an encoded body with its content type, or fail the request when it is built like
+    /// [`Self::json`] does if encoding failed.
+    #[cfg(any(feature = "msgpack", feature = "cbor"))]
+    fn encoded_body(mut self, content_type: &'static str, body: crate::Result<Vec<u8>>) -> Self {
+        /// Serializing always fails with the message, so `reqwest` records the error.
+        struct EncodeError(String);
+
+        impl Serialize for EncodeError {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom(&self.0))
+            }
+        }
+
+        self.inner = match body {
+            Ok(body) => self
+                .inner
+                .header(http::header::CONTENT_TYPE, content_type)
+                .body(body),
+            Err(e) => self.inner.json(&EncodeError(e.to_string())),
+        };
+        self
+    }
+
     /// See [`RequestBuilder::fetch_mode_no_cors`]
     pub fn fetch_mode_no_cors(mut self) -> Self {
         self.inner = self.inner.fetch_mode_no_cors();
@@ -275,98 +1096,721 @@ impl ErgoRequestBuilder {
         self
     }
 
-    /// See [`RequestBuilder::build`]
-    pub fn build(self) -> reqwest::Result<Request> {
-        let mut build_result = self.inner.build()?;
-        if let Some(cookie_store) = self.cookie_store {
-            let url = build_result.url();
-            let cookie_header = cookie_store.to_header_value(url);
+    /// Send `form` as a `multipart/form-data` body, encoded again for each retry or redirect.
+    ///
+    /// Unlike [`Self::multipart`], the request can be retried and redirected with its body. The
+    /// body set by other methods is replaced.
+    pub fn multipart_form(mut self, form: ErgoMultipart) -> Self {
+        self.extensions.remove::<BodyFactory>();
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.upload_body = None;
+        }
+        self.multipart = Some(form);
+        self
+    }
+
+    /// Add a text field to the form set by [`Self::multipart_form`], creating it if not set.
+    pub fn multipart_text<N: Into<String>, V: Into<String>>(self, name: N, value: V) -> Self {
+        let form = self.multipart.to_owned().unwrap_or_default();
+        self.multipart_form(form.text(name, value))
+    }
+
+    /// Add a file read from `path` to the form set by [`Self::multipart_form`], creating it if
+    /// not set. The file name and mime type come from `path`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new()).with_retry_count(3);
+    /// client
+    ///     .put("https://example.com/upload")
+    ///     .multipart_text("title", "holiday")
+    ///     .multipart_file("photo", "photo.jpg")
+    ///     .send()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn multipart_file<N, P>(self, name: N, path: P) -> Self
+    where
+        N: Into<String>,
+        P: Into<std::path::PathBuf>,
+    {
+        let form = self.multipart.to_owned().unwrap_or_default();
+        self.multipart_form(form.file(name, path))
+    }
+
+    /// Add a JSON field to the form set by [`Self::multipart_form`], creating it if not set.
+    pub fn multipart_json<N: Into<String>, T: Serialize + ?Sized>(
+        self,
+        name: N,
+        value: &T,
+    ) -> Self {
+        let form = self.multipart.to_owned().unwrap_or_default();
+        self.multipart_form(form.json(name, value))
+    }
+
+    /// Set `Cookie` header from the `cookie_store` of this request.
+    fn apply_cookie_header(
+        cookie_store: Option<&Arc<dyn CookieContainer + 'static>>,
+        request: &mut Request,
+    ) {
+        if let Some(cookie_store) = cookie_store {
+            let cookie_header = cookie_store.to_header_value(request.url());
             if let Ok(cookie_header) = HeaderValue::from_str(&cookie_header.join("; ")) {
-                let headers = build_result.headers_mut();
-                headers.insert(http::header::COOKIE, cookie_header);
+                request
+                    .headers_mut()
+                    .insert(http::header::COOKIE, cookie_header);
             }
-
-            Ok(build_result)
-        } else {
-            Ok(build_result)
         }
     }
 
+    /// See [`RequestBuilder::build`]
+    pub fn build(self) -> reqwest::Result<Request> {
+        let mut build_result = self.inner.build()?;
+        self.defaults.apply(&mut build_result);
+        Self::apply_cookie_header(self.cookie_store.as_ref(), &mut build_result);
+        Ok(build_result)
+    }
+
     /// See [`RequestBuilder::build_split`]
     pub fn build_split(self) -> (ErgoClient, reqwest::Result<Request>) {
         let (client, build_result) = self.inner.build_split();
-        if let Ok(mut build_result) = build_result {
-            if let Some(cookie_store) = self.cookie_store {
-                let url = build_result.url();
-                let cookie_header = cookie_store.to_header_value(url);
-                if let Ok(cookie_header) = HeaderValue::from_str(&cookie_header.join("; ")) {
-                    let headers = build_result.headers_mut();
-                    headers.insert(http::header::COOKIE, cookie_header);
-                }
+        let build_result = build_result.map(|mut request| {
+            self.defaults.apply(&mut request);
+            Self::apply_cookie_header(self.cookie_store.as_ref(), &mut request);
+            request
+        });
+        (ErgoClient::new(client), build_result)
+    }
 
-                (ErgoClient::new(client), Ok(build_result))
-            } else {
-                (ErgoClient::new(client), Ok(build_result))
-            }
-        } else {
-            (ErgoClient::new(client), build_result)
+    /// Render this request as a copy-pasteable `curl` command, without sending it.
+    ///
+    /// Cookies from the `cookie_store` of this request and the default headers of the
+    /// [`ErgoClient`] are included.
+    /// See [`crate::utils::curl::request_to_curl`] for details.
+    ///
+    /// # Notice
+    /// Headers configured with `reqwest::ClientBuilder::default_headers` cannot be rendered,
+    /// `reqwest::Client` adds them while sending and does not expose them.
+    ///
+    /// # Error
+    /// [`crate::Error::RequestNotCloneable`] is returned if `body` of this request is `stream`.
+    pub fn to_curl(&self) -> crate::error::Result<String> {
+        let mut request = self
+            .inner
+            .try_clone()
+            .ok_or(crate::Error::RequestNotCloneable)?
+            .build()?;
+        if let Some(params) = &self.path_params {
+            expand_path_params(request.url_mut(), params)?;
         }
+        self.defaults.apply(&mut request);
+        Self::apply_cookie_header(self.cookie_store.as_ref(), &mut request);
+        Ok(request_to_curl(&request))
     }
 
     /// See [`RequestBuilder::send`]
     ///
     /// Please notice that this method returns `ergoreq::error::Result` instead of
-    /// `reqwest::Error`
+    /// `reqwest::Error`, and the response is an [`ErgoResponse`] carrying the `Extensions`
+    /// written by middlewares.
     #[instrument(skip(self))]
-    pub fn send(self) -> impl Future<Output = crate::error::Result<Response>> {
+    pub fn send(self) -> impl Future<Output = crate::error::Result<ErgoResponse>> {
         async move {
             let mut my_self = self;
-            my_self
-                .request_middleware
-                .splice(0..0, my_self.client_middleware.iter().map(|v| v.to_owned()));
-
-            // judge if insert AutoRedirect middleware is needed
-            if my_self.max_redirect_times > 0 {
-                let redirect_middleware =
-                    AutoRedirectMiddleware::new(my_self.max_redirect_times.into());
-                my_self
-                    .request_middleware
-                    .push(Arc::new(redirect_middleware));
-            }
+            let mut timings = RequestTimings::new(Instant::now());
+
+            // wait for a running slot if a priority scheduler is set
+            let _permit = match my_self.scheduler.take() {
+                Some(scheduler) => {
+                    let priority = my_self
+                        .extensions
+                        .get::<RequestPriority>()
+                        .copied()
+                        .unwrap_or_default();
+                    Some(scheduler.acquire(priority).await)
+                }
+                None => None,
+            };
+            timings.dequeued();
+            my_self.extensions.insert(timings);
 
-            // judge if insert AutoRetry middleware is needed
-            if let Some(policy) = my_self.retry_policy {
-                let retry_middleware = AutoRetryMiddleware::new(policy);
-                my_self.request_middleware.push(Arc::new(retry_middleware))
+            // send through the pooled client of the transport options
+            if let Some(options) = &my_self.transport_options {
+                my_self.client = my_self.client_pool.client_for(options)?;
             }
 
+            let middlewares = my_self.middleware_stack();
+
             let next = Next::new(
                 &my_self.client,
-                &my_self.request_middleware,
+                &middlewares,
                 my_self.cookie_store,
+                Some(my_self.client_pool),
+                my_self.redactor,
+                my_self.max_response_bytes,
             );
-            let result = next
-                .run(my_self.inner.build()?, &mut my_self.extensions)
-                .await
-                .map_err(crate::error::Error::from)?;
-            Ok(result)
+            let mut request = my_self.inner.build()?;
+            if let Some(params) = &my_self.path_params {
+                expand_path_params(request.url_mut(), params)?;
+            }
+            my_self.defaults.apply(&mut request);
+            if let Some(key) = &my_self.idempotency_key {
+                let key = key.resolve();
+                request.headers_mut().insert(
+                    IDEMPOTENCY_KEY,
+                    HeaderValue::from_str(&key).map_err(http::Error::from)?,
+                );
+                my_self.extensions.insert(SentIdempotencyKey(key));
+            }
+            if let Some(form) = my_self.multipart.take() {
+                let headers = request.headers_mut();
+                headers.insert(
+                    http::header::CONTENT_TYPE,
+                    HeaderValue::from_str(&form.content_type()).map_err(http::Error::from)?,
+                );
+                headers.insert(
+                    http::header::CONTENT_LENGTH,
+                    HeaderValue::from(form.content_length().await?),
+                );
+                my_self.extensions.insert(form.into_factory()?);
+            }
+            #[cfg(not(target_arch = "wasm32"))]
+            if let Some(upload_body) = my_self.upload_body.take() {
+                let factory = upload_body.into_factory(my_self.upload_progress.take());
+                my_self.extensions.insert(factory);
+            }
+            if let Some(factory) = my_self.extensions.get::<BodyFactory>() {
+                *request.body_mut() = Some(factory.make().await?);
+            }
+
+            // select the endpoint before running middlewares
+            let endpoint = my_self.endpoint_pool.as_ref().and_then(|v| v.select());
+            if let Some(endpoint) = &endpoint {
+                replace_origin(request.url_mut(), endpoint.origin());
+                my_self
+                    .extensions
+                    .insert(SelectedEndpoint(endpoint.origin().to_owned()));
+            }
+
+            let method = request.method().to_owned();
+            let result = next.run(request, &mut my_self.extensions).await;
+            if let Some(endpoint) = endpoint {
+                endpoint.finish(matches!(&result, Ok(v) if !v.status().is_server_error()));
+            }
+            let redirects = my_self
+                .extensions
+                .get::<RedirectChain>()
+                .map(|v| v.0.len())
+                .unwrap_or_default();
+            if let Some(timings) = my_self.extensions.get_mut::<RequestTimings>() {
+                timings.finish(redirects);
+            }
+            let result = result?;
+            let checked = my_self.expected_status.is_some() || result.status().is_success();
+            if let Some(expected) = my_self.expected_status {
+                if !expected.contains(result.status()) {
+                    return Err(unexpected_status(result).await);
+                }
+            }
+            if let Some(expected) = my_self.required_content_type.filter(|_| checked) {
+                let actual = result
+                    .headers()
+                    .get(http::header::CONTENT_TYPE)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_owned);
+                if !actual
+                    .as_deref()
+                    .is_some_and(|v| content_type_matches(&expected, v))
+                {
+                    return Err(crate::Error::UnexpectedContentType {
+                        expected,
+                        actual,
+                        status: result.status(),
+                    });
+                }
+            }
+            // responses of middlewares answering without the transport are limited here
+            let result = match my_self.max_response_bytes {
+                Some(max_bytes) => limit_body(result, max_bytes)?,
+                None => result,
+            };
+            let result = match my_self.integrity_check {
+                true => verify_body(result, &method),
+                false => result,
+            };
+            Ok(ErgoResponse::new(result, my_self.extensions))
         }
     }
 
+    /// Run this request through its middlewares without sending it, returns the request which
+    /// would be sent, with cookies, default headers, signatures and other changes of middlewares.
+    ///
+    /// Middlewares see an empty `200 OK` response, which is not checked against the expected
+    /// status, the required content type, the body size limit or the integrity check. `None` is
+    /// returned if a middleware answers without reaching the transport, like a mock or a cache.
+    ///
+    /// # Notice
+    /// Middlewares keeping state (rate limits, circuit breakers, token refreshes) still run, and
+    /// cookies of the response are stored as usual.
+    ///
+    /// # Example
+    /// ```
+    /// # use ergoreq::ErgoClient;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let request = client
+    ///     .get("https://example.com")
+    ///     .bearer_auth("token")
+    ///     .dry_run()
+    ///     .await?
+    ///     .unwrap();
+    /// assert_eq!(request.headers()["authorization"], "Bearer token");
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn dry_run(mut self) -> crate::error::Result<Option<Request>> {
+        let captured = Arc::new(Mutex::new(None));
+        // the synthetic response is not checked
+        self.expected_status = None;
+        self.required_content_type = None;
+        self.max_response_bytes = None;
+        self.integrity_check = false;
+        self.with_middleware_phase(
+            DryRunMiddleware::new(captured.to_owned()),
+            MiddlewarePhase::PostRetry,
+        )
+        .send()
+        .await?;
+        let request = captured.lock().unwrap().take();
+        Ok(request)
+    }
+
+    /// Get every middleware this request will run through, in execution order.
+    fn middleware_stack(&self) -> Vec<Arc<dyn Middleware>> {
+        let phased = |phase: MiddlewarePhase| {
+            self.client_middleware
+                .iter()
+                .chain(self.request_middleware.iter())
+                .filter(move |(v, _)| *v == phase)
+                .map(|(_, v)| v.to_owned())
+        };
+        let mut middlewares: Vec<Arc<dyn Middleware>> = vec![];
+
+        // cancellation and the deadline cover every middleware
+        if let Some(token) = &self.cancellation_token {
+            middlewares.push(Arc::new(CancellationMiddleware::new(token.to_owned())));
+        }
+        if let Some(deadline) = self.deadline {
+            middlewares.push(Arc::new(DeadlineMiddleware::new(deadline)));
+        }
+        middlewares.extend(phased(MiddlewarePhase::PreRedirect));
+
+        // judge if insert AutoRedirect middleware is needed
+        if self.max_redirect_times > 0 {
+            let redirect_middleware = AutoRedirectMiddleware::new(
+                self.max_redirect_times.into(),
+                self.redirect_mode,
+                self.redirect_policy.to_owned(),
+            )
+            .with_cache(self.redirect_cache.to_owned())
+            .with_referer_policy(self.referer_policy)
+            .with_observer(self.redirect_observer.to_owned())
+            .with_methods(self.redirect_methods.to_owned())
+            .with_max_drain(self.redirect_max_drain);
+            #[cfg(feature = "html-redirect")]
+            let redirect_middleware = redirect_middleware.with_html_redirect(self.html_redirect);
+            middlewares.push(Arc::new(redirect_middleware));
+        }
+        middlewares.extend(phased(MiddlewarePhase::PreRetry));
+
+        // judge if insert AutoRetry middleware is needed
+        if let Some(policy) = &self.retry_policy {
+            let retry_middleware =
+                AutoRetryMiddleware::new(policy.to_owned(), self.retry_options.to_owned());
+            middlewares.push(Arc::new(retry_middleware))
+        }
+        middlewares.extend(phased(MiddlewarePhase::PostRetry));
+        middlewares
+    }
+
+    /// Get the names of middlewares this request will run through, in execution order.
+    ///
+    /// Middlewares are ordered by [`MiddlewarePhase`], global middlewares come before request
+    /// middlewares within a phase.
+    pub fn middlewares(&self) -> Vec<&'static str> {
+        self.middleware_stack().iter().map(|v| v.name()).collect()
+    }
+
+    /// Send this request and deserialize the JSON body of the response.
+    ///
+    /// The `Accept` header defaults to `application/json`. A non-success status fails with
+    /// [`crate::Error::UnexpectedStatus`] unless [`Self::expect_status`] is set, a response
+    /// which is not JSON with [`crate::Error::UnexpectedContentType`] unless
+    /// [`Self::require_content_type`] is set, and an invalid body with
+    /// [`crate::Error::Deserialize`] telling the path of the invalid value, like `items[2].id`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let repo = client
+    ///     .get("https://api.github.com/repos/rust-lang/rust")
+    ///     .send_json::<serde_json::Value>()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn send_json<T: DeserializeOwned>(mut self) -> crate::error::Result<T> {
+        self.defaults.set_header(
+            http::header::ACCEPT,
+            HeaderValue::from_static("application/json"),
+        );
+        self.required_content_type
+            .get_or_insert_with(|| "application/json".to_owned());
+        self.send_checked().await?.json_detailed().await
+    }
+
+    /// Send this request and decode the body of the response as a `T` in the format the server
+    /// chose among `formats`, returning which one was used.
+    ///
+    /// The `Accept` header defaults to `formats` with decreasing quality values, so the first
+    /// format is preferred. A non-success status fails like [`Self::send_json`], and a response
+    /// in none of `formats` with [`crate::Error::UnexpectedContentType`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # use ergoreq::wrappers::negotiate::BodyFormat;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let negotiated = client
+    ///     .get("https://example.com/items/1")
+    ///     .negotiate::<serde_json::Value>(&[BodyFormat::Json])
+    ///     .await?;
+    /// println!("{:?}: {}", negotiated.format, negotiated.value);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn negotiate<T: DeserializeOwned>(
+        mut self,
+        formats: &[BodyFormat],
+    ) -> crate::error::Result<Negotiated<T>> {
+        let accept = accept_header(formats);
+        self.defaults.set_header(
+            http::header::ACCEPT,
+            HeaderValue::from_str(&accept).map_err(http::Error::from)?,
+        );
+        let response = self.send_checked().await?;
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_owned);
+        let body = response.bytes().await?;
+        content_type
+            .as_deref()
+            .and_then(|v| decode_negotiated(formats, status, v, &body))
+            .unwrap_or(Err(crate::Error::UnexpectedContentType {
+                expected: accept,
+                actual: content_type,
+                status,
+            }))
+    }
+
+    /// Send this request and decode the MessagePack body of the response.
+    ///
+    /// The `Accept` header defaults to `application/msgpack`, and a non-success status fails
+    /// like [`Self::send_json`].
+    ///
+    /// # Notice
+    /// Requires the `msgpack` feature.
+    #[cfg(feature = "msgpack")]
+    pub async fn send_msgpack<T: DeserializeOwned>(mut self) -> crate::error::Result<T> {
+        self.defaults.set_header(
+            http::header::ACCEPT,
+            HeaderValue::from_static(crate::utils::msgpack::CONTENT_TYPE),
+        );
+        self.send_checked().await?.msgpack().await
+    }
+
+    /// Send this request and decode the CBOR body of the response.
+    ///
+    /// The `Accept` header defaults to `application/cbor`, and a non-success status fails like
+    /// [`Self::send_json`].
+    ///
+    /// # Notice
+    /// Requires the `cbor` feature.
+    #[cfg(feature = "cbor")]
+    pub async fn send_cbor<T: DeserializeOwned>(mut self) -> crate::error::Result<T> {
+        self.defaults.set_header(
+            http::header::ACCEPT,
+            HeaderValue::from_static(crate::utils::cbor::CONTENT_TYPE),
+        );
+        self.send_checked().await?.cbor().await
+    }
+
+    /// Send this request and read the body of the response as text.
+    ///
+    /// A non-success status fails like [`Self::send_json`].
+    pub async fn send_text(self) -> crate::error::Result<String> {
+        Ok(self.send_checked().await?.text().await?)
+    }
+
+    /// Send this request and read the body of the response.
+    ///
+    /// A non-success status fails like [`Self::send_json`].
+    pub async fn send_bytes(self) -> crate::error::Result<bytes::Bytes> {
+        Ok(self.send_checked().await?.bytes().await?)
+    }
+
+    /// Send this request, failing on a non-success status unless expected statuses are set.
+    async fn send_checked(self) -> crate::error::Result<ErgoResponse> {
+        if self.expected_status.is_some() {
+            return self.send().await;
+        }
+        self.send().await?.error_for_status_with_body().await
+    }
+
+    /// Subscribe to the `text/event-stream` of this request, yielding the parsed events.
+    ///
+    /// When the connection ends or breaks, it is opened again with the `Last-Event-ID` header
+    /// as long as the retry policy of this request allows, waiting for the `retry` time sent by
+    /// the server if any. Without a retry policy, the stream ends with the connection. A
+    /// non-success status or `204 No Content` ends the stream without reconnecting.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # use futures::TryStreamExt;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new()).with_retry_count(10);
+    /// let events = client.get("https://example.com/events").send_sse();
+    /// futures::pin_mut!(events);
+    /// while let Some(event) = events.try_next().await? {
+    ///     println!("{}: {}", event.event, event.data);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Notice
+    /// The request is cloned for every connection, so `body` of this request should not be
+    /// `stream`.
+    pub fn send_sse(mut self) -> impl Stream<Item = crate::error::Result<SseEvent>> {
+        self.defaults.set_header(
+            http::header::ACCEPT,
+            HeaderValue::from_static("text/event-stream"),
+        );
+        let retry_policy = self.retry_policy.to_owned();
+        subscribe(self, retry_policy)
+    }
+
+    /// Open a WebSocket connection with the upgrade handshake of this request.
+    ///
+    /// The handshake runs through the middlewares like any request, so cookies, auth headers and
+    /// redirects apply to it. A `ws://` or `wss://` url is sent as `http://` or `https://`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # use ergoreq::wrappers::websocket::WebSocketMessage;
+    /// # use futures::{SinkExt, TryStreamExt};
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let mut socket = client.get("wss://example.com/chat").upgrade_websocket().await?;
+    /// socket.send(WebSocketMessage::Text("hello".to_owned())).await?;
+    /// while let Some(message) = socket.try_next().await? {
+    ///     println!("{:?}", message);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Notice
+    /// Requires the `websocket` feature.
+    #[cfg(all(feature = "websocket", not(target_arch = "wasm32")))]
+    pub async fn upgrade_websocket(self) -> crate::error::Result<ErgoWebSocket> {
+        upgrade(self).await
+    }
+
+    /// Send this request again and again, yielding each response.
+    ///
+    /// The next request is sent when the next item is polled, after the interval of
+    /// `options`, or after a backoff delay if the previous request failed. A cursor extracted
+    /// from each response can be added to the next request. The stream ends when the error
+    /// backoff of `options` gives up.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # use ergoreq::wrappers::long_poll::LongPollOptions;
+    /// # use futures::StreamExt;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let responses = client
+    ///     .get("https://example.com/updates")
+    ///     .long_poll(LongPollOptions::new().with_etag());
+    /// futures::pin_mut!(responses);
+    /// while let Some(response) = responses.next().await {
+    ///     if let Ok(response) = response {
+    ///         println!("{}", response.text().await?);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Notice
+    /// The request is cloned for every poll, so `body` of this request should not be `stream`.
+    pub fn long_poll(
+        self,
+        options: LongPollOptions,
+    ) -> impl Stream<Item = crate::error::Result<ErgoResponse>> {
+        long_poll(self, options)
+    }
+
+    /// Fetch pages one by one, the url of each next page is decided by `strategy`.
+    ///
+    /// Every page runs through the middlewares, and a non-success status ends the stream with
+    /// [`crate::Error::UnexpectedStatus`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # use ergoreq::wrappers::pagination::LinkHeader;
+    /// # use futures::TryStreamExt;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let pages = client
+    ///     .get("https://api.github.com/repos/rust-lang/rust/issues")
+    ///     .paginate(LinkHeader);
+    /// futures::pin_mut!(pages);
+    /// while let Some(page) = pages.try_next().await? {
+    ///     let issues = page.json::<Vec<serde_json::Value>>()?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Notice
+    /// The request is cloned for every page, so `body` of this request should not be `stream`.
+    pub fn paginate<S>(self, strategy: S) -> impl Stream<Item = crate::error::Result<Page>>
+    where
+        S: PaginationStrategy,
+    {
+        paginate(self, Arc::new(strategy))
+    }
+
+    /// Fetch pages like [`Self::paginate`], and yield the items of the JSON array at `pointer`
+    /// of each page, like `/data`.
+    ///
+    /// An empty `pointer` reads the whole body as the array. An invalid item fails with
+    /// [`crate::Error::Deserialize`], its path is relative to the array.
+    pub fn paginate_items<T, S>(
+        self,
+        strategy: S,
+        pointer: &str,
+    ) -> impl Stream<Item = crate::error::Result<T>>
+    where
+        T: DeserializeOwned,
+        S: PaginationStrategy,
+    {
+        let pointer = pointer.to_owned();
+        self.paginate(strategy)
+            .map_ok(move |page| {
+                let items = page.json::<serde_json::Value>().and_then(|mut v| {
+                    let items = v.pointer_mut(&pointer).map(|v| v.take());
+                    // serialized again, so the error tells the path of the invalid item
+                    let items = serde_json::to_vec(&items.unwrap_or_default())
+                        .map_err(|e| crate::Error::Internal(Box::new(e)))?;
+                    deserialize_detailed::<Vec<T>>(page.status, &items)
+                });
+                match items {
+                    Ok(items) => futures::stream::iter(items.into_iter().map(Ok)).left_stream(),
+                    Err(e) => futures::stream::once(async { Err(e) }).right_stream(),
+                }
+            })
+            .try_flatten()
+    }
+
+    /// Send this request to `url` instead.
+    pub(crate) fn with_url(mut self, url: Url) -> crate::error::Result<Self> {
+        let (client, request) = self.inner.build_split();
+        let mut request = request?;
+        self.url = url.to_string();
+        *request.url_mut() = url;
+        self.inner = RequestBuilder::from_parts(client, request);
+        Ok(self)
+    }
+
+    /// Get the url this request is sent to, without default query parameters.
+    pub(crate) fn request_url(&self) -> crate::error::Result<Url> {
+        let request = self
+            .inner
+            .try_clone()
+            .ok_or(crate::Error::RequestNotCloneable)?
+            .build()?;
+        Ok(request.url().to_owned())
+    }
+
     /// See [`RequestBuilder::try_clone`]
     ///
     /// Please notice that this method returns `ErgoRequestBuilder` instead of `reqwest::RequestBuilder`
     pub fn try_clone(&self) -> Option<Self> {
-        self.inner.try_clone().and_then(|v| {
-            Some(ErgoRequestBuilder::new(
+        self.inner.try_clone().map(|v| {
+            let mut builder = ErgoRequestBuilder::new(
                 v,
                 self.cookie_store.to_owned(),
                 self.url.to_owned(),
                 self.client.to_owned(),
                 self.max_redirect_times,
                 self.retry_policy.to_owned(),
-                self.client_middleware.to_owned(),
-            ))
+                Box::new([]),
+            );
+            builder.defaults = self.defaults.to_owned();
+            builder.client_middleware = self.client_middleware.to_owned();
+            builder.request_middleware = self.request_middleware.to_owned();
+            builder.extensions = self.extensions.to_owned();
+            builder.scheduler = self.scheduler.to_owned();
+            builder.client_pool = self.client_pool.to_owned();
+            builder.transport_options = self.transport_options.to_owned();
+            builder.redactor = self.redactor.to_owned();
+            builder.retry_options = self.retry_options.to_owned();
+            builder.deadline = self.deadline;
+            builder.endpoint_pool = self.endpoint_pool.to_owned();
+            builder.expected_status = self.expected_status.to_owned();
+            builder.required_content_type = self.required_content_type.to_owned();
+            builder.path_params = self.path_params.to_owned();
+            builder.idempotency_key = self.idempotency_key.to_owned();
+            builder.cancellation_token = self.cancellation_token.to_owned();
+            builder.max_response_bytes = self.max_response_bytes;
+            builder.integrity_check = self.integrity_check;
+            builder.multipart = self.multipart.to_owned();
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                builder.upload_body = self.upload_body.to_owned();
+                builder.upload_progress = self.upload_progress.to_owned();
+            }
+            builder.redirect_mode = self.redirect_mode;
+            builder.redirect_policy = self.redirect_policy.to_owned();
+            builder.redirect_cache = self.redirect_cache.to_owned();
+            builder.referer_policy = self.referer_policy;
+            builder.redirect_observer = self.redirect_observer.to_owned();
+            builder.redirect_methods = self.redirect_methods.to_owned();
+            builder.redirect_max_drain = self.redirect_max_drain;
+            #[cfg(feature = "html-redirect")]
+            {
+                builder.html_redirect = self.html_redirect;
+            }
+            builder
         })
     }
 
@@ -375,3 +1819,97 @@ impl ErgoRequestBuilder {
         self.cookie_store.to_owned()
     }
 }
+
+/// Whether the `Content-Type` `actual` is the lowercase mime `expected`, ignoring parameters.
+///
+/// `*` matches any type or subtype, and a structured syntax suffix matches its base type, so
+/// `application/problem+json` is `application/json`.
+pub(crate) fn content_type_matches(expected: &str, actual: &str) -> bool {
+    let actual = actual.split(';').next().unwrap_or_default().trim();
+    let actual = actual.to_ascii_lowercase();
+    let (Some((expected_type, expected_subtype)), Some((actual_type, actual_subtype))) =
+        (expected.split_once('/'), actual.split_once('/'))
+    else {
+        return false;
+    };
+    let suffix = actual_subtype.rsplit_once('+').map(|(_, v)| v);
+    (expected_type == "*" || expected_type == actual_type)
+        && (expected_subtype == "*"
+            || expected_subtype == actual_subtype
+            || suffix == Some(expected_subtype))
+}
+
+#[cfg(test)]
+mod test_request_builder_wrapper {
+    use std::sync::Arc;
+
+    use crate::cookie::cookie_container::ErgoCookieContainer;
+    use crate::middleware::curl_log_middleware::CurlLogMiddleware;
+    use crate::scheduler::priority_scheduler::RequestPriority;
+    use crate::wrappers::client_wrapper::ErgoClient;
+
+    use super::content_type_matches;
+
+    #[test]
+    fn test_content_type_matches() {
+        assert!(content_type_matches(
+            "application/json",
+            "Application/JSON; charset=utf-8"
+        ));
+        assert!(content_type_matches(
+            "application/json",
+            "application/problem+json"
+        ));
+        assert!(content_type_matches("image/*", "image/png"));
+        assert!(content_type_matches("*/*", "text/html"));
+        assert!(!content_type_matches("application/json", "text/html"));
+        assert!(!content_type_matches(
+            "application/json",
+            "application/jsonp"
+        ));
+        assert!(!content_type_matches("application/json", "json"));
+    }
+
+    #[test]
+    fn test_to_curl() {
+        let client = ErgoClient::new(reqwest::Client::new());
+        let cookie_store = Arc::new(ErgoCookieContainer::new_secure());
+        cookie_store
+            .set_cookie(
+                vec![cookie::Cookie::new("session", "abc")],
+                "https://example.com/",
+            )
+            .unwrap();
+
+        let command = client
+            .put("https://example.com/")
+            .header("x-trace", "it's")
+            .body("payload")
+            .with_cookie_store_ref(&cookie_store)
+            .to_curl()
+            .unwrap();
+
+        assert_eq!(
+            command,
+            "curl -X PUT 'https://example.com/' -H 'x-trace: it'\\''s' -H 'cookie: session=abc' --data-binary 'payload'"
+        );
+    }
+
+    #[test]
+    fn test_try_clone() {
+        let client = ErgoClient::new(reqwest::Client::new());
+        let builder = client
+            .get("https://example.com/")
+            .with_middleware(CurlLogMiddleware::new())
+            .with_priority(RequestPriority::HIGH);
+
+        let mut cloned = builder.try_clone().unwrap();
+        assert!(cloned
+            .middlewares()
+            .contains(&"ergoreq::middleware::curl_log_middleware::CurlLogMiddleware"));
+        assert_eq!(
+            cloned.get_extension_mut::<RequestPriority>().map(|v| v.0),
+            Some(RequestPriority::HIGH.0)
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use std::fmt::Display;
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use url::Url;
+
+use super::client_wrapper::ErgoClient;
+
+/// A typed REST resource at a base url, like `https://example.com/users`.
+///
+/// Items are addressed by `{base}/{id}`. Every request runs through the middlewares of the
+/// client, and non-success responses fail with [`crate::Error::UnexpectedStatus`].
+///
+/// # Example
+/// ```no_run
+/// # use ergoreq::{ErgoClient, ErgoResource};
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct User {
+///     name: String,
+/// }
+///
+/// # async fn run() -> ergoreq::Result<()> {
+/// let client = ErgoClient::new(reqwest::Client::new());
+/// let users: ErgoResource<User> = client.resource("https://example.com/users".parse().unwrap());
+/// let user = users.get(1).await?;
+/// users.update(1, &User { name: "ergo".to_owned() }).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ErgoResource<T> {
+    client: ErgoClient,
+    base: Url,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for ErgoResource<T> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.to_owned(),
+            base: self.base.to_owned(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> ErgoResource<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Create an `ErgoResource` at `base` sending requests with `client`.
+    pub fn new(client: ErgoClient, base: Url) -> Self {
+        Self {
+            client,
+            base,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get the base url of this resource.
+    pub fn base(&self) -> &Url {
+        &self.base
+    }
+
+    /// Get the url of the item `id`, the id is percent-encoded as a path segment.
+    pub fn item_url<I: Display>(&self, id: I) -> Url {
+        let mut url = self.base.to_owned();
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().push(&id.to_string());
+        }
+        url
+    }
+
+    /// `GET {base}/{id}`
+    pub async fn get<I: Display>(&self, id: I) -> crate::Result<T> {
+        let response = self.client.get(self.item_url(id)).send().await?;
+        Ok(response.error_for_status_with_body().await?.json().await?)
+    }
+
+    /// `GET {base}?{params}`, the response should be a JSON array.
+    pub async fn list<P: Serialize + ?Sized>(&self, params: &P) -> crate::Result<Vec<T>> {
+        let response = self
+            .client
+            .get(self.base.to_owned())
+            .query(params)
+            .send()
+            .await?;
+        Ok(response.error_for_status_with_body().await?.json().await?)
+    }
+
+    /// `POST {base}` with `value` as JSON, returns the created item.
+    pub async fn create(&self, value: &T) -> crate::Result<T> {
+        let response = self
+            .client
+            .post(self.base.to_owned())
+            .json(value)
+            .send()
+            .await?;
+        Ok(response.error_for_status_with_body().await?.json().await?)
+    }
+
+    /// `PUT {base}/{id}` with `value` as JSON, returns the updated item.
+    pub async fn update<I: Display>(&self, id: I, value: &T) -> crate::Result<T> {
+        let response = self
+            .client
+            .put(self.item_url(id))
+            .json(value)
+            .send()
+            .await?;
+        Ok(response.error_for_status_with_body().await?.json().await?)
+    }
+
+    /// `DELETE {base}/{id}`, the response body is ignored.
+    pub async fn delete<I: Display>(&self, id: I) -> crate::Result<()> {
+        let response = self.client.delete(self.item_url(id)).send().await?;
+        response.error_for_status_with_body().await?;
+        Ok(())
+    }
+}
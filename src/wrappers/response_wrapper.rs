@@ -0,0 +1,608 @@
+use std::ops::{Deref, DerefMut};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use http::{Extensions, StatusCode};
+use reqwest::Response;
+use serde::de::DeserializeOwned;
+
+use crate::middleware::auto_redirect_middleware::{RedirectChain, RedirectHop};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::middleware::checksum_middleware::{BodyDigest, ChecksumAlgorithm};
+use crate::utils::json_path::json_path_at;
+use crate::utils::response::content_length;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::utils::response::map_body_stream;
+use crate::utils::timer::Instant;
+use crate::wrappers::json_lines::{decode_json_lines, DEFAULT_MAX_LINE};
+
+/// At most this many characters of the body are kept in [`crate::Error::UnexpectedStatus`] and
+/// [`crate::Error::Deserialize`].
+const MAX_ERROR_BODY_CHARS: usize = 512;
+
+/// Keep the start of `body` for an error.
+fn error_snippet(body: &str) -> String {
+    match body.char_indices().nth(MAX_ERROR_BODY_CHARS) {
+        Some((end, _)) => format!("{}...", &body[..end]),
+        None => body.to_owned(),
+    }
+}
+
+/// Build a [`crate::Error::UnexpectedStatus`] carrying the status, the url and the start of the
+/// body of `response`.
+pub(crate) async fn unexpected_status(response: Response) -> crate::Error {
+    let status = response.status();
+    let url = response.url().to_owned();
+    let body = response.text().await.unwrap_or_default();
+    crate::Error::UnexpectedStatus(status, url, error_snippet(&body))
+}
+
+/// Deserialize `body` as JSON, failing with [`crate::Error::Deserialize`] carrying the path of
+/// the invalid value and the start of `body`.
+pub(crate) fn deserialize_detailed<T: DeserializeOwned>(
+    status: StatusCode,
+    body: &[u8],
+) -> crate::Result<T> {
+    serde_json::from_slice(body).map_err(|source| {
+        let body = String::from_utf8_lossy(body);
+        crate::Error::Deserialize {
+            status,
+            path: json_path_at(&body, source.line(), source.column()),
+            body: error_snippet(&body),
+            source,
+        }
+    })
+}
+
+/// The progress of a download, passed to [`crate::wrappers::download::ErgoDownload::on_progress`]
+/// and [`ErgoResponse::bytes_stream_with_progress`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DownloadProgress {
+    /// Bytes received, including bytes of a resumed download.
+    pub downloaded: u64,
+    /// The size of the body, if known.
+    pub total: Option<u64>,
+}
+
+/// Where the time of a request went, see [`ErgoResponse::timings`].
+///
+/// Attempts are counted when they reach the transport, so responses of mocks or caches have no
+/// attempt.
+#[derive(Clone, Debug)]
+pub struct RequestTimings {
+    started_at: Instant,
+    queued: Duration,
+    attempts: Vec<Duration>,
+    time_to_first_byte: Option<Duration>,
+    redirects: usize,
+    total: Duration,
+}
+
+impl RequestTimings {
+    pub(crate) fn new(started_at: Instant) -> Self {
+        Self {
+            started_at,
+            queued: Duration::ZERO,
+            attempts: vec![],
+            time_to_first_byte: None,
+            redirects: 0,
+            total: Duration::ZERO,
+        }
+    }
+
+    /// Record the end of waiting for a running slot.
+    pub(crate) fn dequeued(&mut self) {
+        self.queued = self.started_at.elapsed();
+    }
+
+    /// Record an attempt started at `attempt_started_at`, whose response headers just arrived.
+    pub(crate) fn attempt(&mut self, attempt_started_at: Instant) {
+        self.attempts.push(attempt_started_at.elapsed());
+        self.time_to_first_byte = Some(self.started_at.elapsed());
+    }
+
+    /// Record the end of the request, after following `redirects`.
+    pub(crate) fn finish(&mut self, redirects: usize) {
+        self.redirects = redirects;
+        self.total = self.started_at.elapsed();
+    }
+
+    /// Time waiting for a running slot of the priority scheduler.
+    pub fn queued(&self) -> Duration {
+        self.queued
+    }
+
+    /// Duration of each attempt sent to the transport, until its response headers arrived.
+    pub fn attempts(&self) -> &[Duration] {
+        &self.attempts
+    }
+
+    /// Time from sending until the response headers of the last attempt arrived, `None` if no
+    /// attempt reached the transport.
+    pub fn time_to_first_byte(&self) -> Option<Duration> {
+        self.time_to_first_byte
+    }
+
+    /// Count of redirects followed.
+    pub fn redirects(&self) -> usize {
+        self.redirects
+    }
+
+    /// Time from sending until the response was returned, including the time queued.
+    pub fn total(&self) -> Duration {
+        self.total
+    }
+}
+
+/// A wrapper for [`reqwest::Response`] carrying the `Extensions` of the request.
+///
+/// Middlewares write information (cache status, selected proxy, custom data) into
+/// `Extensions`, and it is available here after the request completes. Methods of
+/// [`reqwest::Response`] are reachable through `Deref`, and the ones consuming the response are
+/// forwarded.
+#[derive(Debug)]
+pub struct ErgoResponse {
+    inner: Response,
+    extensions: Extensions,
+}
+
+impl ErgoResponse {
+    /// Create a new `ErgoResponse`
+    pub fn new(inner: Response, extensions: Extensions) -> Self {
+        Self { inner, extensions }
+    }
+
+    /// Get the `Extensions` of the request.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// Get the mutable `Extensions` of the request.
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    /// Get a kind of `extension` of the request.
+    pub fn extension<T>(&self) -> Option<&T>
+    where
+        T: Send + Sync + 'static,
+    {
+        self.extensions.get::<T>()
+    }
+
+    /// Get the redirects followed by auto redirect, in order.
+    ///
+    /// It is empty if no redirect was followed.
+    pub fn redirect_chain(&self) -> &[RedirectHop] {
+        self.extension::<RedirectChain>()
+            .map(|v| v.0.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Get the [`RequestTimings`] of the request, `None` if this response was not returned by
+    /// [`crate::ErgoRequestBuilder::send`].
+    pub fn timings(&self) -> Option<&RequestTimings> {
+        self.extension::<RequestTimings>()
+    }
+
+    /// Get the inner [`Response`]
+    pub fn into_inner(self) -> Response {
+        self.inner
+    }
+
+    /// Split into the inner [`Response`] and the `Extensions`.
+    pub fn into_parts(self) -> (Response, Extensions) {
+        (self.inner, self.extensions)
+    }
+
+    /// See [`Response::text`]
+    pub async fn text(self) -> reqwest::Result<String> {
+        self.inner.text().await
+    }
+
+    /// See [`Response::json`]
+    pub async fn json<T: DeserializeOwned>(self) -> reqwest::Result<T> {
+        self.inner.json().await
+    }
+
+    /// Deserialize the JSON body, also returning the exact bytes received.
+    ///
+    /// An invalid body fails with [`crate::Error::Deserialize`] telling the path of the invalid
+    /// value. Useful to verify a signature or archive the untouched payload.
+    pub async fn json_with_raw<T: DeserializeOwned>(self) -> crate::Result<(T, Bytes)> {
+        let status = self.status();
+        let body = self.bytes().await?;
+        let value = deserialize_detailed(status, &body)?;
+        Ok((value, body))
+    }
+
+    /// Copy the body to `writer` while it is read, the body of the returned response is
+    /// unchanged.
+    ///
+    /// `writer` is flushed once the whole body is read, and failing to write fails reading the
+    /// body.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let archive = tokio::fs::File::create("response.json").await?;
+    /// let value = client
+    ///     .get("https://example.com/api")
+    ///     .send()
+    ///     .await?
+    ///     .tee(archive)
+    ///     .json::<serde_json::Value>()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn tee<W>(self, writer: W) -> Self
+    where
+        W: tokio::io::AsyncWrite + Unpin + Send + 'static,
+    {
+        use tokio::io::AsyncWriteExt;
+
+        let inner = map_body_stream(self.inner, |stream| {
+            futures::stream::unfold(Some((stream, writer)), |state| async move {
+                let (mut stream, mut writer) = state?;
+                let result = match stream.next().await {
+                    Some(Ok(chunk)) => writer.write_all(&chunk).await.map(|_| chunk),
+                    Some(Err(e)) => return Some((Err(e.into()), None)),
+                    None => match writer.flush().await {
+                        Ok(()) => return None,
+                        Err(e) => Err(e),
+                    },
+                };
+                match result {
+                    Ok(chunk) => Some((Ok(chunk), Some((stream, writer)))),
+                    Err(e) => Some((
+                        Err(Box::<dyn std::error::Error + Send + Sync>::from(e)),
+                        None,
+                    )),
+                }
+            })
+        });
+        Self::new(inner, self.extensions)
+    }
+
+    /// Parse the body as an html document, to query it with CSS selectors.
+    ///
+    /// # Notice
+    /// Requires the `html` feature.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let document = client.get("https://example.com").send().await?.html().await?;
+    /// let titles = document.select_text("h1")?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "html")]
+    pub async fn html(self) -> crate::Result<crate::utils::html::HtmlDocument> {
+        let url = self.url().to_owned();
+        let text = self.text().await?;
+        Ok(crate::utils::html::HtmlDocument::parse(&text, url))
+    }
+
+    /// Decode the MessagePack body.
+    ///
+    /// # Notice
+    /// Requires the `msgpack` feature.
+    #[cfg(feature = "msgpack")]
+    pub async fn msgpack<T: DeserializeOwned>(self) -> crate::Result<T> {
+        crate::utils::msgpack::from_slice(&self.bytes().await?)
+    }
+
+    /// Decode the CBOR body.
+    ///
+    /// # Notice
+    /// Requires the `cbor` feature.
+    #[cfg(feature = "cbor")]
+    pub async fn cbor<T: DeserializeOwned>(self) -> crate::Result<T> {
+        crate::utils::cbor::from_slice(&self.bytes().await?)
+    }
+
+    /// Decode the XML body.
+    ///
+    /// # Notice
+    /// Requires the `xml` feature.
+    #[cfg(feature = "xml")]
+    pub async fn xml<T: DeserializeOwned>(self) -> crate::Result<T> {
+        let text = self.text().await?;
+        quick_xml::de::from_str(&text).map_err(|e| crate::Error::Codec(format!("Invalid XML: {e}")))
+    }
+
+    /// See [`Response::bytes`]
+    pub async fn bytes(self) -> reqwest::Result<Bytes> {
+        self.inner.bytes().await
+    }
+
+    /// See [`Response::bytes_stream`]
+    pub fn bytes_stream(self) -> impl Stream<Item = reqwest::Result<Bytes>> {
+        self.inner.bytes_stream()
+    }
+
+    /// Decode the newline delimited JSON (JSON Lines) body incrementally, each line as a `T`.
+    ///
+    /// Blank lines are skipped. A line which is not a valid `T` yields
+    /// [`crate::Error::Deserialize`] and the next lines are still decoded. Lines longer than
+    /// 1 MiB end the stream with [`crate::Error::LineTooLong`], see
+    /// [`Self::json_lines_with_max_line`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # use futures::TryStreamExt;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let response = client.get("https://example.com/export.ndjson").send().await?;
+    /// let records = response.json_lines::<serde_json::Value>();
+    /// futures::pin_mut!(records);
+    /// while let Some(record) = records.try_next().await? {
+    ///     println!("{record}");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn json_lines<T: DeserializeOwned>(self) -> impl Stream<Item = crate::Result<T>> {
+        self.json_lines_with_max_line(DEFAULT_MAX_LINE)
+    }
+
+    /// Decode the JSON Lines body like [`Self::json_lines`], with lines of at most `max_line`
+    /// bytes.
+    pub fn json_lines_with_max_line<T: DeserializeOwned>(
+        self,
+        max_line: usize,
+    ) -> impl Stream<Item = crate::Result<T>> {
+        let status = self.status();
+        decode_json_lines(status, self.bytes_stream(), max_line)
+    }
+
+    /// Stream the body like [`Self::bytes_stream`], calling `callback` after each chunk with the
+    /// bytes received so far and the `Content-Length` of the response.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # use futures::TryStreamExt;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let response = client.get("https://example.com/file.zip").send().await?;
+    /// let chunks = response.bytes_stream_with_progress(|v| {
+    ///     println!("{}/{:?}", v.downloaded, v.total);
+    /// });
+    /// futures::pin_mut!(chunks);
+    /// while let Some(chunk) = chunks.try_next().await? {
+    ///     // handle chunk
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn bytes_stream_with_progress<F>(
+        self,
+        mut callback: F,
+    ) -> impl Stream<Item = reqwest::Result<Bytes>>
+    where
+        F: FnMut(DownloadProgress),
+    {
+        let total = content_length(&self.inner);
+        let mut downloaded = 0;
+        self.bytes_stream().inspect(move |chunk| {
+            if let Ok(chunk) = chunk {
+                downloaded += chunk.len() as u64;
+                callback(DownloadProgress { downloaded, total });
+            }
+        })
+    }
+
+    /// Write the body to `writer`, returns the count of bytes written.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn download_to<W>(self, writer: &mut W) -> crate::Result<u64>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        self.download_to_with_progress(writer, |_| {}).await
+    }
+
+    /// Write the body to `writer` like [`Self::download_to`], calling `callback` after each
+    /// chunk like [`Self::bytes_stream_with_progress`].
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn download_to_with_progress<W, F>(
+        self,
+        writer: &mut W,
+        callback: F,
+    ) -> crate::Result<u64>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+        F: FnMut(DownloadProgress),
+    {
+        use futures::TryStreamExt;
+        use tokio::io::AsyncWriteExt;
+
+        let chunks = self.bytes_stream_with_progress(callback);
+        futures::pin_mut!(chunks);
+        let mut written = 0;
+        while let Some(chunk) = chunks.try_next().await? {
+            writer.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        writer.flush().await?;
+        Ok(written)
+    }
+
+    /// Save the body to the file at `path`, returns the count of bytes written.
+    ///
+    /// The body is written to a temporary file next to `path`, which is renamed to `path` once
+    /// complete, so `path` is never left partially written. A body shorter than the
+    /// `Content-Length` fails with [`crate::Error::TruncatedBody`].
+    ///
+    /// # Example
+    /// ```no_run
+    /// # use ergoreq::ErgoClient;
+    /// # async fn run() -> ergoreq::Result<()> {
+    /// let client = ErgoClient::new(reqwest::Client::new());
+    /// let response = client.get("https://example.com/report.csv").send().await?;
+    /// let size = response.save_to_file("report.csv").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn save_to_file<P: AsRef<Path>>(self, path: P) -> crate::Result<u64> {
+        self.save_to_file_inner(path.as_ref(), None).await
+    }
+
+    /// Save the body to the file at `path` like [`Self::save_to_file`], verifying the digest
+    /// header of `algorithm` sent by the server.
+    ///
+    /// A missing or different digest fails with [`crate::Error::ChecksumMismatch`], and the file
+    /// is not written.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn save_to_file_verified<P: AsRef<Path>>(
+        self,
+        path: P,
+        algorithm: ChecksumAlgorithm,
+    ) -> crate::Result<u64> {
+        self.save_to_file_inner(path.as_ref(), Some(algorithm))
+            .await
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn save_to_file_inner(
+        self,
+        path: &Path,
+        algorithm: Option<ChecksumAlgorithm>,
+    ) -> crate::Result<u64> {
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        // unique per save, concurrent saves to the same path do not mix their bytes
+        static SAVES: AtomicU64 = AtomicU64::new(0);
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(format!(
+            ".{}-{}.tmp",
+            std::process::id(),
+            SAVES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let temp = std::path::PathBuf::from(temp);
+
+        let result = self.write_verified(&temp, algorithm).await;
+        match result {
+            Ok(written) => match tokio::fs::rename(&temp, path).await {
+                Ok(()) => Ok(written),
+                Err(e) => {
+                    let _ = tokio::fs::remove_file(&temp).await;
+                    Err(e.into())
+                }
+            },
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&temp).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Write the body to the file at `path`, verifying its `Content-Length` and its digest.
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn write_verified(
+        self,
+        path: &Path,
+        algorithm: Option<ChecksumAlgorithm>,
+    ) -> crate::Result<u64> {
+        use futures::TryStreamExt;
+        use tokio::io::AsyncWriteExt;
+
+        let expected_length = self
+            .headers()
+            .get(http::header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok()?.parse::<u64>().ok());
+        let expected_digest = algorithm.map(|v| (v, self.headers().get(v.header_name()).cloned()));
+        let mut digest = algorithm.map(BodyDigest::new);
+
+        let mut file = tokio::fs::File::create(path).await?;
+        let mut written = 0;
+        let chunks = self.bytes_stream();
+        futures::pin_mut!(chunks);
+        while let Some(chunk) = chunks.try_next().await? {
+            if let Some(digest) = &mut digest {
+                digest.update(&chunk);
+            }
+            file.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        file.sync_all().await?;
+
+        if expected_length.is_some_and(|v| written < v) {
+            return Err(crate::Error::TruncatedBody {
+                expected: expected_length,
+                received: written,
+            });
+        }
+        if let (Some((algorithm, header)), Some(digest)) = (expected_digest, digest) {
+            let actual = digest.finish();
+            if !header
+                .as_ref()
+                .is_some_and(|v| algorithm.header_matches(v, &actual))
+            {
+                let expected = header
+                    .and_then(|v| v.to_str().ok().map(str::to_owned))
+                    .unwrap_or_default();
+                return Err(crate::Error::ChecksumMismatch(expected, actual));
+            }
+        }
+        Ok(written)
+    }
+
+    /// See [`Response::error_for_status`]
+    pub fn error_for_status(self) -> reqwest::Result<Self> {
+        let extensions = self.extensions;
+        self.inner
+            .error_for_status()
+            .map(|inner| Self::new(inner, extensions))
+    }
+
+    /// Fail with [`crate::Error::UnexpectedStatus`] carrying the start of the body if the status
+    /// is not a success.
+    pub(crate) async fn error_for_status_with_body(self) -> crate::Result<Self> {
+        let status = self.status();
+        if status.is_success() {
+            return Ok(self);
+        }
+        Err(unexpected_status(self.inner).await)
+    }
+
+    /// Deserialize the body as JSON, failing with [`crate::Error::Deserialize`] carrying the
+    /// path of the invalid value and the start of the body.
+    pub(crate) async fn json_detailed<T: DeserializeOwned>(self) -> crate::Result<T> {
+        let status = self.status();
+        let body = self.bytes().await?;
+        deserialize_detailed(status, &body)
+    }
+}
+
+impl Deref for ErgoResponse {
+    type Target = Response;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl DerefMut for ErgoResponse {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.inner
+    }
+}
+
+impl From<ErgoResponse> for Response {
+    fn from(value: ErgoResponse) -> Self {
+        value.inner
+    }
+}
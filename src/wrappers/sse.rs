@@ -0,0 +1,314 @@
+use std::collections::VecDeque;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use http::StatusCode;
+use retry_policies::{RetryDecision, RetryPolicy};
+
+use super::request_builder_wrapper::ErgoRequestBuilder;
+use crate::utils::timer::{sleep, system_now};
+
+/// The `Last-Event-ID` header sent when reconnecting.
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// An event of a `text/event-stream` response, yielded by [`ErgoRequestBuilder::send_sse`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// The last event ID set by the stream, sent as `Last-Event-ID` when reconnecting.
+    pub id: Option<String>,
+    /// The `event` field, `message` if not set.
+    pub event: String,
+    /// The `data` fields joined with `\n`.
+    pub data: String,
+    /// The reconnection time set by a `retry` field of this event.
+    pub retry: Option<Duration>,
+}
+
+/// An incremental parser of `text/event-stream`, see the HTML standard.
+#[derive(Debug, Default)]
+struct SseParser {
+    buffer: Vec<u8>,
+    started: bool,
+    data: String,
+    event: String,
+    last_id: Option<String>,
+    retry: Option<Duration>,
+    event_retry: Option<Duration>,
+}
+
+impl SseParser {
+    /// Parse the complete lines of `chunk` and the buffered bytes, returns the dispatched
+    /// events.
+    fn feed(&mut self, chunk: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(chunk);
+        if !self.started && self.buffer.len() >= 3 {
+            self.started = true;
+            if self.buffer.starts_with(b"\xEF\xBB\xBF") {
+                self.buffer.drain(..3);
+            }
+        }
+
+        let mut events = vec![];
+        let mut start = 0;
+        while let Some(end) = self.buffer[start..]
+            .iter()
+            .position(|v| *v == b'\n' || *v == b'\r')
+            .map(|v| v + start)
+        {
+            // wait for the next chunk to know if `\r` is followed by `\n`
+            if self.buffer[end] == b'\r' && end + 1 == self.buffer.len() {
+                break;
+            }
+            let line = String::from_utf8_lossy(&self.buffer[start..end]).into_owned();
+            start = end + 1;
+            if self.buffer[end] == b'\r' && self.buffer[start] == b'\n' {
+                start += 1;
+            }
+            events.extend(self.process_line(&line));
+        }
+        self.buffer.drain(..start);
+        events
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "event" => self.event = value.to_owned(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" if !value.contains('\0') => self.last_id = Some(value.to_owned()),
+            "retry" if !value.is_empty() && value.bytes().all(|v| v.is_ascii_digit()) => {
+                if let Ok(millis) = value.parse() {
+                    self.retry = Some(Duration::from_millis(millis));
+                    self.event_retry = self.retry;
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = std::mem::take(&mut self.event);
+        let retry = self.event_retry.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        let mut data = std::mem::take(&mut self.data);
+        data.pop();
+        Some(SseEvent {
+            id: self.last_id.to_owned(),
+            event: if event.is_empty() {
+                "message".to_owned()
+            } else {
+                event
+            },
+            data,
+            retry,
+        })
+    }
+
+    /// Drop an incomplete event when the connection ends.
+    fn reset(&mut self) {
+        self.buffer.clear();
+        self.data.clear();
+        self.event.clear();
+        self.event_retry = None;
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type BodyStream = Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>;
+#[cfg(target_arch = "wasm32")]
+type BodyStream = Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>>>>;
+
+struct SseState {
+    template: ErgoRequestBuilder,
+    retry_policy: Option<Arc<dyn RetryPolicy + Send + Sync + 'static>>,
+    parser: SseParser,
+    pending: VecDeque<SseEvent>,
+    body: Option<BodyStream>,
+    connected: bool,
+    reconnects: u32,
+    reconnect_start: SystemTime,
+    done: bool,
+}
+
+impl SseState {
+    /// Wait before reconnecting, returns `false` if the retry policy gives up.
+    async fn wait_reconnect(&mut self) -> bool {
+        let Some(policy) = &self.retry_policy else {
+            return false;
+        };
+        if self.reconnects == 0 {
+            self.reconnect_start = system_now();
+        }
+        match policy.should_retry(self.reconnect_start, self.reconnects) {
+            RetryDecision::Retry { execute_after } => {
+                self.reconnects += 1;
+                let delay = self.parser.retry.unwrap_or_else(|| {
+                    execute_after
+                        .duration_since(system_now())
+                        .unwrap_or_default()
+                });
+                sleep(delay).await;
+                true
+            }
+            RetryDecision::DoNotRetry => false,
+        }
+    }
+
+    /// Open the event stream, sending the last event ID if any.
+    async fn connect(&mut self) -> crate::Result<()> {
+        let mut builder = self
+            .template
+            .try_clone()
+            .ok_or(crate::Error::RequestNotCloneable)?;
+        if let Some(last_id) = &self.parser.last_id {
+            builder = builder.header(LAST_EVENT_ID, last_id);
+        }
+        let response = builder.send().await?;
+        if response.status() == StatusCode::NO_CONTENT {
+            // the server asks not to reconnect
+            self.done = true;
+            return Ok(());
+        }
+        let response = response.error_for_status_with_body().await?;
+        #[cfg(not(target_arch = "wasm32"))]
+        let body = response.bytes_stream().boxed();
+        #[cfg(target_arch = "wasm32")]
+        let body = response.bytes_stream().boxed_local();
+        self.body = Some(body);
+        self.connected = true;
+        Ok(())
+    }
+
+    /// Get the next event, reconnecting when the body ends.
+    async fn next_event(&mut self) -> Option<crate::Result<SseEvent>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.done {
+                return None;
+            }
+            let Some(body) = &mut self.body else {
+                let result = self.connect().await;
+                match result {
+                    Ok(()) => continue,
+                    // a status error is not retried, like browsers do
+                    Err(e @ crate::Error::UnexpectedStatus(..)) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                    Err(e) => {
+                        if !self.connected || !self.wait_reconnect().await {
+                            self.done = true;
+                            return Some(Err(e));
+                        }
+                        tracing::debug!("Reconnecting event stream failed: {}", e);
+                        continue;
+                    }
+                }
+            };
+            match body.next().await {
+                Some(Ok(chunk)) => {
+                    let events = self.parser.feed(&chunk);
+                    if !events.is_empty() {
+                        self.reconnects = 0;
+                    }
+                    self.pending.extend(events);
+                }
+                end => {
+                    if let Some(Err(e)) = &end {
+                        tracing::debug!("Event stream interrupted: {}", e);
+                    }
+                    self.body = None;
+                    self.parser.reset();
+                    if !self.wait_reconnect().await {
+                        self.done = true;
+                        return match end {
+                            Some(Err(e)) => Some(Err(e.into())),
+                            _ => None,
+                        };
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Subscribe to the event stream of `template`, see [`ErgoRequestBuilder::send_sse`].
+pub(crate) fn subscribe(
+    template: ErgoRequestBuilder,
+    retry_policy: Option<Arc<dyn RetryPolicy + Send + Sync + 'static>>,
+) -> impl Stream<Item = crate::Result<SseEvent>> {
+    let state = SseState {
+        template,
+        retry_policy,
+        parser: SseParser::default(),
+        pending: VecDeque::new(),
+        body: None,
+        connected: false,
+        reconnects: 0,
+        reconnect_start: system_now(),
+        done: false,
+    };
+    futures::stream::unfold(state, |mut state| async move {
+        let event = state.next_event().await?;
+        Some((event, state))
+    })
+}
+
+#[cfg(test)]
+mod test_sse {
+    use std::time::Duration;
+
+    use super::{SseEvent, SseParser};
+
+    #[test]
+    fn test_parse_events() {
+        let mut parser = SseParser::default();
+        let mut events = parser.feed(b"\xEF\xBB\xBF: comment\r\nid: 1\r\ndata: a\r");
+        assert!(events.is_empty());
+        events.extend(parser.feed(b"\ndata:b\r\n\r\nevent: ping\nretry: 1500\ndata\n\n"));
+        events.extend(parser.feed(b"event: ignored\n\ndata: c\rid\r\n\n"));
+        assert_eq!(
+            events,
+            vec![
+                SseEvent {
+                    id: Some("1".to_owned()),
+                    event: "message".to_owned(),
+                    data: "a\nb".to_owned(),
+                    retry: None,
+                },
+                SseEvent {
+                    id: Some("1".to_owned()),
+                    event: "ping".to_owned(),
+                    data: "".to_owned(),
+                    retry: Some(Duration::from_millis(1500)),
+                },
+                SseEvent {
+                    id: Some("".to_owned()),
+                    event: "message".to_owned(),
+                    data: "c".to_owned(),
+                    retry: None,
+                },
+            ]
+        );
+    }
+}
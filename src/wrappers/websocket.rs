@@ -0,0 +1,197 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use futures::{Sink, SinkExt, Stream, StreamExt};
+use http::header::{
+    CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_PROTOCOL,
+    SEC_WEBSOCKET_VERSION, UPGRADE,
+};
+use http::StatusCode;
+use reqwest::Upgraded;
+use tokio_tungstenite::tungstenite::handshake::derive_accept_key;
+use tokio_tungstenite::tungstenite::protocol::{frame, Role};
+use tokio_tungstenite::tungstenite::{self, Message};
+use tokio_tungstenite::WebSocketStream;
+
+use super::request_builder_wrapper::ErgoRequestBuilder;
+use crate::utils::random::random_bytes;
+
+/// The status code and reason of a close frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CloseFrame {
+    pub code: u16,
+    pub reason: String,
+}
+
+/// A message sent or received on an [`ErgoWebSocket`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WebSocketMessage {
+    Text(String),
+    Binary(Bytes),
+    /// A ping, answered with a pong automatically when received.
+    Ping(Bytes),
+    Pong(Bytes),
+    /// A close frame, answered automatically when received. The stream ends after it.
+    Close(Option<CloseFrame>),
+}
+
+impl From<WebSocketMessage> for Message {
+    fn from(message: WebSocketMessage) -> Self {
+        match message {
+            WebSocketMessage::Text(text) => Message::Text(text.into()),
+            WebSocketMessage::Binary(data) => Message::Binary(data),
+            WebSocketMessage::Ping(data) => Message::Ping(data),
+            WebSocketMessage::Pong(data) => Message::Pong(data),
+            WebSocketMessage::Close(frame) => Message::Close(frame.map(|v| frame::CloseFrame {
+                code: v.code.into(),
+                reason: v.reason.into(),
+            })),
+        }
+    }
+}
+
+fn websocket_error(e: tungstenite::Error) -> crate::Error {
+    match e {
+        tungstenite::Error::Io(e) => e.into(),
+        e => crate::Error::WebSocket(e.to_string()),
+    }
+}
+
+/// A WebSocket connection opened by [`ErgoRequestBuilder::upgrade_websocket`].
+///
+/// Messages are received as a [`Stream`] and sent as a [`Sink`], use
+/// [`StreamExt::split`] to handle them in different tasks. Framing is done by
+/// [`tokio_tungstenite`].
+pub struct ErgoWebSocket {
+    inner: WebSocketStream<Upgraded>,
+    protocol: Option<String>,
+    done: bool,
+}
+
+impl ErgoWebSocket {
+    /// Get the subprotocol selected by the server, if any.
+    pub fn protocol(&self) -> Option<&str> {
+        self.protocol.as_deref()
+    }
+}
+
+impl Stream for ErgoWebSocket {
+    type Item = crate::Result<WebSocketMessage>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            if self.done {
+                return Poll::Ready(None);
+            }
+            let message = match self.inner.poll_next_unpin(cx) {
+                Poll::Ready(v) => v,
+                Poll::Pending => return Poll::Pending,
+            };
+            let message = match message {
+                Some(Ok(Message::Text(text))) => WebSocketMessage::Text(text.to_string()),
+                Some(Ok(Message::Binary(data))) => WebSocketMessage::Binary(data),
+                Some(Ok(Message::Ping(data))) => WebSocketMessage::Ping(data),
+                Some(Ok(Message::Pong(data))) => WebSocketMessage::Pong(data),
+                Some(Ok(Message::Close(frame))) => {
+                    WebSocketMessage::Close(frame.map(|v| CloseFrame {
+                        code: v.code.into(),
+                        reason: v.reason.to_string(),
+                    }))
+                }
+                // never returned when reading
+                Some(Ok(Message::Frame(_))) => continue,
+                Some(Err(tungstenite::Error::ConnectionClosed)) | None => {
+                    self.done = true;
+                    return Poll::Ready(None);
+                }
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Poll::Ready(Some(Err(websocket_error(e))));
+                }
+            };
+            // end after a close frame
+            self.done = matches!(message, WebSocketMessage::Close(_));
+            return Poll::Ready(Some(Ok(message)));
+        }
+    }
+}
+
+impl Sink<WebSocketMessage> for ErgoWebSocket {
+    type Error = crate::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<crate::Result<()>> {
+        self.inner.poll_ready_unpin(cx).map_err(websocket_error)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: WebSocketMessage) -> crate::Result<()> {
+        self.inner
+            .start_send_unpin(item.into())
+            .map_err(websocket_error)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<crate::Result<()>> {
+        self.inner.poll_flush_unpin(cx).map_err(websocket_error)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<crate::Result<()>> {
+        self.inner.poll_close_unpin(cx).map_err(websocket_error)
+    }
+}
+
+/// Send the upgrade handshake of `builder`, see [`ErgoRequestBuilder::upgrade_websocket`].
+pub(crate) async fn upgrade(builder: ErgoRequestBuilder) -> crate::Result<ErgoWebSocket> {
+    let mut url = builder.request_url()?;
+    let scheme = match url.scheme() {
+        "ws" => Some("http"),
+        "wss" => Some("https"),
+        _ => None,
+    };
+    let builder = match scheme {
+        Some(scheme) => {
+            let _ = url.set_scheme(scheme);
+            builder.with_url(url)?
+        }
+        None => builder,
+    };
+
+    let key = STANDARD.encode(random_bytes::<16>());
+    let response = builder
+        .header(CONNECTION, "Upgrade")
+        .header(UPGRADE, "websocket")
+        .header(SEC_WEBSOCKET_VERSION, "13")
+        .header(SEC_WEBSOCKET_KEY, &key)
+        .send()
+        .await?;
+
+    if response.status() != StatusCode::SWITCHING_PROTOCOLS {
+        let status = response.status();
+        response.error_for_status_with_body().await?;
+        return Err(crate::Error::WebSocket(format!(
+            "expected 101 Switching Protocols, got {status}"
+        )));
+    }
+    let accept = response
+        .headers()
+        .get(SEC_WEBSOCKET_ACCEPT)
+        .map(|v| v.as_bytes().to_owned());
+    if accept.as_deref() != Some(derive_accept_key(key.as_bytes()).as_bytes()) {
+        return Err(crate::Error::WebSocket(
+            "invalid Sec-WebSocket-Accept".to_owned(),
+        ));
+    }
+    let protocol = response
+        .headers()
+        .get(SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_owned());
+
+    let upgraded = response.into_inner().upgrade().await?;
+    Ok(ErgoWebSocket {
+        inner: WebSocketStream::from_raw_socket(upgraded, Role::Client, None).await,
+        protocol,
+        done: false,
+    })
+}
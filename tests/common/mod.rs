@@ -0,0 +1,104 @@
+//! Raw HTTP servers and middlewares shared by the integration tests.
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use ergoreq::middleware::middleware::{Middleware, MiddlewarePhase, Next};
+use ergoreq::middleware::mock_middleware::MockMiddleware;
+use http::Extensions;
+use reqwest::{Request, Response};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Bind a listener to a random local port, returns it with its address.
+pub async fn listen() -> (TcpListener, SocketAddr) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    (listener, address)
+}
+
+/// Read a raw request from `stream`, up to the end of its body.
+pub async fn read_request(stream: &mut TcpStream) -> String {
+    let mut request = vec![];
+    let mut buffer = [0u8; 1024];
+    loop {
+        let text = String::from_utf8_lossy(&request).to_ascii_lowercase();
+        let length = text
+            .lines()
+            .find_map(|v| v.strip_prefix("content-length:"))
+            .and_then(|v| v.trim().parse::<usize>().ok());
+        let complete = match text.find("\r\n\r\n") {
+            Some(_) if text.contains("transfer-encoding: chunked") => text.ends_with("0\r\n\r\n"),
+            Some(end) => request.len() >= end + 4 + length.unwrap_or_default(),
+            None => false,
+        };
+        if complete {
+            break;
+        }
+        let read = stream.read(&mut buffer).await.unwrap();
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+    String::from_utf8_lossy(&request).into_owned()
+}
+
+/// Serve every connection with the raw response returned by `handler` for the raw request,
+/// returns the base url.
+pub async fn serve<F>(handler: F) -> String
+where
+    F: Fn(&str) -> String + Send + Sync + 'static,
+{
+    let (listener, address) = listen().await;
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let handler = handler.to_owned();
+            tokio::spawn(async move {
+                let request = read_request(&mut stream).await;
+                let response = handler(&request);
+                stream.write_all(response.as_bytes()).await.unwrap();
+                stream.shutdown().await.unwrap();
+            });
+        }
+    });
+    format!("http://{}", address)
+}
+
+/// Serve the given raw responses to successive connections, then refuse connections, returns
+/// the base url.
+pub async fn serve_responses(responses: Vec<&'static str>) -> String {
+    let (listener, address) = listen().await;
+    tokio::spawn(async move {
+        for response in responses {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            read_request(&mut stream).await;
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream.shutdown().await.unwrap();
+        }
+    });
+    format!("http://{}/", address)
+}
+
+/// Run a shared [`MockMiddleware`] closest to the transport, so every attempt reaches it.
+pub struct SharedMock(pub Arc<MockMiddleware>);
+
+#[async_trait]
+impl Middleware for SharedMock {
+    async fn handle(
+        &self,
+        req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> ergoreq::Result<Response> {
+        self.0.handle(req, ext, next).await
+    }
+
+    fn default_phase(&self) -> MiddlewarePhase {
+        MiddlewarePhase::PostRetry
+    }
+}
@@ -0,0 +1,113 @@
+mod common;
+
+#[cfg(test)]
+mod test_api_key_rotation_middleware {
+    use crate::common::SharedMock;
+    use ergoreq::middleware::api_key_rotation_middleware::{
+        ApiKeyPlacement, ApiKeyRotationMiddleware, RotationStrategy,
+    };
+    use ergoreq::middleware::middleware::MiddlewarePhase;
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+    use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use http::StatusCode;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_bench_rejected_key() {
+        let rotation = Arc::new(
+            ApiKeyRotationMiddleware::new(
+                vec!["k1".to_owned(), "k2".to_owned()],
+                ApiKeyPlacement::Header(http::HeaderName::from_static("x-api-key")),
+            )
+            .with_cool_down(Duration::from_millis(200)),
+        );
+        let mock = Arc::new(
+            MockMiddleware::new()
+                .with_rule(
+                    MockRule::new()
+                        .header("x-api-key", "k1")
+                        .respond_with(MockResponse::new(StatusCode::TOO_MANY_REQUESTS))
+                        .expect(1),
+                )
+                .with_rule(MockRule::new().header("x-api-key", "k2").expect(2)),
+        );
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware_arc(rotation.clone())
+            .with_middleware(SharedMock(mock.clone()));
+
+        let response = client.get("https://example.com").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(rotation.available_keys(), 1);
+        for _ in 0..2 {
+            let response = client.get("https://example.com").send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+        mock.assert_expectations();
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_next_key() {
+        let mock = Arc::new(
+            MockMiddleware::new()
+                .with_rule(
+                    MockRule::new()
+                        .header("x-api-key", "k1")
+                        .respond_with(MockResponse::new(StatusCode::TOO_MANY_REQUESTS))
+                        .expect(1),
+                )
+                .with_rule(MockRule::new().header("x-api-key", "k2").expect(1)),
+        );
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(2)
+            .with_retry_statuses([StatusCode::TOO_MANY_REQUESTS])
+            .with_middleware(ApiKeyRotationMiddleware::new(
+                vec!["k1".to_owned(), "k2".to_owned()],
+                ApiKeyPlacement::Header(http::HeaderName::from_static("x-api-key")),
+            ))
+            .with_middleware(SharedMock(mock.clone()));
+
+        // the retry of the rejected attempt uses the second key
+        let response = client.get("https://example.com").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        mock.assert_expectations();
+    }
+
+    #[tokio::test]
+    async fn test_wait_for_benched_keys() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(
+                ApiKeyRotationMiddleware::new(
+                    vec!["k1".to_owned()],
+                    ApiKeyPlacement::Query("key".to_owned()),
+                )
+                .with_strategy(RotationStrategy::LeastRecentlyUsed)
+                .with_cool_down(Duration::from_millis(200)),
+            )
+            .with_middleware_phase(
+                MockMiddleware::new().with_rule(
+                    MockRule::new()
+                        .path_regex("^/limited$")
+                        .respond_with(MockResponse::new(StatusCode::FORBIDDEN)),
+                ),
+                MiddlewarePhase::PostRetry,
+            );
+
+        client
+            .get("https://example.com/limited?a=1")
+            .send()
+            .await
+            .unwrap();
+        let start = Instant::now();
+        let response = client
+            .get("https://example.com/limited")
+            .send()
+            .await
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(150));
+        assert_eq!(
+            response.url().as_str(),
+            "https://example.com/limited?key=k1"
+        );
+    }
+}
@@ -0,0 +1,363 @@
+mod common;
+
+#[cfg(test)]
+mod test_auto_retry {
+    use crate::common::serve_responses;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use async_trait::async_trait;
+
+    use ergoreq::middleware::auto_retry_middleware::{
+        AttemptOutcome, IdempotencyKey, RetryAttempts, RetryBudget, RetryClassifier,
+        RetryDecisionKind, RetryOptions, SentIdempotencyKey,
+    };
+    use ergoreq::middleware::middleware::{Middleware, MiddlewarePhase, Next};
+    use ergoreq::retry_policies::policies::ExponentialBackoff;
+    use ergoreq::ErgoClient;
+    use http::Extensions;
+    use http::StatusCode;
+    use reqwest::{Request, Response};
+
+    const UNAVAILABLE: &str = "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 1\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const BAD_GATEWAY: &str =
+        "HTTP/1.1 502 Bad Gateway\r\nContent-Length: 4\r\nConnection: close\r\n\r\nfail";
+    const NOT_FOUND: &str =
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+    const OK: &str = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok";
+
+    #[tokio::test]
+    async fn test_retry_after() {
+        let url = serve_responses(vec![UNAVAILABLE, OK]).await;
+        let client = ErgoClient::new(reqwest::Client::new()).with_retry_count(2);
+
+        let start = Instant::now();
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+
+        let url = serve_responses(vec![UNAVAILABLE, OK]).await;
+        let start = Instant::now();
+        let response = client
+            .get(&url)
+            .with_retry_options(RetryOptions::new().with_max_retry_after(Duration::ZERO))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // Without retries left, the response is returned as is.
+        let url = serve_responses(vec![UNAVAILABLE]).await;
+        let response = client.get(&url).with_retry_times(0).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_retry_statuses() {
+        let url = serve_responses(vec![BAD_GATEWAY, OK]).await;
+        let client = ErgoClient::new(reqwest::Client::new()).with_retry_policy(
+            ExponentialBackoff::builder()
+                .retry_bounds(Duration::from_millis(10), Duration::from_millis(10))
+                .build_with_max_retries(2),
+        );
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+
+        let url = serve_responses(vec![BAD_GATEWAY, BAD_GATEWAY]).await;
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+
+        let url = serve_responses(vec![BAD_GATEWAY]).await;
+        let response = client
+            .get(&url)
+            .with_retry_statuses([StatusCode::SERVICE_UNAVAILABLE])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn test_timings() {
+        let url = serve_responses(vec![BAD_GATEWAY, OK]).await;
+        let client = ErgoClient::new(reqwest::Client::new()).with_retry_policy(
+            ExponentialBackoff::builder()
+                .retry_bounds(Duration::from_millis(10), Duration::from_millis(10))
+                .build_with_max_retries(2),
+        );
+        let response = client.get(&url).send().await.unwrap();
+        let timings = response.timings().unwrap();
+        assert_eq!(timings.attempts().len(), 2);
+        assert_eq!(timings.redirects(), 0);
+        let time_to_first_byte = timings.time_to_first_byte().unwrap();
+        assert!(time_to_first_byte >= timings.attempts().iter().sum::<Duration>());
+        assert!(timings.total() >= time_to_first_byte);
+    }
+
+    struct RetryNotFound;
+
+    impl RetryClassifier for RetryNotFound {
+        fn classify(&self, result: &ergoreq::Result<reqwest::Response>) -> RetryDecisionKind {
+            match result {
+                Ok(response) if response.status() == StatusCode::NOT_FOUND => {
+                    RetryDecisionKind::RetryAfter(Duration::from_millis(10))
+                }
+                _ => RetryDecisionKind::Done,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_classifier() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(2)
+            .with_retry_options(RetryOptions::new().with_classifier(RetryNotFound));
+
+        let url = serve_responses(vec![NOT_FOUND, OK]).await;
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+
+        let url = serve_responses(vec![BAD_GATEWAY]).await;
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    }
+
+    #[tokio::test]
+    async fn test_retry_observer() {
+        let observed = Arc::new(Mutex::new(vec![]));
+        let recorder = observed.to_owned();
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(3)
+            .with_retry_observer(move |attempt, result, delay| {
+                let status = result.as_ref().map(|v| v.status()).ok();
+                recorder.lock().unwrap().push((attempt, status, delay));
+            });
+
+        let url = serve_responses(vec![UNAVAILABLE, BAD_GATEWAY, OK]).await;
+        let response = client
+            .get(&url)
+            .with_retry_options(RetryOptions::new().with_max_retry_after(Duration::from_millis(10)))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        // Options of the request replace the ones of the client, including the observer.
+        assert!(observed.lock().unwrap().is_empty());
+
+        let url = serve_responses(vec![UNAVAILABLE, OK]).await;
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let attempts = response.extension::<RetryAttempts>().unwrap();
+        assert!(attempts.is_retried());
+        assert_eq!(
+            attempts.outcomes(),
+            [
+                AttemptOutcome::Status(StatusCode::SERVICE_UNAVAILABLE),
+                AttemptOutcome::Status(StatusCode::OK)
+            ]
+        );
+        assert_eq!(attempts.total_backoff(), Duration::from_secs(1));
+        assert_eq!(
+            *observed.lock().unwrap(),
+            vec![(
+                1,
+                Some(StatusCode::SERVICE_UNAVAILABLE),
+                Duration::from_secs(1)
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_retry_budget() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(3)
+            .with_retry_options(RetryOptions::new().with_max_retry_after(Duration::ZERO))
+            .with_retry_budget(RetryBudget::new(0.2).with_max_tokens(1));
+
+        let url = serve_responses(vec![UNAVAILABLE, OK]).await;
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // The only token is spent, and one success is not enough to refill it.
+        let url = serve_responses(vec![UNAVAILABLE, OK]).await;
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn test_retry_body_factory() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.to_owned();
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(3)
+            .with_retry_options(RetryOptions::new().with_max_retry_after(Duration::ZERO));
+
+        let url = serve_responses(vec![UNAVAILABLE, OK]).await;
+        let response = client
+            .put(&url)
+            .body_factory(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async {
+                    let chunks = vec![Ok::<_, std::io::Error>("streamed "), Ok("body")];
+                    Ok(reqwest::Body::wrap_stream(futures::stream::iter(chunks)))
+                }
+            })
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_retry_idempotency() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(3)
+            .with_retry_options(RetryOptions::new().with_max_retry_after(Duration::ZERO));
+
+        let url = serve_responses(vec![UNAVAILABLE]).await;
+        let response = client.post(&url).body("once").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.extension::<RetryAttempts>().unwrap().count(), 1);
+
+        let url = serve_responses(vec![UNAVAILABLE, OK]).await;
+        let response = client
+            .post(&url)
+            .header("Idempotency-Key", "8e03978e")
+            .body("once")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let url = serve_responses(vec![UNAVAILABLE, OK]).await;
+        let response = client
+            .patch(&url)
+            .with_retry_non_idempotent(true)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_fallback_hosts() {
+        let primary = serve_responses(vec![UNAVAILABLE, UNAVAILABLE]).await;
+        let backup = serve_responses(vec![OK]).await;
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(2)
+            .with_retry_options(RetryOptions::new().with_max_retry_after(Duration::ZERO))
+            .with_fallback_hosts([backup.as_str()]);
+
+        let response = client
+            .get(format!("{}path?query=1", primary))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.url().as_str(), format!("{}path?query=1", backup));
+        assert_eq!(response.extension::<RetryAttempts>().unwrap().count(), 3);
+    }
+
+    struct CountAttempts(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Middleware for CountAttempts {
+        async fn handle(
+            &self,
+            req: Request,
+            ext: &mut Extensions,
+            next: Next<'_>,
+        ) -> ergoreq::Result<Response> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            next.run(req, ext).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_runs_middlewares() {
+        let post_retry = Arc::new(AtomicUsize::new(0));
+        let pre_retry = Arc::new(AtomicUsize::new(0));
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(3)
+            .with_retry_options(RetryOptions::new().with_max_retry_after(Duration::ZERO))
+            .with_middleware(CountAttempts(pre_retry.to_owned()))
+            .with_middleware_phase(
+                CountAttempts(post_retry.to_owned()),
+                MiddlewarePhase::PostRetry,
+            );
+
+        let url = serve_responses(vec![UNAVAILABLE, UNAVAILABLE, OK]).await;
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(pre_retry.load(Ordering::SeqCst), 1);
+        assert_eq!(post_retry.load(Ordering::SeqCst), 3);
+    }
+
+    /// Record the `Idempotency-Key` header of each attempt.
+    struct RecordKeys(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl Middleware for RecordKeys {
+        async fn handle(
+            &self,
+            req: Request,
+            ext: &mut Extensions,
+            next: Next<'_>,
+        ) -> ergoreq::Result<Response> {
+            let key = req.headers()["idempotency-key"]
+                .to_str()
+                .unwrap()
+                .to_owned();
+            self.0.lock().unwrap().push(key);
+            next.run(req, ext).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_idempotency_key() {
+        let keys = Arc::new(Mutex::new(vec![]));
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(3)
+            .with_retry_options(RetryOptions::new().with_max_retry_after(Duration::ZERO))
+            .with_middleware_phase(RecordKeys(keys.to_owned()), MiddlewarePhase::PostRetry);
+
+        let url = serve_responses(vec![UNAVAILABLE, OK, OK]).await;
+        let request = client
+            .post(&url)
+            .body("once")
+            .with_idempotency_key(IdempotencyKey::Auto);
+        let response = request.try_clone().unwrap().send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let sent = response
+            .extension::<SentIdempotencyKey>()
+            .unwrap()
+            .0
+            .to_owned();
+        assert_eq!(sent.len(), 36);
+        assert_eq!(&sent[14..15], "4");
+        assert_eq!(
+            *keys.lock().unwrap(),
+            vec![sent.to_owned(), sent.to_owned()]
+        );
+
+        // a new key is generated for each send
+        request.send().await.unwrap();
+        assert_ne!(keys.lock().unwrap()[2], sent);
+
+        let url = serve_responses(vec![OK]).await;
+        let response = client
+            .post(&url)
+            .with_idempotency_key(IdempotencyKey::Fixed("8e03978e".to_owned()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.extension::<SentIdempotencyKey>(),
+            Some(&SentIdempotencyKey("8e03978e".to_owned()))
+        );
+        assert_eq!(keys.lock().unwrap()[3], "8e03978e");
+    }
+}
@@ -0,0 +1,88 @@
+#[cfg(all(test, feature = "msgpack", feature = "cbor"))]
+mod test_binary_codecs {
+    use async_trait::async_trait;
+    use ergoreq::middleware::middleware::{Middleware, MiddlewarePhase, Next};
+    use ergoreq::utils::response_from_parts;
+    use ergoreq::ErgoClient;
+    use http::{Extensions, HeaderMap, StatusCode};
+    use reqwest::{Request, Response};
+    use serde::{Deserialize, Serialize};
+
+    /// Respond with the body of the request, if its content type is the `accept` header.
+    #[derive(Clone)]
+    struct Echo;
+
+    #[async_trait]
+    impl Middleware for Echo {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::error::Result<Response> {
+            let accept = req.headers().get("accept");
+            let status = match accept == req.headers().get("content-type") {
+                true => StatusCode::OK,
+                false => StatusCode::NOT_ACCEPTABLE,
+            };
+            let body = req
+                .body()
+                .and_then(|v| v.as_bytes())
+                .unwrap_or_default()
+                .to_vec();
+            Ok(response_from_parts(
+                status,
+                HeaderMap::new(),
+                body,
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        id: i64,
+        name: String,
+        tags: Vec<String>,
+        score: Option<f64>,
+    }
+
+    #[tokio::test]
+    async fn test_msgpack_and_cbor() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware_phase(Echo, MiddlewarePhase::PostRetry);
+        let item = Item {
+            id: -7,
+            name: "ergo".to_owned(),
+            tags: vec!["a".to_owned(), "b".to_owned()],
+            score: Some(0.5),
+        };
+
+        let echoed = client
+            .post("https://example.com")
+            .msgpack(&item)
+            .send_msgpack::<Item>()
+            .await
+            .unwrap();
+        assert_eq!(echoed, item);
+
+        let echoed = client
+            .post("https://example.com")
+            .cbor(&item)
+            .send_cbor::<Item>()
+            .await
+            .unwrap();
+        assert_eq!(echoed, item);
+
+        let err = client
+            .post("https://example.com")
+            .cbor(&item)
+            .send_msgpack::<Item>()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ergoreq::Error::UnexpectedStatus(StatusCode::NOT_ACCEPTABLE, ..)
+        ));
+    }
+}
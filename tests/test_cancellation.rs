@@ -0,0 +1,65 @@
+#[cfg(test)]
+mod test_cancellation {
+    use std::time::{Duration, Instant};
+
+    use async_trait::async_trait;
+    use ergoreq::middleware::middleware::{Middleware, MiddlewarePhase, Next};
+    use ergoreq::retry_policies::policies::ExponentialBackoff;
+    use ergoreq::utils::response_from_parts;
+    use ergoreq::{CancellationToken, ErgoClient, Error};
+    use http::{Extensions, HeaderMap, HeaderValue, StatusCode};
+    use reqwest::{Request, Response};
+
+    /// Respond `503` asking to retry after a long time.
+    struct Unavailable;
+
+    #[async_trait]
+    impl Middleware for Unavailable {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::Result<Response> {
+            let mut headers = HeaderMap::new();
+            headers.insert("retry-after", HeaderValue::from_static("30"));
+            Ok(response_from_parts(
+                StatusCode::SERVICE_UNAVAILABLE,
+                headers,
+                "",
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_during_retry() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_policy(ExponentialBackoff::builder().build_with_max_retries(3))
+            .with_middleware_phase(Unavailable, MiddlewarePhase::PostRetry);
+        let token = CancellationToken::new();
+
+        let canceller = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            canceller.cancel();
+        });
+        let start = Instant::now();
+        let result = client
+            .get("https://example.com")
+            .with_cancellation_token(token.to_owned())
+            .send()
+            .await;
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(token.is_cancelled());
+
+        // a cancelled token fails requests right away
+        let result = client
+            .get("https://example.com")
+            .with_cancellation_token(token)
+            .send()
+            .await;
+        assert!(matches!(result, Err(Error::Cancelled)));
+    }
+}
@@ -0,0 +1,57 @@
+#[cfg(test)]
+mod test_checksum_middleware {
+    use ergoreq::middleware::checksum_middleware::{ChecksumAlgorithm, ChecksumMiddleware};
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockRule};
+    use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_checksum_headers() {
+        let mock = Arc::new(
+            MockMiddleware::new()
+                .with_rule(
+                    MockRule::new()
+                        .header("content-md5", "XUFAKrxLKna5cZ2REBfFkg==")
+                        .header(
+                            "x-amz-content-sha256",
+                            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824",
+                        )
+                        .header(
+                            "content-digest",
+                            "sha-256=:LPJNul+wow4m6DsqxbninhsWHlwfp0JecwQzYpOLmCQ=:",
+                        )
+                        .expect(1),
+                )
+                .with_rule(
+                    MockRule::new()
+                        .header(
+                            "x-amz-content-sha256",
+                            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+                        )
+                        .expect(1),
+                ),
+        );
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(
+                ChecksumMiddleware::new(ChecksumAlgorithm::ContentMd5)
+                    .with_algorithm(ChecksumAlgorithm::AmzContentSha256)
+                    .with_algorithm(ChecksumAlgorithm::ContentDigestSha256),
+            )
+            .with_middleware_arc(mock.clone());
+
+        client
+            .put("https://example.com/object")
+            .body("hello")
+            .send()
+            .await
+            .unwrap();
+        // Requests without a body only get the digest of an empty payload.
+        client
+            .get("https://example.com/object")
+            .send()
+            .await
+            .unwrap();
+
+        mock.assert_expectations();
+    }
+}
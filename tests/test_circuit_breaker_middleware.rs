@@ -0,0 +1,35 @@
+mod common;
+
+#[cfg(test)]
+mod test_circuit_breaker_middleware {
+    use crate::common::SharedMock;
+    use ergoreq::middleware::circuit_breaker_middleware::{CircuitBreakerMiddleware, CircuitState};
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+    use ergoreq::ErgoClient;
+    use http::StatusCode;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_count_failed_attempts() {
+        let mock = Arc::new(
+            MockMiddleware::new().with_rule(MockRule::new().respond_with(
+                MockResponse::new(StatusCode::SERVICE_UNAVAILABLE).header("retry-after", "0"),
+            )),
+        );
+        let breaker = Arc::new(CircuitBreakerMiddleware::new().with_failure_threshold(3));
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(3)
+            .with_middleware_arc(breaker.clone())
+            .with_middleware(SharedMock(mock.clone()));
+
+        // the three attempts of one request open the circuit
+        let response = client.get("https://example.com").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(breaker.state("example.com"), CircuitState::Open);
+
+        // rejected without being retried
+        let error = client.get("https://example.com").send().await.unwrap_err();
+        assert!(matches!(error, ergoreq::Error::CircuitOpen(_)));
+        assert_eq!(mock.call_count(0), 3);
+    }
+}
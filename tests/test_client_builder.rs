@@ -0,0 +1,76 @@
+mod common;
+
+#[cfg(test)]
+mod test_client_builder {
+    use crate::common::serve;
+    use std::sync::Arc;
+
+    use ergoreq::cookie::cookie_container::ErgoCookieContainer;
+    use ergoreq::ErgoClient;
+    use http::StatusCode;
+
+    /// Get the value of header `name` of a raw request.
+    fn header<'a>(request: &'a str, name: &str) -> &'a str {
+        request
+            .lines()
+            .find_map(|v| v.strip_prefix(&format!("{name}: ")))
+            .unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn test_client_builder() {
+        let base = serve(|request| match request.split(' ').nth(1).unwrap_or_default() {
+            "/login" => "HTTP/1.1 302 Found\r\nSet-Cookie: session=abc\r\nLocation: /echo\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned(),
+            _ => {
+                let body = format!(
+                    "{} {}",
+                    header(request, "user-agent"),
+                    header(request, "cookie")
+                );
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            }
+        })
+        .await;
+
+        // the redirect policy of reqwest is disabled
+        let client = ErgoClient::builder().build().unwrap();
+        let response = client.get(format!("{base}/login")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::FOUND);
+
+        let cookie_store = Arc::new(ErgoCookieContainer::new(true, false, false));
+        let client = ErgoClient::builder()
+            .user_agent("ergoreq-test")
+            .with_auto_redirect_count(5)
+            .with_cookie_store(cookie_store.to_owned())
+            .build()
+            .unwrap();
+        let response = client.get(format!("{base}/login")).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ergoreq-test session=abc");
+        assert_eq!(cookie_store.serialize_cookies().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pooled_clients_keep_builder_config() {
+        let base = serve(|request| {
+            let body = header(request, "user-agent").to_owned();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        })
+        .await;
+        let address = base.trim_start_matches("http://").parse().unwrap();
+
+        // requests with transport options are sent by a client of the pool
+        let client = ErgoClient::builder()
+            .user_agent("ergoreq-test")
+            .build()
+            .unwrap()
+            .with_resolve("ergoreq.test", address);
+        let response = client.get("http://ergoreq.test/").send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ergoreq-test");
+    }
+}
@@ -0,0 +1,72 @@
+#[cfg(test)]
+mod test_coalesce_middleware {
+    use async_trait::async_trait;
+    use ergoreq::middleware::coalesce_middleware::CoalesceMiddleware;
+    use ergoreq::middleware::middleware::{Middleware, Next};
+    use ergoreq::utils::response_from_parts;
+    use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use http::{Extensions, HeaderMap, StatusCode};
+    use reqwest::{Request, Response};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    struct SlowUpstream(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Middleware for SlowUpstream {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::Result<Response> {
+            let count = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(response_from_parts(
+                StatusCode::OK,
+                HeaderMap::new(),
+                format!("response {count}"),
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_coalesce_concurrent_requests() {
+        let upstream_calls = Arc::new(AtomicUsize::new(0));
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(CoalesceMiddleware::new())
+            .with_middleware(SlowUpstream(upstream_calls.clone()));
+
+        let requests = (0..5).map(|_| async {
+            client
+                .get("https://example.com/data")
+                .send()
+                .await
+                .unwrap()
+                .text()
+                .await
+                .unwrap()
+        });
+        let bodies = futures::future::join_all(requests).await;
+
+        assert_eq!(upstream_calls.load(Ordering::SeqCst), 1);
+        assert!(bodies.iter().all(|v| v == "response 1"));
+
+        // Requests with different vary headers are not coalesced.
+        let (first, second) = tokio::join!(
+            client
+                .get("https://example.com/data")
+                .bearer_auth("a")
+                .send(),
+            client
+                .get("https://example.com/data")
+                .bearer_auth("b")
+                .send(),
+        );
+        first.unwrap();
+        second.unwrap();
+        assert_eq!(upstream_calls.load(Ordering::SeqCst), 3);
+    }
+}
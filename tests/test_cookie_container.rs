@@ -1,7 +1,9 @@
 #[cfg(test)]
 mod test_cookie_container {
     use ergoreq::cookie::cookie_container::ErgoCookieContainer;
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
     use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use http::StatusCode;
     use reqwest::redirect::Policy;
 
     use std::sync::Arc;
@@ -59,4 +61,40 @@ mod test_cookie_container {
 
         assert_eq!(cookie_store.serialize_cookies().len(), 0);
     }
+
+    #[tokio::test]
+    async fn test_client_cookie_store() {
+        let mock = MockMiddleware::new()
+            .with_rule(MockRule::new().path_regex("^/login$").respond_with(
+                MockResponse::new(StatusCode::OK).header("set-cookie", "session=abc"),
+            ))
+            .with_rule(
+                MockRule::new()
+                    .path_regex("^/me$")
+                    .header("cookie", "session=abc"),
+            )
+            .with_rule(MockRule::new().respond_with(MockResponse::new(StatusCode::UNAUTHORIZED)));
+        let cookie_store = Arc::new(ErgoCookieContainer::new(true, false, false));
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(mock)
+            .with_cookie_store(cookie_store.to_owned());
+
+        client
+            .get("https://example.com/login")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(cookie_store.serialize_cookies().len(), 1);
+
+        let response = client.get("https://example.com/me").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = client
+            .get("https://example.com/me")
+            .without_cookie_store()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
 }
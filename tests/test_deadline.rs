@@ -0,0 +1,85 @@
+#[cfg(test)]
+mod test_deadline {
+    use std::time::{Duration, Instant};
+
+    use async_trait::async_trait;
+    use ergoreq::middleware::deadline_middleware::Deadline;
+    use ergoreq::middleware::middleware::{Middleware, MiddlewarePhase, Next};
+    use ergoreq::retry_policies::policies::ExponentialBackoff;
+    use ergoreq::retry_policies::Jitter;
+    use ergoreq::utils::response_from_parts;
+    use ergoreq::{ErgoClient, Error};
+    use http::{Extensions, HeaderMap, StatusCode};
+    use reqwest::{Request, Response};
+
+    struct SlowUpstream(Duration);
+
+    #[async_trait]
+    impl Middleware for SlowUpstream {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::Result<Response> {
+            tokio::time::sleep(self.0).await;
+            Ok(response_from_parts(
+                StatusCode::OK,
+                HeaderMap::new(),
+                "ok",
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    struct FailingUpstream;
+
+    #[async_trait]
+    impl Middleware for FailingUpstream {
+        async fn handle(
+            &self,
+            _req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::Result<Response> {
+            Err(Error::Custom("unavailable".into()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deadline() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_deadline(Duration::from_millis(50))
+            .with_middleware(SlowUpstream(Duration::from_millis(500)));
+        let error = client.get("https://example.com").send().await.unwrap_err();
+        assert!(matches!(error, Error::DeadlineExceeded(_)));
+
+        let response = client
+            .get("https://example.com")
+            .with_deadline(Duration::from_secs(5))
+            .send()
+            .await
+            .unwrap();
+        let deadline = response.extension::<Deadline>().unwrap();
+        assert_eq!(deadline.budget(), Duration::from_secs(5));
+        assert!(!deadline.is_expired());
+    }
+
+    #[tokio::test]
+    async fn test_deadline_across_retries() {
+        let policy = ExponentialBackoff::builder()
+            .retry_bounds(Duration::from_secs(2), Duration::from_secs(4))
+            .jitter(Jitter::None)
+            .build_with_max_retries(3);
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_policy(policy)
+            .with_deadline(Duration::from_secs(1))
+            .with_middleware_phase(FailingUpstream, MiddlewarePhase::PostRetry);
+
+        let start = Instant::now();
+        let error = client.get("https://example.com").send().await.unwrap_err();
+        assert!(matches!(error, Error::DeadlineExceeded(_)));
+        // The retry is abandoned at once, instead of waiting for the deadline.
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+}
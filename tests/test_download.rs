@@ -0,0 +1,335 @@
+mod common;
+
+#[cfg(test)]
+mod test_download {
+    use crate::common::serve;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use ergoreq::middleware::checksum_middleware::ChecksumAlgorithm;
+    use ergoreq::wrappers::download::DownloadChecksum;
+    use ergoreq::{ErgoClient, Error};
+    use sha2::{Digest, Sha256};
+
+    /// Get the value of header `name` of a raw request.
+    fn header<'a>(request: &'a str, name: &str) -> &'a str {
+        request
+            .lines()
+            .find_map(|v| v.strip_prefix(&format!("{name}: ")))
+            .unwrap_or_default()
+    }
+
+    fn content() -> String {
+        (0..1000)
+            .map(|v| char::from(b'a' + (v % 26) as u8))
+            .collect()
+    }
+
+    /// Serve `content()` with range support and the `ETag` `"v1"`, recording the `Range` headers
+    /// received.
+    async fn serve_content(ranges: Arc<Mutex<Vec<String>>>) -> String {
+        serve(move |request| {
+            let content = content();
+            if request.starts_with("HEAD") {
+                return format!(
+                    "HTTP/1.1 200 OK\r\nAccept-Ranges: bytes\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    content.len()
+                );
+            }
+            let range = header(request, "range");
+            let if_range = header(request, "if-range");
+            if range.is_empty() || !(if_range.is_empty() || if_range == "\"v1\"") {
+                return format!(
+                    "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{content}",
+                    content.len()
+                );
+            }
+            ranges.lock().unwrap().push(range.to_owned());
+            let (start, end) = range.trim_start_matches("bytes=").split_once('-').unwrap();
+            let start = start.parse::<usize>().unwrap();
+            let end = end.parse::<usize>().map(|v| v + 1).unwrap_or(content.len());
+            let body = &content[start..end];
+            format!(
+                "HTTP/1.1 206 Partial Content\r\nETag: \"v1\"\r\nContent-Range: bytes {start}-{}/{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                end - 1,
+                content.len(),
+                body.len()
+            )
+        })
+        .await
+    }
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("ergoreq-{}-{name}", std::process::id()))
+    }
+
+    /// Get the path of `path` with `suffix` appended.
+    fn with_suffix(path: &std::path::Path, suffix: &str) -> std::path::PathBuf {
+        let mut path = path.as_os_str().to_owned();
+        path.push(suffix);
+        path.into()
+    }
+
+    #[tokio::test]
+    async fn test_resume_part_file() {
+        let ranges = Arc::new(Mutex::new(vec![]));
+        let base = serve_content(ranges.to_owned()).await;
+        let path = temp_path("resume");
+        std::fs::write(with_suffix(&path, ".part"), &content()[..300]).unwrap();
+
+        let progress = Arc::new(AtomicU64::new(0));
+        let progress_clone = progress.to_owned();
+        let checksum = Sha256::digest(content().as_bytes())
+            .iter()
+            .map(|v| format!("{:02x}", v))
+            .collect::<String>();
+        let size = ErgoClient::new(reqwest::Client::new())
+            .download(format!("{base}/file"))
+            .with_checksum(DownloadChecksum::Sha256(checksum))
+            .on_progress(move |v| progress_clone.store(v.downloaded, Ordering::Relaxed))
+            .to_file(&path)
+            .await
+            .unwrap();
+
+        assert_eq!(size, 1000);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content());
+        assert_eq!(*ranges.lock().unwrap(), vec!["bytes=300-".to_owned()]);
+        assert_eq!(progress.load(Ordering::Relaxed), 1000);
+        assert!(!with_suffix(&path, ".part.meta").exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_checks_content_range() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let requests_clone = requests.to_owned();
+        // ranges are answered from the start of the file
+        let base = serve(move |request| {
+            let content = content();
+            let range = header(request, "range");
+            requests_clone.lock().unwrap().push(range.to_owned());
+            match range.is_empty() {
+                true => format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: 1000\r\nConnection: close\r\n\r\n{content}"
+                ),
+                false => format!(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Range: bytes 0-999/1000\r\nContent-Length: 1000\r\nConnection: close\r\n\r\n{content}"
+                ),
+            }
+        })
+        .await;
+        let path = temp_path("content-range");
+        std::fs::write(with_suffix(&path, ".part"), &content()[..300]).unwrap();
+
+        let size = ErgoClient::new(reqwest::Client::new())
+            .download(format!("{base}/file"))
+            .to_file(&path)
+            .await
+            .unwrap();
+
+        assert_eq!(size, 1000);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content());
+        assert_eq!(*requests.lock().unwrap(), vec!["bytes=300-", ""]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_sends_if_range() {
+        let if_ranges = Arc::new(Mutex::new(vec![]));
+        let if_ranges_clone = if_ranges.to_owned();
+        // the file changed since the `.part` was written
+        let base = serve(move |request| {
+            if_ranges_clone
+                .lock()
+                .unwrap()
+                .push(header(request, "if-range").to_owned());
+            format!(
+                "HTTP/1.1 200 OK\r\nETag: \"v2\"\r\nContent-Length: 1000\r\nConnection: close\r\n\r\n{}",
+                content()
+            )
+        })
+        .await;
+        let path = temp_path("if-range");
+        std::fs::write(with_suffix(&path, ".part"), "x".repeat(300)).unwrap();
+        std::fs::write(
+            with_suffix(&path, ".part.meta"),
+            r#"{"validator":"\"v1\"","total":null,"segments":[]}"#,
+        )
+        .unwrap();
+
+        let size = ErgoClient::new(reqwest::Client::new())
+            .download(format!("{base}/file"))
+            .to_file(&path)
+            .await
+            .unwrap();
+
+        assert_eq!(size, 1000);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content());
+        assert_eq!(*if_ranges.lock().unwrap(), vec!["\"v1\""]);
+        assert!(!with_suffix(&path, ".part.meta").exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_not_satisfiable() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let requests_clone = requests.to_owned();
+        let base = serve(move |request| {
+            let range = header(request, "range");
+            requests_clone.lock().unwrap().push(range.to_owned());
+            match range.is_empty() {
+                true => format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: 1000\r\nConnection: close\r\n\r\n{}",
+                    content()
+                ),
+                false => "HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */1000\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned(),
+            }
+        })
+        .await;
+        let client = ErgoClient::new(reqwest::Client::new());
+        let path = temp_path("not-satisfiable");
+
+        // the `.part` is already complete
+        std::fs::write(with_suffix(&path, ".part"), content()).unwrap();
+        let size = client
+            .download(format!("{base}/file"))
+            .to_file(&path)
+            .await
+            .unwrap();
+        assert_eq!(size, 1000);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content());
+        assert_eq!(*requests.lock().unwrap(), vec!["bytes=1000-"]);
+        std::fs::remove_file(&path).unwrap();
+
+        // the `.part` is larger than the remote file
+        requests.lock().unwrap().clear();
+        std::fs::write(with_suffix(&path, ".part"), "x".repeat(1200)).unwrap();
+        let size = client
+            .download(format!("{base}/file"))
+            .to_file(&path)
+            .await
+            .unwrap();
+        assert_eq!(size, 1000);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content());
+        assert_eq!(*requests.lock().unwrap(), vec!["bytes=1200-", ""]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_resume_segments() {
+        let ranges = Arc::new(Mutex::new(vec![]));
+        let base = serve_content(ranges.to_owned()).await;
+        let path = temp_path("resume-segments");
+        // the second and the last segments were interrupted by a previous run
+        let mut part = content().into_bytes();
+        part[400..500].fill(0);
+        part[750..].fill(0);
+        std::fs::write(with_suffix(&path, ".part"), part).unwrap();
+        std::fs::write(
+            with_suffix(&path, ".part.meta"),
+            r#"{"validator":"\"v1\"","total":1000,"segments":[[250,250],[400,500],[500,500],[750,1000]]}"#,
+        )
+        .unwrap();
+
+        let size = ErgoClient::new(reqwest::Client::new())
+            .download(format!("{base}/file"))
+            .with_segments(4)
+            .to_file(&path)
+            .await
+            .unwrap();
+
+        assert_eq!(size, 1000);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content());
+        let mut ranges = ranges.lock().unwrap().to_owned();
+        ranges.sort();
+        assert_eq!(ranges, vec!["bytes=400-499", "bytes=750-999"]);
+        assert!(!with_suffix(&path, ".part.meta").exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_segments_and_checksum_mismatch() {
+        let ranges = Arc::new(Mutex::new(vec![]));
+        let base = serve_content(ranges.to_owned()).await;
+        let client = ErgoClient::new(reqwest::Client::new());
+        let path = temp_path("segments");
+
+        let size = client
+            .download(format!("{base}/file"))
+            .with_segments(4)
+            .to_file(&path)
+            .await
+            .unwrap();
+        assert_eq!(size, 1000);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content());
+        let mut ranges = ranges.lock().unwrap().to_owned();
+        ranges.sort();
+        assert_eq!(
+            ranges,
+            vec![
+                "bytes=0-249",
+                "bytes=250-499",
+                "bytes=500-749",
+                "bytes=750-999"
+            ]
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        let error = client
+            .download(format!("{base}/file"))
+            .with_checksum(DownloadChecksum::Md5("00".to_owned()))
+            .to_file(&path)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::ChecksumMismatch(_, _)));
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_save_to_file() {
+        let base = serve(|request| {
+            let content = content();
+            let digest = match request.split_whitespace().nth(1).unwrap_or_default() {
+                "/valid" => format!("sha-256=:{}:", STANDARD.encode(Sha256::digest(&content))),
+                _ => "sha-256=:AAAA:".to_owned(),
+            };
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Digest: {digest}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{content}",
+                content.len()
+            )
+        })
+        .await;
+        let client = ErgoClient::new(reqwest::Client::new());
+        let path = temp_path("save");
+
+        let response = client.get(format!("{base}/any")).send().await.unwrap();
+        assert_eq!(response.save_to_file(&path).await.unwrap(), 1000);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content());
+        std::fs::remove_file(&path).unwrap();
+
+        let response = client.get(format!("{base}/valid")).send().await.unwrap();
+        let size = response
+            .save_to_file_verified(&path, ChecksumAlgorithm::ContentDigestSha256)
+            .await
+            .unwrap();
+        assert_eq!(size, 1000);
+        std::fs::remove_file(&path).unwrap();
+
+        let response = client.get(format!("{base}/invalid")).send().await.unwrap();
+        let error = response
+            .save_to_file_verified(&path, ChecksumAlgorithm::ContentDigestSha256)
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::ChecksumMismatch(_, _)));
+        assert!(!path.exists());
+        // no temporary file is left
+        let prefix = path.file_name().unwrap().to_string_lossy().into_owned();
+        assert!(!std::fs::read_dir(std::env::temp_dir()).unwrap().any(|v| v
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with(&prefix)));
+    }
+}
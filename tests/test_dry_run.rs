@@ -0,0 +1,77 @@
+#[cfg(test)]
+mod test_dry_run {
+    use std::sync::Arc;
+
+    use ergoreq::cookie::cookie_container::ErgoCookieContainer;
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+    use ergoreq::ErgoClient;
+    use futures::FutureExt;
+    use http::{HeaderValue, StatusCode};
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_cookie_store(Arc::new(ErgoCookieContainer::new(false, false, false)))
+            .with_default_header("x-client", "ergo")
+            .on_request(|req, _| {
+                async move {
+                    let signature = format!("sig:{}", req.url().path());
+                    req.headers_mut()
+                        .insert("x-signature", HeaderValue::from_str(&signature).unwrap());
+                    Ok(())
+                }
+                .boxed()
+            })
+            .with_middleware(
+                MockMiddleware::new()
+                    .with_rule(
+                        MockRule::new().path_regex("^/login$").respond_with(
+                            MockResponse::new(StatusCode::OK)
+                                .header("set-cookie", "session=abc; Path=/data"),
+                        ),
+                    )
+                    .passthrough_unmatched(true),
+            );
+        client
+            .get("https://example.com/login")
+            .send()
+            .await
+            .unwrap();
+
+        let request = client
+            .get("https://example.com/data")
+            .dry_run()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(request.url().as_str(), "https://example.com/data");
+        assert_eq!(request.headers()["cookie"], "session=abc");
+        assert_eq!(request.headers()["x-client"], "ergo");
+        assert_eq!(request.headers()["x-signature"], "sig:/data");
+
+        // answered by the mock, nothing would be sent
+        let request = client
+            .get("https://example.com/login")
+            .dry_run()
+            .await
+            .unwrap();
+        assert!(request.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_with_response_checks() {
+        let client = ErgoClient::new(reqwest::Client::new());
+
+        let request = client
+            .get("https://example.com/data")
+            .require_content_type("application/json")
+            .expect_status(StatusCode::CREATED)
+            .with_max_response_bytes(0)
+            .with_integrity_check(true)
+            .dry_run()
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(request.url().as_str(), "https://example.com/data");
+    }
+}
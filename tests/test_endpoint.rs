@@ -0,0 +1,72 @@
+#[cfg(test)]
+mod test_endpoint {
+    use std::borrow::Cow;
+
+    use ergoreq::middleware::middleware::MiddlewarePhase;
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+    use ergoreq::{ErgoClient, ErgoEndpoint, ErgoRequestBuilder};
+    use http::{Method, StatusCode};
+    use url::Url;
+
+    struct CreateUser<'a> {
+        name: &'a str,
+    }
+
+    impl ErgoEndpoint for CreateUser<'_> {
+        fn method(&self) -> Method {
+            Method::POST
+        }
+
+        fn url(&self) -> Cow<'_, str> {
+            "users".into()
+        }
+
+        fn prepare(&self, request: ErgoRequestBuilder) -> ErgoRequestBuilder {
+            request.json(&serde_json::json!({ "name": self.name }))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_endpoint() {
+        let mock = MockMiddleware::new()
+            .with_rule(
+                MockRule::new()
+                    .method(Method::POST)
+                    .path_regex("^/v2/users$")
+                    .header("content-type", "application/json")
+                    .respond_with(MockResponse::new(StatusCode::CREATED)),
+            )
+            .with_rule(
+                MockRule::new()
+                    .method(Method::DELETE)
+                    .path_regex("^/v2/users/1$")
+                    .respond_with(MockResponse::new(StatusCode::NO_CONTENT)),
+            )
+            .with_rule(MockRule::new().respond_with(MockResponse::new(StatusCode::NOT_FOUND)));
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_base_url("https://example.com/v2/".parse().unwrap())
+            .with_middleware_phase(mock, MiddlewarePhase::PostRetry);
+
+        let response = client
+            .send_endpoint(CreateUser { name: "ergo" })
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = client
+            .send_endpoint((Method::DELETE, "users/1"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let url = Url::parse("https://example.com/v2/users/1").unwrap();
+        let response = client.send_endpoint((Method::DELETE, url)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = client
+            .send_endpoint((Method::GET, "users/1".to_owned()))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
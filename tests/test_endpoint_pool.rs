@@ -0,0 +1,116 @@
+#[cfg(test)]
+mod test_endpoint_pool {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+    use ergoreq::wrappers::endpoint_pool::{
+        BalanceStrategy, EndpointPool, EndpointStats, SelectedEndpoint,
+    };
+    use ergoreq::ErgoClient;
+    use http::StatusCode;
+
+    #[tokio::test]
+    async fn test_endpoint_pool() {
+        let pool = Arc::new(
+            EndpointPool::new(BalanceStrategy::RoundRobin)
+                .with_endpoint("https://replica1.example.com")
+                .with_endpoint("http://replica2.example.com:8080"),
+        );
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_endpoint_pool(pool.to_owned())
+            .with_middleware(
+                MockMiddleware::new()
+                    .with_rule(
+                        MockRule::new()
+                            .path_regex("^/fail$")
+                            .respond_with(MockResponse::new(StatusCode::SERVICE_UNAVAILABLE)),
+                    )
+                    .with_rule(MockRule::new()),
+            );
+
+        let response = client
+            .get("https://example.com/items?page=2")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.url().as_str(),
+            "https://replica1.example.com/items?page=2"
+        );
+        assert_eq!(
+            response.extension::<SelectedEndpoint>().unwrap().0.as_str(),
+            "https://replica1.example.com/"
+        );
+
+        let response = client.get("https://example.com/fail").send().await.unwrap();
+        assert_eq!(
+            response.url().as_str(),
+            "http://replica2.example.com:8080/fail"
+        );
+
+        let stats = pool.stats();
+        assert_eq!(
+            stats[0].1,
+            EndpointStats {
+                requests: 1,
+                successes: 1,
+                failures: 0,
+                outstanding: 0,
+                healthy: true
+            }
+        );
+        assert_eq!(
+            stats[1].1,
+            EndpointStats {
+                requests: 1,
+                successes: 0,
+                failures: 1,
+                outstanding: 0,
+                healthy: true
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_endpoint_ejection() {
+        let pool = Arc::new(
+            EndpointPool::new(BalanceStrategy::RoundRobin)
+                .with_endpoint("https://replica1.example.com")
+                .with_endpoint("https://replica2.example.com")
+                .with_ejection(1, Duration::from_secs(60))
+                .with_health_check("/healthz"),
+        );
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_endpoint_pool(pool.to_owned())
+            .with_middleware(
+                MockMiddleware::new()
+                    .with_rule(
+                        MockRule::new()
+                            .path_regex("^/fail$")
+                            .respond_with(MockResponse::new(StatusCode::BAD_GATEWAY)),
+                    )
+                    .with_rule(MockRule::new()),
+            );
+
+        let response = client.get("https://example.com/fail").send().await.unwrap();
+        assert_eq!(response.url().host_str(), Some("replica1.example.com"));
+        assert!(!pool.stats()[0].1.healthy);
+
+        for _ in 0..3 {
+            let response = client.get("https://example.com/").send().await.unwrap();
+            assert_eq!(response.url().host_str(), Some("replica2.example.com"));
+        }
+
+        // A successful probe restores the endpoint.
+        pool.check_health(&client).await;
+        assert!(pool.stats()[0].1.healthy);
+        assert_eq!(pool.stats()[0].1.requests, 1);
+        let hosts = [
+            client.get("https://example.com/").send().await.unwrap(),
+            client.get("https://example.com/").send().await.unwrap(),
+        ]
+        .map(|v| v.url().host_str().unwrap().to_owned());
+        assert!(hosts.contains(&"replica1.example.com".to_owned()));
+    }
+}
@@ -0,0 +1,376 @@
+#[cfg(test)]
+mod test_ergo_response {
+    use async_trait::async_trait;
+    use ergoreq::middleware::middleware::{Middleware, MiddlewarePhase, Next};
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+    use ergoreq::middleware::proxy_rotation_middleware::{ProxyRotationMiddleware, SelectedProxy};
+    use ergoreq::utils::response_from_parts;
+    use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use ergoreq::wrappers::response_wrapper::DownloadProgress;
+    use http::{Extensions, HeaderMap, Method, StatusCode};
+    use reqwest::{Request, Response};
+    use tokio::io::AsyncReadExt;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct TraceId(&'static str);
+
+    #[tokio::test]
+    async fn test_response_extensions() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(ProxyRotationMiddleware::round_robin(vec![
+                "http://127.0.0.1:18080".to_owned(),
+            ]))
+            .with_middleware(MockMiddleware::new().with_rule(
+                MockRule::new().respond_with(MockResponse::new(StatusCode::NOT_FOUND).body("gone")),
+            ));
+
+        let response = client
+            .get("https://example.com")
+            .with_extension(TraceId("abc"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(response.extension::<TraceId>(), Some(&TraceId("abc")));
+        assert_eq!(
+            response.extension::<SelectedProxy>(),
+            Some(&SelectedProxy("http://127.0.0.1:18080".to_owned()))
+        );
+
+        let (response, extensions) = response.into_parts();
+        assert!(extensions.get::<SelectedProxy>().is_some());
+        assert_eq!(response.text().await.unwrap(), "gone");
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Item {
+        id: u64,
+    }
+
+    #[tokio::test]
+    async fn test_send_json() {
+        let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+            MockMiddleware::new()
+                .with_rule(
+                    MockRule::new()
+                        .path_regex("^/ok$")
+                        .header("accept", "application/json")
+                        .respond_with(
+                            MockResponse::new(StatusCode::OK).json(&serde_json::json!({"id": 1})),
+                        ),
+                )
+                .with_rule(
+                    MockRule::new().path_regex("^/invalid$").respond_with(
+                        MockResponse::new(StatusCode::OK)
+                            .header("content-type", "application/json; charset=utf-8")
+                            .body(r#"[{"id": 1}, {"id": "2"}]"#),
+                    ),
+                )
+                .with_rule(
+                    MockRule::new().path_regex("^/page$").respond_with(
+                        MockResponse::new(StatusCode::OK)
+                            .header("content-type", "text/html")
+                            .body("<html></html>"),
+                    ),
+                )
+                .with_rule(
+                    MockRule::new()
+                        .respond_with(MockResponse::new(StatusCode::NOT_FOUND).body("gone")),
+                ),
+        );
+
+        let item = client
+            .get("https://example.com/ok")
+            .send_json::<Item>()
+            .await
+            .unwrap();
+        assert_eq!(item, Item { id: 1 });
+        let text = client.get("https://example.com/invalid").send_text().await;
+        assert_eq!(text.unwrap(), r#"[{"id": 1}, {"id": "2"}]"#);
+
+        let err = client
+            .get("https://example.com/invalid")
+            .send_json::<Vec<Item>>()
+            .await
+            .unwrap_err();
+        match err {
+            ergoreq::Error::Deserialize {
+                status, path, body, ..
+            } => {
+                assert_eq!(status, StatusCode::OK);
+                assert_eq!(path, "[1].id");
+                assert_eq!(body, r#"[{"id": 1}, {"id": "2"}]"#);
+            }
+            err => panic!("unexpected error: {err}"),
+        }
+
+        let err = client
+            .get("https://example.com/page")
+            .send_json::<Item>()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ergoreq::Error::UnexpectedContentType { actual: Some(ref v), .. } if v == "text/html"
+        ));
+        let response = client
+            .get("https://example.com/page")
+            .require_content_type("text/*")
+            .send()
+            .await;
+        assert!(response.is_ok());
+
+        let err = client
+            .get("https://example.com/missing")
+            .send_bytes()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ergoreq::Error::UnexpectedStatus(StatusCode::NOT_FOUND, _, body) if body == "gone"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_expect_status() {
+        let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+            MockMiddleware::new().with_rule(
+                MockRule::new().respond_with(MockResponse::new(StatusCode::NOT_FOUND).body("gone")),
+            ),
+        );
+
+        let text = client
+            .get("https://example.com")
+            .expect_status(StatusCode::OK)
+            .expect_status_in(400..500)
+            .send_text()
+            .await
+            .unwrap();
+        assert_eq!(text, "gone");
+
+        let err = client
+            .get("https://example.com")
+            .expect_status(StatusCode::CREATED)
+            .expect_status_in(500..=599)
+            .send()
+            .await
+            .unwrap_err();
+        match err {
+            ergoreq::Error::UnexpectedStatus(status, url, body) => {
+                assert_eq!(status, StatusCode::NOT_FOUND);
+                assert_eq!(url.as_str(), "https://example.com/");
+                assert_eq!(body, "gone");
+            }
+            err => panic!("unexpected error: {err}"),
+        }
+    }
+
+    #[test]
+    fn test_accept_and_content_type() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_default_accept("application/vnd.github+json");
+
+        let curl = client.get("https://example.com").to_curl().unwrap();
+        assert!(curl.contains("accept: application/vnd.github+json"));
+
+        let request = client
+            .patch("https://example.com")
+            .accept("text/plain")
+            .accept_json()
+            .content_type("application/merge-patch+json")
+            .json(&serde_json::json!({"a": 1}))
+            .build()
+            .unwrap();
+        assert_eq!(request.headers().get_all("accept").iter().count(), 1);
+        assert_eq!(request.headers()["accept"], "application/json");
+        assert_eq!(
+            request.headers()["content-type"],
+            "application/merge-patch+json"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_progress() {
+        let body = "a".repeat(1000);
+        let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+            MockMiddleware::new().with_rule(
+                MockRule::new().respond_with(MockResponse::new(StatusCode::OK).body(body.as_str())),
+            ),
+        );
+
+        let response = client.get("https://example.com").send().await.unwrap();
+        let mut progress = vec![];
+        let mut written = vec![];
+        let count = response
+            .download_to_with_progress(&mut written, |v| progress.push(v))
+            .await
+            .unwrap();
+        assert_eq!(count, 1000);
+        assert_eq!(written, body.as_bytes());
+        assert_eq!(
+            progress.last(),
+            Some(&DownloadProgress {
+                downloaded: 1000,
+                total: Some(1000)
+            })
+        );
+    }
+
+    /// Respond with a body of 4 chunks of 100 bytes, without `Content-Length`.
+    struct Chunked;
+
+    #[async_trait]
+    impl Middleware for Chunked {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::Result<Response> {
+            let chunks = (0..4).map(|_| Ok::<_, std::io::Error>(vec![b'a'; 100]));
+            Ok(response_from_parts(
+                StatusCode::OK,
+                HeaderMap::new(),
+                reqwest::Body::wrap_stream(futures::stream::iter(chunks)),
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_response_bytes() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_max_response_bytes(300)
+            .with_middleware_phase(Chunked, MiddlewarePhase::PostRetry);
+
+        // aborted while streaming
+        let response = client.get("https://example.com").send().await.unwrap();
+        let err = ergoreq::Error::from(response.bytes().await.unwrap_err());
+        assert!(matches!(err, ergoreq::Error::BodyTooLarge(300)));
+
+        let response = client
+            .get("https://example.com")
+            .with_max_response_bytes(400)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.bytes().await.unwrap().len(), 400);
+
+        // refused by Content-Length
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_max_response_bytes(10)
+            .with_middleware(
+                MockMiddleware::new().with_rule(
+                    MockRule::new()
+                        .respond_with(MockResponse::new(StatusCode::OK).body("a".repeat(11))),
+                ),
+            );
+        let err = client.get("https://example.com").send().await.unwrap_err();
+        assert!(matches!(err, ergoreq::Error::BodyTooLarge(10)));
+    }
+
+    #[tokio::test]
+    async fn test_json_with_raw_and_tee() {
+        let body = r#"{ "id": 1 }"#;
+        let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+            MockMiddleware::new().with_rule(
+                MockRule::new().respond_with(MockResponse::new(StatusCode::OK).body(body)),
+            ),
+        );
+
+        let (value, raw) = client
+            .get("https://example.com")
+            .send()
+            .await
+            .unwrap()
+            .json_with_raw::<serde_json::Value>()
+            .await
+            .unwrap();
+        assert_eq!(value, serde_json::json!({"id": 1}));
+        assert_eq!(raw, body.as_bytes());
+
+        let (mut sink, archive) = tokio::io::duplex(1024);
+        let value = client
+            .get("https://example.com")
+            .send()
+            .await
+            .unwrap()
+            .tee(archive)
+            .json::<serde_json::Value>()
+            .await
+            .unwrap();
+        assert_eq!(value, serde_json::json!({"id": 1}));
+        let mut archived = String::new();
+        sink.read_to_string(&mut archived).await.unwrap();
+        assert_eq!(archived, body);
+    }
+
+    #[tokio::test]
+    async fn test_integrity_check() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_integrity_check(true)
+            .with_middleware(
+                MockMiddleware::new()
+                    .with_rule(MockRule::new().method(Method::HEAD).respond_with(
+                        MockResponse::new(StatusCode::OK).header("content-length", "10"),
+                    ))
+                    .with_rule(MockRule::new().path_regex("^/cached$").respond_with(
+                        MockResponse::new(StatusCode::NOT_MODIFIED).header("content-length", "10"),
+                    ))
+                    .with_rule(
+                        MockRule::new().path_regex("^/short$").respond_with(
+                            MockResponse::new(StatusCode::OK)
+                                .header("content-length", "10")
+                                .body("abc"),
+                        ),
+                    )
+                    .with_rule(
+                        MockRule::new().respond_with(
+                            MockResponse::new(StatusCode::OK)
+                                .header("content-length", "3")
+                                .body("abc"),
+                        ),
+                    ),
+            );
+
+        let response = client
+            .get("https://example.com/short")
+            .send()
+            .await
+            .unwrap();
+        let err = ergoreq::Error::from(response.text().await.unwrap_err());
+        assert!(matches!(
+            err,
+            ergoreq::Error::TruncatedBody {
+                expected: Some(10),
+                received: 3
+            }
+        ));
+
+        let response = client.get("https://example.com/full").send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "abc");
+
+        // responses to `HEAD` and `304` have no body whatever their `Content-Length`
+        let response = client
+            .head("https://example.com/short")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "");
+        let response = client
+            .get("https://example.com/cached")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "");
+
+        // unchecked bodies are read as they are
+        let response = client
+            .get("https://example.com/short")
+            .with_integrity_check(false)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "abc");
+    }
+}
@@ -0,0 +1,56 @@
+#[cfg(test)]
+mod test_hook_middleware {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+    use ergoreq::{ErgoClient, Error};
+    use http::{HeaderValue, StatusCode};
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Seen(u16);
+
+    #[tokio::test]
+    async fn test_hooks() {
+        let responses = Arc::new(AtomicUsize::new(0));
+        let counter = responses.to_owned();
+        let client = ErgoClient::new(reqwest::Client::new())
+            .on_request(|req, _ext| {
+                Box::pin(async move {
+                    req.headers_mut()
+                        .insert("x-token", HeaderValue::from_static("secret"));
+                    Ok(())
+                })
+            })
+            .on_response(move |resp, ext| {
+                let counter = counter.to_owned();
+                Box::pin(async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    ext.insert(Seen(resp.status().as_u16()));
+                    Ok(())
+                })
+            })
+            .with_middleware(
+                MockMiddleware::new().with_rule(
+                    MockRule::new()
+                        .header("x-token", "secret")
+                        .respond_with(MockResponse::new(StatusCode::ACCEPTED)),
+                ),
+            );
+
+        let response = client.get("https://example.com").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+        assert_eq!(response.extension::<Seen>(), Some(&Seen(202)));
+        assert_eq!(responses.load(Ordering::SeqCst), 1);
+
+        // Per-request hooks run after global middlewares, so use a client without the mock.
+        let error = ErgoClient::new(reqwest::Client::new())
+            .get("https://example.com")
+            .on_request(|_req, _ext| Box::pin(async move { Err(Error::Custom("rejected".into())) }))
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(error.root(), Error::Custom(_)));
+        assert_eq!(responses.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod test_host_config {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use async_trait::async_trait;
+    use ergoreq::middleware::middleware::{Middleware, MiddlewarePhase, Next};
+    use ergoreq::middleware::rate_limit_middleware::RateLimit;
+    use ergoreq::utils::response_from_parts;
+    use ergoreq::wrappers::host_config::HostConfig;
+    use ergoreq::ErgoClient;
+    use http::{Extensions, HeaderMap, HeaderValue, StatusCode};
+    use reqwest::{Request, Response};
+
+    /// Respond `503` with the `accept` header as body, counting attempts.
+    #[derive(Clone, Default)]
+    struct Unavailable(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Middleware for Unavailable {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::error::Result<Response> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            let accept = req
+                .headers()
+                .get_all("accept")
+                .iter()
+                .map(|v| v.to_str().unwrap())
+                .collect::<Vec<_>>()
+                .join(",");
+            let mut headers = HeaderMap::new();
+            headers.insert("retry-after", HeaderValue::from_static("0"));
+            Ok(response_from_parts(
+                StatusCode::SERVICE_UNAVAILABLE,
+                headers,
+                accept,
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_host_config() {
+        let attempts = Unavailable::default();
+        let mut headers = HeaderMap::new();
+        headers.append("accept", HeaderValue::from_static("application/json"));
+        headers.append("accept", HeaderValue::from_static("text/plain"));
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_default_header("accept", "*/*")
+            .with_middleware_phase(attempts.to_owned(), MiddlewarePhase::PostRetry)
+            .with_host_config(
+                "*.example.com",
+                HostConfig {
+                    retry: Some(3),
+                    default_headers: headers,
+                    ..Default::default()
+                },
+            );
+
+        let response = client.get("https://api.example.com/").send().await.unwrap();
+        assert_eq!(
+            response.text().await.unwrap(),
+            "application/json,text/plain"
+        );
+        let retried = attempts.0.swap(0, Ordering::SeqCst);
+        assert!(retried > 1);
+
+        let response = client.get("https://example.org/").send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "*/*");
+        assert_eq!(attempts.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_host_rate_limit() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware_phase(Unavailable::default(), MiddlewarePhase::PostRetry)
+            .with_host_config(
+                "api.example.com",
+                HostConfig {
+                    rate_limit: Some(RateLimit::every(Duration::from_millis(50))),
+                    ..Default::default()
+                },
+            );
+
+        // the bucket is shared by requests of the host
+        let start = Instant::now();
+        for _ in 0..3 {
+            client.get("https://api.example.com/").send().await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            client.get("https://example.org/").send().await.unwrap();
+        }
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+}
@@ -0,0 +1,75 @@
+mod common;
+
+#[cfg(test)]
+mod test_long_poll {
+    use crate::common::serve;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use ergoreq::retry_policies::policies::ExponentialBackoff;
+    use ergoreq::wrappers::long_poll::LongPollOptions;
+    use ergoreq::ErgoClient;
+    use futures::StreamExt;
+
+    fn backoff(max_retries: u32) -> ExponentialBackoff {
+        ExponentialBackoff::builder()
+            .retry_bounds(Duration::from_millis(1), Duration::from_millis(1))
+            .build_with_max_retries(max_retries)
+    }
+
+    #[tokio::test]
+    async fn test_long_poll_etag() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let requests_clone = requests.to_owned();
+        let base = serve(move |request| {
+            let mut requests = requests_clone.lock().unwrap();
+            requests.push(request.to_ascii_lowercase());
+            match requests.len() {
+                1 => "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: 1\r\nConnection: close\r\n\r\na".to_owned(),
+                2 => "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned(),
+                _ => "HTTP/1.1 200 OK\r\nETag: \"v2\"\r\nContent-Length: 1\r\nConnection: close\r\n\r\nb".to_owned(),
+            }
+        })
+        .await;
+
+        let responses = ErgoClient::new(reqwest::Client::new())
+            .get(format!("{base}/updates"))
+            .long_poll(
+                LongPollOptions::new()
+                    .with_etag()
+                    .with_error_backoff(backoff(3)),
+            )
+            .take(4)
+            .collect::<Vec<_>>()
+            .await;
+
+        let statuses = responses
+            .iter()
+            .map(|v| v.as_ref().unwrap().status().as_u16())
+            .collect::<Vec<_>>();
+        assert_eq!(statuses, vec![200, 503, 200, 200]);
+        let requests = requests.lock().unwrap();
+        assert!(!requests[0].contains("if-none-match"));
+        assert!(requests[1].contains("if-none-match: \"v1\""));
+        assert!(requests[2].contains("if-none-match: \"v1\""));
+        assert!(requests[3].contains("if-none-match: \"v2\""));
+    }
+
+    #[tokio::test]
+    async fn test_long_poll_gives_up() {
+        let base = serve(|_| {
+            "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                .to_owned()
+        })
+        .await;
+
+        let responses = ErgoClient::new(reqwest::Client::new())
+            .get(format!("{base}/updates"))
+            .long_poll(LongPollOptions::new().with_error_backoff(backoff(2)))
+            .collect::<Vec<_>>()
+            .await;
+
+        // the first error and two retries
+        assert_eq!(responses.len(), 3);
+    }
+}
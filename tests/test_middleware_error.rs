@@ -0,0 +1,56 @@
+#[cfg(test)]
+mod test_middleware_error {
+    use async_trait::async_trait;
+    use ergoreq::middleware::curl_log_middleware::CurlLogMiddleware;
+    use ergoreq::middleware::middleware::{Middleware, Next};
+    use ergoreq::middleware::mock_middleware::MockMiddleware;
+    use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use ergoreq::Error;
+    use http::Extensions;
+    use reqwest::{Request, Response};
+
+    struct FailingMiddleware;
+
+    #[async_trait]
+    impl Middleware for FailingMiddleware {
+        async fn handle(
+            &self,
+            _req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::error::Result<Response> {
+            Err(anyhow::anyhow!("signing key is missing").into())
+        }
+
+        fn name(&self) -> &'static str {
+            "signer"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middleware_provenance() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(CurlLogMiddleware::new())
+            .with_middleware(FailingMiddleware);
+
+        let error = client.get("https://example.com").send().await.unwrap_err();
+        assert!(matches!(
+            error,
+            Error::Middleware {
+                name: "signer",
+                position: 1,
+                ..
+            }
+        ));
+        assert!(matches!(error.root(), Error::Custom(_)));
+        assert!(error.to_string().contains("signing key is missing"));
+    }
+
+    #[tokio::test]
+    async fn test_typed_errors_are_not_wrapped() {
+        let client = ErgoClient::new(reqwest::Client::new()).with_middleware(MockMiddleware::new());
+
+        let error = client.get("https://example.com").send().await.unwrap_err();
+        assert!(matches!(error, Error::MockNotMatched(_, _)));
+    }
+}
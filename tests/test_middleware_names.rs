@@ -0,0 +1,106 @@
+#[cfg(test)]
+mod test_middleware_names {
+    use async_trait::async_trait;
+    use ergoreq::middleware::curl_log_middleware::CurlLogMiddleware;
+    use ergoreq::middleware::middleware::{Middleware, MiddlewarePhase, Next};
+    use ergoreq::middleware::mock_middleware::MockMiddleware;
+    use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use http::Extensions;
+    use reqwest::{Request, Response};
+
+    struct AuthMiddleware;
+
+    #[async_trait]
+    impl Middleware for AuthMiddleware {
+        async fn handle(
+            &self,
+            req: Request,
+            ext: &mut Extensions,
+            next: Next<'_>,
+        ) -> ergoreq::error::Result<Response> {
+            next.run(req, ext).await
+        }
+
+        fn name(&self) -> &'static str {
+            "auth"
+        }
+    }
+
+    #[test]
+    fn test_middleware_names() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(AuthMiddleware)
+            .with_middleware(CurlLogMiddleware::new())
+            .with_retry_count(3);
+        assert_eq!(
+            client.middlewares(),
+            vec![
+                "auth",
+                "ergoreq::middleware::curl_log_middleware::CurlLogMiddleware"
+            ]
+        );
+
+        let request = client
+            .get("https://example.com")
+            .with_middleware(MockMiddleware::new())
+            .with_max_redirection(5);
+        assert_eq!(
+            request.middlewares(),
+            vec![
+                "auth",
+                "ergoreq::middleware::curl_log_middleware::CurlLogMiddleware",
+                "ergoreq::middleware::mock_middleware::MockMiddleware",
+                "ergoreq::middleware::auto_redirect_middleware::AutoRedirectMiddleware",
+                "ergoreq::middleware::auto_retry_middleware::AutoRetryMiddleware",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_without_and_replace_middleware() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(AuthMiddleware)
+            .with_middleware(CurlLogMiddleware::new())
+            .with_middleware(AuthMiddleware);
+
+        let derived = client.without_middleware::<AuthMiddleware>();
+        assert_eq!(
+            derived.middlewares(),
+            vec!["ergoreq::middleware::curl_log_middleware::CurlLogMiddleware"]
+        );
+        assert_eq!(client.middlewares().len(), 3);
+
+        let derived = client.replace_middleware::<CurlLogMiddleware, _>(MockMiddleware::new());
+        assert_eq!(
+            derived.middlewares(),
+            vec![
+                "auth",
+                "ergoreq::middleware::mock_middleware::MockMiddleware",
+                "auth"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_middleware_phase() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware_phase(MockMiddleware::new(), MiddlewarePhase::PostRetry)
+            .with_middleware(AuthMiddleware)
+            .with_retry_count(2)
+            .with_auto_redirect_count(2);
+
+        let request = client
+            .get("https://example.com")
+            .with_middleware_phase(CurlLogMiddleware::new(), MiddlewarePhase::PreRetry);
+        assert_eq!(
+            request.middlewares(),
+            vec![
+                "auth",
+                "ergoreq::middleware::auto_redirect_middleware::AutoRedirectMiddleware",
+                "ergoreq::middleware::curl_log_middleware::CurlLogMiddleware",
+                "ergoreq::middleware::auto_retry_middleware::AutoRetryMiddleware",
+                "ergoreq::middleware::mock_middleware::MockMiddleware",
+            ]
+        );
+    }
+}
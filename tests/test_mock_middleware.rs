@@ -0,0 +1,105 @@
+#[cfg(test)]
+mod test_mock_middleware {
+    use ergoreq::cookie::cookie_container::ErgoCookieContainer;
+    use ergoreq::middleware::middleware::MiddlewarePhase;
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+    use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use ergoreq::Error;
+    use http::{Method, StatusCode};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_mock_middleware() {
+        let mock = Arc::new(
+            MockMiddleware::new()
+                .with_rule(
+                    MockRule::new()
+                        .method(Method::GET)
+                        .path_regex(r"^/users/\d+$")
+                        .header_matches("authorization", |v| v.as_bytes().starts_with(b"Bearer "))
+                        .respond_with(
+                            MockResponse::new(StatusCode::OK)
+                                .json(&serde_json::json!({"name": "ergo"})),
+                        )
+                        .expect(2),
+                )
+                .with_rule(
+                    MockRule::new()
+                        .method(Method::POST)
+                        .respond_with(MockResponse::new(StatusCode::CREATED))
+                        .expect(1),
+                ),
+        );
+        let client = ErgoClient::new(reqwest::Client::new()).with_middleware_arc(mock.clone());
+
+        for _ in 0..2 {
+            let response = client
+                .get("https://example.com/users/1")
+                .bearer_auth("token")
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(response.url().as_str(), "https://example.com/users/1");
+            let body = response.json::<serde_json::Value>().await.unwrap();
+            assert_eq!(body["name"], "ergo");
+        }
+
+        let response = client
+            .post("https://example.com/users")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let error = client
+            .get("https://example.com/users/1")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(error, Error::MockNotMatched(_, _)));
+
+        mock.assert_expectations();
+        assert_eq!(mock.call_count(0), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "rule #0 expected 1 call(s), got 0")]
+    fn test_mock_expectations_not_met() {
+        let mock = MockMiddleware::new().with_rule(MockRule::new().expect(1));
+        mock.assert_expectations();
+    }
+
+    #[tokio::test]
+    async fn test_execute_prebuilt_request() {
+        let mock = MockMiddleware::new()
+            .with_rule(
+                MockRule::new().path_regex("^/start$").respond_with(
+                    MockResponse::new(StatusCode::FOUND)
+                        .header("location", "/next")
+                        .header("set-cookie", "session=1"),
+                ),
+            )
+            .with_rule(
+                MockRule::new()
+                    .path_regex("^/next$")
+                    .header("cookie", "session=1")
+                    .respond_with(MockResponse::new(StatusCode::OK).body("done")),
+            );
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_auto_redirect_count(5)
+            .with_middleware_phase(mock, MiddlewarePhase::PostRetry);
+
+        let request =
+            reqwest::Request::new(Method::GET, "https://example.com/start".parse().unwrap());
+        let cookie_store = Arc::new(ErgoCookieContainer::new(true, false, false));
+        let response = client.execute_with(request, cookie_store).await.unwrap();
+        assert_eq!(response.url().as_str(), "https://example.com/next");
+        assert_eq!(response.text().await.unwrap(), "done");
+
+        // without a cookie store the second hop is not matched
+        let request =
+            reqwest::Request::new(Method::GET, "https://example.com/start".parse().unwrap());
+        let error = client.execute(request).await.unwrap_err();
+        assert!(matches!(error, Error::MockNotMatched(_, _)));
+    }
+}
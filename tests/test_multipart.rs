@@ -0,0 +1,65 @@
+mod common;
+
+#[cfg(test)]
+mod test_multipart {
+    use crate::common::serve;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use ergoreq::utils::multipart::ErgoMultipart;
+    use ergoreq::ErgoClient;
+
+    #[tokio::test]
+    async fn test_multipart_retried() {
+        // fail the first attempt, then echo the content type and the body
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_clone = attempts.to_owned();
+        let base = serve(move |request| {
+            if attempts_clone.fetch_add(1, Ordering::Relaxed) == 0 {
+                return "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned();
+            }
+            let (head, body) = request.split_once("\r\n\r\n").unwrap();
+            let content_type = head
+                .lines()
+                .find_map(|v| v.strip_prefix("content-type: "))
+                .unwrap();
+            let body = format!("{content_type}\n{body}");
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        })
+        .await;
+
+        let path =
+            std::env::temp_dir().join(format!("ergoreq-multipart-{}.txt", std::process::id()));
+        tokio::fs::write(&path, "file content").await.unwrap();
+
+        let form = ErgoMultipart::new().text("title", "holiday");
+        let boundary = form.boundary().to_owned();
+        let response = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(2)
+            .put(format!("{base}/upload"))
+            .multipart_form(form)
+            .multipart_json("meta", &serde_json::json!({"public": true}))
+            .multipart_file("photo", &path)
+            .send()
+            .await
+            .unwrap();
+        let text = response.text().await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+
+        let file_name = path.file_name().unwrap().to_string_lossy();
+        assert_eq!(attempts.load(Ordering::Relaxed), 2);
+        assert_eq!(
+            text,
+            format!(
+                "multipart/form-data; boundary={boundary}\n\
+                 --{boundary}\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nholiday\r\n\
+                 --{boundary}\r\nContent-Disposition: form-data; name=\"meta\"\r\nContent-Type: application/json\r\n\r\n{{\"public\":true}}\r\n\
+                 --{boundary}\r\nContent-Disposition: form-data; name=\"photo\"; filename=\"{file_name}\"\r\nContent-Type: text/plain\r\n\r\nfile content\r\n\
+                 --{boundary}--\r\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,80 @@
+#[cfg(all(test, feature = "xml", feature = "msgpack"))]
+mod test_negotiate {
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+    use ergoreq::wrappers::negotiate::BodyFormat;
+    use ergoreq::ErgoClient;
+    use http::StatusCode;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Item {
+        id: u32,
+        name: String,
+    }
+
+    #[tokio::test]
+    async fn test_negotiate() {
+        let item = Item {
+            id: 1,
+            name: "ergo".to_owned(),
+        };
+        let packed = ergoreq::utils::msgpack::to_vec(&item).unwrap();
+        let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+            MockMiddleware::new()
+                .with_rule(
+                    MockRule::new()
+                        .header(
+                            "accept",
+                            "application/xml, application/json;q=0.9, application/msgpack;q=0.8",
+                        )
+                        .path_regex("^/xml$")
+                        .respond_with(
+                            MockResponse::new(StatusCode::OK)
+                                .header("content-type", "text/xml; charset=utf-8")
+                                .body("<item><id>1</id><name>ergo</name></item>"),
+                        ),
+                )
+                .with_rule(
+                    MockRule::new().path_regex("^/msgpack$").respond_with(
+                        MockResponse::new(StatusCode::OK)
+                            .header("content-type", "application/x-msgpack")
+                            .body(packed),
+                    ),
+                )
+                .with_rule(
+                    MockRule::new().respond_with(
+                        MockResponse::new(StatusCode::OK)
+                            .header("content-type", "text/html")
+                            .body("<html></html>"),
+                    ),
+                ),
+        );
+        let formats = [BodyFormat::Xml, BodyFormat::Json, BodyFormat::Msgpack];
+
+        let negotiated = client
+            .get("https://example.com/xml")
+            .negotiate::<Item>(&formats)
+            .await
+            .unwrap();
+        assert_eq!(negotiated.format, BodyFormat::Xml);
+        assert_eq!(negotiated.value, item);
+
+        let negotiated = client
+            .get("https://example.com/msgpack")
+            .negotiate::<Item>(&formats)
+            .await
+            .unwrap();
+        assert_eq!(negotiated.format, BodyFormat::Msgpack);
+        assert_eq!(negotiated.value, item);
+
+        let err = client
+            .get("https://example.com/html")
+            .negotiate::<Item>(&formats)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            ergoreq::Error::UnexpectedContentType { actual: Some(ref v), .. } if v == "text/html"
+        ));
+    }
+}
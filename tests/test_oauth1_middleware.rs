@@ -0,0 +1,70 @@
+#[cfg(test)]
+mod test_oauth1_middleware {
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+    use ergoreq::middleware::middleware::{Middleware, MiddlewarePhase, Next};
+    use ergoreq::middleware::oauth1_middleware::OAuth1Middleware;
+    use ergoreq::utils::response_from_parts;
+    use ergoreq::ErgoClient;
+    use http::{Extensions, HeaderMap, StatusCode};
+    use reqwest::{Request, Response};
+
+    /// Fails the first attempt with `503`, recording the `Authorization` of every attempt.
+    struct FlakyUpstream(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait]
+    impl Middleware for FlakyUpstream {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::Result<Response> {
+            let authorization = req
+                .headers()
+                .get(http::header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_owned();
+            let mut attempts = self.0.lock().unwrap();
+            attempts.push(authorization);
+            let status = match attempts.len() {
+                1 => StatusCode::SERVICE_UNAVAILABLE,
+                _ => StatusCode::OK,
+            };
+            Ok(response_from_parts(
+                status,
+                HeaderMap::new(),
+                "",
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    fn nonce(authorization: &str) -> &str {
+        authorization
+            .split(", ")
+            .find_map(|v| v.strip_prefix("oauth_nonce=\""))
+            .unwrap()
+            .trim_end_matches('"')
+    }
+
+    #[tokio::test]
+    async fn test_retry_is_signed_again() {
+        let attempts = Arc::new(Mutex::new(vec![]));
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(2)
+            .with_middleware(OAuth1Middleware::hmac_sha1("key", "secret"))
+            .with_middleware_phase(
+                FlakyUpstream(attempts.to_owned()),
+                MiddlewarePhase::PostRetry,
+            );
+
+        let response = client.get("https://example.com").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let attempts = attempts.lock().unwrap();
+        assert_eq!(attempts.len(), 2);
+        assert_ne!(nonce(&attempts[0]), nonce(&attempts[1]));
+    }
+}
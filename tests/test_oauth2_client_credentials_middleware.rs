@@ -0,0 +1,78 @@
+#[cfg(test)]
+mod test_oauth2_client_credentials_middleware {
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+    use ergoreq::middleware::oauth2_client_credentials_middleware::OAuth2ClientCredentialsMiddleware;
+    use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use ergoreq::Error;
+    use http::{Method, StatusCode};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_client_credentials_single_flight() {
+        let token_endpoint = Arc::new(
+            MockMiddleware::new().with_rule(
+                MockRule::new()
+                    .method(Method::POST)
+                    .path_regex("^/token$")
+                    .header("authorization", "Basic aWQ6c2VjcmV0")
+                    .respond_with(MockResponse::new(StatusCode::OK).json(
+                        &serde_json::json!({"access_token": "t1", "token_type": "Bearer", "expires_in": 3600}),
+                    ))
+                    .expect(1),
+            ),
+        );
+        let api = Arc::new(
+            MockMiddleware::new().with_rule(
+                MockRule::new()
+                    .header("authorization", "Bearer t1")
+                    .expect(3),
+            ),
+        );
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(OAuth2ClientCredentialsMiddleware::new(
+                ErgoClient::new(reqwest::Client::new()).with_middleware_arc(token_endpoint.clone()),
+                "https://auth.example.com/token",
+                "id",
+                "secret",
+            ))
+            .with_middleware_arc(api.clone());
+
+        let (a, b, c) = tokio::join!(
+            client.get("https://api.example.com/a").send(),
+            client.get("https://api.example.com/b").send(),
+            client.get("https://api.example.com/c").send(),
+        );
+        a.unwrap();
+        b.unwrap();
+        c.unwrap();
+
+        token_endpoint.assert_expectations();
+        api.assert_expectations();
+    }
+
+    #[tokio::test]
+    async fn test_client_credentials_token_error() {
+        let client = ErgoClient::new(reqwest::Client::new()).with_middleware(
+            OAuth2ClientCredentialsMiddleware::new(
+                ErgoClient::new(reqwest::Client::new()).with_middleware(
+                    MockMiddleware::new().with_rule(MockRule::new().respond_with(
+                        MockResponse::new(StatusCode::BAD_REQUEST).body("invalid_client"),
+                    )),
+                ),
+                "https://auth.example.com/token",
+                "id",
+                "secret",
+            ),
+        );
+
+        let error = client
+            .get("https://api.example.com/a")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            Error::OAuth2TokenRequest(StatusCode::BAD_REQUEST, body) if body == "invalid_client"
+        ));
+    }
+}
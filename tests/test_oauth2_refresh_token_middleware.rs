@@ -0,0 +1,111 @@
+#[cfg(test)]
+mod test_oauth2_refresh_token_middleware {
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+    use ergoreq::middleware::oauth2_refresh_token_middleware::OAuth2RefreshTokenMiddleware;
+    use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use http::{Method, StatusCode};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    fn token_endpoint() -> Arc<MockMiddleware> {
+        Arc::new(
+            MockMiddleware::new().with_rule(
+                MockRule::new()
+                    .method(Method::POST)
+                    .respond_with(MockResponse::new(StatusCode::OK).json(&serde_json::json!({
+                        "access_token": "a2",
+                        "refresh_token": "r2",
+                        "expires_in": 3600
+                    })))
+                    .expect(1),
+            ),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_refresh_on_unauthorized() {
+        let token_endpoint = token_endpoint();
+        let api = Arc::new(
+            MockMiddleware::new()
+                .with_rule(
+                    MockRule::new()
+                        .header("authorization", "Bearer a1")
+                        .respond_with(MockResponse::new(StatusCode::UNAUTHORIZED))
+                        .expect(1),
+                )
+                .with_rule(
+                    MockRule::new()
+                        .header("authorization", "Bearer a2")
+                        .expect(2),
+                ),
+        );
+        let rotated = Arc::new(Mutex::new(vec![]));
+        let rotated_clone = rotated.clone();
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(
+                OAuth2RefreshTokenMiddleware::new(
+                    ErgoClient::new(reqwest::Client::new())
+                        .with_middleware_arc(token_endpoint.clone()),
+                    "https://auth.example.com/token",
+                    "id",
+                    "a1",
+                    "r1",
+                )
+                .on_refresh_token_rotated(move |v| {
+                    rotated_clone.lock().unwrap().push(v.to_owned())
+                }),
+            )
+            .with_middleware_arc(api.clone());
+
+        let response = client
+            .post("https://api.example.com/items")
+            .body("item")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        client
+            .get("https://api.example.com/items")
+            .send()
+            .await
+            .unwrap();
+
+        token_endpoint.assert_expectations();
+        api.assert_expectations();
+        assert_eq!(*rotated.lock().unwrap(), vec!["r2".to_owned()]);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_on_expiry() {
+        let token_endpoint = token_endpoint();
+        let api = Arc::new(
+            MockMiddleware::new().with_rule(
+                MockRule::new()
+                    .header("authorization", "Bearer a2")
+                    .expect(1),
+            ),
+        );
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(
+                OAuth2RefreshTokenMiddleware::new(
+                    ErgoClient::new(reqwest::Client::new())
+                        .with_middleware_arc(token_endpoint.clone()),
+                    "https://auth.example.com/token",
+                    "id",
+                    "a1",
+                    "r1",
+                )
+                .with_expires_in(Duration::from_secs(10)),
+            )
+            .with_middleware_arc(api.clone());
+
+        client
+            .get("https://api.example.com/items")
+            .send()
+            .await
+            .unwrap();
+
+        token_endpoint.assert_expectations();
+        api.assert_expectations();
+    }
+}
@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod test_pagination {
+    use async_trait::async_trait;
+    use ergoreq::middleware::middleware::{Middleware, MiddlewarePhase, Next};
+    use ergoreq::utils::response_from_parts;
+    use ergoreq::wrappers::pagination::{JsonCursor, LinkHeader, PageNumber};
+    use ergoreq::ErgoClient;
+    use futures::TryStreamExt;
+    use http::{Extensions, HeaderMap, HeaderValue, StatusCode};
+    use reqwest::{Request, Response};
+    use serde_json::json;
+
+    /// Serve three pages for each pagination strategy.
+    struct Pages;
+
+    #[async_trait]
+    impl Middleware for Pages {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::error::Result<Response> {
+            let query = |name: &str| {
+                req.url()
+                    .query_pairs()
+                    .find(|(k, _)| k == name)
+                    .and_then(|(_, v)| v.parse::<u64>().ok())
+            };
+            let mut headers = HeaderMap::new();
+            let body = match req.url().path() {
+                "/link" => {
+                    let page = query("page").unwrap_or(1);
+                    if page < 3 {
+                        let link = format!("</link?page={}>; rel=\"next\"", page + 1);
+                        headers.insert("link", HeaderValue::from_str(&link).unwrap());
+                    }
+                    json!([page])
+                }
+                "/cursor" => {
+                    let cursor = query("cursor").unwrap_or(1);
+                    let next = (cursor < 3).then(|| (cursor + 1).to_string());
+                    json!({"items": [cursor], "next": next})
+                }
+                _ => {
+                    let page = query("page").unwrap_or(1);
+                    json!({"data": if page <= 3 { vec![page] } else { vec![] }})
+                }
+            };
+            Ok(response_from_parts(
+                StatusCode::OK,
+                headers,
+                body.to_string(),
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    fn client() -> ErgoClient {
+        ErgoClient::new(reqwest::Client::new())
+            .with_middleware_phase(Pages, MiddlewarePhase::PostRetry)
+    }
+
+    #[tokio::test]
+    async fn test_link_header() {
+        let pages = client()
+            .get("https://example.com/link")
+            .paginate(LinkHeader)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        let urls = pages.iter().map(|v| v.url.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            vec![
+                "https://example.com/link",
+                "https://example.com/link?page=2",
+                "https://example.com/link?page=3"
+            ]
+        );
+        assert_eq!(pages[2].json::<Vec<u64>>().unwrap(), vec![3]);
+    }
+
+    #[tokio::test]
+    async fn test_json_cursor_and_page_number() {
+        let items = client()
+            .get("https://example.com/cursor")
+            .query(&[("limit", "1")])
+            .paginate_items::<u64, _>(JsonCursor::new("/next", "cursor"), "/items")
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(items, vec![1, 2, 3]);
+
+        let items = client()
+            .get("https://example.com/number")
+            .paginate_items::<u64, _>(PageNumber::new("page").with_items_pointer("/data"), "/data")
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert_eq!(items, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_items() {
+        let error = client()
+            .get("https://example.com/cursor")
+            .paginate_items::<String, _>(JsonCursor::new("/next", "cursor"), "/items")
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap_err();
+        match error {
+            ergoreq::Error::Deserialize { status, path, .. } => {
+                assert_eq!(status, StatusCode::OK);
+                assert_eq!(path, "[0]");
+            }
+            error => panic!("unexpected error: {error}"),
+        }
+    }
+}
@@ -0,0 +1,96 @@
+#[cfg(test)]
+mod test_proxy_rotation_middleware {
+    use async_trait::async_trait;
+    use ergoreq::middleware::middleware::{Middleware, Next};
+    use ergoreq::middleware::proxy_rotation_middleware::{ProxyRotationMiddleware, SelectedProxy};
+    use ergoreq::utils::response_from_parts;
+    use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use http::{Extensions, HeaderMap, HeaderValue, StatusCode};
+    use reqwest::{Request, Response};
+
+    /// Respond with the selected proxy in the `x-proxy` header.
+    struct EchoProxy;
+
+    #[async_trait]
+    impl Middleware for EchoProxy {
+        async fn handle(
+            &self,
+            req: Request,
+            ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::error::Result<Response> {
+            let mut headers = HeaderMap::new();
+            if let Some(SelectedProxy(proxy)) = ext.get::<SelectedProxy>() {
+                headers.insert("x-proxy", HeaderValue::from_str(proxy).unwrap());
+            }
+            Ok(response_from_parts(
+                StatusCode::OK,
+                headers,
+                "",
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    async fn proxy_of(client: &ErgoClient, url: &str) -> Option<String> {
+        let response = client.get(url).send().await.unwrap();
+        response
+            .headers()
+            .get("x-proxy")
+            .map(|v| v.to_str().unwrap().to_owned())
+    }
+
+    fn proxies() -> Vec<String> {
+        vec![
+            "http://127.0.0.1:18080".to_owned(),
+            "http://127.0.0.1:18081".to_owned(),
+        ]
+    }
+
+    #[tokio::test]
+    async fn test_round_robin() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(ProxyRotationMiddleware::round_robin(proxies()))
+            .with_middleware(EchoProxy);
+
+        let mut used = vec![];
+        for _ in 0..3 {
+            used.push(proxy_of(&client, "https://example.com").await.unwrap());
+        }
+        let proxies = proxies();
+        assert_eq!(
+            used,
+            vec![
+                proxies[0].to_owned(),
+                proxies[1].to_owned(),
+                proxies[0].to_owned()
+            ]
+        );
+        assert_eq!(client.get_client_pool().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sticky_per_domain_and_provider() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(ProxyRotationMiddleware::sticky_per_domain(proxies()))
+            .with_middleware(EchoProxy);
+        let a = proxy_of(&client, "https://a.example.com").await;
+        let b = proxy_of(&client, "https://b.example.com").await;
+        assert_ne!(a, b);
+        assert_eq!(proxy_of(&client, "https://a.example.com/other").await, a);
+
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(ProxyRotationMiddleware::from_provider(|req| {
+                (req.url().host_str() == Some("proxied.example.com"))
+                    .then(|| "http://127.0.0.1:18082".to_owned())
+            }))
+            .with_middleware(EchoProxy);
+        assert_eq!(
+            proxy_of(&client, "https://proxied.example.com")
+                .await
+                .as_deref(),
+            Some("http://127.0.0.1:18082")
+        );
+        assert_eq!(proxy_of(&client, "https://direct.example.com").await, None);
+    }
+}
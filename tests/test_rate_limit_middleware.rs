@@ -0,0 +1,56 @@
+mod common;
+
+#[cfg(test)]
+mod test_rate_limit_middleware {
+    use crate::common::SharedMock;
+    use ergoreq::middleware::middleware::MiddlewarePhase;
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+    use ergoreq::middleware::rate_limit_middleware::{RateLimit, RateLimitMiddleware};
+    use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use http::StatusCode;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn test_rate_limit_per_host() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(
+                RateLimitMiddleware::new().with_per_host_limit(RateLimit::new(1, 20.0)),
+            )
+            .with_middleware_phase(
+                MockMiddleware::new().with_rule(MockRule::new()),
+                MiddlewarePhase::PostRetry,
+            );
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            client.get("https://a.example.com").send().await.unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(90));
+
+        // Another host has its own bucket.
+        let start = Instant::now();
+        client.get("https://b.example.com").send().await.unwrap();
+        assert!(start.elapsed() < Duration::from_millis(40));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_retries() {
+        let mock = Arc::new(
+            MockMiddleware::new().with_rule(MockRule::new().respond_with(
+                MockResponse::new(StatusCode::SERVICE_UNAVAILABLE).header("retry-after", "0"),
+            )),
+        );
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(3)
+            .with_middleware(RateLimitMiddleware::new().with_global_limit(RateLimit::new(1, 10.0)))
+            .with_middleware(SharedMock(mock.clone()));
+
+        // each of the three attempts takes a token
+        let start = Instant::now();
+        let response = client.get("https://example.com").send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(mock.call_count(0), 3);
+        assert!(start.elapsed() >= Duration::from_millis(180));
+    }
+}
@@ -0,0 +1,688 @@
+mod common;
+
+#[cfg(test)]
+mod test_redirect {
+    use crate::common::serve;
+    use ergoreq::cookie::cookie_container::ErgoCookieContainer;
+    use ergoreq::middleware::auto_redirect_middleware::{
+        PermanentRedirectCache, RedirectAttempt, RedirectDecision, RedirectDrainStats,
+        RedirectMethod, RedirectMode, RedirectPolicy, RefererPolicy,
+    };
+    use ergoreq::ErgoClient;
+    use http::StatusCode;
+    use reqwest::redirect::Policy;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn redirect(status: &str, location: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\nLocation: {location}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+        )
+    }
+
+    fn ok(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn client() -> ErgoClient {
+        let client = reqwest::ClientBuilder::new()
+            .redirect(Policy::none())
+            .build()
+            .unwrap();
+        ErgoClient::new(client).with_auto_redirect_count(5)
+    }
+
+    /// Get the path of a raw request.
+    fn path(request: &str) -> &str {
+        request.split(' ').nth(1).unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn test_redirect_chain() {
+        let base = serve(|request| match path(request) {
+            "/a" => redirect("302 Found", "/b"),
+            "/b" => redirect("301 Moved Permanently", "/c"),
+            _ => ok("done"),
+        })
+        .await;
+        let client = client();
+
+        let response = client.get(format!("{base}/a")).send().await.unwrap();
+        assert_eq!(response.url().as_str(), format!("{base}/c"));
+        let chain = response.redirect_chain();
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0].url.as_str(), format!("{base}/a"));
+        assert_eq!(chain[0].status, StatusCode::FOUND);
+        assert_eq!(chain[0].location.as_str(), format!("{base}/b"));
+        assert_eq!(chain[1].url.as_str(), format!("{base}/b"));
+        assert_eq!(chain[1].status, StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.text().await.unwrap(), "done");
+
+        let response = client.get(format!("{base}/c")).send().await.unwrap();
+        assert!(response.redirect_chain().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_redirect_cookies() {
+        let base = serve(|request| match path(request) {
+            "/login" => "HTTP/1.1 302 Found\r\nSet-Cookie: session=abc; Path=/\r\nLocation: /home\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned(),
+            _ => {
+                let cookie = request
+                    .lines()
+                    .find_map(|v| v.strip_prefix("cookie: "))
+                    .unwrap_or_default();
+                ok(cookie)
+            }
+        })
+        .await;
+        let cookie_store = Arc::new(ErgoCookieContainer::new(true, false, false));
+
+        let response = client()
+            .get(format!("{base}/login"))
+            .with_cookie_store_ref(&cookie_store)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.redirect_chain().len(), 1);
+        assert_eq!(response.text().await.unwrap(), "session=abc");
+    }
+
+    /// Stop at `/b`, fail at `/c` and add a header for `/d`.
+    struct TestPolicy;
+
+    impl RedirectPolicy for TestPolicy {
+        fn on_redirect(&self, attempt: &RedirectAttempt) -> RedirectDecision {
+            match attempt.url().path() {
+                "/b" => RedirectDecision::Stop,
+                "/c" => RedirectDecision::Error(ergoreq::Error::RedirectLocationInvalid),
+                "/d" => {
+                    let mut request = attempt.request().try_clone().unwrap();
+                    request
+                        .headers_mut()
+                        .insert("x-modified", "true".parse().unwrap());
+                    RedirectDecision::ModifyRequest(request)
+                }
+                _ => RedirectDecision::Allow,
+            }
+        }
+    }
+
+    struct AllowAll;
+
+    impl RedirectPolicy for AllowAll {
+        fn on_redirect(&self, _: &RedirectAttempt) -> RedirectDecision {
+            RedirectDecision::Allow
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redirect_policy() {
+        let base = serve(|request| match path(request) {
+            "/to-b" => redirect("302 Found", "/b"),
+            "/to-c" => redirect("302 Found", "/c"),
+            "/to-d" => redirect("302 Found", "/d"),
+            "/to-e" => redirect("302 Found", "/e"),
+            _ => {
+                let modified = request
+                    .lines()
+                    .find_map(|v| v.strip_prefix("x-modified: "))
+                    .unwrap_or_default();
+                ok(modified)
+            }
+        })
+        .await;
+        let client = client().with_redirect_policy(TestPolicy);
+
+        let response = client.get(format!("{base}/to-b")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert!(response.redirect_chain().is_empty());
+
+        let error = client.get(format!("{base}/to-c")).send().await.unwrap_err();
+        assert!(matches!(error, ergoreq::Error::RedirectLocationInvalid));
+
+        let response = client.get(format!("{base}/to-d")).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "true");
+
+        let response = client.get(format!("{base}/to-e")).send().await.unwrap();
+        assert_eq!(response.redirect_chain().len(), 1);
+        assert_eq!(response.text().await.unwrap(), "");
+
+        // the policy of request overrides the global one
+        let response = client
+            .get(format!("{base}/to-b"))
+            .with_redirect_policy(AllowAll)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    /// Redirect to `localhost` with credentials instead.
+    struct CrossOriginPolicy;
+
+    impl RedirectPolicy for CrossOriginPolicy {
+        fn on_redirect(&self, attempt: &RedirectAttempt) -> RedirectDecision {
+            let mut url = attempt.url().to_owned();
+            url.set_host(Some("localhost")).unwrap();
+            let mut request = reqwest::Request::new(http::Method::GET, url);
+            request
+                .headers_mut()
+                .insert("authorization", "Bearer secret".parse().unwrap());
+            RedirectDecision::ModifyRequest(request)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_redirect_policy_cross_origin() {
+        let base = serve(|request| match path(request) {
+            "/start" => redirect("302 Found", "/echo"),
+            _ => {
+                let authorization = request
+                    .lines()
+                    .filter(|v| v.to_ascii_lowercase().starts_with("authorization:"))
+                    .collect::<Vec<_>>();
+                ok(&authorization.join(","))
+            }
+        })
+        .await;
+        let client = client().with_redirect_policy(CrossOriginPolicy);
+
+        let response = client.get(format!("{base}/start")).send().await.unwrap();
+        assert_eq!(response.url().host_str(), Some("localhost"));
+        assert_eq!(response.text().await.unwrap(), "");
+
+        let error = client
+            .get(format!("{base}/start"))
+            .with_redirect_mode(RedirectMode::SameOrigin)
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ergoreq::Error::RedirectNotAllowed(RedirectMode::SameOrigin, _)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_relative_location() {
+        let base = serve(|request| match path(request) {
+            "/dir/sub/page" => redirect("302 Found", "../other"),
+            "/dir/other" => redirect("302 Found", "?q=1"),
+            "/scheme-relative" => {
+                let host = request
+                    .lines()
+                    .find_map(|v| v.strip_prefix("host: "))
+                    .unwrap();
+                redirect("302 Found", &format!("//{host}/done"))
+            }
+            path => ok(path),
+        })
+        .await;
+        let client = client();
+
+        let response = client
+            .get(format!("{base}/dir/sub/page"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.url().as_str(), format!("{base}/dir/other?q=1"));
+        assert_eq!(response.text().await.unwrap(), "/dir/other?q=1");
+
+        let response = client
+            .get(format!("{base}/scheme-relative"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "/done");
+    }
+
+    #[tokio::test]
+    async fn test_redirect_method_and_fragment() {
+        let base = serve(|request| match path(request) {
+            "/see-other" => redirect("303 See Other", "/echo"),
+            "/temporary" => redirect("307 Temporary Redirect", "/echo"),
+            "/fragment" => redirect("302 Found", "/echo"),
+            _ => {
+                // echo the method, the body digest and the body
+                let method = request.split(' ').next().unwrap_or_default();
+                let digest = match request.contains("content-md5: ") {
+                    true => "md5 ",
+                    false => "",
+                };
+                let body = request.split("\r\n\r\n").nth(1).unwrap_or_default();
+                ok(&format!("{method} {digest}{body}"))
+            }
+        })
+        .await;
+        let client = client();
+
+        let response = client
+            .post(format!("{base}/see-other"))
+            .header("content-md5", "MyRwdDxUeCxN2HtNHSr3bA==")
+            .body("payload")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "GET ");
+
+        let response = client
+            .post(format!("{base}/temporary"))
+            .header("content-md5", "MyRwdDxUeCxN2HtNHSr3bA==")
+            .body("payload")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "POST md5 payload");
+
+        let response = client
+            .get(format!("{base}/fragment#section"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.url().as_str(), format!("{base}/echo#section"));
+    }
+
+    #[tokio::test]
+    async fn test_redirect_strips_credentials() {
+        let base = serve(|request| match path(request) {
+            "/cross-origin" => {
+                let port = request
+                    .lines()
+                    .find_map(|v| v.strip_prefix("host: 127.0.0.1:"))
+                    .unwrap_or_default();
+                redirect("302 Found", &format!("http://localhost:{port}/echo"))
+            }
+            "/same-origin" => redirect("302 Found", "/echo"),
+            _ => {
+                let headers = request
+                    .lines()
+                    .filter(|v| {
+                        let v = v.to_ascii_lowercase();
+                        v.starts_with("authorization:")
+                            || v.starts_with("proxy-authorization:")
+                            || v.starts_with("cookie:")
+                    })
+                    .collect::<Vec<_>>();
+                ok(&headers.join(","))
+            }
+        })
+        .await;
+        let client = client();
+        let send = |path: &str| {
+            client
+                .get(format!("{base}{path}"))
+                .bearer_auth("secret")
+                .header("proxy-authorization", "Basic abc")
+                .header("cookie", "session=abc")
+                .send()
+        };
+
+        let response = send("/same-origin").await.unwrap();
+        let echoed = response.text().await.unwrap().to_ascii_lowercase();
+        assert!(echoed.contains("authorization: bearer secret"));
+        assert!(echoed.contains("cookie: session=abc"));
+
+        let response = send("/cross-origin").await.unwrap();
+        assert_eq!(response.url().host_str(), Some("localhost"));
+        assert_eq!(response.text().await.unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn test_redirect_mode() {
+        let base = serve(|request| match path(request) {
+            "/cross-origin" => {
+                let port = request
+                    .lines()
+                    .find_map(|v| v.strip_prefix("host: 127.0.0.1:"))
+                    .unwrap_or_default();
+                redirect("302 Found", &format!("http://localhost:{port}/done"))
+            }
+            "/same-origin" => redirect("302 Found", "/done"),
+            path => ok(path),
+        })
+        .await;
+        let client = client().with_redirect_mode(RedirectMode::SameOrigin);
+
+        let response = client
+            .get(format!("{base}/same-origin"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "/done");
+
+        let error = client
+            .get(format!("{base}/cross-origin"))
+            .send()
+            .await
+            .unwrap_err();
+        match error {
+            ergoreq::Error::RedirectNotAllowed(mode, url) => {
+                assert_eq!(mode, RedirectMode::SameOrigin);
+                assert_eq!(url.host_str(), Some("localhost"));
+            }
+            error => panic!("unexpected error: {error}"),
+        }
+
+        // the mode of request overrides the global one
+        let response = client
+            .get(format!("{base}/cross-origin"))
+            .with_redirect_mode(RedirectMode::Any)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "/done");
+
+        let error = client
+            .get(format!("{base}/same-origin"))
+            .with_redirect_mode(RedirectMode::HttpsOnly)
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ergoreq::Error::RedirectNotAllowed(RedirectMode::HttpsOnly, _)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_permanent_redirect_cache() {
+        let moved_hits = Arc::new(AtomicUsize::new(0));
+        let hits = moved_hits.to_owned();
+        let base = serve(move |request| match path(request) {
+            "/old" => {
+                hits.fetch_add(1, Ordering::SeqCst);
+                redirect("301 Moved Permanently", "/new")
+            }
+            path => ok(path),
+        })
+        .await;
+        let cache = Arc::new(PermanentRedirectCache::new(16));
+        let client = client().with_permanent_redirect_cache(cache.to_owned());
+        let old_url: url::Url = format!("{base}/old").parse().unwrap();
+
+        let response = client.get(old_url.as_str()).send().await.unwrap();
+        assert_eq!(response.redirect_chain().len(), 1);
+        assert_eq!(response.text().await.unwrap(), "/new");
+        assert_eq!(
+            cache.peek(&old_url).unwrap().as_str(),
+            format!("{base}/new")
+        );
+
+        // the cached target is requested directly, and recorded in the chain
+        let response = client.get(old_url.as_str()).send().await.unwrap();
+        let chain = response.redirect_chain();
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].url, old_url);
+        assert_eq!(chain[0].status, StatusCode::MOVED_PERMANENTLY);
+        assert_eq!(response.url().as_str(), format!("{base}/new"));
+        assert_eq!(moved_hits.load(Ordering::SeqCst), 1);
+
+        // a 301 target is not used by other methods
+        client.post(old_url.as_str()).send().await.unwrap();
+        assert_eq!(moved_hits.load(Ordering::SeqCst), 2);
+
+        cache.invalidate(&old_url);
+        assert!(cache.is_empty());
+        client.get(old_url.as_str()).send().await.unwrap();
+        assert_eq!(moved_hits.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_redirect_cache_counts_hops() {
+        let base = serve(|request| match path(request) {
+            "/a" => redirect("301 Moved Permanently", "/b"),
+            "/b" => redirect("301 Moved Permanently", "/c"),
+            path => ok(path),
+        })
+        .await;
+        let cache = Arc::new(PermanentRedirectCache::new(16));
+        let client = client().with_permanent_redirect_cache(cache.to_owned());
+
+        let response = client.get(format!("{base}/a")).send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "/c");
+        assert_eq!(cache.len(), 2);
+
+        // both cached hops count
+        let response = client
+            .get(format!("{base}/a"))
+            .with_max_redirection(2)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.redirect_chain().len(), 2);
+        let error = client
+            .get(format!("{base}/a"))
+            .with_max_redirection(1)
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ergoreq::Error::TooManyRedirect(_, 1)));
+    }
+
+    #[tokio::test]
+    async fn test_permanent_redirect_cache_strips_credentials() {
+        let moved_hits = Arc::new(AtomicUsize::new(0));
+        let hits = moved_hits.to_owned();
+        let base = serve(move |request| match path(request) {
+            "/old" => {
+                hits.fetch_add(1, Ordering::SeqCst);
+                let port = request
+                    .lines()
+                    .find_map(|v| v.strip_prefix("host: 127.0.0.1:"))
+                    .unwrap_or_default();
+                redirect(
+                    "301 Moved Permanently",
+                    &format!("http://localhost:{port}/echo"),
+                )
+            }
+            _ => {
+                let authorization = request
+                    .lines()
+                    .filter(|v| v.to_ascii_lowercase().starts_with("authorization:"))
+                    .collect::<Vec<_>>();
+                ok(&authorization.join(","))
+            }
+        })
+        .await;
+        let cache = Arc::new(PermanentRedirectCache::new(16));
+        let client = client().with_permanent_redirect_cache(cache.to_owned());
+        let send = || {
+            client
+                .get(format!("{base}/old"))
+                .bearer_auth("secret")
+                .send()
+        };
+
+        let response = send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "");
+
+        // the cached cross-origin target does not get the credentials either
+        let response = send().await.unwrap();
+        assert_eq!(moved_hits.load(Ordering::SeqCst), 1);
+        assert_eq!(response.url().host_str(), Some("localhost"));
+        assert_eq!(response.redirect_chain().len(), 1);
+        assert_eq!(response.text().await.unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn test_redirect_referer() {
+        let base = serve(|request| match path(request) {
+            "/from" => redirect("302 Found", "/to"),
+            _ => {
+                let referer = request
+                    .lines()
+                    .find_map(|v| v.strip_prefix("referer: "))
+                    .unwrap_or_default();
+                ok(referer)
+            }
+        })
+        .await;
+        let client = client();
+        let send = |referer_policy| {
+            client
+                .get(format!("{base}/from#section"))
+                .header(http::header::REFERER, "https://example.com/")
+                .with_referer_policy(referer_policy)
+                .send()
+        };
+
+        let response = send(RefererPolicy::Unchanged).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "https://example.com/");
+
+        let response = send(RefererPolicy::Update).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), format!("{base}/from"));
+
+        let response = send(RefererPolicy::Never).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "");
+    }
+
+    #[tokio::test]
+    async fn test_redirect_observer() {
+        let base = serve(|request| match path(request) {
+            "/a" => redirect("302 Found", "/b"),
+            "/b" => redirect("302 Found", "/forbidden"),
+            path => ok(path),
+        })
+        .await;
+        let observed = Arc::new(Mutex::new(vec![]));
+        let hops = observed.to_owned();
+        let client = client().with_redirect_observer(move |hop| {
+            hops.lock().unwrap().push(hop.location.path().to_owned());
+            hop.location.path() != "/forbidden"
+        });
+
+        let response = client.get(format!("{base}/a")).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::FOUND);
+        assert_eq!(response.redirect_chain().len(), 1);
+        assert_eq!(*observed.lock().unwrap(), vec!["/b", "/forbidden"]);
+
+        // the observer of request overrides the global one
+        let response = client
+            .get(format!("{base}/a"))
+            .with_redirect_observer(|_| true)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "/forbidden");
+        assert_eq!(observed.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_redirect_method_config() {
+        let base = serve(|request| match path(request) {
+            "/found" => redirect("302 Found", "/echo"),
+            "/temporary" => redirect("307 Temporary Redirect", "/echo"),
+            _ => {
+                let method = request.split(' ').next().unwrap_or_default();
+                let body = request.split("\r\n\r\n").nth(1).unwrap_or_default();
+                ok(&format!("{method} {body}"))
+            }
+        })
+        .await;
+        let client = client().with_redirect_method(StatusCode::FOUND, RedirectMethod::Keep);
+
+        let response = client
+            .post(format!("{base}/found"))
+            .body("payload")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "POST payload");
+
+        let response = client
+            .post(format!("{base}/temporary"))
+            .body("payload")
+            .with_redirect_method(StatusCode::TEMPORARY_REDIRECT, RedirectMethod::Get)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "GET ");
+    }
+
+    #[tokio::test]
+    async fn test_redirect_drain() {
+        let base = serve(|request| match path(request) {
+            "/moved" => "HTTP/1.1 302 Found\r\nLocation: /done\r\nContent-Length: 5\r\nConnection: close\r\n\r\nmoved".to_owned(),
+            path => ok(path),
+        })
+        .await;
+        let client = client();
+
+        let response = client.get(format!("{base}/moved")).send().await.unwrap();
+        assert_eq!(
+            response.extension::<RedirectDrainStats>(),
+            Some(&RedirectDrainStats {
+                bytes: 5,
+                drained: 1,
+                abandoned: 0,
+            })
+        );
+
+        let response = client
+            .get(format!("{base}/moved"))
+            .with_redirect_max_drain(2)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.extension::<RedirectDrainStats>(),
+            Some(&RedirectDrainStats {
+                bytes: 0,
+                drained: 0,
+                abandoned: 1,
+            })
+        );
+        assert_eq!(response.text().await.unwrap(), "/done");
+    }
+
+    #[cfg(feature = "html-redirect")]
+    #[tokio::test]
+    async fn test_html_redirect() {
+        fn html(body: &str) -> String {
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        }
+
+        let base = serve(|request| match path(request) {
+            "/interstitial" => html(r#"<meta http-equiv="refresh" content="0;url=/script">"#),
+            "/script" => html(r#"<script>location.replace("/done")</script>"#),
+            path => ok(path),
+        })
+        .await;
+        let client = client().with_html_redirect(true);
+
+        let response = client
+            .get(format!("{base}/interstitial"))
+            .send()
+            .await
+            .unwrap();
+        let chain = response.redirect_chain();
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0].status, StatusCode::OK);
+        assert_eq!(response.text().await.unwrap(), "/done");
+
+        // the size of limited bodies is still known
+        let response = client
+            .get(format!("{base}/interstitial"))
+            .with_max_response_bytes(1024)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.redirect_chain().len(), 2);
+        assert_eq!(response.text().await.unwrap(), "/done");
+
+        // the html page is returned with its body if not enabled
+        let response = client
+            .get(format!("{base}/script"))
+            .with_html_redirect(false)
+            .send()
+            .await
+            .unwrap();
+        assert!(response.text().await.unwrap().contains("location.replace"));
+    }
+}
@@ -0,0 +1,102 @@
+#[cfg(test)]
+mod test_resource {
+    use ergoreq::middleware::middleware::MiddlewarePhase;
+    use ergoreq::middleware::mock_middleware::{MockMiddleware, MockResponse, MockRule};
+    use ergoreq::{ErgoClient, ErgoResource, Error};
+    use http::{Method, StatusCode};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct User {
+        id: u32,
+        name: String,
+    }
+
+    #[tokio::test]
+    async fn test_resource() {
+        let mock = MockMiddleware::new()
+            .with_rule(
+                MockRule::new()
+                    .method(Method::GET)
+                    .path_regex("^/users/1$")
+                    .respond_with(
+                        MockResponse::new(StatusCode::OK).json(&json!({"id": 1, "name": "ergo"})),
+                    ),
+            )
+            .with_rule(
+                MockRule::new()
+                    .method(Method::GET)
+                    .path_regex("^/users/a%20b$")
+                    .respond_with(MockResponse::new(StatusCode::NOT_FOUND).body("no such user")),
+            )
+            .with_rule(
+                MockRule::new()
+                    .method(Method::GET)
+                    .path_regex("^/users/?$")
+                    .respond_with(
+                        MockResponse::new(StatusCode::OK)
+                            .json(&json!([{"id": 1, "name": "ergo"}, {"id": 2, "name": "req"}])),
+                    ),
+            )
+            .with_rule(
+                MockRule::new()
+                    .method(Method::POST)
+                    .path_regex("^/users/?$")
+                    .respond_with(
+                        MockResponse::new(StatusCode::CREATED)
+                            .json(&json!({"id": 3, "name": "new"})),
+                    ),
+            )
+            .with_rule(
+                MockRule::new()
+                    .method(Method::PUT)
+                    .path_regex("^/users/3$")
+                    .respond_with(
+                        MockResponse::new(StatusCode::OK)
+                            .json(&json!({"id": 3, "name": "renamed"})),
+                    ),
+            )
+            .with_rule(
+                MockRule::new()
+                    .method(Method::DELETE)
+                    .path_regex("^/users/3$")
+                    .respond_with(MockResponse::new(StatusCode::NO_CONTENT)),
+            );
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware_phase(mock, MiddlewarePhase::PostRetry);
+        let users: ErgoResource<User> =
+            client.resource("https://example.com/users/".parse().unwrap());
+
+        let user = users.get(1).await.unwrap();
+        assert_eq!(user.name, "ergo");
+        assert_eq!(users.list(&[("page", "1")]).await.unwrap().len(), 2);
+
+        let created = users
+            .create(&User {
+                id: 0,
+                name: "new".to_owned(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(created.id, 3);
+        let updated = users
+            .update(
+                3,
+                &User {
+                    id: 3,
+                    name: "renamed".to_owned(),
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(updated.name, "renamed");
+        users.delete(3).await.unwrap();
+
+        let error = users.get("a b").await.unwrap_err();
+        assert!(matches!(
+            error,
+            Error::UnexpectedStatus(StatusCode::NOT_FOUND, _, ref body) if body == "no such user"
+        ));
+    }
+}
@@ -0,0 +1,160 @@
+mod common;
+
+#[cfg(test)]
+mod test_revalidation_middleware {
+    use crate::common::listen;
+    use async_trait::async_trait;
+    use ergoreq::cache::cache_storage::{CacheStorage, MemoryCacheStorage};
+    use ergoreq::middleware::middleware::{Middleware, Next};
+    use ergoreq::middleware::revalidation_middleware::{CacheStatus, RevalidationMiddleware};
+    use ergoreq::utils::response_from_parts;
+    use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use http::{Extensions, HeaderMap, HeaderValue, StatusCode};
+    use reqwest::{Request, Response};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Returns `304` when `If-None-Match` matches the current etag.
+    struct EtagUpstream(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Middleware for EtagUpstream {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::Result<Response> {
+            let mut headers = HeaderMap::new();
+            headers.insert(http::header::ETAG, HeaderValue::from_static("\"v1\""));
+            if req.headers().get(http::header::IF_NONE_MATCH) == Some(&"\"v1\"".parse().unwrap()) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                return Ok(response_from_parts(
+                    StatusCode::NOT_MODIFIED,
+                    headers,
+                    "",
+                    req.url().to_owned(),
+                ));
+            }
+            Ok(response_from_parts(
+                StatusCode::OK,
+                headers,
+                "content v1",
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_revalidate_with_etag() {
+        let not_modified = Arc::new(AtomicUsize::new(0));
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(RevalidationMiddleware::new())
+            .with_middleware(EtagUpstream(not_modified.clone()));
+
+        for _ in 0..3 {
+            let response = client.get("https://example.com/doc").send().await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(response.text().await.unwrap(), "content v1");
+        }
+        assert_eq!(not_modified.load(Ordering::SeqCst), 2);
+    }
+
+    /// Returns `304` with `headers` when `If-None-Match` is sent, or else `200` with `headers`
+    /// and `Content-Length`.
+    struct HeadersUpstream(HeaderMap);
+
+    #[async_trait]
+    impl Middleware for HeadersUpstream {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::Result<Response> {
+            let mut headers = self.0.to_owned();
+            let (status, body) = match req.headers().contains_key(http::header::IF_NONE_MATCH) {
+                true => (StatusCode::NOT_MODIFIED, ""),
+                false => (StatusCode::OK, "content v1"),
+            };
+            headers.insert(http::header::CONTENT_LENGTH, body.len().into());
+            Ok(response_from_parts(
+                status,
+                headers,
+                body,
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_revalidated_headers() {
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::ETAG, HeaderValue::from_static("\"v1\""));
+        headers.insert(http::header::CONNECTION, HeaderValue::from_static("x-hop"));
+        headers.insert("x-hop", HeaderValue::from_static("1"));
+        headers.insert("x-version", HeaderValue::from_static("1"));
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(RevalidationMiddleware::new())
+            .with_middleware(HeadersUpstream(headers));
+
+        client.get("https://example.com/doc").send().await.unwrap();
+        let response = client.get("https://example.com/doc").send().await.unwrap();
+        assert_eq!(
+            response.extension::<CacheStatus>(),
+            Some(&CacheStatus::Revalidated)
+        );
+        // the framing of the stored body is kept
+        assert_eq!(response.headers()[http::header::CONTENT_LENGTH], "10");
+        assert_eq!(response.headers()["x-version"], "1");
+        assert_eq!(response.text().await.unwrap(), "content v1");
+    }
+
+    #[tokio::test]
+    async fn test_vary_not_stored() {
+        let mut headers = HeaderMap::new();
+        headers.insert(http::header::ETAG, HeaderValue::from_static("\"v1\""));
+        headers.insert(
+            http::header::VARY,
+            HeaderValue::from_static("accept-language"),
+        );
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(RevalidationMiddleware::new())
+            .with_middleware(HeadersUpstream(headers));
+
+        for _ in 0..2 {
+            let response = client.get("https://example.com/doc").send().await.unwrap();
+            assert_eq!(
+                response.extension::<CacheStatus>(),
+                Some(&CacheStatus::Miss)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_max_response_bytes_before_store() {
+        let (listener, address) = listen().await;
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.read(&mut [0u8; 1024]).await.unwrap();
+            // a chunked body has no `Content-Length` to refuse it early
+            let chunk = "a".repeat(1000);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n{:x}\r\n{chunk}\r\n0\r\n\r\n",
+                chunk.len()
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let storage = Arc::new(MemoryCacheStorage::new());
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_max_response_bytes(100)
+            .with_middleware(RevalidationMiddleware::new().with_storage(storage.clone()));
+        let url = format!("http://{address}/doc");
+        let err = client.get(&url).send().await.unwrap_err();
+        assert!(matches!(err, ergoreq::Error::BodyTooLarge(100)));
+        // the body is refused before the cache buffers it
+        assert!(storage.get(&url).await.is_none());
+    }
+}
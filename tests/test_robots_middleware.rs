@@ -0,0 +1,193 @@
+#[cfg(test)]
+mod test_robots_middleware {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use async_trait::async_trait;
+    use ergoreq::middleware::middleware::{Middleware, MiddlewarePhase, Next};
+    use ergoreq::middleware::robots_middleware::{RobotsDisallowed, RobotsMiddleware, RobotsMode};
+    use ergoreq::utils::response_from_parts;
+    use ergoreq::ErgoClient;
+    use http::header::LOCATION;
+    use http::{Extensions, HeaderMap, HeaderValue, StatusCode};
+    use reqwest::{Request, Response};
+
+    const ROBOTS: &str = "\
+        User-agent: *\n\
+        Disallow: /\n\
+        \n\
+        User-agent: ExampleBot\n\
+        Disallow: /private\n\
+        Crawl-delay: 0.2\n";
+
+    /// Serve `ROBOTS` as `/robots.txt`, counting fetches, and respond to other paths with
+    /// `flagged` or `ok`.
+    #[derive(Clone, Default)]
+    struct Site(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Middleware for Site {
+        async fn handle(
+            &self,
+            req: Request,
+            ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::error::Result<Response> {
+            let body = if req.url().path() == "/robots.txt" {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                ROBOTS
+            } else if ext.get::<RobotsDisallowed>().is_some() {
+                "flagged"
+            } else {
+                "ok"
+            };
+            Ok(response_from_parts(
+                StatusCode::OK,
+                HeaderMap::new(),
+                body,
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    fn client(site: &Site, robots: RobotsMiddleware) -> ErgoClient {
+        let robots_client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware_phase(site.to_owned(), MiddlewarePhase::PostRetry);
+        ErgoClient::new(reqwest::Client::new())
+            .with_middleware(robots.with_robots_client(robots_client))
+            .with_middleware_phase(site.to_owned(), MiddlewarePhase::PostRetry)
+    }
+
+    #[tokio::test]
+    async fn test_robots_refuse() {
+        let site = Site::default();
+        let client = client(&site, RobotsMiddleware::new("ExampleBot/1.0"));
+
+        let start = Instant::now();
+        let response = client.get("https://example.com/a").send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        let response = client.get("https://example.com/b").send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        // the second request waits for the crawl delay
+        assert!(start.elapsed() >= Duration::from_millis(150));
+
+        let err = client
+            .get("https://example.com/private/a")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ergoreq::Error::RobotsDisallowed(_)));
+        assert_eq!(site.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_robots_flag() {
+        let site = Site::default();
+        let robots = RobotsMiddleware::new("OtherBot").with_mode(RobotsMode::Flag);
+        let client = client(&site, robots);
+
+        let response = client.get("https://example.com/a").send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "flagged");
+        let response = client.get("https://example.org/a").send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "flagged");
+        assert_eq!(site.0.load(Ordering::SeqCst), 2);
+    }
+
+    /// Redirect `/robots.txt` to `/robots/real.txt`, serving `ROBOTS` there.
+    struct RedirectingSite;
+
+    #[async_trait]
+    impl Middleware for RedirectingSite {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::error::Result<Response> {
+            let mut headers = HeaderMap::new();
+            let (status, body) = match req.url().path() {
+                "/robots.txt" => {
+                    headers.insert(LOCATION, HeaderValue::from_static("/robots/real.txt"));
+                    (StatusCode::MOVED_PERMANENTLY, "")
+                }
+                "/robots/real.txt" => (StatusCode::OK, ROBOTS),
+                _ => (StatusCode::OK, "ok"),
+            };
+            Ok(response_from_parts(
+                status,
+                headers,
+                body,
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_robots_redirect() {
+        // like the default robots client, it follows no redirect by itself
+        let robots_client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware_phase(RedirectingSite, MiddlewarePhase::PostRetry);
+        let robots = RobotsMiddleware::new("ExampleBot").with_robots_client(robots_client);
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(robots)
+            .with_middleware_phase(RedirectingSite, MiddlewarePhase::PostRetry);
+
+        let response = client.get("https://example.com/a").send().await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        let err = client
+            .get("https://example.com/private/a")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ergoreq::Error::RobotsDisallowed(_)));
+    }
+
+    /// Serve `ROBOTS` as the first `/robots.txt`, then a `robots.txt` without `Crawl-delay`.
+    #[derive(Clone, Default)]
+    struct ChangingSite(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Middleware for ChangingSite {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::error::Result<Response> {
+            let body = match req.url().path() {
+                "/robots.txt" if self.0.fetch_add(1, Ordering::SeqCst) == 0 => ROBOTS,
+                "/robots.txt" => "User-agent: *\nAllow: /\n",
+                _ => "ok",
+            };
+            Ok(response_from_parts(
+                StatusCode::OK,
+                HeaderMap::new(),
+                body,
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_robots_crawl_delay_removed() {
+        let site = ChangingSite::default();
+        let robots_client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware_phase(site.to_owned(), MiddlewarePhase::PostRetry);
+        let robots = RobotsMiddleware::new("ExampleBot")
+            .with_cache_ttl(Duration::ZERO)
+            .with_robots_client(robots_client);
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(robots)
+            .with_middleware_phase(site.to_owned(), MiddlewarePhase::PostRetry);
+
+        let start = Instant::now();
+        for _ in 0..3 {
+            let response = client.get("https://example.com/a").send().await.unwrap();
+            assert_eq!(response.text().await.unwrap(), "ok");
+        }
+        // the crawl delay of the first robots.txt is gone once it is fetched again
+        assert!(start.elapsed() < Duration::from_millis(150));
+        assert_eq!(site.0.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod test_send_all {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use ergoreq::middleware::middleware::{Middleware, MiddlewarePhase, Next};
+    use ergoreq::utils::response_from_parts;
+    use ergoreq::ErgoClient;
+    use futures::StreamExt;
+    use http::{Extensions, HeaderMap, StatusCode};
+    use reqwest::{Request, Response};
+
+    /// Respond with the path after waiting for as many milliseconds as the path says, recording
+    /// the highest count of running requests.
+    #[derive(Clone, Default)]
+    struct Delayed {
+        running: Arc<AtomicUsize>,
+        max_running: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Middleware for Delayed {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::error::Result<Response> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            let path = req.url().path().trim_start_matches('/').to_owned();
+            tokio::time::sleep(Duration::from_millis(path.parse().unwrap())).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(response_from_parts(
+                StatusCode::OK,
+                HeaderMap::new(),
+                path,
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    fn client(delayed: &Delayed) -> ErgoClient {
+        ErgoClient::new(reqwest::Client::new())
+            .with_middleware_phase(delayed.to_owned(), MiddlewarePhase::PostRetry)
+    }
+
+    #[tokio::test]
+    async fn test_send_all_in_order() {
+        let delayed = Delayed::default();
+        let client = client(&delayed);
+        let delays = [60, 10, 40, 0, 20];
+        let requests = delays.map(|v| client.get(format!("https://example.com/{v}")));
+
+        let responses = client.send_all(requests, 2).await;
+        let mut bodies = vec![];
+        for response in responses {
+            bodies.push(response.unwrap().text().await.unwrap());
+        }
+
+        assert_eq!(bodies, vec!["60", "10", "40", "0", "20"]);
+        assert_eq!(delayed.max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_send_all_stream() {
+        let delayed = Delayed::default();
+        let client = client(&delayed);
+        let delays = [80, 0, 40];
+        let requests = delays.map(|v| client.get(format!("https://example.com/{v}")));
+
+        let indexes = client
+            .send_all_stream(requests, 3)
+            .map(|(index, response)| {
+                assert!(response.is_ok());
+                index
+            })
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(indexes, vec![1, 2, 0]);
+        assert_eq!(delayed.max_running.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,70 @@
+mod common;
+
+#[cfg(test)]
+mod test_sse {
+    use crate::common::serve;
+    use std::sync::{Arc, Mutex};
+
+    use ergoreq::ErgoClient;
+    use futures::TryStreamExt;
+
+    fn event_stream(body: &str) -> String {
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    #[tokio::test]
+    async fn test_sse_reconnect() {
+        let requests = Arc::new(Mutex::new(vec![]));
+        let requests_clone = requests.to_owned();
+        let base = serve(move |request| {
+            let mut requests = requests_clone.lock().unwrap();
+            requests.push(request.to_ascii_lowercase());
+            match requests.len() {
+                1 => event_stream("retry: 0\nid: 1\ndata: a\n\ndata: incomplete"),
+                2 => event_stream("event: update\nid: 2\ndata: b\ndata: c\n\n"),
+                _ => "HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n".to_owned(),
+            }
+        })
+        .await;
+
+        let events = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(3)
+            .get(format!("{base}/events"))
+            .send_sse()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+
+        assert_eq!(
+            events
+                .iter()
+                .map(|v| (v.id.as_deref(), v.event.as_str(), v.data.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(Some("1"), "message", "a"), (Some("2"), "update", "b\nc")]
+        );
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0].contains("accept: text/event-stream"));
+        assert!(!requests[0].contains("last-event-id"));
+        assert!(requests[1].contains("last-event-id: 1"));
+        assert!(requests[2].contains("last-event-id: 2"));
+    }
+
+    #[tokio::test]
+    async fn test_sse_without_retry_policy() {
+        let base = serve(|_| event_stream("data: a\n\n")).await;
+
+        let events = ErgoClient::new(reqwest::Client::new())
+            .get(format!("{base}/events"))
+            .send_sse()
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].data, "a");
+    }
+}
@@ -0,0 +1,105 @@
+#[cfg(test)]
+mod test_throttle_middleware {
+    use async_trait::async_trait;
+    use ergoreq::middleware::middleware::{Middleware, Next};
+    use ergoreq::middleware::throttle_middleware::ThrottleMiddleware;
+    use ergoreq::utils::response_from_parts;
+    use ergoreq::wrappers::client_wrapper::ErgoClient;
+    use http::{Extensions, HeaderMap, StatusCode};
+    use reqwest::{Request, Response};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    struct BodyUpstream;
+
+    #[async_trait]
+    impl Middleware for BodyUpstream {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::error::Result<Response> {
+            Ok(response_from_parts(
+                StatusCode::OK,
+                HeaderMap::new(),
+                vec![b'a'; 15_000],
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_throttle_download_per_host() {
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_middleware(ThrottleMiddleware::new().with_per_host_download_limit(10_000))
+            .with_middleware(BodyUpstream);
+
+        let start = Instant::now();
+        let response = client.get("https://a.example.com").send().await.unwrap();
+        assert_eq!(response.url().as_str(), "https://a.example.com/");
+        assert_eq!(response.bytes().await.unwrap().len(), 15_000);
+        // The first 10000 bytes are a burst, the rest takes half a second.
+        assert!(start.elapsed() >= Duration::from_millis(400));
+
+        // Another host has its own budget.
+        let start = Instant::now();
+        let body = client
+            .get("https://b.example.com")
+            .send()
+            .await
+            .unwrap()
+            .bytes()
+            .await
+            .unwrap();
+        assert_eq!(body.len(), 15_000);
+        assert!(start.elapsed() < Duration::from_millis(1000));
+    }
+
+    /// Respond `503` to the first attempt and `200` to the next ones, counting them.
+    struct FlakyUpstream(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Middleware for FlakyUpstream {
+        async fn handle(
+            &self,
+            req: Request,
+            _ext: &mut Extensions,
+            _next: Next<'_>,
+        ) -> ergoreq::error::Result<Response> {
+            assert!(req.body().is_some());
+            let status = match self.0.fetch_add(1, Ordering::SeqCst) {
+                0 => StatusCode::SERVICE_UNAVAILABLE,
+                _ => StatusCode::OK,
+            };
+            Ok(response_from_parts(
+                status,
+                HeaderMap::new(),
+                "",
+                req.url().to_owned(),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_throttled_upload_is_retried() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let client = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(2)
+            .with_middleware(ThrottleMiddleware::new().with_global_upload_limit(1024 * 1024))
+            .with_middleware_phase(
+                FlakyUpstream(attempts.to_owned()),
+                ergoreq::middleware::middleware::MiddlewarePhase::PostRetry,
+            );
+
+        let response = client
+            .put("https://example.com")
+            .body(vec![b'a'; 40_000])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,78 @@
+mod common;
+
+#[cfg(test)]
+mod test_transport_options {
+    use crate::common::serve;
+    use ergoreq::wrappers::client_pool::TransportOptions;
+    use ergoreq::ErgoClient;
+
+    #[tokio::test]
+    async fn test_with_transport_options() {
+        // a proxy answering with the request target it received
+        let proxy = serve(|request| {
+            let body = request.split(' ').nth(1).unwrap_or_default().to_owned();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        })
+        .await;
+        let client = ErgoClient::new(reqwest::Client::new());
+
+        let options = TransportOptions::new().with_proxy(proxy);
+        for _ in 0..2 {
+            let response = client
+                .get("http://example.invalid/path")
+                .with_transport_options(options.to_owned())
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(
+                response.text().await.unwrap(),
+                "http://example.invalid/path"
+            );
+        }
+        assert_eq!(client.get_client_pool().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_with_resolve() {
+        // a server answering with the host header it received
+        let server = serve(|request| {
+            let body = request
+                .lines()
+                .find_map(|v| v.strip_prefix("host: "))
+                .unwrap_or_default()
+                .to_owned();
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        })
+        .await;
+        let port = server.rsplit(':').next().unwrap().to_owned();
+        let address = "127.0.0.1:0".parse().unwrap();
+
+        let client =
+            ErgoClient::new(reqwest::Client::new()).with_resolve("pinned.invalid", address);
+        let url = format!("http://pinned.invalid:{port}/");
+        let response = client.get(&url).send().await.unwrap();
+        assert_eq!(
+            response.text().await.unwrap(),
+            format!("pinned.invalid:{port}")
+        );
+
+        let client = ErgoClient::new(reqwest::Client::new());
+        let url = format!("http://other.invalid:{port}/");
+        let response = client
+            .get(&url)
+            .with_resolve("other.invalid", address)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.text().await.unwrap(),
+            format!("other.invalid:{port}")
+        );
+    }
+}
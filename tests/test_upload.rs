@@ -0,0 +1,50 @@
+mod common;
+
+#[cfg(test)]
+mod test_upload {
+    use crate::common::serve;
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use ergoreq::ErgoClient;
+
+    #[tokio::test]
+    async fn test_body_async_read_retried() {
+        // fail the first attempt, then echo the body
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let attempts_clone = attempts.to_owned();
+        let base = serve(move |request| {
+            if attempts_clone.fetch_add(1, Ordering::Relaxed) == 0 {
+                return "HTTP/1.1 503 Service Unavailable\r\nRetry-After: 0\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned();
+            }
+            let body = request.split_once("\r\n\r\n").unwrap().1;
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        })
+        .await;
+
+        let content = "x".repeat(200 * 1024);
+        let uploaded = Arc::new(AtomicU64::new(0));
+        let uploaded_clone = uploaded.to_owned();
+        let response = ErgoClient::new(reqwest::Client::new())
+            .with_retry_count(2)
+            .put(format!("{base}/upload"))
+            .body_async_read(
+                std::io::Cursor::new(content.to_owned()),
+                content.len() as u64,
+            )
+            .with_upload_progress(move |v| {
+                assert_eq!(v.total, 200 * 1024);
+                uploaded_clone.store(v.uploaded, Ordering::Relaxed);
+            })
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.text().await.unwrap(), content);
+        assert_eq!(attempts.load(Ordering::Relaxed), 2);
+        assert_eq!(uploaded.load(Ordering::Relaxed), 200 * 1024);
+    }
+}
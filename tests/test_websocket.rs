@@ -0,0 +1,152 @@
+mod common;
+
+#[cfg(all(test, feature = "websocket"))]
+mod test_websocket {
+    use crate::common::{listen, read_request};
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use ergoreq::wrappers::websocket::{CloseFrame, WebSocketMessage};
+    use ergoreq::ErgoClient;
+    use futures::{SinkExt, StreamExt, TryStreamExt};
+    use sha1::{Digest, Sha1};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    /// Read a masked client frame, returns the opcode and the payload.
+    async fn read_frame(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+        let mut head = [0u8; 2];
+        stream.read_exact(&mut head).await.unwrap();
+        assert_eq!(head[1] & 0x80, 0x80, "client frames are masked");
+        let mut mask = [0u8; 4];
+        stream.read_exact(&mut mask).await.unwrap();
+        let mut payload = vec![0u8; (head[1] & 0x7F) as usize];
+        stream.read_exact(&mut payload).await.unwrap();
+        let payload = payload
+            .iter()
+            .enumerate()
+            .map(|(i, v)| v ^ mask[i % 4])
+            .collect();
+        (head[0] & 0x0F, payload)
+    }
+
+    /// Answer the upgrade request `head` with `101 Switching Protocols`.
+    async fn accept_upgrade(stream: &mut TcpStream, head: &str) {
+        let key = head
+            .lines()
+            .find_map(|v| {
+                v.split_once(": ")
+                    .filter(|(k, _)| k.eq_ignore_ascii_case("sec-websocket-key"))
+            })
+            .unwrap()
+            .1;
+        let mut hasher = Sha1::new();
+        hasher.update(key.as_bytes());
+        hasher.update(b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11");
+        let accept = STANDARD.encode(hasher.finalize());
+        stream
+            .write_all(format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\nSec-WebSocket-Protocol: chat\r\n\r\n").as_bytes())
+            .await
+            .unwrap();
+    }
+
+    /// Serve a redirect to `/ws`, then a WebSocket echoing a fragmented message, pinging and
+    /// closing.
+    async fn serve() -> String {
+        let (listener, address) = listen().await;
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let head = read_request(&mut stream).await;
+                let lower = head.to_ascii_lowercase();
+                if lower.starts_with("get /old ") {
+                    stream
+                        .write_all(b"HTTP/1.1 307 Temporary Redirect\r\nLocation: /ws\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                        .await
+                        .unwrap();
+                    continue;
+                }
+                assert!(lower.starts_with("get /ws "));
+                assert!(lower.contains("upgrade: websocket"));
+                assert!(lower.contains("x-token: secret"));
+                accept_upgrade(&mut stream, &head).await;
+
+                // echo the text in two fragments
+                let (opcode, payload) = read_frame(&mut stream).await;
+                assert_eq!(opcode, 0x1);
+                let (first, second) = payload.split_at(2);
+                stream.write_all(&[0x01, first.len() as u8]).await.unwrap();
+                stream.write_all(first).await.unwrap();
+                stream.write_all(&[0x89, 0x01, b'p']).await.unwrap();
+                stream.write_all(&[0x80, second.len() as u8]).await.unwrap();
+                stream.write_all(second).await.unwrap();
+                assert_eq!(read_frame(&mut stream).await, (0xA, b"p".to_vec()));
+
+                stream
+                    .write_all(&[0x88, 0x05, 0x03, 0xE8, b'b', b'y', b'e'])
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    read_frame(&mut stream).await,
+                    (0x8, vec![0x03, 0xE8, b'b', b'y', b'e'])
+                );
+                break;
+            }
+        });
+        format!("ws://{}", address)
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_websocket() {
+        let base = serve().await;
+
+        let mut socket = ErgoClient::new(reqwest::Client::new())
+            .with_default_header("x-token", "secret")
+            .get(format!("{base}/old"))
+            .header("Sec-WebSocket-Protocol", "chat")
+            .upgrade_websocket()
+            .await
+            .unwrap();
+        assert_eq!(socket.protocol(), Some("chat"));
+
+        socket
+            .send(WebSocketMessage::Text("hello".to_owned()))
+            .await
+            .unwrap();
+        let messages = socket.try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(
+            messages,
+            vec![
+                WebSocketMessage::Ping("p".into()),
+                WebSocketMessage::Text("hello".to_owned()),
+                WebSocketMessage::Close(Some(CloseFrame {
+                    code: 1000,
+                    reason: "bye".to_owned()
+                })),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reject_masked_server_frame() {
+        let (listener, address) = listen().await;
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let head = read_request(&mut stream).await;
+            accept_upgrade(&mut stream, &head).await;
+            // servers must not mask frames
+            stream
+                .write_all(&[0x81, 0x82, 0x00, 0x00, 0x00, 0x00, b'h', b'i'])
+                .await
+                .unwrap();
+            let _ = stream.read(&mut [0u8; 64]).await;
+        });
+
+        let mut socket = ErgoClient::new(reqwest::Client::new())
+            .get(format!("ws://{address}/ws"))
+            .upgrade_websocket()
+            .await
+            .unwrap();
+        assert!(socket.next().await.unwrap().is_err());
+        assert!(socket.next().await.is_none());
+    }
+}